@@ -12,6 +12,19 @@ __license__ = "MIT"
 # 导入所有核心模块
 from . import core
 from . import llm
+from .kernel import (
+    AgentOSKernel,
+    KernelConfig,
+    KernelStats,
+    KernelEvent,
+    KernelEventType,
+    KernelState,
+    HealthReport,
+    ComponentHealth,
+    AgentSummary,
+    ReplayResult,
+    StepFunction,
+)
 
 __all__ = [
     "__version__",
@@ -19,4 +32,15 @@ __all__ = [
     "__license__",
     "core",
     "llm",
+    "AgentOSKernel",
+    "KernelConfig",
+    "KernelStats",
+    "KernelEvent",
+    "KernelEventType",
+    "KernelState",
+    "HealthReport",
+    "ComponentHealth",
+    "AgentSummary",
+    "ReplayResult",
+    "StepFunction",
 ]
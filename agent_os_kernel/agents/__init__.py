@@ -3,7 +3,8 @@
 from .base import BaseAgent, AgentState, AgentConfig
 from .react import ReActAgent
 from .autogen_bridge import AutoGenBridge
-from .workflow_agent import WorkflowAgent
+from .progress import AgentProgress
+from .workflow_agent import WorkflowAgent, WorkflowStep, StepAction, RunAgent, CallTool, Transform
 
 __all__ = [
     'BaseAgent',
@@ -12,4 +13,10 @@ __all__ = [
     'ReActAgent',
     'AutoGenBridge',
     'WorkflowAgent',
+    'WorkflowStep',
+    'StepAction',
+    'RunAgent',
+    'CallTool',
+    'Transform',
+    'AgentProgress',
 ]
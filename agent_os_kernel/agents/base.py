@@ -5,6 +5,7 @@
 """
 
 import asyncio
+import json
 import logging
 from typing import Any, Dict, List, Optional, Callable, Awaitable
 from dataclasses import dataclass, field
@@ -12,6 +13,11 @@ from datetime import datetime
 from enum import Enum
 from abc import ABC, abstractmethod
 
+from ..core.cancellation import CancellationToken
+from ..core.exceptions import AgentCancelledError
+from ..llm.rate_limited import rate_limit_kwargs
+from .progress import ProgressReporting
+
 logger = logging.getLogger(__name__)
 
 
@@ -42,7 +48,7 @@ class AgentConfig:
     memory: Dict = field(default_factory=dict)
 
 
-class BaseAgent(ABC):
+class BaseAgent(ProgressReporting, ABC):
     """
     Agent 基类
     
@@ -88,49 +94,115 @@ class BaseAgent(ABC):
         
         return self.pid
     
-    async def run(self, task: str) -> Dict[str, Any]:
-        """运行 Agent"""
+    async def run(self, task: str,
+                  cancel_token: Optional[CancellationToken] = None) -> Dict[str, Any]:
+        """
+        运行 Agent
+        
+        Args:
+            task: 任务描述
+            cancel_token: 取消令牌；取消时中断正在进行的思考或动作，
+                          返回 {"success": False, "error": "cancelled"}
+        
+        Returns:
+            运行结果
+        """
         self.state = AgentState.RUNNING
         start_time = datetime.now()
+        token = cancel_token or CancellationToken()
+        iterations = 0
         
         try:
             # 执行主循环
             for iteration in range(self.config.max_iterations):
+                iterations = iteration + 1
                 if self.state == AgentState.TERMINATED:
                     break
+                token.raise_if_cancelled()
                 
                 # 生成回复
-                response = await self._think(task)
+                response = await token.race(self._think(task))
                 
                 if response.get("stop"):
                     break
                 
                 # 执行动作
                 if response.get("action"):
-                    result = await self._execute(response["action"])
+                    result = await token.race(self._execute(response["action"]))
                     response["result"] = result
                 
                 # 更新状态
                 task = response.get("result", "")
+                await self._report_progress(iteration + 1, f"iteration {iteration + 1} done")
             
             # 返回结果
             return {
                 "success": True,
-                "iterations": iteration + 1,
+                "iterations": iterations,
                 "duration": (datetime.now() - start_time).total_seconds(),
                 "messages": self._messages
             }
             
+        except AgentCancelledError as e:
+            logger.info(f"Agent cancelled: {self.pid} ({e.message})")
+            return {
+                "success": False,
+                "error": "cancelled",
+                "cancelled": True,
+                "iterations": iterations
+            }
         except Exception as e:
             logger.error(f"Agent error: {e}")
             return {
                 "success": False,
                 "error": str(e),
-                "iterations": iteration + 1 if 'iteration' in locals() else 0
+                "iterations": iterations
             }
         finally:
             self.state = AgentState.IDLE
     
+    def input_schema(self) -> Optional[Dict[str, Any]]:
+        """
+        声明 run_value 接受的输入结构（JSON Schema）
+        
+        Returns:
+            JSON Schema 字典；None 表示未声明（接受任意输入）
+        """
+        return None
+    
+    def output_schema(self) -> Optional[Dict[str, Any]]:
+        """
+        声明 run_value 返回的输出结构（JSON Schema）
+        
+        Returns:
+            JSON Schema 字典；None 表示未声明
+        """
+        return None
+    
+    async def run_value(self, input_data: Any,
+                        cancel_token: Optional[CancellationToken] = None) -> Dict[str, Any]:
+        """
+        以结构化输入运行 Agent
+        
+        默认实现把输入转换为任务字符串后调用 run()：字典中的 "task"
+        字段直接作为任务，其他字典和列表序列化为 JSON，其余值转为字符串。
+        需要直接处理结构化输入的 Agent 可以覆盖此方法。
+        
+        Args:
+            input_data: 结构化输入（应符合 input_schema）
+            cancel_token: 取消令牌（同 run）
+        
+        Returns:
+            运行结果（与 run() 相同）
+        """
+        if isinstance(input_data, dict) and isinstance(input_data.get("task"), str):
+            task = input_data["task"]
+        elif isinstance(input_data, (dict, list)):
+            task = json.dumps(input_data, ensure_ascii=False)
+        else:
+            task = str(input_data)
+        return await self.run(task, cancel_token=cancel_token)
+    
     @abstractmethod
     async def _think(self, task: str) -> Dict[str, Any]:
         """思考步骤 - 生成回复或动作"""
@@ -251,7 +323,7 @@ class ConversableAgent(BaseAgent):
         """生成回复"""
         if self._llm:
             # 使用 LLM 生成
-            response = await self._llm.complete(messages)
+            response = await self._llm.complete(messages, **rate_limit_kwargs(self._llm, self.pid))
             return response.content
         
         # 默认回复
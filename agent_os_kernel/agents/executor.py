@@ -12,7 +12,7 @@ from datetime import timezone, timezone
 from uuid import uuid4
 
 from .base import BaseAgent, AgentConfig, AgentState
-from ..llm import LLMProviderFactory
+from ..llm import LLMProviderFactory, rate_limit_kwargs
 from ..core.events import EventBus, EventType
 from ..core.cost_tracker import CostTracker
 from ..core.observability import Observability
@@ -196,7 +196,7 @@ class AgentExecutor:
         provider = self.agent.llm_provider
         
         # 调用 LLM
-        response = await provider.chat(messages)
+        response = await provider.chat(messages, **rate_limit_kwargs(provider, self.agent.pid))
         
         # 计算成本
         duration_ms = (datetime.now(timezone.utc) - start_time).total_seconds() * 1000
@@ -0,0 +1,102 @@
+# -*- coding: utf-8 -*-
+"""Agent Progress - 长时间运行的进度上报
+
+Agent 运行可能持续数十秒，调用方在结束前拿不到任何信息。
+run_with_progress() 在运行期间把每一步的进度放入调用方提供的
+asyncio.Queue，API 层可以把这些事件转发给客户端（如 WebSocket / SSE）。
+运行结束时总会放入一条 done=True 的事件，消费方据此停止读取。
+"""
+
+import asyncio
+import time
+from dataclasses import dataclass, field
+from typing import Any, Dict, Optional
+
+
+@dataclass
+class AgentProgress:
+    """
+    一条进度事件
+
+    Attributes:
+        step: 当前步骤序号（从 1 开始；结束事件为已完成的步骤数）
+        message: 进度描述
+        fraction: 完成比例 0-1（无法估计时为 None）
+        done: 是否为运行结束事件
+        timestamp: 事件时间
+    """
+    step: int
+    message: str
+    fraction: Optional[float] = None
+    done: bool = False
+    timestamp: float = field(default_factory=time.time)
+
+    def to_dict(self) -> Dict[str, Any]:
+        """序列化为字典（用于 API 推送）"""
+        return {
+            'step': self.step,
+            'message': self.message,
+            'fraction': self.fraction,
+            'done': self.done,
+            'timestamp': self.timestamp,
+        }
+
+
+class ProgressReporting:
+    """
+    进度上报混入类
+
+    子类在每一步调用 _report_progress()；没有通过 run_with_progress()
+    运行时上报是空操作。
+    """
+
+    _progress: Optional[asyncio.Queue] = None
+
+    async def run_with_progress(self, task: Any, progress: asyncio.Queue,
+                                **kwargs) -> Dict[str, Any]:
+        """
+        运行 Agent 并上报进度
+
+        Args:
+            task: 传给 run() 的任务
+            progress: 接收 AgentProgress 的队列；有界队列满时运行会等待消费方
+            **kwargs: 传给 run() 的其他参数（如 cancel_token）
+
+        Returns:
+            run() 的结果
+        """
+        self._progress = progress
+        self._progress_step = 0
+        result: Dict[str, Any] = {}
+        try:
+            result = await self.run(task, **kwargs)
+            return result
+        finally:
+            self._progress = None
+            if result.get("success"):
+                message = "completed"
+            else:
+                message = f"failed: {result.get('error', 'interrupted')}"
+            await progress.put(AgentProgress(
+                step=self._progress_step,
+                message=message,
+                fraction=1.0 if result.get("success") else None,
+                done=True,
+            ))
+
+    async def _report_progress(self, step: int, message: str,
+                               fraction: Optional[float] = None):
+        """
+        上报一步进度
+
+        Args:
+            step: 步骤序号（从 1 开始）
+            message: 进度描述
+            fraction: 完成比例 0-1（可选）
+        """
+        if self._progress is None:
+            return
+        self._progress_step = step
+        if fraction is not None:
+            fraction = max(0.0, min(1.0, fraction))
+        await self._progress.put(AgentProgress(step=step, message=message, fraction=fraction))
@@ -7,12 +7,18 @@
 """
 
 import asyncio
+import inspect
 import logging
-from typing import Dict, List, Any, Optional
+from typing import Callable, Dict, List, Any, Optional
 from dataclasses import dataclass
 from enum import Enum
 from datetime import datetime
 
+from ..core.cancellation import CancellationToken
+from ..core.exceptions import AgentCancelledError
+from ..llm.rate_limited import rate_limit_kwargs
+from .progress import ProgressReporting
+
 logger = logging.getLogger(__name__)
 
 
@@ -35,7 +41,7 @@ class ReActStep:
     confidence: float = 0.5
 
 
-class ReActAgent:
+class ReActAgent(ProgressReporting):
     """
     ReAct Agent 实现
     
@@ -52,14 +58,24 @@ class ReActAgent:
         max_steps: int = 10,
         llm = None,
         tools: List[Dict] = None,
-        mode: str = "standard"
+        mode: str = "standard",
+        on_observation: Optional[Callable[[str, Dict[str, Any]], Any]] = None,
+        agent_pid: Optional[str] = None
     ):
+        """
+        Args:
+            on_observation: 每次工具执行后以 (工具名, 结果) 调用，
+                例如 lambda tool, result: kernel.record_observation(pid, tool, result)
+            agent_pid: 在内核中运行时的进程 PID（LLM 为 RateLimitedProvider 时按它限速）
+        """
         self.name = name
         self.system_prompt = system_prompt or self._default_prompt()
         self.max_steps = max_steps
         self.llm = llm
         self.tools = tools or []
         self.mode = mode
+        self.on_observation = on_observation
+        self.agent_pid = agent_pid
         
         self.steps: List[ReActStep] = []
         self.history: List[Dict] = []
@@ -89,16 +105,32 @@ class ReActAgent:
 - action: null
 - observation: 解释原因"""
     
-    async def run(self, query: str) -> Dict[str, Any]:
+    async def run(self, query: str,
+                  cancel_token: Optional[CancellationToken] = None) -> Dict[str, Any]:
         """
         运行 Agent
         
         Args:
             query: 用户查询
+            cancel_token: 取消令牌；取消时中断当前的思考或工具调用
             
         Returns:
-            结果字典
+            结果字典；被取消时为 {"success": False, "error": "cancelled", ...}
         """
+        token = cancel_token or CancellationToken()
+        try:
+            return await self._run_loop(query, token)
+        except AgentCancelledError:
+            logger.info(f"ReAct agent cancelled after {len(self.steps)} steps")
+            return {
+                "success": False,
+                "error": "cancelled",
+                "cancelled": True,
+                "steps": len(self.steps),
+            }
+    
+    async def _run_loop(self, query: str, token: CancellationToken) -> Dict[str, Any]:
+        """思考-行动-观察主循环"""
         self.steps.clear()
         self.history.clear()
         
@@ -111,11 +143,13 @@ class ReActAgent:
         
         # 主循环
         for step_num in range(self.max_steps):
+            token.raise_if_cancelled()
+            
             # 1. 思考
-            thought = await self._think(query, context)
+            thought = await token.race(self._think(query, context))
             
             # 2. 决定动作
-            action = await self._decide_action(thought, context)
+            action = await token.race(self._decide_action(thought, context))
             
             step = ReActStep(
                 step_num=step_num,
@@ -125,7 +159,7 @@ class ReActAgent:
             
             # 3. 执行动作
             if action:
-                result = await self._execute_action(action)
+                result = await token.race(self._execute_action(action))
                 step.action = action
                 step.observation = result.get("observation", "")
                 context["history"].append({
@@ -136,8 +170,15 @@ class ReActAgent:
             self.steps.append(step)
             
             # 4. 检查是否结束
-            if self._should_finish(thought, result if action else None):
+            finished = self._should_finish(thought, result if action else None)
+            if finished:
                 step.action_type = ActionType.FINISH
+            await self._report_progress(
+                step_num + 1,
+                f"{step.action_type.value}: {(action or {}).get('tool') or thought[:80]}",
+                1.0 if finished else (step_num + 1) / self.max_steps,
+            )
+            if finished:
                 break
         
         # 生成最终回复
@@ -157,7 +198,7 @@ class ReActAgent:
         if self.llm:
             # 使用 LLM 生成思考
             prompt = self._build_thought_prompt(query, context)
-            response = await self.llm.complete(prompt)
+            response = await self.llm.complete(prompt, **rate_limit_kwargs(self.llm, self.agent_pid))
             return response.content
         
         # 默认思考
@@ -210,6 +251,12 @@ class ReActAgent:
             # 模拟工具执行
             result = await self._call_tool(tool_name, params)
             
+            # 把观察结果反馈给调用方（如写回内核上下文）
+            if self.on_observation:
+                feedback = self.on_observation(tool_name, {"success": True, "data": result})
+                if inspect.isawaitable(feedback):
+                    await feedback
+            
             return {
                 "observation": f"工具 {tool_name} 返回: {result}",
                 "success": True
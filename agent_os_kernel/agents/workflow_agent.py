@@ -2,6 +2,14 @@
 """Workflow Agent - 工作流 Agent
 
 支持复杂的多步骤工作流执行。
+
+每个步骤可以携带一个 StepAction：
+- RunAgent: 运行一个 Agent
+- CallTool: 通过 ToolRegistry 调用工具
+- Transform: 对上游输出做纯函数变换
+
+步骤的输入是其依赖步骤的输出（单个依赖时为该输出本身，多个依赖时为
+{step_id: output} 字典），没有依赖的步骤以 run() 的 input_data 为输入。
 """
 
 import asyncio
@@ -11,6 +19,13 @@ from dataclasses import dataclass, field
 from enum import Enum
 from datetime import datetime
 from abc import ABC, abstractmethod
+from typing import TYPE_CHECKING
+
+from .progress import ProgressReporting
+
+if TYPE_CHECKING:
+    from .base import BaseAgent
+    from ..tools.registry import ToolRegistry
 
 logger = logging.getLogger(__name__)
 
@@ -25,6 +40,84 @@ class WorkflowStatus(Enum):
     CANCELLED = "cancelled"
 
 
+class StepAction(ABC):
+    """步骤动作接口"""
+    
+    @abstractmethod
+    async def execute(self, input_data: Any) -> Any:
+        """
+        执行动作
+        
+        Args:
+            input_data: 上游步骤的输出
+        
+        Returns:
+            动作输出，传递给下游步骤
+        
+        Raises:
+            Exception: 动作失败（步骤标记为 FAILED）
+        """
+        pass
+
+
+class RunAgent(StepAction):
+    """运行一个 Agent，上游输出通过 run_value 作为结构化输入传入"""
+    
+    def __init__(self, agent: "BaseAgent"):
+        self.agent = agent
+    
+    async def execute(self, input_data: Any) -> Any:
+        result = await self.agent.run_value(input_data)
+        if not result.get("success"):
+            raise RuntimeError(result.get("error") or f"Agent {self.agent.name} failed")
+        return result
+
+
+class CallTool(StepAction):
+    """
+    通过 ToolRegistry 调用工具
+    
+    params 为固定参数；指定 input_param 时，上游输出作为该参数传入。
+    指定 agent_pid 时以该 Agent 的身份调用（经过沙箱策略检查）。
+    """
+    
+    def __init__(self, registry: "ToolRegistry", name: str,
+                 params: Optional[Dict[str, Any]] = None,
+                 input_param: Optional[str] = None,
+                 agent_pid: Optional[str] = None):
+        self.registry = registry
+        self.name = name
+        self.params = params or {}
+        self.input_param = input_param
+        self.agent_pid = agent_pid
+    
+    async def execute(self, input_data: Any) -> Any:
+        params = dict(self.params)
+        if self.input_param:
+            params[self.input_param] = input_data
+        if self.agent_pid is not None:
+            result = await asyncio.to_thread(self.registry.execute_as, self.agent_pid,
+                                             self.name, **params)
+        else:
+            result = await asyncio.to_thread(self.registry.execute, self.name, **params)
+        if isinstance(result, dict) and not result.get("success", True):
+            raise RuntimeError(result.get("error") or f"Tool '{self.name}' failed")
+        return result
+
+
+class Transform(StepAction):
+    """对上游输出应用函数（同步或异步）"""
+    
+    def __init__(self, func: Callable[[Any], Any]):
+        self.func = func
+    
+    async def execute(self, input_data: Any) -> Any:
+        output = self.func(input_data)
+        if asyncio.iscoroutine(output):
+            output = await output
+        return output
+
+
 class StepStatus(Enum):
     """步骤状态"""
     PENDING = "pending"
@@ -50,6 +143,7 @@ class WorkflowStep:
     started_at: Optional[datetime] = None
     completed_at: Optional[datetime] = None
     timeout_seconds: int = 300
+    action: Optional[StepAction] = None  # None 时为占位步骤
 
 
 @dataclass
@@ -63,7 +157,7 @@ class WorkflowConfig:
     continue_on_failure: bool = False
 
 
-class WorkflowAgent:
+class WorkflowAgent(ProgressReporting):
     """
     工作流 Agent
     
@@ -84,6 +178,7 @@ class WorkflowAgent:
         
         self._start_time: Optional[datetime] = None
         self._end_time: Optional[datetime] = None
+        self._input_data: Any = None
     
     def add_step(
         self,
@@ -92,9 +187,24 @@ class WorkflowAgent:
         task: str,
         agent_type: str = "assistant",
         depends_on: List[str] = None,
-        parallel_with: List[str] = None
+        parallel_with: List[str] = None,
+        action: Optional[StepAction] = None
     ) -> str:
-        """添加步骤"""
+        """
+        添加步骤
+        
+        Args:
+            step_id: 步骤 ID
+            name: 步骤名称
+            task: 任务描述（无依赖且 run() 未提供输入时作为动作输入）
+            agent_type: Agent 类型
+            depends_on: 依赖的步骤 ID
+            parallel_with: 可并行的步骤 ID
+            action: 步骤动作；None 时步骤不做实际工作
+        
+        Returns:
+            步骤 ID
+        """
         step = WorkflowStep(
             step_id=step_id,
             name=name,
@@ -102,7 +212,8 @@ class WorkflowAgent:
             task=task,
             agent_type=agent_type,
             depends_on=depends_on or [],
-            parallel_with=parallel_with or []
+            parallel_with=parallel_with or [],
+            action=action
         )
         
         self.steps[step_id] = step
@@ -124,7 +235,8 @@ class WorkflowAgent:
                 name=step_def.get("name", f"Step {i+1}"),
                 task=step_def.get("task", ""),
                 agent_type=step_def.get("agent", "assistant"),
-                depends_on=depends_on
+                depends_on=depends_on,
+                action=step_def.get("action")
             )
             
             step_ids.append(step_id)
@@ -148,7 +260,8 @@ class WorkflowAgent:
                 name=task_def.get("name", f"Task {i+1}"),
                 task=task_def.get("task", ""),
                 agent_type=task_def.get("agent", "assistant"),
-                parallel_with=[f"{name}_step_{j}" for j in range(len(tasks)) if j != i]
+                parallel_with=[f"{name}_step_{j}" for j in range(len(tasks)) if j != i],
+                action=task_def.get("action")
             )
             
             step_ids.append(step_id)
@@ -190,13 +303,22 @@ class WorkflowAgent:
                 depends_on=[condition_step_id]
             )
     
-    async def run(self) -> Dict[str, Any]:
-        """执行工作流"""
+    async def run(self, input_data: Any = None) -> Dict[str, Any]:
+        """
+        执行工作流
+        
+        Args:
+            input_data: 无依赖步骤的输入（None 时使用各步骤的 task）
+        
+        Returns:
+            执行结果，results 中每个步骤的 result["output"] 为其动作输出
+        """
         self.status = WorkflowStatus.RUNNING
         self._start_time = datetime.now()
+        self._input_data = input_data
         
         ready_steps = []
-        running_tasks = []
+        running_tasks: Dict[asyncio.Task, str] = {}
         completed_steps = set()
         
         # 找到所有没有依赖的步骤
@@ -214,7 +336,9 @@ class WorkflowAgent:
                     step_id = ready_steps.pop(0)
                     step = self.steps[step_id]
                     
-                    # 检查依赖是否都完成
+                    # 检查依赖是否都完成（或已被调度）
+                    if step.status != StepStatus.PENDING or step_id in running_tasks.values():
+                        continue
                     if not all(d in completed_steps for d in step.depends_on):
                         ready_steps.append(step_id)
                         continue
@@ -223,22 +347,29 @@ class WorkflowAgent:
                     task = asyncio.create_task(
                         self._execute_step(step)
                     )
-                    running_tasks.append((step_id, task))
+                    running_tasks[task] = step_id
+                
+                if not running_tasks:
+                    raise RuntimeError("Workflow has unsatisfiable dependencies")
                 
                 # 等待完成
                 if running_tasks:
-                    done, running_tasks = await asyncio.wait(
-                        [t for _, t in running_tasks],
+                    done, _ = await asyncio.wait(
+                        list(running_tasks),
                         return_when=asyncio.FIRST_COMPLETED
                     )
                     
-                    for step_id, task in done:
+                    for task in done:
+                        step_id = running_tasks.pop(task)
                         result = task.result()
                         step = self.steps[step_id]
                         
                         if result["success"]:
                             step.status = StepStatus.COMPLETED
                             completed_steps.add(step_id)
+                            await self._report_progress(
+                                len(completed_steps), f"step {step.name} completed",
+                                len(completed_steps) / len(self.steps))
                             
                             # 检查是否有并行步骤可以启动
                             for pid, pstep in self.steps.items():
@@ -248,14 +379,22 @@ class WorkflowAgent:
                                         for d in pstep.depends_on
                                     )
                                     
-                                    if deps_done:
+                                    if deps_done and pid not in ready_steps:
                                         ready_steps.append(pid)
                         else:
                             if self.config.continue_on_failure:
                                 step.status = StepStatus.FAILED
                                 completed_steps.add(step_id)
+                                # 依赖失败步骤的下游步骤无法获得输入，直接跳过
+                                completed_steps.update(self._skip_dependents(step_id))
+                                await self._report_progress(
+                                    len(completed_steps), f"step {step.name} failed",
+                                    len(completed_steps) / len(self.steps))
                             else:
                                 step.status = StepStatus.FAILED
+                                self.status = WorkflowStatus.FAILED
+                                for pending in running_tasks:
+                                    pending.cancel()
                                 return {
                                     "success": False,
                                     "failed_step": step_id,
@@ -284,8 +423,13 @@ class WorkflowAgent:
         try:
             logger.info(f"Executing step: {step.name}")
             
-            # 模拟步骤执行
-            await asyncio.sleep(0.5)
+            if step.action is not None:
+                output = await asyncio.wait_for(
+                    step.action.execute(self._step_input(step)),
+                    step.timeout_seconds
+                )
+            else:
+                output = f"步骤 {step.name} 执行完成"
             
             # 生成结果
             result = {
@@ -293,6 +437,7 @@ class WorkflowAgent:
                 "step_id": step.step_id,
                 "name": step.name,
                 "result": f"步骤 {step.name} 执行完成",
+                "output": output,
                 "duration": (datetime.now() - step.started_at).total_seconds()
             }
             
@@ -303,6 +448,7 @@ class WorkflowAgent:
             return result
             
         except Exception as e:
+            logger.warning(f"Step {step.step_id} failed: {e}")
             step.error = str(e)
             step.status = StepStatus.FAILED
             
@@ -312,6 +458,32 @@ class WorkflowAgent:
                 "error": str(e)
             }
     
+    def _skip_dependents(self, step_id: str) -> List[str]:
+        """将直接或间接依赖 step_id 的待执行步骤标记为 SKIPPED"""
+        skipped = []
+        frontier = [step_id]
+        while frontier:
+            current = frontier.pop()
+            for sid, s in self.steps.items():
+                if current in s.depends_on and s.status == StepStatus.PENDING:
+                    s.status = StepStatus.SKIPPED
+                    skipped.append(sid)
+                    frontier.append(sid)
+        return skipped
+    
+    def _step_input(self, step: WorkflowStep) -> Any:
+        """计算步骤输入：依赖步骤的输出，或工作流输入"""
+        if not step.depends_on:
+            return step.task if self._input_data is None else self._input_data
+        
+        outputs = {
+            dep: (self.steps[dep].result or {}).get("output")
+            for dep in step.depends_on
+        }
+        if len(outputs) == 1:
+            return next(iter(outputs.values()))
+        return outputs
+    
     def _generate_result(self) -> Dict[str, Any]:
         """生成结果"""
         completed = sum(1 for s in self.steps.values() if s.status == StepStatus.COMPLETED)
@@ -10,13 +10,15 @@ REST API Server
 """
 
 import asyncio
+import json
 import logging
 from typing import Dict, Any, List, Optional
 from dataclasses import dataclass
 from datetime import datetime
 from contextlib import asynccontextmanager
 
-from fastapi import FastAPI, HTTPException, Query
+from fastapi import FastAPI, Header, HTTPException, Query
+from fastapi.responses import JSONResponse
 from pydantic import BaseModel, Field
 import uvicorn
 
@@ -79,9 +81,16 @@ class StatusResponse(BaseModel):
 class AgentOSKernelAPI:
     """API 服务器"""
     
-    def __init__(self, host: str = "0.0.0.0", port: int = 8000):
+    def __init__(self, host: str = "0.0.0.0", port: int = 8000, enable_debug: bool = False):
+        """
+        Args:
+            host: 监听地址
+            port: 监听端口
+            enable_debug: 是否开放 /api/v1/debug/dump（默认关闭）
+        """
         self.host = host
         self.port = port
+        self.enable_debug = enable_debug
         self.kernel = None
         self.metrics = create_metrics_collector()
         self.start_time = datetime.now()
@@ -126,15 +135,26 @@ class AgentOSKernelAPI:
         async def health():
             return {"status": "healthy"}
         
+        @app.get("/healthz", tags=["Health"])
+        async def healthz():
+            """就绪探针：任一子系统不健康时返回 503"""
+            if self.kernel is None:
+                return JSONResponse(status_code=503, content={"healthy": False})
+            report = await asyncio.to_thread(self.kernel.health)
+            return JSONResponse(status_code=200 if report.healthy else 503,
+                                content=report.to_dict())
+        
         # ========== Agent Management ==========
         
         @app.post("/api/v1/agents", response_model=AgentResponse, tags=["Agents"])
-        async def create_agent(request: AgentCreateRequest):
-            """创建 Agent"""
+        async def create_agent(request: AgentCreateRequest,
+                               idempotency_key: Optional[str] = Header(None, alias="Idempotency-Key")):
+            """创建 Agent（带 Idempotency-Key 头的重试请求返回同一个 Agent）"""
             agent_id = self.kernel.spawn_agent(
                 name=request.name,
                 task=request.task,
-                priority=request.priority
+                priority=request.priority,
+                idempotency_key=idempotency_key
             )
             
             self.metrics.counter("agents_created_total")
@@ -157,12 +177,12 @@ class AgentOSKernelAPI:
             
             return [
                 AgentResponse(
-                    agent_id=a["pid"],
-                    name=a["name"],
-                    task=a["task"],
-                    priority=a["priority"],
-                    state=a.get("state", "unknown"),
-                    created_at=a.get("created_at", datetime.now().isoformat())
+                    agent_id=a.pid,
+                    name=a.name,
+                    task=a.task,
+                    priority=a.priority,
+                    state=a.state,
+                    created_at=datetime.fromtimestamp(a.created_at).isoformat()
                 )
                 for a in agents
             ]
@@ -176,11 +196,11 @@ class AgentOSKernelAPI:
             
             return AgentResponse(
                 agent_id=agent_id,
-                name=agent.get("name", ""),
-                task=agent.get("task", ""),
-                priority=agent.get("priority", 0),
-                state=agent.get("state", "unknown"),
-                created_at=agent.get("created_at", datetime.now().isoformat())
+                name=agent.name,
+                task=agent.task,
+                priority=agent.priority,
+                state=agent.state,
+                created_at=datetime.fromtimestamp(agent.created_at).isoformat()
             )
         
         @app.delete("/api/v1/agents/{agent_id}", tags=["Agents"])
@@ -273,6 +293,14 @@ class AgentOSKernelAPI:
                 uptime_seconds=uptime,
                 metrics=self.metrics.get_stats()
             )
+        
+        @app.get("/api/v1/debug/dump", tags=["System"])
+        async def debug_dump():
+            """导出完整内核状态（诊断包，上下文页面内容已脱敏）"""
+            if not self.enable_debug:
+                raise HTTPException(status_code=404, detail="Debug endpoints are disabled")
+            snapshot = await asyncio.to_thread(self.kernel.export_snapshot, redact_content=True)
+            return json.loads(json.dumps(snapshot, default=str))
 
 
 def run_server(host: str = "0.0.0.0", port: int = 8000):
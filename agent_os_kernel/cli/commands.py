@@ -109,15 +109,17 @@ class KernelCommands:
                 "agents": []
             }
             
-            if kernel and hasattr(kernel, 'scheduler'):
+            if kernel and hasattr(kernel, 'list_agents'):
                 try:
-                    processes = kernel.scheduler.list_processes()
-                    for proc in processes:
+                    for agent in kernel.list_agents():
                         result["agents"].append({
-                            "agent_id": proc.pid if hasattr(proc, 'pid') else str(proc),
-                            "name": proc.name if hasattr(proc, 'name') else "Unknown",
-                            "status": proc.status if hasattr(proc, 'status') else "unknown",
-                            "priority": proc.priority if hasattr(proc, 'priority') else 50
+                            "agent_id": agent.pid,
+                            "name": agent.name,
+                            "status": agent.state,
+                            "priority": agent.priority,
+                            "token_usage": agent.token_usage,
+                            "context_tokens": agent.context_tokens,
+                            "error_count": agent.error_count
                         })
                 except Exception as e:
                     result["error"] = str(e)
@@ -139,12 +141,11 @@ class KernelCommands:
             
             agents_found = False
             
-            if kernel and hasattr(kernel, 'scheduler'):
+            if kernel and hasattr(kernel, 'list_agents'):
                 try:
-                    processes = kernel.scheduler.list_processes()
-                    for proc in processes:
+                    for agent in kernel.list_agents():
                         agents_found = True
-                        print(f"{proc.pid:<12} {proc.name:<20} {proc.status:<12} {proc.priority:<10}")
+                        print(f"{agent.pid:<12} {agent.name:<20} {agent.state:<12} {agent.priority:<10}")
                 except Exception as e:
                     print(f"获取Agent列表失败: {e}")
             
@@ -10,8 +10,10 @@
 """
 
 import argparse
+import json
 import sys
 import os
+import uuid
 from typing import Optional
 from datetime import datetime
 from datetime import timezone, timezone
@@ -22,6 +24,7 @@ class CLI:
     
     def __init__(self):
         self.kernel = None
+        self.storage_url: Optional[str] = None
         self.parser = self._create_parser()
     
     def _create_parser(self) -> argparse.ArgumentParser:
@@ -34,22 +37,34 @@ class CLI:
   agent-os init                    # 初始化项目
   agent-os create -n "Researcher" -t "Research AI"  # 创建 Agent
   agent-os list                    # 列出所有 Agent
+  agent-os ps                      # 显示进程表
   agent-os serve --port 8000       # 启动 API 服务器
+  agent-os checkpoint <pid> --desc "before deploy"  # 创建检查点
+  agent-os restore <checkpoint-id> # 从检查点恢复
+  agent-os dump --output state.json   # 导出内核状态（诊断包）
+  agent-os --storage-url file:///var/lib/agent-os ps  # 使用持久化存储
             """
         )
         
         parser.add_argument("--config", "-c", default="config.yaml")
         parser.add_argument("--verbose", "-v", action="store_true")
+        parser.add_argument("--storage-url", "-s",
+                            help="内核存储 URL，如 file:///path 或 postgresql://...；"
+                                 "设置后命令之间共享 Agent 与检查点（默认内存存储）")
         
         subparsers = parser.add_subparsers(dest="command", title="commands")
         
         self._add_init_command(subparsers)
         self._add_create_command(subparsers)
         self._add_list_command(subparsers)
+        self._add_ps_command(subparsers)
         self._add_delete_command(subparsers)
         self._add_demo_command(subparsers)
         self._add_serve_command(subparsers)
         self._add_status_command(subparsers)
+        self._add_checkpoint_command(subparsers)
+        self._add_restore_command(subparsers)
+        self._add_dump_command(subparsers)
         
         return parser
     
@@ -69,6 +84,10 @@ class CLI:
         cmd = subparsers.add_parser("list", help="列出 Agent")
         cmd.add_argument("--json", action="store_true")
     
+    def _add_ps_command(self, subparsers):
+        cmd = subparsers.add_parser("ps", help="显示进程表")
+        cmd.add_argument("--running", "-r", action="store_true", help="只显示运行中的进程")
+    
     def _add_delete_command(self, subparsers):
         cmd = subparsers.add_parser("delete", help="删除 Agent")
         cmd.add_argument("--agent-id", "-a", required=True)
@@ -86,6 +105,21 @@ class CLI:
         cmd = subparsers.add_parser("status", help="显示状态")
         cmd.add_argument("--json", action="store_true")
     
+    def _add_checkpoint_command(self, subparsers):
+        cmd = subparsers.add_parser("checkpoint", help="为 Agent 创建检查点")
+        cmd.add_argument("pid")
+        cmd.add_argument("--desc", "-d", default="")
+    
+    def _add_restore_command(self, subparsers):
+        cmd = subparsers.add_parser("restore", help="从检查点恢复 Agent")
+        cmd.add_argument("checkpoint_id", type=_parse_checkpoint_id)
+    
+    def _add_dump_command(self, subparsers):
+        cmd = subparsers.add_parser("dump", help="导出完整内核状态（JSON）")
+        cmd.add_argument("--output", "-o", help="写入文件（默认输出到 stdout）")
+        cmd.add_argument("--url", "-u", help="正在运行的 API 服务器地址，如 http://localhost:8000"
+                                              "（默认导出本地内核）")
+    
     def run(self, args=None):
         """运行 CLI"""
         parsed = self.parser.parse_args(args)
@@ -94,7 +128,14 @@ class CLI:
             self.parser.print_help()
             return 0
         
-        return self._execute_command(parsed)
+        self.storage_url = parsed.storage_url
+        try:
+            return self._execute_command(parsed)
+        finally:
+            # 关闭内核时保存调度器和上下文快照，供下一条命令恢复
+            if self.kernel is not None:
+                self.kernel.shutdown()
+                self.kernel = None
     
     def _execute_command(self, args):
         """执行命令"""
@@ -104,10 +145,14 @@ class CLI:
             "init": self._cmd_init,
             "create": self._cmd_create,
             "list": self._cmd_list,
+            "ps": self._cmd_ps,
             "delete": self._cmd_delete,
             "demo": self._cmd_demo,
             "serve": self._cmd_serve,
             "status": self._cmd_status,
+            "checkpoint": self._cmd_checkpoint,
+            "restore": self._cmd_restore,
+            "dump": self._cmd_dump,
         }
         
         handler = handlers.get(command)
@@ -127,20 +172,35 @@ class CLI:
     
     def _cmd_create(self, args):
         """创建 Agent"""
-        from ..core import AgentOSKernel
-        kernel = AgentOSKernel()
-        agent_id = kernel.spawn_agent(name=args.name, task=args.task)
+        agent_id = self._get_kernel().spawn_agent(name=args.name, task=args.task,
+                                                  priority=args.priority)
         print(f"Agent 创建成功: {agent_id}")
         return 0
     
     def _cmd_list(self, args):
         """列出 Agent"""
-        from ..core import AgentOSKernel
-        kernel = AgentOSKernel()
-        agents = kernel.list_agents()
+        agents = self._get_kernel().list_agents()
+        if args.json:
+            print(json.dumps([a.to_dict() for a in agents], indent=2, ensure_ascii=False))
+            return 0
         print(f"{'ID':<8} {'Name':<20} {'Status':<12}")
         for a in agents:
-            print(f"{a.get('agent_id', 'N/A'):<8} {a.get('name', 'N/A'):<20} {a.get('status', 'unknown'):<12}")
+            print(f"{a.pid[:8]:<8} {a.name[:20]:<20} {a.state:<12}")
+        return 0
+    
+    def _cmd_ps(self, args):
+        """显示进程表"""
+        kernel = self._get_kernel()
+        running_pids = {p.pid for p in kernel.scheduler.running_processes()}
+        agents = kernel.list_agents()
+        if args.running:
+            agents = [a for a in agents if a.pid in running_pids]
+        
+        print(f"{'PID':<10} {'Name':<20} {'State':<12} {'Prio':>4} {'Tokens':>8} {'Errors':>6}")
+        for a in agents:
+            marker = "*" if a.pid in running_pids else " "
+            print(f"{a.pid[:8]:<8}{marker:<2} {a.name[:20]:<20} {a.state:<12} "
+                  f"{a.priority:>4} {a.token_usage:>8} {a.error_count:>6}")
         return 0
     
     def _cmd_delete(self, args):
@@ -163,6 +223,73 @@ class CLI:
         print(f"时间: {datetime.now(timezone.utc).isoformat()}")
         print("内核: 运行中")
         return 0
+    
+    def _get_kernel(self):
+        """获取内核实例（指定 --storage-url 时从存储恢复上次的调度器状态）"""
+        if self.kernel is None:
+            from ..kernel import AgentOSKernel, KernelConfig
+            if self.storage_url:
+                config = KernelConfig(storage_url=self.storage_url,
+                                      restore_scheduler_state=True)
+            else:
+                config = None
+            self.kernel = AgentOSKernel(config=config)
+        return self.kernel
+    
+    def _cmd_checkpoint(self, args):
+        """创建检查点"""
+        from ..core.exceptions import AgentOSKernelError
+        kernel = self._get_kernel()
+        try:
+            checkpoint_id = kernel.create_checkpoint(args.pid, description=args.desc)
+        except AgentOSKernelError as e:
+            print(f"创建检查点失败: {e}", file=sys.stderr)
+            return 1
+        print(checkpoint_id)
+        return 0
+    
+    def _cmd_restore(self, args):
+        """从检查点恢复"""
+        from ..core.exceptions import AgentOSKernelError
+        kernel = self._get_kernel()
+        try:
+            new_pid = kernel.restore_checkpoint(str(args.checkpoint_id))
+        except AgentOSKernelError as e:
+            print(f"恢复失败: {e}", file=sys.stderr)
+            return 1
+        print(new_pid)
+        return 0
+    
+    def _cmd_dump(self, args):
+        """导出内核状态"""
+        if args.url:
+            import urllib.request
+            url = args.url.rstrip("/") + "/api/v1/debug/dump"
+            try:
+                with urllib.request.urlopen(url, timeout=30) as response:
+                    snapshot = json.load(response)
+            except (OSError, ValueError) as e:
+                print(f"获取内核状态失败: {e}", file=sys.stderr)
+                return 1
+        else:
+            snapshot = self._get_kernel().export_snapshot()
+        
+        document = json.dumps(snapshot, indent=2, ensure_ascii=False, default=str)
+        if args.output:
+            with open(args.output, "w", encoding="utf-8") as f:
+                f.write(document)
+            print(f"已写入: {args.output}")
+        else:
+            print(document)
+        return 0
+
+
+def _parse_checkpoint_id(value: str) -> uuid.UUID:
+    """解析检查点 ID（必须是合法 UUID）"""
+    try:
+        return uuid.UUID(value)
+    except ValueError:
+        raise argparse.ArgumentTypeError(f"无效的检查点 ID（需要 UUID）: {value!r}")
 
 
 def main():
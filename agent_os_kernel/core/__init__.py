@@ -136,6 +136,11 @@ from .cache_utils import (
     TTLCache,
 )
 
+# === cancellation ===
+from .cancellation import (
+    CancellationToken,
+)
+
 # === checkpointer ===
 from .checkpointer import (
     CheckpointStatus,
@@ -153,6 +158,16 @@ from .circuit_breaker import (
     CircuitBreakerManager,
 )
 
+# === clock ===
+from .clock import (
+    Clock,
+    SystemClock,
+    MockClock,
+    IdGenerator,
+    UUIDGenerator,
+    SequentialIdGenerator,
+)
+
 # === command_pattern ===
 from .command_pattern import (
     CommandStatus,
@@ -204,10 +219,22 @@ from .connection_pool import (
 # === context_manager ===
 from .context_manager import (
     PageStatus,
+    ContentType,
+    OverflowStrategy,
+    Evictability,
+    ContextSnapshot,
     ContextPage,
+    AccessRecord,
+    ContextStats,
+    ContextFitReport,
+    VacuumReport,
+    EvictionEvent,
+    EvictionSubscription,
     MemoryHierarchy,
     KVCacheOptimizer,
     SemanticImportanceCalculator,
+    ImportanceScorer,
+    PageTypeImportanceScorer,
     ContextManager,
 )
 
@@ -318,17 +345,23 @@ from .exceptions import (
     AgentCreationError,
     AgentExecutionError,
     AgentTimeoutError,
+    AgentCancelledError,
     ContextError,
     ContextOverflowError,
+    ContextBudgetExceededError,
     ContextNotFoundError,
     PageFaultError,
     StorageError,
     StorageConnectionError,
     StorageOperationError,
+    StorageTimeoutError,
     CheckpointError,
+    CheckpointNotFoundError,
+    InvalidStateError,
     SchedulerError,
     SchedulerFullError,
     SchedulingError,
+    QuotaExceededError,
     TaskError,
     TaskTimeoutError,
     SecurityError,
@@ -336,6 +369,7 @@ from .exceptions import (
     SandboxViolationError,
     ValidationError,
     ConfigurationError,
+    TemplateVariableError,
     ErrorHandler,
     retry,
 )
@@ -377,6 +411,11 @@ from .logging_system import (
     LogLevel,
     LogRecord,
     StructuredLogger,
+    log_context,
+    current_log_context,
+    install_log_context,
+    LogContextDefaults,
+    traced,
 )
 
 # === memory_feedback ===
@@ -400,6 +439,7 @@ from .message_queue import (
 from .metrics import (
     MetricType,
     Metric,
+    MetricsBatch,
     MetricsCollector,
 )
 
@@ -493,6 +533,11 @@ from .plugin_system import (
     PluginManager,
 )
 
+# === prompt_template ===
+from .prompt_template import (
+    PromptTemplate,
+)
+
 # === rate_bucket ===
 from .rate_bucket import (
     TokenBucketConfig,
@@ -531,17 +576,26 @@ from .retry_mechanism import (
 # === scheduler ===
 from .scheduler import (
     AgentState,
+    TaskQueueStatus,
     ResourceQuota,
+    WaitReasonKind,
+    WaitReason,
     AgentProcess,
     SchedulableProcess,
     IPCChannel,
     ResourceQuotaManager,
+    SimEventKind,
+    SimEvent,
+    SimProcessStats,
+    SimReport,
+    SchedulerStats,
     AgentScheduler,
 )
 
 # === security ===
 from .security import (
     PermissionLevel,
+    SecurityViolationType,
     SecurityPolicy,
     SandboxManager,
     PermissionManager,
@@ -584,12 +638,17 @@ from .state_machine import (
 # === storage ===
 from .storage import (
     StorageStats,
+    PoolStats,
+    CheckpointInfo,
     StorageInterface,
     MemoryStorage,
     FileStorage,
+    Migration,
+    SCHEMA_MIGRATIONS,
     PostgreSQLStorage,
     VectorStorage,
     StorageManager,
+    CachedStorage,
 )
 
 # === storage_enhanced ===
@@ -653,6 +712,14 @@ from .time_window import (
     WindowStats,
 )
 
+# === tokenizer ===
+from .tokenizer import (
+    Tokenizer,
+    HeuristicTokenizer,
+    TiktokenTokenizer,
+    default_tokenizer,
+)
+
 # === tool_market ===
 from .tool_market import (
     ToolInfo,
@@ -690,6 +757,7 @@ from .types import (
     AgentState,
     PageType,
     StorageBackend,
+    SecuritySeverity,
     ToolCategory,
     ResourceQuota,
     ToolParameter,
@@ -707,6 +775,14 @@ from .validation_utils import (
     SchemaValidator,
 )
 
+# === wal ===
+from .wal import (
+    WALRecord,
+    WriteAheadLog,
+    InMemoryWriteAheadLog,
+    FileWriteAheadLog,
+)
+
 # === worker ===
 from .worker import (
     WorkerStatus,
@@ -808,6 +884,7 @@ __all__ = [
     "CacheWarmer",
     "LRUCache",
     "TTLCache",
+    "CancellationToken",
     "CheckpointStatus",
     "Checkpoint",
     "Checkpointer",
@@ -817,6 +894,12 @@ __all__ = [
     "CircuitConfig",
     "CircuitBreaker",
     "CircuitBreakerManager",
+    "Clock",
+    "SystemClock",
+    "MockClock",
+    "IdGenerator",
+    "UUIDGenerator",
+    "SequentialIdGenerator",
     "CommandStatus",
     "CommandContext",
     "Command",
@@ -846,10 +929,22 @@ __all__ = [
     "Connection",
     "ConnectionPool",
     "PageStatus",
+    "ContentType",
+    "OverflowStrategy",
+    "Evictability",
+    "ContextSnapshot",
     "ContextPage",
+    "AccessRecord",
+    "ContextStats",
+    "ContextFitReport",
+    "VacuumReport",
+    "EvictionEvent",
+    "EvictionSubscription",
     "MemoryHierarchy",
     "KVCacheOptimizer",
     "SemanticImportanceCalculator",
+    "ImportanceScorer",
+    "PageTypeImportanceScorer",
     "ContextManager",
     "CostEntry",
     "CostLimit",
@@ -916,17 +1011,23 @@ __all__ = [
     "AgentCreationError",
     "AgentExecutionError",
     "AgentTimeoutError",
+    "AgentCancelledError",
     "ContextError",
     "ContextOverflowError",
+    "ContextBudgetExceededError",
     "ContextNotFoundError",
     "PageFaultError",
     "StorageError",
     "StorageConnectionError",
     "StorageOperationError",
+    "StorageTimeoutError",
     "CheckpointError",
+    "CheckpointNotFoundError",
+    "InvalidStateError",
     "SchedulerError",
     "SchedulerFullError",
     "SchedulingError",
+    "QuotaExceededError",
     "TaskError",
     "TaskTimeoutError",
     "SecurityError",
@@ -934,6 +1035,7 @@ __all__ = [
     "SandboxViolationError",
     "ValidationError",
     "ConfigurationError",
+    "TemplateVariableError",
     "ErrorHandler",
     "retry",
     "GPUInfo",
@@ -959,6 +1061,11 @@ __all__ = [
     "LogLevel",
     "LogRecord",
     "StructuredLogger",
+    "log_context",
+    "current_log_context",
+    "install_log_context",
+    "LogContextDefaults",
+    "traced",
     "FeedbackType",
     "MemoryFeedback",
     "MemoryFeedbackSystem",
@@ -970,6 +1077,7 @@ __all__ = [
     "MessageBroker",
     "MetricType",
     "Metric",
+    "MetricsBatch",
     "MetricsCollector",
     "MetricType",
     "ExportFormat",
@@ -1025,6 +1133,7 @@ __all__ = [
     "PluginEvent",
     "PluginBase",
     "PluginManager",
+    "PromptTemplate",
     "TokenBucketConfig",
     "TokenBucket",
     "AsyncTokenBucket",
@@ -1046,12 +1155,20 @@ __all__ = [
     "RetryMechanism",
     "AgentState",
     "ResourceQuota",
+    "WaitReasonKind",
+    "WaitReason",
     "AgentProcess",
     "SchedulableProcess",
     "IPCChannel",
     "ResourceQuotaManager",
+    "SimEventKind",
+    "SimEvent",
+    "SimProcessStats",
+    "SimReport",
+    "SchedulerStats",
     "AgentScheduler",
     "PermissionLevel",
+    "SecurityViolationType",
     "SecurityPolicy",
     "SandboxManager",
     "PermissionManager",
@@ -1078,12 +1195,17 @@ __all__ = [
     "HierarchicalStateMachine",
     "ParallelStateMachine",
     "StorageStats",
+    "PoolStats",
+    "CheckpointInfo",
     "StorageInterface",
     "MemoryStorage",
     "FileStorage",
+    "Migration",
+    "SCHEMA_MIGRATIONS",
     "PostgreSQLStorage",
     "VectorStorage",
     "StorageManager",
+    "CachedStorage",
     "StorageRole",
     "StorageStats",
     "EnhancedStorageManager",
@@ -1117,6 +1239,10 @@ __all__ = [
     "FixedWindow",
     "WindowMerger",
     "WindowStats",
+    "Tokenizer",
+    "HeuristicTokenizer",
+    "TiktokenTokenizer",
+    "default_tokenizer",
     "ToolInfo",
     "ToolMarket",
     "ToolStatus",
@@ -1136,6 +1262,7 @@ __all__ = [
     "AgentState",
     "PageType",
     "StorageBackend",
+    "SecuritySeverity",
     "ToolCategory",
     "ResourceQuota",
     "ToolParameter",
@@ -1147,6 +1274,10 @@ __all__ = [
     "ValidationResult",
     "Validator",
     "SchemaValidator",
+    "WALRecord",
+    "WriteAheadLog",
+    "InMemoryWriteAheadLog",
+    "FileWriteAheadLog",
     "WorkerStatus",
     "Worker",
     "WorkerPool",
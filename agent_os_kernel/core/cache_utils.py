@@ -49,9 +49,14 @@ class LRUCache:
             return True
         return False
     
-    def clear(self) -> None:
-        self._cache.clear()
-        self._order.clear()
+    def clear(self, prefix: Optional[str] = None) -> None:
+        """清除缓存；指定 prefix 时只清除以其开头的键"""
+        if prefix is None:
+            self._cache.clear()
+            self._order.clear()
+            return
+        for key in [k for k in self._order if k.startswith(prefix)]:
+            self._remove(key)
     
     def _remove(self, key: str) -> None:
         del self._cache[key]
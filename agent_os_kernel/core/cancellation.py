@@ -0,0 +1,122 @@
+"""
+取消令牌 - 中断长时间运行的 Agent
+
+内核为每个 Agent 进程创建一个 CancellationToken，进程被终止时触发取消。
+Agent 在迭代之间检查令牌，并用 race() 包装 LLM 调用和工具调用等
+可能耗时数十秒的协程，取消时这些协程会被立即中断。
+
+令牌是线程安全的：调度线程和 API 线程都可以调用 cancel()。
+"""
+
+import asyncio
+import logging
+import threading
+from typing import Any, Awaitable, Callable, List, Optional
+
+from .exceptions import AgentCancelledError
+
+logger = logging.getLogger(__name__)
+
+
+class CancellationToken:
+    """协作式取消令牌"""
+
+    def __init__(self):
+        self._event = threading.Event()
+        self._lock = threading.Lock()
+        self._callbacks: List[Callable[[], Any]] = []
+        self.reason: Optional[str] = None
+
+    @property
+    def cancelled(self) -> bool:
+        """是否已取消"""
+        return self._event.is_set()
+
+    def cancel(self, reason: str = "cancelled") -> bool:
+        """
+        触发取消
+
+        Args:
+            reason: 取消原因
+
+        Returns:
+            是否为首次取消（重复取消返回 False）
+        """
+        with self._lock:
+            if self._event.is_set():
+                return False
+            self.reason = reason
+            self._event.set()
+            callbacks, self._callbacks = self._callbacks, []
+        for callback in callbacks:
+            try:
+                callback()
+            except Exception as e:
+                logger.error(f"Error in cancellation callback: {e}")
+        return True
+
+    def add_callback(self, callback: Callable[[], Any]) -> Callable[[], None]:
+        """
+        注册取消回调（已取消时立即调用）
+
+        Args:
+            callback: 无参回调
+
+        Returns:
+            注销该回调的函数
+        """
+        with self._lock:
+            if not self._event.is_set():
+                self._callbacks.append(callback)
+                return lambda: self._remove_callback(callback)
+        callback()
+        return lambda: None
+
+    def _remove_callback(self, callback: Callable[[], Any]):
+        with self._lock:
+            if callback in self._callbacks:
+                self._callbacks.remove(callback)
+
+    def raise_if_cancelled(self):
+        """
+        已取消时抛出异常
+
+        Raises:
+            AgentCancelledError: 令牌已取消
+        """
+        if self._event.is_set():
+            raise AgentCancelledError(self.reason or "cancelled",
+                                      details={'reason': self.reason})
+
+    def wait(self, timeout: Optional[float] = None) -> bool:
+        """阻塞等待取消，返回是否已取消"""
+        return self._event.wait(timeout)
+
+    async def race(self, awaitable: Awaitable) -> Any:
+        """
+        运行协程，取消时立即中断
+
+        Args:
+            awaitable: 要运行的协程（如 LLM 调用）
+
+        Returns:
+            协程结果
+
+        Raises:
+            AgentCancelledError: 运行前或运行中令牌被取消
+        """
+        if self.cancelled and asyncio.iscoroutine(awaitable):
+            awaitable.close()
+        self.raise_if_cancelled()
+        task = asyncio.ensure_future(awaitable)
+        loop = asyncio.get_running_loop()
+        unregister = self.add_callback(lambda: loop.call_soon_threadsafe(task.cancel))
+        try:
+            return await task
+        except asyncio.CancelledError:
+            if self.cancelled:
+                raise AgentCancelledError(self.reason or "cancelled",
+                                          details={'reason': self.reason}) from None
+            raise
+        finally:
+            unregister()
@@ -0,0 +1,109 @@
+"""
+时钟与 ID 生成器 - 可注入的时间源和标识符来源
+
+ContextManager 和 AgentScheduler 通过这里的抽象获取当前时间和新 ID，
+测试中注入 MockClock / SequentialIdGenerator 即可得到确定性的
+换出顺序、截止时间判断和页面 ID。
+
+Clock 和 IdGenerator 的实例本身可调用，因此也可以直接传给只接受
+Callable[[], float] / Callable[[], str] 的旧接口。
+"""
+
+import itertools
+import time
+import uuid
+from abc import ABC, abstractmethod
+
+
+class Clock(ABC):
+    """时间源接口"""
+
+    @abstractmethod
+    def now(self) -> float:
+        """返回当前时间（Unix 时间戳，秒）"""
+        pass
+
+    def __call__(self) -> float:
+        return self.now()
+
+
+class SystemClock(Clock):
+    """系统时钟（生产环境默认）"""
+
+    def now(self) -> float:
+        return time.time()
+
+
+class MockClock(Clock):
+    """
+    手动推进的时钟（用于测试）
+
+    时间只在调用 advance() / set() 时变化。
+    """
+
+    def __init__(self, start: float = 0.0):
+        self._now = start
+
+    def now(self) -> float:
+        return self._now
+
+    def advance(self, seconds: float) -> float:
+        """
+        时间前进
+
+        Args:
+            seconds: 前进的秒数
+
+        Returns:
+            前进后的时间
+
+        Raises:
+            ValueError: seconds 为负数
+        """
+        if seconds < 0:
+            raise ValueError(f"cannot move clock backwards by {seconds}")
+        self._now += seconds
+        return self._now
+
+    def set(self, timestamp: float):
+        """将时间设为指定值"""
+        self._now = timestamp
+
+
+class IdGenerator(ABC):
+    """ID 生成器接口"""
+
+    @abstractmethod
+    def new_id(self) -> str:
+        """生成一个新的唯一 ID"""
+        pass
+
+    def __call__(self) -> str:
+        return self.new_id()
+
+
+class UUIDGenerator(IdGenerator):
+    """随机 UUID4（生产环境默认）"""
+
+    def new_id(self) -> str:
+        return str(uuid.uuid4())
+
+
+class SequentialIdGenerator(IdGenerator):
+    """
+    顺序 ID 生成器（用于测试）
+
+    依次生成 "{prefix}{n:08d}"，如 id-00000001、id-00000002。
+    定长编号保证按字符串排序与生成顺序一致。
+    """
+
+    def __init__(self, prefix: str = "id-", start: int = 1):
+        self.prefix = prefix
+        self._counter = itertools.count(start)
+
+    def new_id(self) -> str:
+        return f"{self.prefix}{next(self._counter):08d}"
+
+
+SYSTEM_CLOCK = SystemClock()
+UUID_GENERATOR = UUIDGenerator()
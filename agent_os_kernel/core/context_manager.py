@@ -15,23 +15,34 @@ Context Manager - 上下文管理器
 3. 需要内存层次结构（L1/L2/RAM/Disk - DeepSeek Engram 论文）
 """
 
+import asyncio
+import copy
 import uuid
 import time
 import heapq
+import hashlib
+import inspect
+import json
 import logging
-from typing import Optional, Dict, Any, List, Set, Tuple, Callable
-from collections import defaultdict
-from dataclasses import dataclass, field
+import math
+import random
+import re
+import threading
+from abc import ABC, abstractmethod
+from concurrent.futures import ThreadPoolExecutor
+from typing import Optional, Dict, Any, List, Set, Tuple, Callable, Iterator
+from collections import defaultdict, deque
+from itertools import islice
+from dataclasses import asdict, dataclass, field
 from enum import Enum
 
+from .clock import Clock, IdGenerator, SYSTEM_CLOCK, UUID_GENERATOR
+from .exceptions import ContextOverflowError, ContextBudgetExceededError, ConfigurationError
+from .tokenizer import Tokenizer, default_tokenizer
+from .wal import WriteAheadLog
+
 
 logger = logging.getLogger(__name__)
-# 尝试导入 tiktoken 用于精确 token 计算
-try:
-    import tiktoken
-    HAS_TIKTOKEN = True
-except ImportError:
-    HAS_TIKTOKEN = False
 
 
 class PageStatus(Enum):
@@ -41,6 +52,55 @@ class PageStatus(Enum):
     DIRTY = "dirty"              # 已修改但未写回
 
 
+class ContentType(Enum):
+    """页面内容类型"""
+    TEXT = "text"                # 纯文本
+    MARKDOWN = "markdown"        # Markdown 文本
+    IMAGE_URL = "image_url"      # 图片引用（content 为 URL 或 data URI）
+    TOOL_JSON = "tool_json"      # 工具调用的 JSON 结果
+    
+    def is_text(self) -> bool:
+        """是否可以直接作为文本拼接进上下文"""
+        return self in (ContentType.TEXT, ContentType.MARKDOWN)
+
+
+class OverflowStrategy(Enum):
+    """上下文超出 token 预算时的处理策略"""
+    PRIORITY = "priority"        # 按重要性优先预留预算，丢弃低优先级页面
+    COMPRESS = "compress"        # 将最旧的页面压缩为摘要
+
+
+class Evictability(Enum):
+    """页面类型的换出保护级别"""
+    NEVER = "never"              # 永不换出
+    NORMAL = "normal"            # 按 LRU + 重要性换出（importance >= 0.95 的页面除外）
+    ALWAYS = "always"            # 优先换出，不受重要性保护
+
+
+# 默认的按页面类型保护级别（未列出的类型为 NORMAL）
+DEFAULT_EVICTION_PROTECTION: Dict[str, Evictability] = {
+    'system': Evictability.NEVER,
+    'task': Evictability.NEVER,
+    'tool_result': Evictability.ALWAYS,
+}
+
+
+# allocate_message 在页面 metadata 中保留的消息字段
+MESSAGE_METADATA_KEYS = ('name', 'tool_call_id', 'tool_calls')
+
+# 消息角色对应的页面类型（未列出的角色为 working）
+MESSAGE_PAGE_TYPES: Dict[str, str] = {
+    'system': 'system',
+    'tool': 'tool_result',
+}
+
+# 没有记录角色的页面按类型对应的消息角色（未列出的类型为 user）
+PAGE_TYPE_ROLES: Dict[str, str] = {
+    'system': 'system',
+    'tools': 'system',
+}
+
+
 @dataclass
 class ContextPage:
     """
@@ -53,18 +113,23 @@ class ContextPage:
         tokens: Token 数（估算）
         importance_score: 重要性评分 0-1
         page_type: 页面类型（system/tools/user/task/memory）
+        content_type: 内容类型（text/markdown/image_url/tool_json）
         status: 当前状态
         access_count: 访问次数
         last_accessed: 最后访问时间
         created_at: 创建时间
+        ttl: 存活时间（秒），到期后直接丢弃而不换出；None 表示永不过期
         embedding: 语义嵌入向量（可选）
         metadata: 额外元数据
+        chunk_group: 大文档分块后各分块共享的分组 ID（未分块时为 None）
+        chunk_index: 分块在原文中的序号（从 0 开始）
     """
     agent_pid: str
     content: str
     tokens: int = 0
     importance_score: float = 0.5
     page_type: str = "general"  # system, tools, user, task, memory, working
+    content_type: ContentType = ContentType.TEXT
     status: PageStatus = PageStatus.IN_MEMORY
     
     # 内部字段
@@ -72,16 +137,19 @@ class ContextPage:
     access_count: int = 0
     last_accessed: float = field(default_factory=time.time)
     created_at: float = field(default_factory=time.time)
+    ttl: Optional[float] = None
     embedding: Optional[List[float]] = None
     metadata: Dict[str, Any] = field(default_factory=dict)
+    chunk_group: Optional[str] = None
+    chunk_index: int = 0
     
     # 脏页追踪
     _dirty: bool = False
     
-    def touch(self):
+    def touch(self, now: Optional[float] = None):
         """访问页面（更新访问统计）"""
         self.access_count += 1
-        self.last_accessed = time.time()
+        self.last_accessed = time.time() if now is None else now
     
     def mark_dirty(self):
         """标记为脏页"""
@@ -98,6 +166,14 @@ class ContextPage:
         """是否脏页"""
         return self._dirty
     
+    def is_expired(self, current_time: Optional[float] = None) -> bool:
+        """是否已超过存活时间（未设置 TTL 的页面永不过期）"""
+        if self.ttl is None:
+            return False
+        if current_time is None:
+            current_time = time.time()
+        return current_time >= self.created_at + self.ttl
+    
     def get_lru_score(self, current_time: Optional[float] = None) -> float:
         """
         获取 LRU 分数（越高表示越不常用）
@@ -120,12 +196,16 @@ class ContextPage:
             'tokens': self.tokens,
             'importance_score': self.importance_score,
             'page_type': self.page_type,
+            'content_type': self.content_type.value,
             'status': self.status.value,
             'access_count': self.access_count,
             'last_accessed': self.last_accessed,
             'created_at': self.created_at,
+            'ttl': self.ttl,
             'embedding': self.embedding,
             'metadata': self.metadata,
+            'chunk_group': self.chunk_group,
+            'chunk_index': self.chunk_index,
         }
     
     @classmethod
@@ -137,15 +217,248 @@ class ContextPage:
             tokens=data['tokens'],
             importance_score=data['importance_score'],
             page_type=data['page_type'],
+            content_type=ContentType(data.get('content_type', ContentType.TEXT.value)),
             status=PageStatus(data['status']),
             page_id=data['page_id'],
             access_count=data['access_count'],
             last_accessed=data['last_accessed'],
             created_at=data['created_at'],
+            ttl=data.get('ttl'),
             embedding=data.get('embedding'),
             metadata=data.get('metadata', {}),
+            chunk_group=data.get('chunk_group'),
+            chunk_index=data.get('chunk_index', 0),
         )
         return page
+    
+    def to_message_part(self) -> Dict[str, Any]:
+        """
+        转换为 OpenAI 风格的消息内容片段
+        
+        文本页面生成 text 片段，图片页面生成 image_url 片段，
+        工具 JSON 以紧凑 JSON 文本的形式生成 text 片段。
+        """
+        if self.content_type == ContentType.IMAGE_URL:
+            return {'type': 'image_url', 'image_url': {'url': self.content}}
+        if self.content_type == ContentType.TOOL_JSON:
+            try:
+                text = json.dumps(json.loads(self.content), ensure_ascii=False,
+                                  separators=(',', ':'))
+            except (TypeError, ValueError):
+                text = self.content
+            return {'type': 'text', 'text': text}
+        return {'type': 'text', 'text': self.content}
+    
+    def to_message(self) -> Dict[str, Any]:
+        """
+        转换为 OpenAI 风格的完整消息
+        
+        角色及 name / tool_call_id / tool_calls 取自 metadata（由
+        allocate_message 写入）；没有记录角色时按 PAGE_TYPE_ROLES 决定。
+        """
+        message: Dict[str, Any] = {
+            'role': self.metadata.get('role', PAGE_TYPE_ROLES.get(self.page_type, 'user')),
+            'content': self.content,
+        }
+        for key in MESSAGE_METADATA_KEYS:
+            if self.metadata.get(key) is not None:
+                message[key] = self.metadata[key]
+        return message
+
+
+@dataclass
+class AccessRecord:
+    """页面访问记录（用于诊断页面抖动）"""
+    page_id: str
+    hit: bool          # True=命中内存，False=缺页
+    timestamp: float = field(default_factory=time.time)
+
+
+@dataclass
+class ContextStats:
+    """
+    上下文管理器统计（get_stats 的稳定结构）
+    
+    新增字段只能追加，不修改已有字段的名称和类型。
+    """
+    page_faults: int
+    swaps_in: int
+    swaps_out: int
+    total_accesses: int
+    cache_hits: int
+    dedup_hits: int
+    context_overflows: int
+    pages_expired: int
+    current_usage: int
+    max_tokens: int
+    usage_percent: float
+    pages_in_memory: int
+    pages_swapped: int
+    total_agents: int
+    cache_hit_rate: float
+    kv_cache_stats: Dict[str, Any] = field(default_factory=dict)
+    
+    def to_dict(self) -> Dict[str, Any]:
+        """序列化为字典"""
+        return asdict(self)
+
+
+@dataclass
+class ContextFitReport:
+    """
+    上下文超出模型上限时的压缩记录
+    
+    Attributes:
+        model_limit: 目标模型的上下文上限（tokens）
+        original_tokens: 压缩前的 token 数
+        final_tokens: 压缩后的 token 数
+        removed_pages: 被压缩进摘要或丢弃的页面 ID
+        summary_pages: 插入的摘要页面数
+    """
+    model_limit: int
+    original_tokens: int
+    final_tokens: int
+    removed_pages: List[str] = field(default_factory=list)
+    summary_pages: int = 0
+    
+    @property
+    def compressed(self) -> bool:
+        return self.final_tokens < self.original_tokens
+
+
+@dataclass
+class VacuumReport:
+    """
+    vacuum() 的清理结果
+    
+    Attributes:
+        orphaned_pages: 丢弃的、所属 Agent 已不存在的换出页面数
+        offloaded_pages: 写入存储后从内存移除的冷页面数
+        freed_bytes: 释放的页面内容字节数（UTF-8）
+    """
+    orphaned_pages: int = 0
+    offloaded_pages: int = 0
+    freed_bytes: int = 0
+    
+    @property
+    def freed_pages(self) -> int:
+        return self.orphaned_pages + self.offloaded_pages
+
+
+@dataclass
+class EvictionEvent:
+    """
+    页面被换出时发布的事件
+    
+    Attributes:
+        page_id: 被换出的页面 ID
+        agent_pid: 页面所属 Agent
+        page_type: 页面类型
+        tokens: 页面 token 数
+        timestamp: 换出时间
+    """
+    page_id: str
+    agent_pid: str
+    page_type: str
+    tokens: int
+    timestamp: float
+
+
+class EvictionSubscription:
+    """
+    换出事件订阅（有界广播队列）
+    
+    发布永不阻塞：队列满时丢弃最旧的事件并累加 lagged，
+    因此处理缓慢的订阅者不会拖慢换出。
+    """
+    
+    def __init__(self, capacity: int):
+        self._events: deque = deque(maxlen=capacity)
+        self._cond = threading.Condition()
+        self.lagged = 0
+        self.closed = False
+    
+    def _publish(self, event: EvictionEvent):
+        with self._cond:
+            if len(self._events) == self._events.maxlen:
+                self.lagged += 1
+            self._events.append(event)
+            self._cond.notify()
+    
+    def get(self, timeout: Optional[float] = None) -> Optional[EvictionEvent]:
+        """
+        取出下一个事件
+        
+        Args:
+            timeout: 最长等待秒数（None 表示一直等待，0 表示不等待）
+        
+        Returns:
+            事件；超时或订阅已关闭且没有剩余事件时返回 None
+        """
+        with self._cond:
+            self._cond.wait_for(lambda: self._events or self.closed, timeout)
+            return self._events.popleft() if self._events else None
+    
+    def drain(self) -> List[EvictionEvent]:
+        """取出所有已到达的事件（不等待）"""
+        with self._cond:
+            events = list(self._events)
+            self._events.clear()
+            return events
+    
+    def close(self):
+        """关闭订阅，唤醒等待中的 get"""
+        with self._cond:
+            self.closed = True
+            self._cond.notify_all()
+
+
+@dataclass
+class ContextSnapshot:
+    """
+    上下文管理器的完整状态快照（可 JSON 序列化）
+    
+    Attributes:
+        pages: 内存中的页面
+        swapped_pages: 已换出的页面
+        agent_pages: 每个 Agent 的页面 ID 列表（保持上下文顺序）
+        token_usage: 导出时内存中的 token 总数
+        stats: 统计计数器
+        created_at: 快照时间
+        wal_seq: 快照已包含的最后一条 WAL 记录序号（未启用 WAL 时为 0）
+    """
+    pages: List[ContextPage] = field(default_factory=list)
+    swapped_pages: List[ContextPage] = field(default_factory=list)
+    agent_pages: Dict[str, List[str]] = field(default_factory=dict)
+    token_usage: int = 0
+    stats: Dict[str, int] = field(default_factory=dict)
+    created_at: float = field(default_factory=time.time)
+    wal_seq: int = 0
+    
+    def to_dict(self) -> Dict[str, Any]:
+        """序列化为字典"""
+        return {
+            'pages': [p.to_dict() for p in self.pages],
+            'swapped_pages': [p.to_dict() for p in self.swapped_pages],
+            'agent_pages': {pid: list(ids) for pid, ids in self.agent_pages.items()},
+            'token_usage': self.token_usage,
+            'stats': dict(self.stats),
+            'created_at': self.created_at,
+            'wal_seq': self.wal_seq,
+        }
+    
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> 'ContextSnapshot':
+        """从字典反序列化"""
+        return cls(
+            pages=[ContextPage.from_dict(p) for p in data.get('pages', [])],
+            swapped_pages=[ContextPage.from_dict(p) for p in data.get('swapped_pages', [])],
+            agent_pages={pid: list(ids) for pid, ids in data.get('agent_pages', {}).items()},
+            token_usage=data.get('token_usage', 0),
+            stats=dict(data.get('stats', {})),
+            created_at=data.get('created_at', time.time()),
+            wal_seq=data.get('wal_seq', 0),
+        )
 
 
 class MemoryHierarchy:
@@ -286,6 +599,31 @@ class KVCacheOptimizer:
         # 组装：L1 -> L2 -> RAM
         return l1_pages + l2_pages + ram_pages
     
+    def iter_layout(self, pages: List[ContextPage]) -> Iterator[ContextPage]:
+        """
+        按 optimize_layout 的顺序惰性产出页面
+        
+        使用堆代替全量排序，只取前 K 个页面时开销为 O(n + K log n)。
+        """
+        l2_heap = []
+        ram_heap = []
+        
+        for index, page in enumerate(pages):
+            if page.page_type == 'system':
+                yield page
+            elif page.page_type in ('tools', 'task'):
+                l2_heap.append((-page.access_count, -page.importance_score, index, page))
+            else:
+                ram_heap.append((-(page.access_count * page.importance_score), index, page))
+        
+        heapq.heapify(l2_heap)
+        while l2_heap:
+            yield heapq.heappop(l2_heap)[-1]
+        
+        heapq.heapify(ram_heap)
+        while ram_heap:
+            yield heapq.heappop(ram_heap)[-1]
+    
     def estimate_cache_hit_rate(self, current_pages: List[ContextPage]) -> float:
         """
         预估缓存命中率
@@ -468,6 +806,58 @@ class SemanticImportanceCalculator:
         return min(base_score, 1.0)
 
 
+class ImportanceScorer(ABC):
+    """
+    页面重要性评分器
+    
+    allocate_page 未指定 importance 时，由 ContextManager 调用评分器
+    为新页面打分，集中管理评分策略。
+    """
+    
+    @abstractmethod
+    def score(self, content: str, page_type: str) -> float:
+        """
+        为页面内容打分
+        
+        Args:
+            content: 页面内容
+            page_type: 页面类型
+        
+        Returns:
+            重要性分数 0-1
+        """
+        pass
+
+
+class PageTypeImportanceScorer(ImportanceScorer):
+    """
+    按页面类型打分的默认评分器
+    
+    默认分数与引入评分器之前各调用方写死的 importance 一致：
+    spawn_agent 的 system/task/tools 页面、record_observation 的工具结果，
+    其余类型沿用 allocate_page 原来的 0.5。
+    """
+    
+    DEFAULT_WEIGHTS: Dict[str, float] = {
+        'system': 1.0,
+        'task': 0.9,
+        'tools': 0.8,
+        'tool_result': 0.7,
+    }
+    
+    def __init__(self, weights: Optional[Dict[str, float]] = None, default: float = 0.5):
+        """
+        Args:
+            weights: 覆盖或补充 DEFAULT_WEIGHTS 的类型分数
+            default: 未列出的页面类型的分数
+        """
+        self.weights = {**self.DEFAULT_WEIGHTS, **(weights or {})}
+        self.default = default
+    
+    def score(self, content: str, page_type: str) -> float:
+        return self.weights.get(page_type, self.default)
+
+
 class ContextManager:
     """
     上下文管理器 - 操作系统级的虚拟内存管理
@@ -507,7 +897,20 @@ class ContextManager:
     def __init__(self, 
                  max_context_tokens: int = 128000,
                  enable_semantic_importance: bool = False,
-                 storage_backend: Optional[Any] = None):
+                 storage_backend: Optional[Any] = None,
+                 access_history_size: int = 256,
+                 per_agent_token_limit: Optional[int] = None,
+                 dedup_pages: bool = False,
+                 tokenizer: Optional[Tokenizer] = None,
+                 overflow_strategy: OverflowStrategy = OverflowStrategy.PRIORITY,
+                 eviction_high_watermark: float = 1.0,
+                 eviction_low_watermark: float = 0.9,
+                 clock: Optional[Clock] = None,
+                 id_generator: Optional[IdGenerator] = None,
+                 importance_scorer: Optional[ImportanceScorer] = None,
+                 eviction_protection: Optional[Dict[str, Evictability]] = None,
+                 wal: Optional[WriteAheadLog] = None,
+                 page_size: Optional[int] = None):
         """
         初始化上下文管理器
         
@@ -515,8 +918,58 @@ class ContextManager:
             max_context_tokens: 最大上下文 token 数（默认 128K）
             enable_semantic_importance: 是否启用语义重要性计算
             storage_backend: 存储后端（用于页面换入换出）
+            access_history_size: 页面访问历史环形缓冲区大小
+            per_agent_token_limit: 单个 Agent 在内存中可占用的最大 token 数
+                                   （None 表示不限制）
+            dedup_pages: 是否对同一 Agent、同一类型的相同内容去重
+            tokenizer: Token 计数器（默认优先使用 tiktoken，否则启发式估计）
+            overflow_strategy: get_agent_context 超出 token 预算时的处理策略
+            eviction_high_watermark: 使用量（占 max_context_tokens 的比例）超过该值时开始换出
+            eviction_low_watermark: 换出开始后一次性降到的使用量比例，减少频繁的小规模换出
+            clock: 时间源（默认系统时钟，测试中可注入 MockClock）
+            id_generator: 页面 ID 生成器（默认 UUID4）
+            importance_scorer: 未指定 importance 时使用的评分器
+                               （默认 PageTypeImportanceScorer）
+            eviction_protection: 按页面类型覆盖 DEFAULT_EVICTION_PROTECTION
+                                 的换出保护级别
+            wal: 预写日志；设置后每次页面分配、更新、换入换出都会追加一条记录，
+                 可用 replay_wal 在重启后恢复（None 表示不记录）
+            page_size: 单个页面的最大 token 数；超出的文本内容在 allocate_page 时
+                       拆分为多个分块页面（None 表示不拆分）
+        
+        Raises:
+            ConfigurationError: 水位线不满足 0 < low < high <= 1.0
+        
+        其他不合法的数值参数会被修正并记录警告（兼容旧行为）；
+        需要严格校验时请使用 ContextManager.create。
         """
+        self._check_watermarks(eviction_high_watermark, eviction_low_watermark)
+        if max_context_tokens <= 0:
+            logger.warning(f"max_context_tokens={max_context_tokens} is not positive, using 1")
+            max_context_tokens = 1
+        if per_agent_token_limit is not None and not 0 < per_agent_token_limit <= max_context_tokens:
+            clamped = min(max(per_agent_token_limit, 1), max_context_tokens)
+            logger.warning(f"per_agent_token_limit={per_agent_token_limit} is out of range, "
+                           f"using {clamped}")
+            per_agent_token_limit = clamped
+        if access_history_size < 0:
+            logger.warning(f"access_history_size={access_history_size} is negative, using 0")
+            access_history_size = 0
+        if page_size is not None and page_size <= 0:
+            logger.warning(f"page_size={page_size} is not positive, disabling chunking")
+            page_size = None
+        
         self.max_context_tokens = max_context_tokens
+        self.eviction_high_watermark = eviction_high_watermark
+        self.eviction_low_watermark = eviction_low_watermark
+        self.overflow_strategy = overflow_strategy
+        self.per_agent_token_limit = per_agent_token_limit
+        self.page_size = page_size
+        self.tokenizer = tokenizer or default_tokenizer()
+        self.clock = clock or SYSTEM_CLOCK
+        self.id_generator = id_generator or UUID_GENERATOR
+        self.importance_scorer = importance_scorer or PageTypeImportanceScorer()
+        self.eviction_protection = {**DEFAULT_EVICTION_PROTECTION, **(eviction_protection or {})}
         self.current_usage = 0
         
         # 页面存储
@@ -535,6 +988,13 @@ class ContextManager:
         self.importance_calculator = SemanticImportanceCalculator()
         self.enable_semantic_importance = enable_semantic_importance
         
+        # 内容去重索引（(agent_pid, page_type, 内容哈希) -> page_id）
+        self.dedup_pages = dedup_pages
+        self._content_index: Dict[Tuple[str, str, str], str] = {}
+        
+        # 设置了 TTL 的页面（expire_pages 只需扫描这些页面）
+        self._expiring_pages: Set[str] = set()
+        
         # 统计
         self.stats = {
             'page_faults': 0,          # 缺页次数
@@ -542,69 +1002,476 @@ class ContextManager:
             'swaps_out': 0,            # 换出次数
             'total_accesses': 0,       # 总访问次数
             'cache_hits': 0,           # 缓存命中
+            'dedup_hits': 0,           # 去重命中
+            'context_overflows': 0,    # 上下文超出预算次数
+            'pages_expired': 0,        # 因 TTL 到期丢弃的页面
         }
         
+        # 最近的页面访问历史（环形缓冲区）
+        self.access_history: deque = deque(maxlen=access_history_size)
+        
+        # 预写日志（_wal_seq 为当前状态已包含的最后一条记录）
+        self.wal = wal
+        self._wal_seq = 0
+        self._replaying = False
+        
+        # 换出事件的订阅者和回调
+        self._eviction_subscriptions: List[EvictionSubscription] = []
+        self._eviction_callbacks: List[Callable[[EvictionEvent], Any]] = []
+        self._eviction_executor: Optional[ThreadPoolExecutor] = None
+        # 执行 async 换出回调的函数（内核会替换为在其自有事件循环中运行）
+        self.coroutine_runner: Callable[[Any], Any] = asyncio.run
+        
         logger.info(f"ContextManager initialized with {max_context_tokens} tokens limit")
     
+    @classmethod
+    def create(cls, **kwargs) -> 'ContextManager':
+        """
+        校验参数后创建上下文管理器
+        
+        与直接构造不同，不合法的参数不会被静默修正，而是抛出描述性错误。
+        内核使用此方法创建上下文管理器。
+        
+        Args:
+            **kwargs: 与 ContextManager.__init__ 相同的参数
+        
+        Returns:
+            上下文管理器
+        
+        Raises:
+            ConfigurationError: 参数不合法（如 max_context_tokens <= 0、
+                                per_agent_token_limit 超过 max_context_tokens）
+        """
+        cls.validate_config(**kwargs)
+        return cls(**kwargs)
+    
+    @classmethod
+    def validate_config(cls,
+                        max_context_tokens: int = 128000,
+                        per_agent_token_limit: Optional[int] = None,
+                        access_history_size: int = 256,
+                        eviction_high_watermark: float = 1.0,
+                        eviction_low_watermark: float = 0.9,
+                        page_size: Optional[int] = None,
+                        **_: Any):
+        """
+        校验上下文管理器参数
+        
+        Raises:
+            ConfigurationError: 参数不合法
+        """
+        if not isinstance(max_context_tokens, int) or max_context_tokens <= 0:
+            raise ConfigurationError(
+                f"max_context_tokens must be a positive integer, got {max_context_tokens!r}"
+            )
+        if per_agent_token_limit is not None:
+            if per_agent_token_limit <= 0:
+                raise ConfigurationError(
+                    f"per_agent_token_limit must be positive, got {per_agent_token_limit}"
+                )
+            if per_agent_token_limit > max_context_tokens:
+                raise ConfigurationError(
+                    f"per_agent_token_limit ({per_agent_token_limit}) exceeds "
+                    f"max_context_tokens ({max_context_tokens})"
+                )
+        if access_history_size < 0:
+            raise ConfigurationError(
+                f"access_history_size must not be negative, got {access_history_size}"
+            )
+        if page_size is not None and page_size <= 0:
+            raise ConfigurationError(f"page_size must be positive, got {page_size}")
+        cls._check_watermarks(eviction_high_watermark, eviction_low_watermark)
+    
+    @staticmethod
+    def _check_watermarks(high: float, low: float):
+        """校验换出水位线：0 < low < high <= 1.0"""
+        if not 0 < low < high <= 1.0:
+            raise ConfigurationError(
+                f"Invalid eviction watermarks: low={low}, high={high} "
+                f"(expected 0 < low < high <= 1.0)"
+            )
+    
     def allocate_page(self, 
                      agent_pid: str, 
                      content: str, 
-                     importance: float = 0.5,
+                     importance: Optional[float] = None,
                      page_type: str = "general",
-                     embedding: Optional[List[float]] = None) -> str:
+                     embedding: Optional[List[float]] = None,
+                     content_type: ContentType = ContentType.TEXT,
+                     ttl: Optional[float] = None,
+                     metadata: Optional[Dict[str, Any]] = None,
+                     page_size: Optional[int] = None) -> str:
         """
         分配新的上下文页面
         
         如果当前使用超过限制，会自动触发页面置换（swap out）。
         
+        文本内容超过 page_size 个 token 时拆分为多个分块页面，各分块共享
+        chunk_group，可以单独换出和换入；get_agent_context 按原顺序拼接
+        仍在上下文中的分块。带 metadata 的页面（如对话消息）不拆分。
+        
         Args:
             agent_pid: Agent 进程 ID
             content: 页面内容
-            importance: 重要性评分 0-1（影响置换决策）
+            importance: 重要性评分 0-1（影响置换决策）；None 时由 importance_scorer 评分
             page_type: 页面类型（system/tools/user/task/memory/working）
             embedding: 语义嵌入向量（可选）
+            content_type: 内容类型（图片等非文本内容以 URL 形式保存）
+            ttl: 存活时间（秒），到期后由 expire_pages 丢弃；None 表示永不过期
+            metadata: 页面元数据；带元数据的页面不参与内容去重
+            page_size: 覆盖管理器的 page_size（None 表示使用管理器设置）
         
         Returns:
-            页面 ID
+            页面 ID（拆分时为第一个分块的 ID，其余分块见 chunk_page_ids）
         
         Raises:
             MemoryError: 如果无法分配（所有页面都不可换出）
         """
+        existing = None if metadata else self._find_duplicate(agent_pid, content, page_type)
+        if existing:
+            return existing
+        
         tokens = self._estimate_tokens(content)
+        if importance is None:
+            importance = self.importance_scorer.score(content, page_type)
         
-        # 检查是否需要换出页面
-        while self.current_usage + tokens > self.max_context_tokens:
-            if not self._swap_out_page():
-                raise ContextOverflowError(
-                    f"Cannot allocate page with {tokens} tokens. "
-                    f"Current usage: {self.current_usage}/{self.max_context_tokens}. "
-                    "All pages are critical and cannot be swapped out."
-                )
+        page_size = page_size or self.page_size
+        if page_size and tokens > page_size and content_type.is_text() and not metadata:
+            return self._allocate_chunks(agent_pid, content, page_size, importance, page_type,
+                                         embedding, content_type, ttl)
+        
+        # 检查 Agent 预算，再检查是否需要换出页面
+        self._enforce_agent_budget(agent_pid, tokens)
+        self._reserve_tokens(tokens)
+        
+        return self._insert_page(agent_pid, content, tokens, importance, page_type, embedding,
+                                 content_type, ttl, metadata)
+    
+    def allocate_message(self,
+                         agent_pid: str,
+                         message: Dict[str, Any],
+                         importance: Optional[float] = None,
+                         ttl: Optional[float] = None) -> str:
+        """
+        将一条对话消息分配为上下文页面
+        
+        角色和 name / tool_call_id / tool_calls 保存在页面 metadata 中，
+        get_agent_messages 按原样还原，使工具调用对话可以完整往返。
+        
+        Args:
+            agent_pid: Agent 进程 ID
+            message: OpenAI 风格的消息字典（可用 Message.to_dict() 生成）
+            importance: 重要性评分 0-1；None 时由 importance_scorer 评分
+            ttl: 存活时间（秒）
+        
+        Returns:
+            页面 ID
+        """
+        role = message['role']
+        metadata = {'role': role}
+        for key in MESSAGE_METADATA_KEYS:
+            if message.get(key) is not None:
+                metadata[key] = message[key]
+        return self.allocate_page(
+            agent_pid=agent_pid,
+            content=message.get('content') or "",
+            importance=importance,
+            page_type=MESSAGE_PAGE_TYPES.get(role, 'working'),
+            ttl=ttl,
+            metadata=metadata,
+        )
+    
+    def _allocate_chunks(self,
+                         agent_pid: str,
+                         content: str,
+                         page_size: int,
+                         importance: float,
+                         page_type: str,
+                         embedding: Optional[List[float]],
+                         content_type: ContentType,
+                         ttl: Optional[float]) -> str:
+        """把超过 page_size 的内容拆分为分块页面，返回第一个分块的 ID"""
+        chunks = [(text, self._estimate_tokens(text))
+                  for text in self._split_into_chunks(content, page_size)]
+        total = sum(tokens for _, tokens in chunks)
+        
+        self._enforce_agent_budget(agent_pid, total)
+        self._reserve_tokens(total)
+        
+        chunk_group = self.id_generator.new_id()
+        page_ids = [
+            self._insert_page(agent_pid, text, tokens, importance, page_type,
+                              embedding if index == 0 else None, content_type, ttl,
+                              chunk_group=chunk_group, chunk_index=index)
+            for index, (text, tokens) in enumerate(chunks)
+        ]
+        logger.debug(f"Split {total} tokens into {len(page_ids)} chunks "
+                     f"(group {chunk_group[:8]}, page_size={page_size})")
+        return page_ids[0]
+    
+    def _split_into_chunks(self, content: str, page_size: int) -> List[str]:
+        """
+        按 token 数拆分文本
+        
+        在空白处切分，每个分块保留词后的空白，分块直接相连即可还原原文。
+        token 计数不一定可加，因此用倍增加二分查找每个分块能容纳的最多词数，
+        每个分块只需 O(log n) 次计数。单个超过 page_size 的词独占一个分块。
+        """
+        segments = re.findall(r'\s*\S+\s*', content) or [content]
+        
+        def fits(start: int, end: int) -> bool:
+            return self._estimate_tokens("".join(segments[start:end])) <= page_size
+        
+        chunks: List[str] = []
+        start = 0
+        while start < len(segments):
+            # 倍增找到第一个放不下的长度，再在 [good, bad) 之间二分
+            good, step = 1, 1
+            while start + good < len(segments) and fits(start, start + good + step):
+                good += step
+                step *= 2
+            bad = min(start + good + step, len(segments) + 1) - start
+            while bad - good > 1:
+                mid = (good + bad) // 2
+                if start + mid <= len(segments) and fits(start, start + mid):
+                    good = mid
+                else:
+                    bad = mid
+            chunks.append("".join(segments[start:start + good]))
+            start += good
+        return chunks
+    
+    def chunk_page_ids(self, page_id: str) -> List[str]:
+        """
+        获取与页面同属一个分块组的全部页面 ID
+        
+        Args:
+            page_id: 任一分块（或普通页面）的 ID
+        
+        Returns:
+            按 chunk_index 排列的页面 ID；普通页面返回 [page_id]，页面不存在时返回 []
+        """
+        page = self.pages_in_memory.get(page_id) or self.swapped_pages.get(page_id)
+        if page is None:
+            return []
+        if page.chunk_group is None:
+            return [page_id]
+        chunks = []
+        for pid in self.agent_pages.get(page.agent_pid, []):
+            other = self.pages_in_memory.get(pid) or self.swapped_pages.get(pid)
+            if other is not None and other.chunk_group == page.chunk_group:
+                chunks.append(other)
+        return [p.page_id for p in sorted(chunks, key=lambda p: p.chunk_index)]
+    
+    def allocate_pages(self,
+                       agent_pid: str,
+                       specs: List[Tuple[str, Optional[float], str]]) -> List[str]:
+        """
+        批量分配上下文页面
+        
+        先一次性为整批页面腾出空间，再插入所有页面，因此同一批次中的
+        页面不会互相触发换出。
+        
+        Args:
+            agent_pid: Agent 进程 ID
+            specs: (content, importance, page_type) 列表，importance 为 None 时
+                   由 importance_scorer 评分
+        
+        Returns:
+            按输入顺序排列的页面 ID 列表
+        
+        Raises:
+            ContextOverflowError: 无法为整批页面腾出空间
+            ContextBudgetExceededError: 整批页面超出该 Agent 的预算
+        """
+        page_ids: List[Optional[str]] = [None] * len(specs)
+        sized = []
+        batch_keys: Dict[Tuple[str, str, str], int] = {}
+        
+        for index, (content, importance, page_type) in enumerate(specs):
+            existing = self._find_duplicate(agent_pid, content, page_type)
+            if existing:
+                page_ids[index] = existing
+                continue
+            if self.dedup_pages:
+                key = self._content_key(agent_pid, page_type, content)
+                if key in batch_keys:
+                    # 同一批次内的重复内容，插入后回填
+                    self.stats['dedup_hits'] += 1
+                    continue
+                batch_keys[key] = index
+            if importance is None:
+                importance = self.importance_scorer.score(content, page_type)
+            sized.append((index, content, self._estimate_tokens(content), importance, page_type))
+        
+        total = sum(tokens for _, _, tokens, _, _ in sized)
+        
+        self._enforce_agent_budget(agent_pid, total)
+        self._reserve_tokens(total)
+        
+        for index, content, tokens, importance, page_type in sized:
+            page_ids[index] = self._insert_page(agent_pid, content, tokens, importance, page_type)
+        
+        for index, (content, _, page_type) in enumerate(specs):
+            if page_ids[index] is None:
+                key = self._content_key(agent_pid, page_type, content)
+                page_ids[index] = page_ids[batch_keys[key]]
+        
+        return page_ids
+    
+    @staticmethod
+    def _content_key(agent_pid: str, page_type: str, content: str) -> Tuple[str, str, str]:
+        """生成去重索引键"""
+        return (agent_pid, page_type, hashlib.sha256(content.encode()).hexdigest())
+    
+    def _find_duplicate(self, agent_pid: str, content: str, page_type: str) -> Optional[str]:
+        """
+        查找相同内容的已有页面（仅在启用去重时生效）
+        
+        命中时更新该页面的最后访问时间并计入 dedup_hits。
+        """
+        if not self.dedup_pages:
+            return None
+        
+        page_id = self._content_index.get(self._content_key(agent_pid, page_type, content))
+        page = self.pages_in_memory.get(page_id) or self.swapped_pages.get(page_id)
+        if not page:
+            return None
+        
+        page.last_accessed = self.clock.now()
+        self.stats['dedup_hits'] += 1
+        logger.debug(f"Dedup hit for agent {agent_pid[:8]}: reusing page {page_id[:8]}")
+        return page_id
+    
+    def _reserve_tokens(self, tokens: int):
+        """
+        为 tokens 个新 token 腾出空间
+        
+        分配后的使用量超过高水位线时开始换出，一次性降到低水位线；
+        无法继续换出时，只要总量不超过 max_context_tokens 仍然允许分配。
+        """
+        high = self.max_context_tokens * self.eviction_high_watermark
+        if self.current_usage + tokens <= high:
+            return
+        
+        low = self.max_context_tokens * self.eviction_low_watermark
+        self._free_tokens(self.current_usage + tokens - low)
+        
+        if self.current_usage + tokens > self.max_context_tokens:
+            raise ContextOverflowError(
+                f"Cannot allocate page with {tokens} tokens. "
+                f"Current usage: {self.current_usage}/{self.max_context_tokens}. "
+                "All pages are critical and cannot be swapped out."
+            )
+    
+    def _agent_memory_usage(self, agent_pid: str) -> int:
+        """Agent 当前在内存中占用的 token 数"""
+        return sum(
+            self.pages_in_memory[page_id].tokens
+            for page_id in self.agent_pages.get(agent_pid, [])
+            if page_id in self.pages_in_memory
+        )
+    
+    def _enforce_agent_budget(self, agent_pid: str, tokens: int):
+        """
+        确保 Agent 分配 tokens 后不超过单 Agent 预算
+        
+        超出时只换出该 Agent 自己的页面（重要性最低的优先），
+        不会影响其他 Agent。先确认换出后能满足预算再换出，分配失败时
+        不会换出任何页面。
+        
+        Raises:
+            ContextBudgetExceededError: 换出自身可换出页面后仍超出预算
+        """
+        limit = self.per_agent_token_limit
+        if limit is None:
+            return
+        
+        usage = self._agent_memory_usage(agent_pid)
+        if usage + tokens <= limit:
+            return
+        
+        own_pages = sorted(
+            (self.pages_in_memory[page_id]
+             for page_id in self.agent_pages.get(agent_pid, [])
+             if page_id in self.pages_in_memory
+             and self._is_evictable(self.pages_in_memory[page_id])),
+            key=lambda p: (self.evictability(p) != Evictability.ALWAYS,
+                           p.importance_score, p.last_accessed)
+        )
         
-        # 创建新页面
+        victims = []
+        for page in own_pages:
+            if usage + tokens <= limit:
+                break
+            victims.append(page)
+            usage -= page.tokens
+        
+        if usage + tokens > limit:
+            raise ContextBudgetExceededError(
+                f"Agent {agent_pid[:8]} would use {usage + tokens} tokens, "
+                f"exceeding its budget of {limit}.",
+                details={'agent_pid': agent_pid, 'requested': tokens, 'limit': limit}
+            )
+        
+        for page in victims:
+            self._evict_page(page)
+    
+    def _insert_page(self,
+                     agent_pid: str,
+                     content: str,
+                     tokens: int,
+                     importance: float,
+                     page_type: str,
+                     embedding: Optional[List[float]] = None,
+                     content_type: ContentType = ContentType.TEXT,
+                     ttl: Optional[float] = None,
+                     metadata: Optional[Dict[str, Any]] = None,
+                     chunk_group: Optional[str] = None,
+                     chunk_index: int = 0) -> str:
+        """创建页面并放入内存（调用方负责预留空间）"""
+        now = self.clock.now()
         page = ContextPage(
+            page_id=self.id_generator.new_id(),
+            last_accessed=now,
+            created_at=now,
             agent_pid=agent_pid,
             content=content,
             tokens=tokens,
             importance_score=importance,
             page_type=page_type,
+            content_type=content_type,
             status=PageStatus.IN_MEMORY,
-            embedding=embedding
+            ttl=ttl,
+            embedding=embedding,
+            metadata=dict(metadata or {}),
+            chunk_group=chunk_group,
+            chunk_index=chunk_index
         )
-        
-        # 注册静态内容（用于 KV-Cache 优化）
-        if page_type in ('system', 'tools'):
-            self.kv_cache_optimizer.register_static_content(content)
-        
-        self.pages_in_memory[page.page_id] = page
-        self.agent_pages[agent_pid].append(page.page_id)
-        self.current_usage += tokens
+        self._register_page(page)
+        self._log_wal('allocate', page=page.to_dict())
         
         logger.debug(f"Allocated page {page.page_id[:8]} for agent {agent_pid[:8]} "
                     f"({tokens} tokens, type={page_type})")
         
         return page.page_id
     
+    def _register_page(self, page: ContextPage):
+        """把新页面放入内存并更新各项索引"""
+        # 注册静态内容（用于 KV-Cache 优化）
+        if page.page_type in ('system', 'tools'):
+            self.kv_cache_optimizer.register_static_content(page.content)
+        
+        self.pages_in_memory[page.page_id] = page
+        self.agent_pages[page.agent_pid].append(page.page_id)
+        self.current_usage += page.tokens
+        if page.ttl is not None:
+            self._expiring_pages.add(page.page_id)
+        
+        if self.dedup_pages and page.chunk_group is None:
+            key = self._content_key(page.agent_pid, page.page_type, page.content)
+            self._content_index[key] = page.page_id
+    
     def access_page(self, 
                    page_id: str, 
                    agent_pid: Optional[str] = None,
@@ -634,10 +1501,15 @@ class ContextManager:
                 logger.warning(f"Access denied: page {page_id[:8]} belongs to different agent")
                 return None
             
-            page.touch()
+            now = self.clock.now()
+            page.touch(now)
             self.stats['cache_hits'] += 1
+            self.access_history.append(AccessRecord(page_id=page_id, hit=True, timestamp=now))
             return page
         
+        self.access_history.append(AccessRecord(page_id=page_id, hit=False,
+                                                timestamp=self.clock.now()))
+        
         # 页面在磁盘上，需要换入（缺页中断）
         if auto_swap and page_id in self.swapped_pages:
             self.stats['page_faults'] += 1
@@ -655,7 +1527,9 @@ class ContextManager:
                          agent_pid: str, 
                          max_pages: Optional[int] = None,
                          optimize_for_cache: bool = True,
-                         include_swapped: bool = False) -> str:
+                         include_swapped: bool = False,
+                         token_budget: Optional[int] = None,
+                         model_limit: Optional[int] = None) -> str:
         """
         获取 Agent 的完整上下文
         
@@ -664,15 +1538,155 @@ class ContextManager:
             max_pages: 最大返回页面数（None 表示不限制）
             optimize_for_cache: 是否优化布局以提高 KV-Cache 命中率
             include_swapped: 是否包含已换出的页面（会自动换入）
+            token_budget: 上下文 token 预算（None 表示不限制），
+                          超出时按 overflow_strategy 处理
+            model_limit: 目标模型的上下文上限（None 表示不限制）。
+                         按 token_budget 处理后仍超出时，无论 overflow_strategy
+                         如何都会压缩旧页面；需要压缩记录时使用 fit_agent_context
         
         Returns:
-            合并后的上下文字符串
+            合并后的上下文字符串（非文本页面以原始字符串拼接，
+            多模态场景请使用 get_agent_context_parts）
         """
-        page_ids = self.agent_pages.get(agent_pid, [])
-        pages = []
+        return self.fit_agent_context(agent_pid, model_limit, max_pages,
+                                      optimize_for_cache, include_swapped, token_budget)[0]
+    
+    def fit_agent_context(self,
+                          agent_pid: str,
+                          model_limit: Optional[int],
+                          max_pages: Optional[int] = None,
+                          optimize_for_cache: bool = True,
+                          include_swapped: bool = False,
+                          token_budget: Optional[int] = None
+                          ) -> Tuple[str, Optional[ContextFitReport]]:
+        """
+        获取适应模型上限的 Agent 上下文，并返回压缩记录
         
-        for pid in page_ids:
-            if include_swapped:
+        参数含义同 get_agent_context。
+        
+        Returns:
+            (上下文字符串, 压缩记录)；未超出 model_limit 时压缩记录为 None
+        """
+        pages, report = self._collect_context_pages(agent_pid, max_pages,
+                                                    optimize_for_cache, include_swapped,
+                                                    token_budget, model_limit)
+        return "\n\n".join(content for _, content in self._join_chunks(pages)), report
+    
+    def get_agent_context_parts(self,
+                                agent_pid: str,
+                                max_pages: Optional[int] = None,
+                                optimize_for_cache: bool = True,
+                                include_swapped: bool = False,
+                                token_budget: Optional[int] = None,
+                                model_limit: Optional[int] = None) -> List[Dict[str, Any]]:
+        """
+        以消息内容片段的形式获取 Agent 的上下文（多模态）
+        
+        页面顺序与 get_agent_context 一致；相邻的文本片段合并为一个，
+        图片等非文本页面生成独立片段而不是内联为字符串。
+        
+        Args:
+            agent_pid: Agent 进程 ID
+            max_pages: 最大返回页面数（None 表示不限制）
+            optimize_for_cache: 是否优化布局以提高 KV-Cache 命中率
+            include_swapped: 是否包含已换出的页面（会自动换入）
+            token_budget: 上下文 token 预算（None 表示不限制）
+            model_limit: 目标模型的上下文上限（同 get_agent_context）
+        
+        Returns:
+            OpenAI 风格的内容片段列表，如 {'type': 'text', 'text': ...}
+        """
+        pages, _ = self._collect_context_pages(agent_pid, max_pages,
+                                               optimize_for_cache, include_swapped,
+                                               token_budget, model_limit)
+        parts: List[Dict[str, Any]] = []
+        for page, content in self._join_chunks(pages):
+            if page.chunk_group is None:
+                part = page.to_message_part()
+            else:
+                part = {'type': 'text', 'text': content}
+            if part['type'] == 'text' and parts and parts[-1]['type'] == 'text':
+                parts[-1]['text'] += "\n\n" + part['text']
+            else:
+                parts.append(part)
+        return parts
+    
+    def get_agent_messages(self,
+                           agent_pid: str,
+                           include_swapped: bool = False,
+                           token_budget: Optional[int] = None) -> List[Dict[str, Any]]:
+        """
+        以消息列表的形式获取 Agent 的上下文
+        
+        保持页面的分配顺序（不做 KV-Cache 重排），因为工具调用消息
+        必须紧跟在发起调用的 assistant 消息之后。system 消息（系统提示、
+        工具定义）集中放在开头，很多 Provider 拒绝对话中间的 system 消息。
+        
+        Args:
+            agent_pid: Agent 进程 ID
+            include_swapped: 是否包含已换出的页面（会自动换入）
+            token_budget: 上下文 token 预算（None 表示不限制）
+        
+        Returns:
+            OpenAI 风格的消息字典列表
+        """
+        pages, _ = self._collect_context_pages(agent_pid, None, False,
+                                               include_swapped, token_budget)
+        messages = [page.to_message() for page in pages]
+        return ([m for m in messages if m['role'] == 'system'] +
+                [m for m in messages if m['role'] != 'system'])
+    
+    @staticmethod
+    def _join_chunks(pages: List[ContextPage]) -> List[Tuple[ContextPage, str]]:
+        """
+        把同一分块组的页面按 chunk_index 拼接回原文
+        
+        拼接结果出现在该组第一个分块所在的位置；已换出或被裁剪的分块
+        不在 pages 中，直接跳过。
+        
+        Returns:
+            (代表页面, 内容) 列表
+        """
+        groups: Dict[str, List[ContextPage]] = {}
+        for page in pages:
+            if page.chunk_group is not None:
+                groups.setdefault(page.chunk_group, []).append(page)
+        if not groups:
+            return [(page, page.content) for page in pages]
+        
+        joined = []
+        for page in pages:
+            if page.chunk_group is None:
+                joined.append((page, page.content))
+            elif page.chunk_group in groups:
+                chunks = sorted(groups.pop(page.chunk_group), key=lambda p: p.chunk_index)
+                joined.append((chunks[0], "".join(p.content for p in chunks)))
+        return joined
+    
+    def _collect_context_pages(self,
+                               agent_pid: str,
+                               max_pages: Optional[int],
+                               optimize_for_cache: bool,
+                               include_swapped: bool,
+                               token_budget: Optional[int] = None,
+                               model_limit: Optional[int] = None
+                               ) -> Tuple[List[ContextPage], Optional[ContextFitReport]]:
+        """
+        按上下文顺序收集 Agent 的页面
+        
+        先只依据页面元数据（类型、访问次数、重要性、token 数）完成排序和
+        max_pages / token_budget 裁剪，最后才读取入选页面的内容更新
+        KV-Cache 的 token 集合，因此拥有数千页面的 Agent 不会在每次
+        调用时对全部页面内容分词。
+        
+        Returns:
+            (页面列表, 因 model_limit 压缩时的记录，否则为 None)
+        """
+        page_ids = self.agent_pages.get(agent_pid, [])
+        pages = []
+        
+        for pid in page_ids:
+            if include_swapped:
                 page = self.access_page(pid, agent_pid, auto_swap=True)
             else:
                 page = self.pages_in_memory.get(pid)
@@ -681,19 +1695,231 @@ class ContextManager:
                 pages.append(page)
         
         if not pages:
-            return ""
+            return [], None
         
-        # 优化布局以最大化 KV-Cache 命中率
+        # 优化布局以最大化 KV-Cache 命中率；只取前 max_pages 页时用堆按需产出
+        if optimize_for_cache:
+            if max_pages:
+                pages = list(islice(self.kv_cache_optimizer.iter_layout(pages), max_pages))
+            else:
+                pages = self.kv_cache_optimizer.optimize_layout(pages)
+        elif max_pages:
+            pages = pages[:max_pages]
+        
+        if token_budget is not None and sum(p.tokens for p in pages) > token_budget:
+            self.stats['context_overflows'] += 1
+            if self.overflow_strategy == OverflowStrategy.COMPRESS:
+                pages = self._fit_by_compression(agent_pid, pages, token_budget)
+            else:
+                pages = self._fit_by_priority(pages, token_budget)
+        
+        report = None
+        if model_limit is not None and sum(p.tokens for p in pages) > model_limit:
+            pages, report = self._fit_to_model_limit(agent_pid, pages, model_limit)
+        
+        # 用最终进入上下文的页面更新 token 集合（用于下次命中率预估）
         if optimize_for_cache:
-            pages = self.kv_cache_optimizer.optimize_layout(pages)
-            # 更新优化后的 token 集合（用于下次命中率预估）
             self.kv_cache_optimizer.update_previous_tokens(pages)
         
-        # 限制页面数
-        if max_pages:
-            pages = pages[:max_pages]
+        return pages, report
+    
+    def _fit_to_model_limit(self, agent_pid: str, pages: List[ContextPage],
+                            model_limit: int) -> Tuple[List[ContextPage], ContextFitReport]:
+        """压缩上下文以适应模型上限，返回压缩后的页面和压缩记录"""
+        original_tokens = sum(p.tokens for p in pages)
+        self.stats['context_overflows'] += 1
+        fitted = self._fit_by_compression(agent_pid, pages, model_limit)
+        
+        kept_ids = {p.page_id for p in fitted}
+        original_ids = {p.page_id for p in pages}
+        report = ContextFitReport(
+            model_limit=model_limit,
+            original_tokens=original_tokens,
+            final_tokens=sum(p.tokens for p in fitted),
+            removed_pages=[p.page_id for p in pages if p.page_id not in kept_ids],
+            summary_pages=sum(1 for p in fitted if p.page_id not in original_ids),
+        )
+        logger.info(f"Context for agent {agent_pid[:8]} exceeded model limit {model_limit}: "
+                    f"{original_tokens} -> {report.final_tokens} tokens")
+        return fitted, report
+    
+    def _fit_by_priority(self, pages: List[ContextPage],
+                         token_budget: int) -> List[ContextPage]:
+        """
+        按优先级裁剪页面：重要性高者先预留预算，同等重要性时较新的优先
+        
+        放不下的页面被跳过，之后更小的页面仍可填充剩余预算；
+        返回结果保持原有的上下文顺序。
+        """
+        ranked = sorted(pages, key=lambda p: (-p.importance_score, -p.created_at))
+        kept: Set[str] = set()
+        used = 0
+        for page in ranked:
+            if used + page.tokens <= token_budget:
+                kept.add(page.page_id)
+                used += page.tokens
+        
+        dropped = len(pages) - len(kept)
+        if dropped:
+            logger.debug(f"Context over budget, dropped {dropped} low-priority pages")
+        return [p for p in pages if p.page_id in kept]
+    
+    def _fit_by_compression(self, agent_pid: str, pages: List[ContextPage],
+                            token_budget: int) -> List[ContextPage]:
+        """
+        将最旧的页面压缩为摘要以适应预算
+        
+        系统、任务等关键页面不参与压缩；摘要作为临时页面插入在
+        静态前缀之后，不注册到管理器中。压缩后仍超预算时按优先级裁剪。
+        """
+        from .optimization.compressor import (
+            ContextCompressor, CompressionConfig, CompressionStrategy
+        )
         
-        return "\n\n".join(p.content for p in pages)
+        compressor = ContextCompressor(
+            CompressionConfig(max_tokens=token_budget, token_per_message=0),
+            tokenizer=self.tokenizer
+        )
+        messages = []
+        for page in sorted(pages, key=lambda p: p.created_at):
+            pinned = (page.importance_score >= 0.9
+                      or page.page_type in ('system', 'task'))
+            messages.append({
+                'role': 'system' if pinned else 'user',
+                'content': page.content,
+                '_page': page,
+            })
+        
+        compressed = compressor.compress_messages(messages, CompressionStrategy.SUMMARIZE)
+        kept_ids = {m['_page'].page_id for m in compressed if '_page' in m}
+        result = [p for p in pages if p.page_id in kept_ids]
+        
+        # 摘要放在静态前缀（系统提示、工具定义）之后
+        insert_at = 0
+        while insert_at < len(result) and result[insert_at].page_type in ('system', 'tools'):
+            insert_at += 1
+        for message in compressed:
+            if '_page' in message:
+                continue
+            summary = ContextPage(
+                page_id=self.id_generator.new_id(),
+                agent_pid=agent_pid,
+                content=message['content'],
+                tokens=self._estimate_tokens(message['content']),
+                importance_score=0.5,
+                page_type='summary',
+            )
+            result.insert(insert_at, summary)
+            insert_at += 1
+        
+        if sum(p.tokens for p in result) > token_budget:
+            result = self._fit_by_priority(result, token_budget)
+        return result
+    
+    def get_agent_context_sampled(self,
+                                  agent_pid: str,
+                                  token_budget: int,
+                                  temperature: float = 1.0,
+                                  rng: Optional[random.Random] = None) -> str:
+        """
+        按重要性加权随机抽样组装上下文
+        
+        系统和任务页面总是包含；其余页面按 importance^(1/temperature) 加权
+        不放回抽样，放不下的页面被跳过，直到预算用完。temperature 越高分布越平坦。
+        同一分块组的页面作为整体抽样。只读取内存中的页面，不修改管理器状态
+        （不更新访问记录和 KV-Cache 统计），可与其他调用并发执行。
+        
+        Args:
+            agent_pid: Agent 进程 ID
+            token_budget: 上下文 token 预算
+            temperature: 抽样温度（> 0）
+            rng: 随机数生成器（测试中可注入固定种子的实例）
+        
+        Returns:
+            入选页面按原有顺序拼接的上下文字符串
+        
+        Raises:
+            ValueError: temperature 不大于 0
+        """
+        if temperature <= 0:
+            raise ValueError(f"temperature must be > 0, got {temperature}")
+        rng = rng or random.Random()
+        
+        pages = [self.pages_in_memory[page_id]
+                 for page_id in list(self.agent_pages.get(agent_pid, []))
+                 if page_id in self.pages_in_memory]
+        units: Dict[str, List[ContextPage]] = {}
+        for page in pages:
+            units.setdefault(page.chunk_group or page.page_id, []).append(page)
+        
+        selected: Set[str] = set()
+        used = 0
+        candidates = []
+        for key, unit in units.items():
+            if any(p.page_type in ('system', 'task') for p in unit):
+                selected.add(key)
+                used += sum(p.tokens for p in unit)
+                continue
+            # Efraimidis-Spirakis：key = log(u) / w，按 key 降序即为加权不放回抽样
+            weight = max(max(p.importance_score for p in unit), 1e-6) ** (1.0 / temperature)
+            candidates.append((math.log(1.0 - rng.random()) / weight, key))
+        
+        for _, key in sorted(candidates, reverse=True):
+            tokens = sum(p.tokens for p in units[key])
+            if used + tokens <= token_budget:
+                selected.add(key)
+                used += tokens
+        
+        chosen = [p for p in pages if (p.chunk_group or p.page_id) in selected]
+        return "\n\n".join(content for _, content in self._join_chunks(chosen))
+    
+    def iter_agent_context(self,
+                           agent_pid: str,
+                           optimize_for_cache: bool = True) -> Iterator[ContextPage]:
+        """
+        惰性遍历 Agent 在内存中的上下文页面
+        
+        顺序与 get_agent_context 一致，但不会一次性拼接所有页面，
+        适用于拥有大量页面的 Agent。
+        
+        Args:
+            agent_pid: Agent 进程 ID
+            optimize_for_cache: 是否按 KV-Cache 友好的顺序产出
+        """
+        pages = [
+            self.pages_in_memory[page_id]
+            for page_id in self.agent_pages.get(agent_pid, [])
+            if page_id in self.pages_in_memory
+        ]
+        
+        if optimize_for_cache:
+            return self.kv_cache_optimizer.iter_layout(pages)
+        return iter(pages)
+    
+    def get_agent_context_top_k(self,
+                                agent_pid: str,
+                                k: int,
+                                optimize_for_cache: bool = True) -> List[ContextPage]:
+        """
+        获取 Agent 优先级最高的至多 k 个上下文页面
+        
+        Args:
+            agent_pid: Agent 进程 ID
+            k: 最多返回的页面数
+            optimize_for_cache: 是否按 KV-Cache 友好的顺序排序
+        
+        Returns:
+            页面列表（已排序）
+        """
+        if k <= 0:
+            return []
+        
+        pages = list(islice(self.iter_agent_context(agent_pid, optimize_for_cache), k))
+        
+        if optimize_for_cache and pages:
+            self.kv_cache_optimizer.update_previous_tokens(pages)
+        
+        return pages
     
     def update_page_content(self, page_id: str, new_content: str):
         """
@@ -706,24 +1932,300 @@ class ContextManager:
             logger.warning(f"Cannot update page {page_id[:8]}: not in memory")
             return
         
+        if self.dedup_pages:
+            old_key = self._content_key(page.agent_pid, page.page_type, page.content)
+            if self._content_index.get(old_key) == page_id:
+                del self._content_index[old_key]
+            self._content_index[self._content_key(page.agent_pid, page.page_type, new_content)] = page_id
+        
         # 更新 token 计数
         old_tokens = page.tokens
         page.content = new_content
         page.tokens = self._estimate_tokens(new_content)
         page.mark_dirty()
-        page.touch()
+        page.touch(self.clock.now())
         
         # 更新总使用量
         self.current_usage += (page.tokens - old_tokens)
+        self._log_wal('update_content', page_id=page_id, content=new_content, tokens=page.tokens)
         
         logger.debug(f"Updated page {page_id[:8]} content ({old_tokens} -> {page.tokens} tokens)")
     
     def update_page_importance(self, page_id: str, importance: float):
-        """更新页面的重要性评分"""
+        """更新页面的重要性评分（兼容旧接口，见 update_importance）"""
+        self.update_importance(page_id, importance)
+    
+    def update_importance(self, page_id: str, new_importance: float) -> Optional[float]:
+        """
+        重新设置页面的重要性评分
+        
+        页面可以在内存中，也可以已被换出；评分被限制在 [0, 1] 区间内。
+        换出决策使用新的评分，因此被反复引用的页面可以借此避免被换出。
+        
+        Args:
+            page_id: 页面 ID
+            new_importance: 新的重要性评分
+        
+        Returns:
+            限制后的实际评分，页面不存在时返回 None
+        """
         page = self.pages_in_memory.get(page_id) or self.swapped_pages.get(page_id)
-        if page:
-            page.importance_score = importance
-            logger.debug(f"Updated importance for page {page_id[:8]}: {importance}")
+        if not page:
+            logger.warning(f"Cannot update importance of page {page_id[:8]}: not found")
+            return None
+        
+        page.importance_score = max(0.0, min(1.0, new_importance))
+        if page.status == PageStatus.IN_MEMORY:
+            page.mark_dirty()
+        self._log_wal('update_importance', page_id=page_id, importance=page.importance_score)
+        logger.debug(f"Updated importance for page {page_id[:8]}: {page.importance_score}")
+        return page.importance_score
+    
+    def boost_importance(self, page_id: str, delta: float) -> Optional[float]:
+        """
+        增量调整页面的重要性评分
+        
+        Args:
+            page_id: 页面 ID
+            delta: 调整量（可为负数）
+        
+        Returns:
+            调整并限制后的评分，页面不存在时返回 None
+        """
+        page = self.pages_in_memory.get(page_id) or self.swapped_pages.get(page_id)
+        if not page:
+            logger.warning(f"Cannot boost importance of page {page_id[:8]}: not found")
+            return None
+        return self.update_importance(page_id, page.importance_score + delta)
+    
+    def export_agent_pages(self, agent_pid: str) -> List[ContextPage]:
+        """
+        导出 Agent 的所有页面（含已换出的页面）
+        
+        按分配顺序返回，不触发换入，也不更新访问统计。用于检查点持久化。
+        """
+        pages = []
+        for page_id in self.agent_pages.get(agent_pid, []):
+            page = self.pages_in_memory.get(page_id) or self.swapped_pages.get(page_id)
+            if page:
+                pages.append(page)
+        return pages
+    
+    def import_agent_pages(self, agent_pid: str,
+                           pages: List[ContextPage]) -> Dict[str, str]:
+        """
+        把 export_agent_pages 导出的页面作为一个批次重新分配给 Agent
+        
+        页面获得新 ID，内容类型、TTL、元数据、嵌入和分块信息保持不变
+        （分块组映射到新的组 ID）。先为整批页面检查预算并腾出空间再插入，
+        空间不足时不会留下任何已插入的页面。用于检查点恢复。
+        
+        Args:
+            agent_pid: 接收页面的 Agent 进程 ID
+            pages: 待导入的页面（不会被修改）
+        
+        Returns:
+            旧页面 ID 到新页面 ID 的映射
+        
+        Raises:
+            ContextOverflowError: 无法为整批页面腾出空间
+            ContextBudgetExceededError: 整批页面超出该 Agent 的预算
+        """
+        total = sum(page.tokens for page in pages)
+        self._enforce_agent_budget(agent_pid, total)
+        self._reserve_tokens(total)
+        
+        now = self.clock.now()
+        page_id_map: Dict[str, str] = {}
+        chunk_groups: Dict[str, str] = {}
+        for source in pages:
+            page = copy.deepcopy(source)
+            page.page_id = self.id_generator.new_id()
+            page.agent_pid = agent_pid
+            page.status = PageStatus.IN_MEMORY
+            page.created_at = now
+            page.last_accessed = now
+            page._dirty = False
+            if page.chunk_group is not None:
+                page.chunk_group = chunk_groups.setdefault(page.chunk_group,
+                                                           self.id_generator.new_id())
+            self._register_page(page)
+            self._log_wal('allocate', page=page.to_dict())
+            page_id_map[source.page_id] = page.page_id
+        
+        logger.debug(f"Imported {len(pages)} pages ({total} tokens) for agent {agent_pid[:8]}")
+        return page_id_map
+    
+    def export_snapshot(self) -> ContextSnapshot:
+        """
+        导出完整上下文状态（用于调试和迁移）
+        
+        包含内存中与已换出的页面、每个 Agent 的页面顺序和 token 使用量，
+        与调度器的 snapshot() 配合实现全状态持久化。页面按 last_accessed
+        排序导出，恢复后 LRU 顺序保持不变。
+        """
+        def by_recency(pages):
+            return [copy.deepcopy(p) for p in sorted(pages, key=lambda p: p.last_accessed)]
+        
+        return ContextSnapshot(
+            pages=by_recency(self.pages_in_memory.values()),
+            swapped_pages=by_recency(self.swapped_pages.values()),
+            agent_pages={pid: list(ids) for pid, ids in self.agent_pages.items()},
+            token_usage=self.current_usage,
+            stats=dict(self.stats),
+            wal_seq=self._wal_seq,
+        )
+    
+    def import_snapshot(self, snapshot: ContextSnapshot):
+        """
+        从 export_snapshot() 导出的状态重建上下文管理器
+        
+        现有页面会被替换。token 使用量按恢复后的内存页面重新计算，
+        去重索引、TTL 追踪和 KV-Cache 静态内容签名同时重建。
+        
+        Args:
+            snapshot: 上下文快照（ContextSnapshot 或其 to_dict() 结果）
+        """
+        if isinstance(snapshot, dict):
+            snapshot = ContextSnapshot.from_dict(snapshot)
+        
+        self.pages_in_memory = {}
+        self.swapped_pages = {}
+        self.agent_pages = defaultdict(list)
+        self._content_index = {}
+        self._expiring_pages = set()
+        
+        for page in snapshot.pages:
+            page = copy.deepcopy(page)
+            page._dirty = page.status == PageStatus.DIRTY
+            self.pages_in_memory[page.page_id] = page
+        for page in snapshot.swapped_pages:
+            page = copy.deepcopy(page)
+            page.status = PageStatus.SWAPPED
+            self.swapped_pages[page.page_id] = page
+        
+        for agent_pid, page_ids in snapshot.agent_pages.items():
+            self.agent_pages[agent_pid] = [
+                pid for pid in page_ids
+                if pid in self.pages_in_memory or pid in self.swapped_pages
+            ]
+        
+        for page in list(self.pages_in_memory.values()) + list(self.swapped_pages.values()):
+            if page.page_type in ('system', 'tools'):
+                self.kv_cache_optimizer.register_static_content(page.content)
+            if page.ttl is not None:
+                self._expiring_pages.add(page.page_id)
+            if self.dedup_pages:
+                key = self._content_key(page.agent_pid, page.page_type, page.content)
+                self._content_index[key] = page.page_id
+        
+        self.current_usage = sum(p.tokens for p in self.pages_in_memory.values())
+        if self.current_usage != snapshot.token_usage:
+            logger.warning(f"Snapshot token usage {snapshot.token_usage} does not match "
+                           f"restored pages ({self.current_usage})")
+        self.stats.update(snapshot.stats)
+        self._wal_seq = snapshot.wal_seq
+        
+        logger.info(f"Imported context snapshot: {len(self.pages_in_memory)} in memory, "
+                    f"{len(self.swapped_pages)} swapped, {len(self.agent_pages)} agents")
+    
+    def replay_wal(self) -> int:
+        """
+        重放 WAL 中当前状态之后的记录
+        
+        启动时先 import_snapshot 导入最近的快照（没有快照时从空状态开始），
+        再调用此方法应用快照之后的所有操作。重放过程不会再次写入 WAL。
+        访问次数等统计不记录在 WAL 中，恢复后从快照中的值继续累计。
+        
+        Returns:
+            应用的记录数
+        """
+        if self.wal is None:
+            return 0
+        
+        applied = 0
+        self._replaying = True
+        try:
+            for record in self.wal.records(after_seq=self._wal_seq):
+                try:
+                    self._apply_wal_record(record.op, record.data)
+                except (KeyError, ValueError) as e:
+                    logger.error(f"Skipping WAL record {record.seq} ({record.op}): {e}")
+                self._wal_seq = record.seq
+                applied += 1
+        finally:
+            self._replaying = False
+        self.wal.advance_to(self._wal_seq)
+        
+        if applied:
+            logger.info(f"Replayed {applied} WAL records: {len(self.pages_in_memory)} in memory, "
+                        f"{len(self.swapped_pages)} swapped")
+        return applied
+    
+    def _log_wal(self, op: str, **data):
+        """向 WAL 追加一条记录（未启用 WAL 或正在重放时跳过）"""
+        if self.wal is None or self._replaying:
+            return
+        self._wal_seq = self.wal.append(op, data).seq
+    
+    def _apply_wal_record(self, op: str, data: Dict[str, Any]):
+        """把一条 WAL 记录应用到当前状态"""
+        if op == 'allocate':
+            self._register_page(ContextPage.from_dict(data['page']))
+        elif op == 'load':
+            page = ContextPage.from_dict(data['page'])
+            self.pages_in_memory[page.page_id] = page
+            self.current_usage += page.tokens
+            if page.ttl is not None:
+                self._expiring_pages.add(page.page_id)
+        elif op == 'update_importance':
+            self.update_importance(data['page_id'], data['importance'])
+        elif op == 'update_content':
+            page = self.pages_in_memory[data['page_id']]
+            self.current_usage += data['tokens'] - page.tokens
+            page.content = data['content']
+            page.tokens = data['tokens']
+            page.mark_dirty()
+        elif op == 'evict':
+            self._evict_page(self.pages_in_memory[data['page_id']])
+        elif op == 'swap_in':
+            page = self.swapped_pages.pop(data['page_id'])
+            page.status = PageStatus.IN_MEMORY
+            self.pages_in_memory[page.page_id] = page
+            self.current_usage += page.tokens
+        elif op == 'remove':
+            page = (self.pages_in_memory.get(data['page_id'])
+                    or self.swapped_pages[data['page_id']])
+            self._remove_page(page)
+        elif op == 'release_agent':
+            self.release_agent_pages(data['agent_pid'])
+        elif op == 'merge':
+            self._merge_page_into(self.pages_in_memory[data['target_id']],
+                                  self.pages_in_memory[data['page_id']])
+        elif op == 'reorder':
+            self.agent_pages[data['agent_pid']] = list(data['page_ids'])
+        elif op == 'vacuum':
+            for page_id in data['page_ids']:
+                self.swapped_pages.pop(page_id, None)
+                self._expiring_pages.discard(page_id)
+        else:
+            raise ValueError(f"unknown WAL operation '{op}'")
+    
+    def agent_token_usage(self, agent_pid: str) -> int:
+        """
+        获取单个 Agent 占用的 token 数（含已换出的页面）
+        
+        Args:
+            agent_pid: Agent 进程 ID
+        
+        Returns:
+            该 Agent 所有页面的 token 总数，未知 Agent 返回 0
+        """
+        return sum(page.tokens for page in self.export_agent_pages(agent_pid))
+    
+    def all_agent_usage(self) -> Dict[str, int]:
+        """获取所有 Agent 的 token 占用（pid -> tokens）"""
+        return {pid: self.agent_token_usage(pid) for pid in self.agent_pages}
     
     def release_agent_pages(self, agent_pid: str) -> int:
         """
@@ -746,98 +2248,424 @@ class ContextManager:
                 released += 1
         
         del self.agent_pages[agent_pid]
+        self._expiring_pages.difference_update(page_ids)
+        self._log_wal('release_agent', agent_pid=agent_pid)
+        
+        if self.dedup_pages:
+            self._content_index = {
+                key: page_id for key, page_id in self._content_index.items()
+                if key[0] != agent_pid
+            }
         
         logger.info(f"Released {released} pages for agent {agent_pid[:8]}")
         return released
     
-    def get_stats(self) -> Dict[str, Any]:
-        """获取统计信息"""
-        hit_rate = 0
-        if self.stats['total_accesses'] > 0:
-            hit_rate = self.stats['cache_hits'] / self.stats['total_accesses']
+    def expire_pages(self, current_time: Optional[float] = None) -> int:
+        """
+        丢弃已超过存活时间的页面
         
-        return {
-            **self.stats,
-            'current_usage': self.current_usage,
-            'max_tokens': self.max_context_tokens,
-            'usage_percent': (self.current_usage / self.max_context_tokens) * 100,
-            'pages_in_memory': len(self.pages_in_memory),
-            'pages_swapped': len(self.swapped_pages),
-            'total_agents': len(self.agent_pages),
-            'cache_hit_rate': hit_rate,
-            'kv_cache_stats': self.kv_cache_optimizer.get_hit_rate_stats(),
-        }
+        过期页面（无论在内存中还是已换出）直接删除而不写回存储，
+        释放其 token 并从所属 Agent 的页面列表中移除。未设置 TTL 的页面
+        永不过期。内核主循环会定期调用此方法。
+        
+        Args:
+            current_time: 当前时间（默认取自 self.clock）
+        
+        Returns:
+            丢弃的页面数
+        """
+        if not self._expiring_pages:
+            return 0
+        if current_time is None:
+            current_time = self.clock.now()
+        
+        expired = 0
+        for page_id in list(self._expiring_pages):
+            page = self.pages_in_memory.get(page_id) or self.swapped_pages.get(page_id)
+            if page is None:
+                self._expiring_pages.discard(page_id)
+                continue
+            if not page.is_expired(current_time):
+                continue
+            
+            self._remove_page(page)
+            self._log_wal('remove', page_id=page_id)
+            expired += 1
+        
+        if expired:
+            self.stats['pages_expired'] += expired
+            logger.debug(f"Expired {expired} pages")
+        return expired
     
-    def _estimate_tokens(self, text: str) -> int:
+    def vacuum(self, cold_after: Optional[float] = None) -> VacuumReport:
         """
-        估算文本的 token 数
+        清理 swapped_pages
+        
+        丢弃不再属于任何 Agent 页面列表的换出页面；设置 cold_after 且存储
+        后端支持 save_context_page 时，还会把超过该时间未访问的换出页面写入
+        存储并从内存移除，之后访问时由 access_page 从存储加载。
+        内核可按 KernelConfig.context_vacuum_interval 定期调用。
+        
+        Args:
+            cold_after: 未访问多少秒的换出页面视为冷页面（None 表示不迁移）
         
-        优先使用 tiktoken（精确），否则使用启发式估计
+        Returns:
+            清理结果
         """
-        if HAS_TIKTOKEN:
-            try:
-                encoding = tiktoken.encoding_for_model("gpt-4")
-                return len(encoding.encode(text))
-            except Exception:
-                pass
+        report = VacuumReport()
+        owned = {page_id for page_ids in self.agent_pages.values() for page_id in page_ids}
+        can_offload = (cold_after is not None and self.storage is not None
+                       and hasattr(self.storage, 'save_context_page'))
+        now = self.clock.now()
+        
+        dropped = []
+        for page_id, page in list(self.swapped_pages.items()):
+            if page_id not in owned:
+                report.orphaned_pages += 1
+            elif can_offload and now - page.last_accessed >= cold_after:
+                self._write_to_storage(page)
+                page.mark_clean()
+                report.offloaded_pages += 1
+            else:
+                continue
+            report.freed_bytes += len(str(page.content).encode('utf-8'))
+            del self.swapped_pages[page_id]
+            self._expiring_pages.discard(page_id)
+            dropped.append(page_id)
+        
+        if not dropped:
+            return report
+        orphans = set(dropped) - owned
+        if orphans and self._content_index:
+            self._content_index = {key: page_id for key, page_id in self._content_index.items()
+                                   if page_id not in orphans}
+        self._log_wal('vacuum', page_ids=dropped)
+        logger.info(f"Vacuumed {report.orphaned_pages} orphaned and {report.offloaded_pages} "
+                    f"cold swapped pages ({report.freed_bytes} bytes)")
+        return report
+    
+    def _remove_page(self, page: ContextPage):
+        """删除页面（内存中或已换出）并清理各项索引"""
+        page_id = page.page_id
+        if page_id in self.pages_in_memory:
+            self.current_usage -= page.tokens
+            del self.pages_in_memory[page_id]
+        else:
+            self.swapped_pages.pop(page_id, None)
+        
+        agent_page_ids = self.agent_pages.get(page.agent_pid)
+        if agent_page_ids and page_id in agent_page_ids:
+            agent_page_ids.remove(page_id)
+        
+        if self.dedup_pages:
+            key = self._content_key(page.agent_pid, page.page_type, page.content)
+            if self._content_index.get(key) == page_id:
+                del self._content_index[key]
         
-        # 回退：按空格分词后乘以经验系数 1.3
-        words = len(text.split())
-        return int(words * 1.3)
+        self._expiring_pages.discard(page_id)
     
-    def _swap_out_page(self) -> bool:
+    def compact_agent(self, agent_pid: str, max_merged_tokens: int = 1024) -> int:
         """
-        换出一个页面（页面置换算法）
+        合并 Agent 相邻的同类型小页面
+        
+        按分配顺序遍历 Agent 的内存页面，将相邻且 page_type 相同的页面合并，
+        前提是合并后的 token 数不超过 max_merged_tokens。关键页面
+        （importance >= 0.95）、已换出的页面和已迁移到存储的页面不参与合并，
+        并会打断相邻关系。被合并的页面在存储中的副本会一并删除。
         
-        策略：LRU + 重要性评分 + 语义相似度
+        Args:
+            agent_pid: Agent 进程 ID
+            max_merged_tokens: 合并后单个页面的最大 token 数
         
         Returns:
-            是否成功换出
+            被移除的页面数
         """
-        if not self.pages_in_memory:
-            return False
+        page_ids = self.agent_pages.get(agent_pid)
+        if not page_ids:
+            return 0
         
-        # 计算每个页面的"受害者分数"（越高越应该被换出）
-        candidates = []
-        current_time = time.time()
+        kept: List[str] = []
+        removed = 0
+        target: Optional[ContextPage] = None
         
-        for page_id, page in self.pages_in_memory.items():
-            # 跳过重要性极高的页面
-            if page.importance_score >= 0.95:
+        for page_id in page_ids:
+            page = self.pages_in_memory.get(page_id) or self.swapped_pages.get(page_id)
+            if page is None:
+                # 已由 vacuum 迁移到存储，访问时再加载
+                kept.append(page_id)
+                target = None
                 continue
             
-            # 计算 LRU 分数
-            lru_score = page.get_lru_score(current_time)
+            # 分块页面保持原有粒度，拼接由 get_agent_context 完成
+            mergeable = (page.status != PageStatus.SWAPPED
+                         and page.importance_score < 0.95
+                         and page.chunk_group is None)
             
-            # 综合考虑重要性：重要性越低，越容易被换出
-            victim_score = lru_score * (1 - page.importance_score * 0.5)
+            if (mergeable and target is not None
+                    and target.page_type == page.page_type
+                    and target.tokens + page.tokens <= max_merged_tokens):
+                self._merge_page_into(target, page)
+                self._delete_from_storage(page.page_id)
+                self._log_wal('merge', target_id=target.page_id, page_id=page.page_id)
+                removed += 1
+                continue
             
-            candidates.append((page_id, victim_score, page))
+            kept.append(page.page_id)
+            target = page if mergeable else None
         
-        if not candidates:
-            logger.warning("No swappable pages found (all pages are critical)")
-            return False
+        self.agent_pages[agent_pid] = kept
+        self._log_wal('reorder', agent_pid=agent_pid, page_ids=kept)
+        
+        if removed:
+            logger.info(f"Compacted agent {agent_pid[:8]}: merged {removed} pages")
+        return removed
+    
+    def _merge_page_into(self, target: ContextPage, page: ContextPage):
+        """将 page 合并进 target 并从内存中移除 page"""
+        if self.dedup_pages:
+            for p in (target, page):
+                key = self._content_key(p.agent_pid, p.page_type, p.content)
+                if self._content_index.get(key) == p.page_id:
+                    del self._content_index[key]
+        
+        target.content = f"{target.content}\n\n{page.content}"
+        target.tokens += page.tokens
+        target.importance_score = max(target.importance_score, page.importance_score)
+        target.access_count += page.access_count
+        target.last_accessed = max(target.last_accessed, page.last_accessed)
+        target.mark_dirty()
+        
+        if self.dedup_pages:
+            key = self._content_key(target.agent_pid, target.page_type, target.content)
+            self._content_index[key] = target.page_id
+        
+        del self.pages_in_memory[page.page_id]
+    
+    def recent_accesses(self, limit: Optional[int] = None) -> List[AccessRecord]:
+        """
+        获取最近的页面访问记录（按时间顺序，最新的在最后）
+        
+        Args:
+            limit: 最多返回的记录数（None 表示全部）
+        """
+        records = list(self.access_history)
+        if limit is not None:
+            records = records[-limit:] if limit > 0 else []
+        return records
+    
+    def collect_stats(self) -> ContextStats:
+        """获取类型化的统计信息"""
+        hit_rate = 0.0
+        if self.stats['total_accesses'] > 0:
+            hit_rate = self.stats['cache_hits'] / self.stats['total_accesses']
+        
+        return ContextStats(
+            page_faults=self.stats['page_faults'],
+            swaps_in=self.stats['swaps_in'],
+            swaps_out=self.stats['swaps_out'],
+            total_accesses=self.stats['total_accesses'],
+            cache_hits=self.stats['cache_hits'],
+            dedup_hits=self.stats['dedup_hits'],
+            context_overflows=self.stats['context_overflows'],
+            pages_expired=self.stats['pages_expired'],
+            current_usage=self.current_usage,
+            max_tokens=self.max_context_tokens,
+            usage_percent=(self.current_usage / self.max_context_tokens) * 100,
+            pages_in_memory=len(self.pages_in_memory),
+            pages_swapped=len(self.swapped_pages),
+            total_agents=len(self.agent_pages),
+            cache_hit_rate=hit_rate,
+            kv_cache_stats=self.kv_cache_optimizer.get_hit_rate_stats(),
+        )
+    
+    def get_stats(self) -> Dict[str, Any]:
+        """获取统计信息（ContextStats 的字典形式）"""
+        return self.collect_stats().to_dict()
+    
+    def _estimate_tokens(self, text: str) -> int:
+        """
+        估算文本的 token 数（委托给 tokenizer）
+        """
+        return self.tokenizer.count_tokens(text)
+    
+    def _swap_out_page(self) -> bool:
+        """
+        换出一个页面（页面置换算法）
+        
+        Returns:
+            是否成功换出
+        """
+        victims = self._plan_evictions(1, max_pages=1)
+        for page in victims:
+            self._evict_page(page)
+        return bool(victims)
+    
+    def _free_tokens(self, tokens_to_free: float) -> int:
+        """
+        换出页面直到释放至少 tokens_to_free 个 token（或没有可换出的页面）
+        
+        只对候选页面评分一次，而不是每换出一页重新扫描全部页面。
+        
+        Returns:
+            实际释放的 token 数
+        """
+        if tokens_to_free <= 0:
+            return 0
+        freed = 0
+        for page in self._plan_evictions(tokens_to_free):
+            self._evict_page(page)
+            freed += page.tokens
+        return freed
+    
+    def _plan_evictions(self, tokens_to_free: float,
+                        max_pages: Optional[int] = None) -> List[ContextPage]:
+        """
+        选出要换出的页面（页面置换算法），不修改任何状态
+        
+        策略：LRU + 重要性评分；保护级别为 ALWAYS 的页面类型优先于其他
+        页面换出，NEVER 的页面类型不参与换出。
         
-        # 选择得分最高的（最应该被换出的）
-        victim_id, score, victim_page = max(candidates, key=lambda x: x[1])
+        先对候选页面的分数做一次快照，再用堆按"受害者分数"从高到低取出，
+        直到累计 token 数达到 tokens_to_free，开销为 O(n + k log n)。
+        同分页面按在内存中的先后顺序换出。
         
-        # 执行换出
-        victim_page.status = PageStatus.SWAPPED
-        del self.pages_in_memory[victim_id]
-        self.swapped_pages[victim_id] = victim_page
-        self.current_usage -= victim_page.tokens
+        Args:
+            tokens_to_free: 需要释放的 token 数
+            max_pages: 最多选出的页面数（None 表示不限制）
+        
+        Returns:
+            按换出顺序排列的页面列表
+        """
+        current_time = self.clock.now()
+        heap = []
+        for index, page in enumerate(self.pages_in_memory.values()):
+            if not self._is_evictable(page):
+                continue
+            # 综合考虑重要性：重要性越低，越容易被换出
+            victim_score = page.get_lru_score(current_time) * (1 - page.importance_score * 0.5)
+            preferred = self.evictability(page) == Evictability.ALWAYS
+            heap.append((not preferred, -victim_score, index, page))
+        
+        if not heap:
+            if self.pages_in_memory:
+                logger.warning("No swappable pages found (all pages are critical)")
+            return []
+        
+        heapq.heapify(heap)
+        victims = []
+        freed = 0
+        while heap and freed < tokens_to_free:
+            if max_pages is not None and len(victims) >= max_pages:
+                break
+            _, neg_score, _, page = heapq.heappop(heap)
+            victims.append(page)
+            freed += page.tokens
+            logger.debug(f"Swapping out page {page.page_id[:8]} "
+                         f"({page.tokens} tokens, score={-neg_score:.3f})")
+        return victims
+    
+    def evictability(self, page: ContextPage) -> Evictability:
+        """页面类型的换出保护级别"""
+        return self.eviction_protection.get(page.page_type, Evictability.NORMAL)
+    
+    def _is_evictable(self, page: ContextPage) -> bool:
+        """页面是否可以被换出"""
+        level = self.evictability(page)
+        if level == Evictability.NEVER:
+            return False
+        if level == Evictability.ALWAYS:
+            return True
+        return page.importance_score < 0.95
+    
+    def _evict_page(self, page: ContextPage):
+        """将指定的内存页面换出"""
+        page.status = PageStatus.SWAPPED
+        del self.pages_in_memory[page.page_id]
+        self.swapped_pages[page.page_id] = page
+        self.current_usage -= page.tokens
+        self._log_wal('evict', page_id=page.page_id)
         
         # 如果 dirty，写回存储
-        if victim_page.is_dirty() and self.storage:
-            self._write_to_storage(victim_page)
-            victim_page.mark_clean()
+        if page.is_dirty() and self.storage:
+            self._write_to_storage(page)
+            page.mark_clean()
         
         self.stats['swaps_out'] += 1
+        if not self._replaying:
+            self._publish_eviction(page)
+    
+    def subscribe_evictions(self, capacity: int = 1024) -> EvictionSubscription:
+        """
+        订阅页面换出事件
+        
+        Args:
+            capacity: 订阅队列容量，超出后丢弃最旧的事件
+        
+        Returns:
+            订阅对象；调用其 close() 或 unsubscribe_evictions 取消订阅
+        """
+        subscription = EvictionSubscription(max(capacity, 1))
+        self._eviction_subscriptions.append(subscription)
+        return subscription
+    
+    def unsubscribe_evictions(self, subscription: EvictionSubscription):
+        """取消换出事件订阅"""
+        subscription.close()
+        if subscription in self._eviction_subscriptions:
+            self._eviction_subscriptions.remove(subscription)
+    
+    def on_eviction(self, callback: Callable[[EvictionEvent], Any]):
+        """
+        注册页面换出回调（例如换出时触发摘要）
         
-        logger.debug(f"Swapped out page {victim_id[:8]} "
-                    f"({victim_page.tokens} tokens, score={score:.3f})")
+        回调可以是普通函数或 async 函数，在后台线程中执行，不会阻塞换出；
+        回调中的异常只会被记录。
+        
+        Args:
+            callback: 接收 EvictionEvent 的回调
+        """
+        self._eviction_callbacks.append(callback)
+    
+    def close(self):
+        """关闭所有换出订阅并停止回调线程池"""
+        for subscription in self._eviction_subscriptions:
+            subscription.close()
+        self._eviction_subscriptions.clear()
+        if self._eviction_executor is not None:
+            self._eviction_executor.shutdown(wait=False)
+            self._eviction_executor = None
+    
+    def _publish_eviction(self, page: ContextPage):
+        if not self._eviction_subscriptions and not self._eviction_callbacks:
+            return
+        event = EvictionEvent(
+            page_id=page.page_id,
+            agent_pid=page.agent_pid,
+            page_type=page.page_type,
+            tokens=page.tokens,
+            timestamp=self.clock.now(),
+        )
+        self._eviction_subscriptions = [s for s in self._eviction_subscriptions if not s.closed]
+        for subscription in self._eviction_subscriptions:
+            subscription._publish(event)
         
-        return True
+        if not self._eviction_callbacks:
+            return
+        if self._eviction_executor is None:
+            self._eviction_executor = ThreadPoolExecutor(
+                max_workers=2, thread_name_prefix="context-evictions"
+            )
+        for callback in list(self._eviction_callbacks):
+            self._eviction_executor.submit(self._run_eviction_callback, callback, event)
+    
+    def _run_eviction_callback(self, callback: Callable, event: EvictionEvent):
+        """执行单个换出回调（隔离异常）"""
+        try:
+            result = callback(event)
+            if inspect.isawaitable(result):
+                self.coroutine_runner(result)
+        except Exception:
+            logger.exception("Error in eviction callback for page %s", event.page_id[:8])
     
     def _swap_in_page(self, page_id: str) -> Optional[ContextPage]:
         """
@@ -855,17 +2683,18 @@ class ContextManager:
         page = self.swapped_pages[page_id]
         
         # 确保有足够空间
-        while self.current_usage + page.tokens > self.max_context_tokens:
-            if not self._swap_out_page():
-                logger.error(f"Cannot swap in page {page_id[:8]}: no space available")
-                return None
+        self._free_tokens(self.current_usage + page.tokens - self.max_context_tokens)
+        if self.current_usage + page.tokens > self.max_context_tokens:
+            logger.error(f"Cannot swap in page {page_id[:8]}: no space available")
+            return None
         
         # 执行换入
         page.status = PageStatus.IN_MEMORY
-        page.touch()
+        page.touch(self.clock.now())
         self.pages_in_memory[page_id] = page
         del self.swapped_pages[page_id]
         self.current_usage += page.tokens
+        self._log_wal('swap_in', page_id=page_id)
         
         self.stats['swaps_in'] += 1
         
@@ -879,6 +2708,11 @@ class ContextManager:
             self.storage.save_context_page(page)
             logger.debug(f"Wrote page {page.page_id[:8]} to storage")
     
+    def _delete_from_storage(self, page_id: str):
+        """从存储后端删除页面（页面被合并后不应再被加载）"""
+        if self.storage and hasattr(self.storage, 'delete_context_page'):
+            self.storage.delete_context_page(page_id)
+    
     def _load_from_storage(self, page_id: str) -> Optional[ContextPage]:
         """从存储后端加载页面"""
         if not self.storage or not hasattr(self.storage, 'load_context_page'):
@@ -887,12 +2721,15 @@ class ContextManager:
         page = self.storage.load_context_page(page_id)
         if page:
             # 确保有足够空间
-            while self.current_usage + page.tokens > self.max_context_tokens:
-                if not self._swap_out_page():
-                    return None
+            self._free_tokens(self.current_usage + page.tokens - self.max_context_tokens)
+            if self.current_usage + page.tokens > self.max_context_tokens:
+                return None
             
             self.pages_in_memory[page_id] = page
             self.current_usage += page.tokens
+            if page.ttl is not None:
+                self._expiring_pages.add(page_id)
+            self._log_wal('load', page=page.to_dict())
             self.stats['swaps_in'] += 1
             logger.debug(f"Loaded page {page_id[:8]} from storage")
         
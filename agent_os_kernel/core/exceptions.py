@@ -50,6 +50,11 @@ class AgentTimeoutError(AgentError):
     pass
 
 
+class AgentCancelledError(AgentError):
+    """Agent 运行被取消（进程被终止）"""
+    pass
+
+
 class ContextError(AgentOSKernelError):
     """上下文相关错误"""
     pass
@@ -85,11 +90,31 @@ class StorageOperationError(StorageError):
     pass
 
 
+class StorageTimeoutError(StorageOperationError):
+    """存储查询超过 statement_timeout 被取消"""
+    pass
+
+
 class CheckpointError(StorageError):
     """检查点错误"""
     pass
 
 
+class CheckpointNotFoundError(CheckpointError):
+    """检查点不存在"""
+    pass
+
+
+class ContextBudgetExceededError(ContextOverflowError):
+    """Agent 超出单 Agent 上下文预算"""
+    pass
+
+
+class InvalidStateError(AgentOSKernelError):
+    """内核或 Agent 状态不允许该操作"""
+    pass
+
+
 class SchedulerError(AgentOSKernelError):
     """调度相关异常"""
     pass
@@ -105,6 +130,11 @@ class SchedulingError(SchedulerError):
     pass
 
 
+class QuotaExceededError(SchedulerError):
+    """资源请求超过配额上限，即使配额窗口重置也无法批准"""
+    pass
+
+
 class TaskError(AgentOSKernelError):
     """Task 执行错误"""
     pass
@@ -140,6 +170,11 @@ class ConfigurationError(AgentOSKernelError):
     pass
 
 
+class TemplateVariableError(ValidationError):
+    """提示词模板缺少变量"""
+    pass
+
+
 class ErrorHandler:
     """错误处理器"""
     
@@ -2,17 +2,129 @@
 Logging System - 日志系统
 
 结构化日志、级别控制、输出格式
+
+日志上下文：log_context() 打开一个作用域（类似 tracing 的 span），作用域内
+的所有日志（包括标准 logging 的 logger.info 等）都带上 agent_pid、iteration
+等字段，多个 Agent 并发运行时每行日志都能对应到具体 Agent。
 """
 
+from contextlib import contextmanager
+from contextvars import ContextVar
 from dataclasses import dataclass, field
-from typing import Dict, Optional, List
+from typing import Any, Callable, Dict, Optional, List
 from datetime import datetime
 from datetime import timezone, timezone
 from enum import Enum
+import functools
+import inspect
 import json
+import logging
 import sys
 
 
+# 当前日志上下文（随线程 / 协程隔离）
+_LOG_CONTEXT: ContextVar[Dict[str, Any]] = ContextVar('agent_os_log_context', default={})
+
+
+@contextmanager
+def log_context(**fields):
+    """
+    打开日志上下文作用域
+    
+    作用域可以嵌套，内层字段覆盖外层同名字段；值为 None 的字段被忽略。
+    
+    示例：
+        with log_context(agent_pid=process.pid, iteration=3):
+            logger.info("step started")   # -> [pid=1a2b3c4d iter=3] step started
+    """
+    merged = dict(_LOG_CONTEXT.get())
+    merged.update({k: v for k, v in fields.items() if v is not None})
+    token = _LOG_CONTEXT.set(merged)
+    try:
+        yield
+    finally:
+        _LOG_CONTEXT.reset(token)
+
+
+def current_log_context() -> Dict[str, Any]:
+    """获取当前日志上下文的副本"""
+    return dict(_LOG_CONTEXT.get())
+
+
+def format_log_context(context: Dict[str, Any]) -> str:
+    """格式化为日志行前缀，如 "[pid=1a2b3c4d iter=3] "；上下文为空时返回空串"""
+    parts = []
+    if context.get('agent_pid'):
+        parts.append(f"pid={str(context['agent_pid'])[:8]}")
+    if 'iteration' in context:
+        parts.append(f"iter={context['iteration']}")
+    for key in sorted(context):
+        if key not in ('agent_pid', 'iteration'):
+            parts.append(f"{key}={context[key]}")
+    return f"[{' '.join(parts)}] " if parts else ""
+
+
+def traced(pid_arg: str = "pid") -> Callable:
+    """
+    方法装饰器：以参数 pid_arg 的值作为 agent_pid 打开日志上下文
+    
+    参数值是带 pid 属性的对象（如 AgentProcess）时取其 pid。
+    """
+    def decorator(func: Callable) -> Callable:
+        index = list(inspect.signature(func).parameters).index(pid_arg)
+        
+        @functools.wraps(func)
+        def wrapper(*args, **kwargs):
+            value = kwargs.get(pid_arg, args[index] if index < len(args) else None)
+            with log_context(agent_pid=getattr(value, 'pid', value)):
+                return func(*args, **kwargs)
+        return wrapper
+    return decorator
+
+
+_record_factory_installed = False
+
+
+def install_log_context():
+    """
+    让标准 logging 的每条记录带上日志上下文（可重复调用）
+    
+    记录上会增加 agent_pid、iteration（缺省为 "-"）和 log_context（格式化
+    后的前缀）属性，格式串中可以使用 %(log_context)s 等。
+    """
+    global _record_factory_installed
+    if _record_factory_installed:
+        return
+    base_factory = logging.getLogRecordFactory()
+    
+    def factory(*args, **kwargs):
+        record = base_factory(*args, **kwargs)
+        context = _LOG_CONTEXT.get()
+        record.agent_pid = context.get('agent_pid', '-')
+        record.iteration = context.get('iteration', '-')
+        record.log_context = format_log_context(context)
+        return record
+    
+    logging.setLogRecordFactory(factory)
+    _record_factory_installed = True
+
+
+class LogContextDefaults(logging.Filter):
+    """
+    处理器过滤器：为缺少日志上下文字段的记录补上缺省值
+    
+    install_log_context() 之前创建的记录没有 log_context 等属性，挂上该
+    过滤器的处理器在格式串中使用 %(log_context)s 时不会报错。
+    """
+    
+    def filter(self, record: logging.LogRecord) -> bool:
+        if not hasattr(record, 'log_context'):
+            record.agent_pid = '-'
+            record.iteration = '-'
+            record.log_context = ''
+        return True
+
+
 class LogLevel(Enum):
     """日志级别"""
     DEBUG = 10
@@ -51,7 +163,7 @@ class StructuredLogger:
             timestamp=datetime.now(timezone.utc).isoformat(),
             level=level.name,
             message=message,
-            extra=extra
+            extra={**current_log_context(), **extra}
         )
         
         for handler in self._handlers:
@@ -7,10 +7,13 @@ Metrics - 性能指标
 2. 计算统计信息
 3. 导出指标
 4. 设置告警阈值
+5. 批量累积热点路径上的更新（MetricsBatch）
 """
 
 import asyncio
+import copy
 import logging
+import threading
 import time
 from typing import Dict, List, Optional, Callable
 from dataclasses import dataclass, field
@@ -64,6 +67,11 @@ class MetricsCollector:
         self._history: List[Dict] = []
         self._max_history = max_history
         self._lock = asyncio.Lock()
+        # 保护 _metrics 的写入；热点路径应通过 batch() 减少对它的竞争
+        self._write_lock = threading.Lock()
+        self._local = threading.local()
+        self._batches: List["MetricsBatch"] = []
+        self._batches_lock = threading.Lock()
         
         # 注册默认指标
         self._register_defaults()
@@ -94,22 +102,79 @@ class MetricsCollector:
     
     def counter(self, name: str, value: float = 1, labels: Dict = None):
         """增加计数器"""
-        return self._update_metric(name, value, "counter", labels)
+        with self._write_lock:
+            return self._update_metric(name, value, "counter", labels)
     
     def gauge(self, name: str, value: float, labels: Dict = None):
         """设置仪表盘"""
-        return self._update_metric(name, value, "gauge", labels)
+        with self._write_lock:
+            return self._update_metric(name, value, "gauge", labels)
     
     def histogram(self, name: str, value: float, labels: Dict = None):
         """记录直方图"""
-        metric = self._update_metric(name, value, "histogram", labels)
+        with self._write_lock:
+            return self._observe(name, [value], labels)
+    
+    def _observe(self, name: str, values: List[float], labels: Dict = None) -> Metric:
+        """记录一组直方图观察值（调用方持有 _write_lock）"""
+        for value in values:
+            metric = self._update_metric(name, value, "histogram", labels)
+            for bucket in metric.buckets:
+                if value <= bucket:
+                    metric.buckets[bucket] += 1
+        return metric
+    
+    def batch(self, flush_every: int = 100,
+              flush_interval: float = 1.0) -> "MetricsBatch":
+        """
+        获取当前线程的批量累积器
         
-        # 更新 bucket
-        for bucket in metric.buckets:
-            if value <= bucket:
-                metric.buckets[bucket] += 1
+        同一线程（包括其中运行的所有 asyncio 任务）共享一个累积器，
+        参数只在首次创建时生效。
         
-        return metric
+        Args:
+            flush_every: 累积多少次更新后自动刷新
+            flush_interval: 距上次刷新超过多少秒后自动刷新
+        
+        Returns:
+            MetricsBatch 实例
+        """
+        batch = getattr(self._local, "batch", None)
+        if batch is None:
+            batch = MetricsBatch(self, flush_every, flush_interval)
+            self._local.batch = batch
+            with self._batches_lock:
+                self._batches.append(batch)
+        return batch
+    
+    def flush(self) -> int:
+        """
+        把所有线程累积器中尚未刷新的更新写入收集器
+        
+        读取接口（get / get_all / get_stats / 导出）会自动调用。
+        
+        Returns:
+            写入的更新次数
+        """
+        with self._batches_lock:
+            batches = list(self._batches)
+        flushed = sum(batch.flush() for batch in batches)
+        with self._batches_lock:
+            # 所属线程已退出的累积器刷新后不会再有新数据
+            self._batches = [b for b in self._batches if b.owner.is_alive()]
+        return flushed
+    
+    def _merge(self, counters: Dict, gauges: Dict, histograms: Dict):
+        """合并一个累积器的更新（key 为 (name, 排序后的标签元组)）"""
+        with self._write_lock:
+            for (name, labels), (total, count) in counters.items():
+                metric = self._update_metric(name, total, "counter", dict(labels))
+                # _update_metric 按一次更新计数，这里补齐批量中的次数
+                metric.count += count - 1
+            for (name, labels), value in gauges.items():
+                self._update_metric(name, value, "gauge", dict(labels))
+            for (name, labels), values in histograms.items():
+                self._observe(name, values, dict(labels))
     
     def _update_metric(
         self,
@@ -126,8 +191,7 @@ class MetricsCollector:
             self._metrics[key] = Metric(
                 name=name,
                 metric_type=mtype_enum,
-                labels=labels or {},
-                value=value
+                labels=labels or {}
             )
         
         metric = self._metrics[key]
@@ -154,15 +218,19 @@ class MetricsCollector:
     
     def get(self, name: str, labels: Dict = None) -> Optional[Metric]:
         """获取指标"""
+        self.flush()
         key = self._make_key(name, labels)
         return self._metrics.get(key)
     
     def get_all(self) -> Dict[str, Metric]:
         """获取所有指标"""
-        return copy.deepcopy(self._metrics)
+        self.flush()
+        with self._write_lock:
+            return copy.deepcopy(self._metrics)
     
     def get_stats(self) -> Dict:
         """获取统计信息"""
+        self.flush()
         return {
             "total_metrics": len(self._metrics),
             "metrics": {
@@ -178,6 +246,7 @@ class MetricsCollector:
     
     def reset(self, name: str = None, labels: Dict = None):
         """重置指标"""
+        self.flush()
         if name:
             key = self._make_key(name, labels)
             if key in self._metrics:
@@ -188,6 +257,7 @@ class MetricsCollector:
     
     def export_prometheus(self) -> str:
         """导出 Prometheus 格式"""
+        self.flush()
         lines = ["# Agent OS Kernel Metrics"]
         
         for metric in self._metrics.values():
@@ -228,6 +298,93 @@ class MetricsCollector:
         return json.dumps(self.get_stats(), indent=2, default=str)
 
 
+class MetricsBatch:
+    """
+    指标批量累积器
+    
+    在本地累积计数器、仪表盘和直方图更新，达到次数阈值或时间间隔后
+    一次性合并进共享的 MetricsCollector，热点循环不必每次都竞争收集器的锁。
+    通过 MetricsCollector.batch() 获取当前线程的实例。
+    """
+    
+    def __init__(self, collector: MetricsCollector, flush_every: int = 100,
+                 flush_interval: float = 1.0):
+        """
+        初始化累积器
+        
+        Args:
+            collector: 刷新目标
+            flush_every: 累积多少次更新后自动刷新
+            flush_interval: 距上次刷新超过多少秒后自动刷新
+        """
+        if flush_every < 1:
+            raise ValueError("flush_every must be >= 1")
+        self.collector = collector
+        self.flush_every = flush_every
+        self.flush_interval = flush_interval
+        self.owner = threading.current_thread()
+        self._counters: Dict[tuple, List[float]] = {}
+        self._gauges: Dict[tuple, float] = {}
+        self._histograms: Dict[tuple, List[float]] = {}
+        self._pending = 0
+        self._last_flush = time.monotonic()
+        # 只在其他线程调用 collector.flush() 时才会发生竞争
+        self._lock = threading.Lock()
+    
+    @staticmethod
+    def _key(name: str, labels: Optional[Dict]) -> tuple:
+        return (name, tuple(sorted(labels.items())) if labels else ())
+    
+    def counter(self, name: str, value: float = 1, labels: Dict = None):
+        """累积计数器增量"""
+        with self._lock:
+            entry = self._counters.setdefault(self._key(name, labels), [0.0, 0])
+            entry[0] += value
+            entry[1] += 1
+            self._pending += 1
+        self._maybe_flush()
+    
+    def gauge(self, name: str, value: float, labels: Dict = None):
+        """记录仪表盘的最新值"""
+        with self._lock:
+            self._gauges[self._key(name, labels)] = value
+            self._pending += 1
+        self._maybe_flush()
+    
+    def histogram(self, name: str, value: float, labels: Dict = None):
+        """累积直方图观察值"""
+        with self._lock:
+            self._histograms.setdefault(self._key(name, labels), []).append(value)
+            self._pending += 1
+        self._maybe_flush()
+    
+    @property
+    def pending(self) -> int:
+        """尚未刷新的更新次数"""
+        return self._pending
+    
+    def _maybe_flush(self):
+        if (self._pending >= self.flush_every
+                or time.monotonic() - self._last_flush >= self.flush_interval):
+            self.flush()
+    
+    def flush(self) -> int:
+        """
+        立即把累积的更新写入收集器
+        
+        Returns:
+            写入的更新次数
+        """
+        # 合并期间持有本地锁，保证 flush() 返回时读取方能看到全部更新
+        with self._lock:
+            pending, self._pending = self._pending, 0
+            self._last_flush = time.monotonic()
+            if pending:
+                self.collector._merge(self._counters, self._gauges, self._histograms)
+                self._counters, self._gauges, self._histograms = {}, {}, {}
+            return pending
+
+
 # Timer 装饰器
 def timer(metrics: MetricsCollector, metric_name: str):
     """计时装饰器"""
@@ -19,6 +19,9 @@ from datetime import datetime, timezone, timedelta
 import hashlib
 import json
 
+from ..tokenizer import Tokenizer, default_tokenizer
+from ..prompt_template import PromptTemplate
+
 logger = logging.getLogger(__name__)
 
 
@@ -51,6 +54,7 @@ class CompressionConfig:
     preserve_recent: int = 3         # 保留最近 N 条消息
     importance_threshold: float = 0.4  # 重要性阈值
     summary_model: Optional[str] = None  # 摘要模型
+    summary_prompt: str = "[历史对话摘要]\n{{summary}}"  # 摘要消息模板
     token_per_message: int = 4       # 每个消息的 token 开销
 
 
@@ -61,8 +65,11 @@ class ContextCompressor:
     参考 AutoGen 的上下文管理实现，提供多种压缩策略。
     """
     
-    def __init__(self, config: CompressionConfig = None):
+    def __init__(self, config: CompressionConfig = None,
+                 tokenizer: Optional[Tokenizer] = None):
         self.config = config or CompressionConfig()
+        self.tokenizer = tokenizer or default_tokenizer()
+        self.summary_prompt = PromptTemplate(self.config.summary_prompt)
         self._importance_cache: Dict[str, float] = {}
     
     def compress_messages(
@@ -141,7 +148,7 @@ class ContextCompressor:
             summary = self._generate_summary(old_messages)
             result.append({
                 "role": "system",
-                "content": f"[历史对话摘要]\n{summary}",
+                "content": self.summary_prompt.render(summary=summary),
                 "_compressed": True
             })
         
@@ -232,7 +239,7 @@ class ContextCompressor:
                 summary = self._generate_summary(less_important)
                 result.append({
                     "role": "system",
-                    "content": f"[历史对话摘要]\n{summary}",
+                    "content": self.summary_prompt.render(summary=summary),
                     "_compressed": True
                 })
             
@@ -373,13 +380,13 @@ class ContextCompressor:
             
             # 内容
             if isinstance(content, str):
-                total += len(content) // 4  # 英文约 4 字符/token
+                total += self.tokenizer.count_tokens(content)
             elif isinstance(content, list):
                 for item in content:
                     if isinstance(item, dict) and item.get("type") == "text":
-                        total += len(item.get("text", "")) // 4
+                        total += self.tokenizer.count_tokens(item.get("text", ""))
                     else:
-                        total += len(str(item)) // 4
+                        total += self.tokenizer.count_tokens(str(item))
         
         return total
     
@@ -0,0 +1,61 @@
+# -*- coding: utf-8 -*-
+"""
+Prompt Template - 提示词模板
+
+用 {{var}} 占位符描述提示词，渲染时缺少变量会抛出 TemplateVariableError，
+而不是静默留空。内核的系统提示词和压缩器的摘要提示词都通过模板构造，
+可以在配置中覆盖（例如本地化）。
+"""
+
+import re
+from typing import Any, Dict, List, Mapping, Optional
+
+from .exceptions import TemplateVariableError
+
+
+class PromptTemplate:
+    """
+    提示词模板
+
+    Example:
+        template = PromptTemplate("You are {{name}}. Your task: {{task}}")
+        template.render(name="Researcher", task="summarize the paper")
+    """
+
+    PLACEHOLDER = re.compile(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}")
+
+    def __init__(self, template: str):
+        """
+        Args:
+            template: 模板文本（占位符两侧的空白会被忽略，如 {{ name }}）
+        """
+        self.template = template
+        self.variables: List[str] = list(dict.fromkeys(
+            match.group(1) for match in self.PLACEHOLDER.finditer(template)
+        ))
+
+    def render(self, values: Optional[Mapping[str, Any]] = None, **kwargs: Any) -> str:
+        """
+        渲染模板
+
+        Args:
+            values: 变量名 -> 值（值按 str() 转换）
+            **kwargs: 额外的变量，优先于 values
+
+        Returns:
+            渲染后的文本
+
+        Raises:
+            TemplateVariableError: 模板中的变量没有提供值
+        """
+        merged: Dict[str, Any] = {**(values or {}), **kwargs}
+        missing = [name for name in self.variables if name not in merged]
+        if missing:
+            raise TemplateVariableError(
+                f"Missing template variables: {', '.join(missing)}",
+                details={'missing': missing, 'template': self.template}
+            )
+        return self.PLACEHOLDER.sub(lambda match: str(merged[match.group(1)]), self.template)
+
+    def __repr__(self) -> str:
+        return f"PromptTemplate({self.template!r})"
@@ -14,15 +14,22 @@ Agent Scheduler - 进程调度器
 - 当 Agent 成为长期运行的服务，真正的需求才会浮现
 """
 
+import copy
 import time
+import threading
+import heapq
 import logging
-from typing import Optional, Dict, Any, List, Callable, Tuple
+from typing import Optional, Dict, Any, List, Callable, Set, Tuple, Union
 from queue import PriorityQueue, Empty
 from collections import defaultdict
-from dataclasses import dataclass, field
+from dataclasses import asdict, dataclass, field
 from enum import Enum
 from abc import ABC, abstractmethod
 
+from .clock import Clock, IdGenerator, MockClock, UUID_GENERATOR
+from .exceptions import QuotaExceededError, SchedulingError
+from .logging_system import traced
+
 
 logger = logging.getLogger(__name__)
 
@@ -33,10 +40,20 @@ class AgentState(Enum):
     RUNNING = "running"       # 正在执行
     WAITING = "waiting"       # 等待资源（如 API 限流）
     SUSPENDED = "suspended"   # 被挂起（主动暂停）
+    COMPLETED = "completed"   # 已完成（正常结束并产生结果）
     TERMINATED = "terminated" # 已终止
     ERROR = "error"           # 错误状态
 
 
+class TaskQueueStatus(Enum):
+    """持久化任务队列中的任务状态"""
+    PENDING = "pending"       # 已加入调度器，尚未开始运行
+    RUNNING = "running"       # 已开始运行
+    COMPLETED = "completed"   # 正常完成
+    FAILED = "failed"         # 因错误终止
+    TERMINATED = "terminated" # 因其他原因终止
+
+
 @dataclass
 class ResourceQuota:
     """资源配额配置"""
@@ -46,6 +63,58 @@ class ResourceQuota:
     window_seconds: float = 3600            # 配额窗口（秒）
 
 
+class WaitReasonKind(Enum):
+    """等待原因类型"""
+    RESOURCE_TOKENS = "resource_tokens"   # 等待 token 配额
+    DEPENDENCY = "dependency"             # 等待另一个进程结束
+    EXTERNAL = "external"                 # 等待外部事件（需手动唤醒或超时）
+
+
+@dataclass
+class WaitReason:
+    """
+    结构化的等待原因
+    
+    调度器据此自动唤醒进程：配额足够时唤醒 RESOURCE_TOKENS，
+    依赖进程终止时唤醒 DEPENDENCY。
+    """
+    kind: WaitReasonKind
+    tokens: int = 0
+    dependency_pid: Optional[str] = None
+    description: str = ""
+    
+    @classmethod
+    def resource_tokens(cls, tokens: int, description: str = "") -> 'WaitReason':
+        return cls(WaitReasonKind.RESOURCE_TOKENS, tokens=tokens,
+                   description=description or f"waiting for {tokens} tokens")
+    
+    @classmethod
+    def dependency(cls, pid: str) -> 'WaitReason':
+        return cls(WaitReasonKind.DEPENDENCY, dependency_pid=pid,
+                   description=f"waiting for {pid[:8]}")
+    
+    @classmethod
+    def external(cls, description: str) -> 'WaitReason':
+        return cls(WaitReasonKind.EXTERNAL, description=description)
+    
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            'kind': self.kind.value,
+            'tokens': self.tokens,
+            'dependency_pid': self.dependency_pid,
+            'description': self.description,
+        }
+    
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> 'WaitReason':
+        return cls(
+            kind=WaitReasonKind(data['kind']),
+            tokens=data.get('tokens', 0),
+            dependency_pid=data.get('dependency_pid'),
+            description=data.get('description', ''),
+        )
+
+
 @dataclass
 class AgentProcess:
     """
@@ -57,10 +126,12 @@ class AgentProcess:
     name: str
     state: AgentState = AgentState.READY
     priority: int = 50                      # 优先级（0-100，越小越高）
+    weight: float = 1.0                     # 公平调度权重（越大分到的 token 份额越多）
     
     # 资源使用统计
     token_usage: int = 0
     api_calls: int = 0
+    cost_usd: float = 0.0                   # 累计 LLM 调用成本
     execution_time: float = 0.0
     cpu_time: float = 0.0                   # 实际 LLM 推理时间
     
@@ -78,6 +149,7 @@ class AgentProcess:
     # 等待信息
     waiting_since: Optional[float] = None
     waiting_reason: Optional[str] = None
+    wait_reason: Optional[WaitReason] = None
     
     # 错误处理
     error_count: int = 0
@@ -88,10 +160,20 @@ class AgentProcess:
     parent_pid: Optional[str] = None
     child_pids: List[str] = field(default_factory=list)
     
+    # 组调度（同组进程一起运行或都不运行）
+    group_id: Optional[str] = None
+    
+    # 完成结果
+    result: Any = None
+    
     def is_active(self) -> bool:
         """是否处于活动状态"""
         return self.state in (AgentState.READY, AgentState.RUNNING, AgentState.WAITING, AgentState.SUSPENDED)
     
+    def is_finished(self) -> bool:
+        """是否已结束（完成或终止）"""
+        return self.state in (AgentState.COMPLETED, AgentState.TERMINATED)
+    
     def to_dict(self) -> Dict[str, Any]:
         """序列化为字典"""
         return {
@@ -99,8 +181,10 @@ class AgentProcess:
             'name': self.name,
             'state': self.state.value,
             'priority': self.priority,
+            'weight': self.weight,
             'token_usage': self.token_usage,
             'api_calls': self.api_calls,
+            'cost_usd': self.cost_usd,
             'execution_time': self.execution_time,
             'cpu_time': self.cpu_time,
             'context': self.context,
@@ -109,10 +193,14 @@ class AgentProcess:
             'last_run': self.last_run,
             'started_at': self.started_at,
             'terminated_at': self.terminated_at,
+            'time_slice': self.time_slice,
             'error_count': self.error_count,
             'last_error': self.last_error,
+            'max_errors': self.max_errors,
             'parent_pid': self.parent_pid,
             'child_pids': self.child_pids,
+            'group_id': self.group_id,
+            'result': self.result,
         }
     
     @classmethod
@@ -123,8 +211,10 @@ class AgentProcess:
             name=data['name'],
             state=AgentState(data['state']),
             priority=data.get('priority', 50),
+            weight=data.get('weight', 1.0),
             token_usage=data.get('token_usage', 0),
             api_calls=data.get('api_calls', 0),
+            cost_usd=data.get('cost_usd', 0.0),
             execution_time=data.get('execution_time', 0.0),
             cpu_time=data.get('cpu_time', 0.0),
             context=data.get('context', {}),
@@ -133,10 +223,14 @@ class AgentProcess:
             last_run=data.get('last_run', 0.0),
             started_at=data.get('started_at'),
             terminated_at=data.get('terminated_at'),
+            time_slice=data.get('time_slice', 60.0),
             error_count=data.get('error_count', 0),
             last_error=data.get('last_error'),
+            max_errors=data.get('max_errors', 3),
             parent_pid=data.get('parent_pid'),
             child_pids=data.get('child_pids', []),
+            group_id=data.get('group_id'),
+            result=data.get('result'),
         )
         return process
 
@@ -195,17 +289,19 @@ class ResourceQuotaManager:
     管理 API 调用、Token 使用等资源配额，防止单一 Agent 耗尽预算。
     """
     
-    def __init__(self, quota: ResourceQuota):
+    def __init__(self, quota: ResourceQuota,
+                 clock: Optional[Callable[[], float]] = None):
         self.quota = quota
+        self._clock = clock or time.time
         self.current_usage = {'tokens': 0, 'api_calls': 0}
         self.per_agent_usage: Dict[str, Dict[str, int]] = defaultdict(lambda: {
             'tokens': 0, 'api_calls': 0
         })
-        self.window_start = time.time()
+        self.window_start = self._clock()
     
     def reset_if_needed(self):
         """检查并重置配额窗口"""
-        current_time = time.time()
+        current_time = self._clock()
         if current_time - self.window_start >= self.quota.window_seconds:
             logger.info(f"Resetting quota window")
             self.current_usage = {'tokens': 0, 'api_calls': 0}
@@ -222,6 +318,42 @@ class ResourceQuotaManager:
         """
         self.reset_if_needed()
         
+        approved, reason = self.check_quota(agent_pid, tokens, api_calls)
+        if not approved:
+            return False, reason
+        
+        # 批准并记录
+        agent_usage = self.per_agent_usage[agent_pid]
+        self.current_usage['tokens'] += tokens
+        self.current_usage['api_calls'] += api_calls
+        agent_usage['tokens'] += tokens
+        agent_usage['api_calls'] += api_calls
+        
+        return True, "Approved"
+    
+    def check_satisfiable(self, tokens: int, api_calls: int = 1) -> Tuple[bool, str]:
+        """
+        检查请求在空的配额窗口中能否被批准（与当前使用量无关）
+        
+        Returns:
+            (能否批准, 原因)
+        """
+        if tokens > self.quota.max_tokens_per_request:
+            return False, "Request exceeds max tokens per request"
+        if tokens > self.quota.max_tokens_per_window * 0.3:
+            return False, "Request exceeds the per-agent token quota (30% of global)"
+        if api_calls > self.quota.max_api_calls_per_window * 0.3:
+            return False, "Request exceeds the per-agent API call quota (30% of global)"
+        return True, "Satisfiable"
+    
+    def check_quota(self, agent_pid: str, tokens: int,
+                    api_calls: int = 1) -> Tuple[bool, str]:
+        """
+        检查配额是否足够（不记录使用量）
+        
+        Returns:
+            (是否足够, 原因)
+        """
         # 检查全局配额
         if self.current_usage['tokens'] + tokens > self.quota.max_tokens_per_window:
             return False, "Global token quota exceeded"
@@ -244,19 +376,13 @@ class ResourceQuotaManager:
         if agent_usage['api_calls'] + api_calls > max_per_agent_calls:
             return False, "Agent API call quota exceeded (30% of global)"
         
-        # 批准并记录
-        self.current_usage['tokens'] += tokens
-        self.current_usage['api_calls'] += api_calls
-        agent_usage['tokens'] += tokens
-        agent_usage['api_calls'] += api_calls
-        
         return True, "Approved"
     
     def get_usage_stats(self) -> Dict[str, Any]:
         """获取使用统计"""
         return {
             'window_start': self.window_start,
-            'window_elapsed': time.time() - self.window_start,
+            'window_elapsed': self._clock() - self.window_start,
             'global_usage': self.current_usage.copy(),
             'global_limits': {
                 'tokens': self.quota.max_tokens_per_window,
@@ -270,6 +396,107 @@ class ResourceQuotaManager:
         }
 
 
+class SimEventKind(Enum):
+    """模拟事件类型"""
+    ARRIVAL = "arrival"         # 进程到达
+    CONSUME = "consume"         # 进程消耗 token
+    COMPLETE = "complete"       # 进程完成
+
+
+@dataclass
+class SimEvent:
+    """
+    模拟事件（带虚拟时间戳，单位为秒）
+    
+    ARRIVAL 使用 name/priority/weight/time_slice 创建进程，
+    CONSUME 使用 tokens 申请配额。
+    """
+    time: float
+    kind: SimEventKind
+    pid: str
+    name: str = ""
+    priority: int = 50
+    weight: float = 1.0
+    time_slice: float = 60.0
+    tokens: int = 0
+
+
+@dataclass
+class SimProcessStats:
+    """单个进程的模拟统计"""
+    pid: str
+    arrival_time: float
+    completion_time: Optional[float] = None
+    wait_time: float = 0.0              # 就绪但未运行的累计时间
+    run_cycles: int = 0
+    starvation_count: int = 0           # 连续等待达到阈值的次数
+    
+    @property
+    def turnaround_time(self) -> Optional[float]:
+        """周转时间（完成时间 - 到达时间），未完成时为 None"""
+        if self.completion_time is None:
+            return None
+        return self.completion_time - self.arrival_time
+
+
+@dataclass
+class SimReport:
+    """调度模拟报告"""
+    cycles: int
+    timeline: List[Optional[str]]       # 每个周期运行的进程 PID（空闲为 None）
+    processes: Dict[str, SimProcessStats]
+    stats: Dict[str, Any] = field(default_factory=dict)
+    
+    @property
+    def average_wait_time(self) -> float:
+        if not self.processes:
+            return 0.0
+        return sum(p.wait_time for p in self.processes.values()) / len(self.processes)
+    
+    @property
+    def average_turnaround_time(self) -> Optional[float]:
+        completed = [p.turnaround_time for p in self.processes.values()
+                     if p.turnaround_time is not None]
+        if not completed:
+            return None
+        return sum(completed) / len(completed)
+    
+    @property
+    def total_starvation(self) -> int:
+        return sum(p.starvation_count for p in self.processes.values())
+
+
+@dataclass
+class SchedulerStats:
+    """
+    调度器统计（get_process_stats 的稳定结构）
+    
+    新增字段只能追加，不修改已有字段的名称和类型。
+    """
+    total_scheduled: int
+    total_preempted: int
+    total_completed: int
+    total_errors: int
+    total_checkpoints: int
+    total_restores: int
+    total_yields: int
+    total_processes: int
+    active_processes: int
+    running: Optional[str]              # 主运行进程名称
+    gang_running: List[str]
+    co_running: List[str]
+    running_count: int
+    groups: Dict[str, List[str]]
+    ready_queue_size: int
+    waiting_queue_size: int
+    state_distribution: Dict[str, int]
+    quota_usage: Dict[str, Any] = field(default_factory=dict)
+    
+    def to_dict(self) -> Dict[str, Any]:
+        """序列化为字典"""
+        return asdict(self)
+
+
 class AgentScheduler:
     """
     Agent 调度器 - 真正的操作系统级进程管理
@@ -303,7 +530,14 @@ class AgentScheduler:
     
     def __init__(self, time_slice: float = 60.0,
                  quota: Optional[ResourceQuota] = None,
-                 storage: Optional[Any] = None):
+                 storage: Optional[Any] = None,
+                 fair_share: bool = False,
+                 max_gang_size: int = 8,
+                 clock: Optional[Union[Clock, Callable[[], float]]] = None,
+                 max_concurrent: Optional[int] = None,
+                 id_generator: Optional[IdGenerator] = None,
+                 persist_tasks: bool = False,
+                 finished_task_retention: int = 1000):
         """
         初始化调度器
         
@@ -311,9 +545,33 @@ class AgentScheduler:
             time_slice: 默认时间片（秒）
             quota: 资源配额配置
             storage: 存储后端（用于检查点）
+            fair_share: 是否启用加权公平调度（按 token_usage / weight 选择进程，
+                        而不是按优先级）
+            max_gang_size: 单个进程组可同时运行的最大进程数
+            clock: 时间源（Clock 或返回时间戳的函数，默认 time.time；
+                   模拟和测试时使用 MockClock）
+            max_concurrent: 同时处于运行状态的最大进程数（含组成员，None 表示不限制）
+            id_generator: 检查点 ID 生成器（默认 UUID4）
+            persist_tasks: 是否把进程的加入、开始运行和结束写入存储的任务队列，
+                           以便崩溃后用 recover_pending_tasks 恢复（需要 storage
+                           支持 save_task / list_tasks）
+            finished_task_retention: 任务队列中最多保留的已结束任务记录数，
+                                     超出时删除最早结束的记录
+        
+        Raises:
+            SchedulingError: max_concurrent 小于 1
         """
+        if max_concurrent is not None and max_concurrent < 1:
+            raise SchedulingError(f"max_concurrent must be at least 1, got {max_concurrent}")
         self.time_slice = time_slice
+        self.max_concurrent = max_concurrent
+        self._clock = clock or time.time
+        self.id_generator = id_generator or UUID_GENERATOR
         self.storage = storage
+        self.persist_tasks = persist_tasks
+        self.finished_task_retention = finished_task_retention
+        self.fair_share = fair_share
+        self.max_gang_size = max_gang_size
         
         # 队列
         self.ready_queue: PriorityQueue[SchedulableProcess] = PriorityQueue()
@@ -323,11 +581,18 @@ class AgentScheduler:
         self.processes: Dict[str, AgentProcess] = {}
         self.running: Optional[AgentProcess] = None
         
+        # 组调度：group_id -> 成员 PID，以及与 running 一同运行的组成员
+        self.groups: Dict[str, List[str]] = {}
+        self.gang_running: List[AgentProcess] = []
+        
+        # schedule_batch 额外调度、与 running 并行运行的进程
+        self.co_running: List[AgentProcess] = []
+        
         # IPC 通道
         self.ipc_channels: Dict[str, IPCChannel] = {}
         
         # 资源配额
-        self.quota_manager = ResourceQuotaManager(quota or ResourceQuota())
+        self.quota_manager = ResourceQuotaManager(quota or ResourceQuota(), clock=self._clock)
         
         # 统计
         self.stats = {
@@ -337,34 +602,183 @@ class AgentScheduler:
             'total_errors': 0,
             'total_checkpoints': 0,
             'total_restores': 0,
+            'total_yields': 0,
         }
         
         # 优雅终止标志
         self._shutdown_requested = False
         self._shutdown_callbacks: List[Callable] = []
         
+        # 有进程可能变为可调度时置位（主循环据此立即唤醒，而不是轮询）
+        self._work_event = threading.Event()
+        
+        # 调度器拥有的后台任务（shutdown 时统一停止）
+        self._stop_event = threading.Event()
+        self._background_tasks: List[threading.Thread] = []
+        
         logger.info(f"AgentScheduler initialized (time_slice={time_slice}s)")
     
+    @traced("process")
     def add_process(self, process: AgentProcess):
         """
         添加新进程到调度队列
         
+        设置了 parent_pid 且父进程已在调度器中时，进程会登记为父进程的子进程。
+        
         Args:
             process: Agent 进程
         """
+        self._register_process(process)
+        logger.info(f"Added process {process.name} (PID: {process.pid[:8]}...)")
+    
+    def _register_process(self, process: AgentProcess):
+        """登记进程、关联父进程并加入就绪队列（add_process 与 add_processes 共用）"""
         self.processes[process.pid] = process
+        parent = self.processes.get(process.parent_pid) if process.parent_pid else None
+        if parent is not None and process.pid not in parent.child_pids:
+            parent.child_pids.append(process.pid)
         self._enqueue(process)
-        logger.info(f"Added process {process.name} (PID: {process.pid[:8]}...)")
+        self._persist_task(process, TaskQueueStatus.PENDING)
+    
+    def add_processes(self, processes: List[AgentProcess]) -> List[str]:
+        """
+        批量添加进程到调度队列
+        
+        先校验整批 PID，再一次性加入，任一 PID 冲突时不添加任何进程。
+        资源使用记录与单个添加一样在首次申请资源时创建；设置了 parent_pid
+        的进程与单个添加一样登记为父进程（已在调度器中或在批次中更靠前）的子进程。
+        
+        Args:
+            processes: Agent 进程列表
+        
+        Returns:
+            已添加进程的 PID 列表（与输入顺序一致）
+        
+        Raises:
+            SchedulingError: PID 在批次内重复或已存在于调度器中
+        """
+        seen = set()
+        for process in processes:
+            if process.pid in seen or process.pid in self.processes:
+                raise SchedulingError(
+                    f"Duplicate process PID: {process.pid}",
+                    details={'pid': process.pid}
+                )
+            seen.add(process.pid)
+        
+        for process in processes:
+            self._register_process(process)
+        logger.info(f"Added {len(processes)} processes")
+        return [process.pid for process in processes]
+    
+    def add_process_to_group(self, process: AgentProcess, group_id: str):
+        """
+        将进程加入进程组（组调度）
+        
+        同组进程只有在全部就绪时才会一起被调度运行。进程尚未加入调度器时
+        会一并添加。
+        
+        Args:
+            process: Agent 进程
+            group_id: 进程组 ID
+        
+        Raises:
+            SchedulingError: 进程组已达到 max_gang_size
+        """
+        members = self.groups.setdefault(group_id, [])
+        if process.pid not in members:
+            if len(members) >= self.max_gang_size:
+                raise SchedulingError(
+                    f"Group {group_id} already has {len(members)} members "
+                    f"(max_gang_size={self.max_gang_size})",
+                    details={'group_id': group_id, 'pid': process.pid}
+                )
+            members.append(process.pid)
+        process.group_id = group_id
+        
+        if process.pid not in self.processes:
+            self.add_process(process)
+    
+    def _gang_ready(self, process: AgentProcess) -> Optional[List[AgentProcess]]:
+        """
+        返回与 process 一同运行的组成员
+        
+        Returns:
+            其余组成员列表；如果有成员未就绪则返回 None
+        """
+        others = []
+        for pid in self.groups.get(process.group_id, []):
+            if pid == process.pid:
+                continue
+            member = self.processes.get(pid)
+            if member is None or member.is_finished():
+                continue
+            if member.state != AgentState.READY:
+                return None
+            others.append(member)
+        return others
+    
+    def _requeue_gang(self):
+        """将与 running 一同运行的组成员放回就绪队列"""
+        for member in self.gang_running:
+            if member.state == AgentState.RUNNING:
+                self._enqueue(member)
+        self.gang_running = []
+    
+    def _release_gang_member(self, pid: str):
+        """进程离开运行状态时，从 gang_running 和 co_running 中移除"""
+        self.gang_running = [p for p in self.gang_running if p.pid != pid]
+        self.co_running = [p for p in self.co_running if p.pid != pid]
+    
+    def _running_count(self) -> int:
+        """当前处于运行状态的进程数"""
+        return (1 if self.running else 0) + len(self.gang_running) + len(self.co_running)
+    
+    def _free_slots(self) -> Optional[int]:
+        """剩余可运行名额（None 表示不限制）"""
+        if self.max_concurrent is None:
+            return None
+        return self.max_concurrent - self._running_count()
+    
+    def _start_running(self, process: AgentProcess):
+        """将进程置为运行状态"""
+        process.state = AgentState.RUNNING
+        process.last_run = self._clock()
+        if process.started_at is None:
+            process.started_at = self._clock()
+            self._persist_task(process, TaskQueueStatus.RUNNING)
     
     def _enqueue(self, process: AgentProcess):
         """将进程加入就绪队列"""
         process.state = AgentState.READY
         schedulable = SchedulableProcess(
             priority=process.priority,
-            timestamp=time.time(),
+            timestamp=self._clock(),
             process=process
         )
         self.ready_queue.put(schedulable)
+        self._work_event.set()
+    
+    def notify(self):
+        """通知等待中的主循环：可能有进程可以调度（如等待的依赖已结束）"""
+        self._work_event.set()
+    
+    def wait_for_work(self, timeout: float) -> bool:
+        """
+        阻塞直到有进程加入就绪队列（或 notify），最多 timeout 秒
+        
+        返回前清除信号；调用方应在返回后重新 schedule()，因此清除之后
+        才入队的进程不会被遗漏。
+        
+        Args:
+            timeout: 最长等待时间（秒）
+        
+        Returns:
+            是否因新的可调度工作被唤醒（False 表示超时）
+        """
+        signaled = self._work_event.wait(timeout)
+        self._work_event.clear()
+        return signaled
     
     def schedule(self) -> Optional[AgentProcess]:
         """
@@ -384,37 +798,238 @@ class AgentScheduler:
             if self._should_preempt(self.running):
                 logger.debug(f"Preempting {self.running.name}")
                 self._enqueue(self.running)
+                self._requeue_gang()
                 self.running = None
                 self.stats['total_preempted'] += 1
         
         # 检查等待队列中是否有进程可以唤醒
         self._check_waiting_queue()
         
-        # 如果没有运行中的进程，从队列取一个
-        if not self.running:
-            try:
-                schedulable = self.ready_queue.get(block=False)
+        # 如果没有运行中的进程，从队列取一个（已达到 max_concurrent 时不再调度）
+        free_slots = self._free_slots()
+        if not self.running and (free_slots is None or free_slots > 0):
+            deferred: List[SchedulableProcess] = []
+            while True:
+                try:
+                    schedulable = self._dequeue()
+                except Empty:
+                    break
                 process = schedulable.process
                 
-                # 检查进程是否仍然有效
-                if process.state == AgentState.TERMINATED:
-                    return self.schedule()  # 递归获取下一个
+                # 检查进程是否仍然有效（跳过已终止、等待中或已被组调度带走的过期条目）
+                if process.state != AgentState.READY:
+                    continue
+                
+                # 组调度：同组成员全部就绪才一起运行，否则整组都不运行
+                gang = []
+                if process.group_id:
+                    gang = self._gang_ready(process)
+                    if gang is None or (free_slots is not None and len(gang) + 1 > free_slots):
+                        deferred.append(schedulable)
+                        continue
                 
-                process.state = AgentState.RUNNING
-                process.last_run = time.time()
-                if process.started_at is None:
-                    process.started_at = time.time()
+                self._start_running(process)
+                for member in gang:
+                    self._start_running(member)
                 
                 self.running = process
+                self.gang_running = gang
                 self.stats['total_scheduled'] += 1
                 
-                logger.debug(f"Scheduled {process.name} (priority={process.priority})")
-                
-            except Empty:
-                pass
+                logger.debug(f"Scheduled {process.name} (priority={process.priority}"
+                             + (f", gang of {len(gang) + 1}" if gang else "") + ")")
+                break
+            
+            for schedulable in deferred:
+                self.ready_queue.put(schedulable)
         
         return self.running
     
+    def schedule_batch(self, n: int) -> List[AgentProcess]:
+        """
+        批量调度：让最多 n 个进程同时处于运行状态
+        
+        先执行一次 schedule()（抢占、唤醒、调度主进程），再从就绪队列中
+        取出进程填满剩余名额，总数不超过 max_concurrent。额外调度的
+        进程时间片用完后回到就绪队列。组进程只通过 schedule() 整组调度。
+        
+        Args:
+            n: 期望同时运行的进程数
+        
+        Returns:
+            当前所有运行中的进程（主进程、组成员和额外调度的进程）
+        """
+        self.schedule()
+        if self._shutdown_requested:
+            return []
+        
+        for process in list(self.co_running):
+            if self._clock() - process.last_run > process.time_slice:
+                self.co_running.remove(process)
+                self._enqueue(process)
+                self.stats['total_preempted'] += 1
+        
+        target = n if self.max_concurrent is None else min(n, self.max_concurrent)
+        deferred: List[SchedulableProcess] = []
+        while self._running_count() < target:
+            try:
+                schedulable = self._dequeue()
+            except Empty:
+                break
+            process = schedulable.process
+            
+            if process.state != AgentState.READY:
+                continue
+            if process.group_id:
+                deferred.append(schedulable)
+                continue
+            
+            self._start_running(process)
+            if self.running is None:
+                self.running = process
+            else:
+                self.co_running.append(process)
+            self.stats['total_scheduled'] += 1
+            logger.debug(f"Batch-scheduled {process.name} (priority={process.priority})")
+        
+        for schedulable in deferred:
+            self.ready_queue.put(schedulable)
+        
+        running = [self.running] if self.running else []
+        return running + self.gang_running + self.co_running
+    
+    def _dequeue(self) -> SchedulableProcess:
+        """
+        从就绪队列取出下一个进程
+        
+        fair_share 时按加权公平策略选择；否则按有效优先级（含继承的优先级）
+        选择，没有依赖等待时直接取堆顶。
+        
+        Raises:
+            Empty: 就绪队列为空
+        """
+        if self.fair_share:
+            return self._dequeue_fair()
+        
+        inherited = self._inherited_priorities()
+        if not inherited:
+            return self.ready_queue.get(block=False)
+        
+        with self.ready_queue.mutex:
+            entries = self.ready_queue.queue
+            if not entries:
+                raise Empty
+            
+            def rank(entry: SchedulableProcess) -> Tuple[int, float]:
+                boost = inherited.get(entry.process.pid, entry.priority)
+                return (min(entry.priority, boost), entry.timestamp)
+            
+            index = min(range(len(entries)), key=lambda i: rank(entries[i]))
+            schedulable = entries.pop(index)
+            heapq.heapify(entries)
+            self.ready_queue.not_full.notify()
+            return schedulable
+    
+    def _inherited_priorities(self) -> Dict[str, int]:
+        """
+        计算依赖等待带来的优先级继承（避免优先级反转）
+        
+        进程等待另一个进程结束时，被等待的进程继承等待者的优先级，
+        沿依赖链传递（A 等 B、B 等 C 时 C 继承 A 和 B 中较高的优先级）。
+        等待者被唤醒后继承自动失效。
+        
+        Returns:
+            被等待进程 PID -> 继承到的最高优先级（数值最小）
+        """
+        inherited: Dict[str, int] = {}
+        for waiter in self.waiting_queue.values():
+            priority = waiter.priority
+            current = waiter
+            seen = {waiter.pid}
+            while True:
+                reason = current.wait_reason
+                if not reason or reason.kind != WaitReasonKind.DEPENDENCY:
+                    break
+                dependency = self.processes.get(reason.dependency_pid)
+                if dependency is None or dependency.is_finished() or dependency.pid in seen:
+                    break
+                seen.add(dependency.pid)
+                inherited[dependency.pid] = min(inherited.get(dependency.pid, priority), priority)
+                priority = min(priority, dependency.priority)
+                current = dependency
+        return inherited
+    
+    def effective_priority(self, pid: str) -> Optional[int]:
+        """
+        进程当前的有效优先级（自身优先级与继承优先级中较高者）
+        
+        Returns:
+            有效优先级；进程不存在时返回 None
+        """
+        process = self.processes.get(pid)
+        if process is None:
+            return None
+        return min(process.priority, self._inherited_priorities().get(pid, process.priority))
+    
+    def _dequeue_fair(self) -> SchedulableProcess:
+        """
+        按加权公平策略从就绪队列取出进程
+        
+        选择 token_usage / weight 最小的就绪进程，权重为 2 的进程
+        大约可以使用两倍的 token 才会被降低优先。
+        
+        Raises:
+            Empty: 就绪队列为空
+        """
+        with self.ready_queue.mutex:
+            entries = self.ready_queue.queue
+            if not entries:
+                raise Empty
+            
+            def share(entry: SchedulableProcess) -> Tuple[float, int, float]:
+                process = entry.process
+                weight = process.weight if process.weight > 0 else 1.0
+                return (process.token_usage / weight, entry.priority, entry.timestamp)
+            
+            index = min(range(len(entries)), key=lambda i: share(entries[i]))
+            schedulable = entries.pop(index)
+            heapq.heapify(entries)
+            self.ready_queue.not_full.notify()
+            return schedulable
+    
+    @traced()
+    def yield_process(self, pid: str) -> bool:
+        """
+        运行中的进程主动让出 CPU（协作式调度）
+        
+        进程回到就绪队列中同优先级进程的末尾，状态置为 READY。
+        
+        Args:
+            pid: 进程 ID
+        
+        Returns:
+            是否成功让出（仅当前运行的进程可以让出）
+        """
+        co_running = next((p for p in self.co_running if p.pid == pid), None)
+        if co_running:
+            self.co_running.remove(co_running)
+            self._enqueue(co_running)
+            self.stats['total_yields'] += 1
+            logger.debug(f"Process {co_running.name} yielded")
+            return True
+        
+        if not self.running or self.running.pid != pid:
+            return False
+        
+        process = self.running
+        self.running = None
+        self._enqueue(process)
+        self._requeue_gang()
+        self.stats['total_yields'] += 1
+        
+        logger.debug(f"Process {process.name} yielded")
+        return True
+    
     def _should_preempt(self, process: AgentProcess) -> bool:
         """
         判断是否应该抢占当前进程
@@ -426,14 +1041,15 @@ class AgentScheduler:
         4. 进程执行时间过长
         """
         # 1. 时间片用完
-        if time.time() - process.last_run > process.time_slice:
+        if self._clock() - process.last_run > process.time_slice:
             logger.debug(f"Time slice expired for {process.name}")
             return True
         
         # 2. 有更高优先级的进程在等待
         if not self.ready_queue.empty():
             next_schedulable = self.ready_queue.queue[0]
-            if next_schedulable.priority < process.priority - 10:
+            inherited = self._inherited_priorities().get(process.pid, process.priority)
+            if next_schedulable.priority < min(process.priority, inherited) - 10:
                 logger.debug(f"Higher priority process waiting")
                 return True
         
@@ -449,22 +1065,34 @@ class AgentScheduler:
     def _check_waiting_queue(self):
         """检查等待队列，尝试唤醒进程"""
         to_wakeup = []
+        self.quota_manager.reset_if_needed()
         
         for pid, process in self.waiting_queue.items():
-            # 检查资源是否可用
-            if process.waiting_reason and process.waiting_reason.startswith("quota"):
-                approved, _ = self.quota_manager.request_quota(pid, 100, 0)
+            reason = process.wait_reason
+            
+            # 配额刷新后资源可用
+            if reason and reason.kind == WaitReasonKind.RESOURCE_TOKENS:
+                approved, _ = self.quota_manager.check_quota(pid, reason.tokens, 0)
                 if approved:
                     to_wakeup.append(pid)
             
-            # 超时唤醒
-            elif process.waiting_since and time.time() - process.waiting_since > 30:
+            # 依赖的进程已结束
+            elif reason and reason.kind == WaitReasonKind.DEPENDENCY:
+                dependency = self.processes.get(reason.dependency_pid)
+                if dependency is None or dependency.is_finished():
+                    to_wakeup.append(pid)
+            
+            # 外部事件：超时唤醒
+            elif process.waiting_since and self._clock() - process.waiting_since > 30:
                 to_wakeup.append(pid)
         
         for pid in to_wakeup:
             self.wakeup_process(pid)
     
-    def suspend_process(self, pid: str, create_checkpoint: bool = True) -> Optional[str]:
+    @traced()
+    def suspend_process(self, pid: str, create_checkpoint: bool = True,
+                        context_pages: Optional[List[Dict[str, Any]]] = None,
+                        description: Optional[str] = None) -> Optional[str]:
         """
         挂起进程（保存检查点）
         
@@ -473,6 +1101,8 @@ class AgentScheduler:
         Args:
             pid: 进程 ID
             create_checkpoint: 是否创建检查点
+            context_pages: 随检查点保存的上下文页面（序列化后的字典）
+            description: 检查点描述
         
         Returns:
             检查点 ID（如果创建）
@@ -483,26 +1113,38 @@ class AgentScheduler:
         
         if self.running and self.running.pid == pid:
             self.running = None
+            self._requeue_gang()
+        self._release_gang_member(pid)
         
         process.state = AgentState.SUSPENDED
         
         checkpoint_id = None
         if create_checkpoint and self.storage:
             try:
-                checkpoint_id = self.storage.save_checkpoint(
-                    agent_pid=pid,
-                    process_state=process.to_dict(),
-                    context_pages=[],  # 应该从 ContextManager 获取
-                    description=f"Suspended at {time.time()}"
-                )
-                process.checkpoint_id = checkpoint_id
-                self.stats['total_checkpoints'] += 1
-                logger.info(f"Created checkpoint {checkpoint_id[:8]} for {process.name}")
+                new_checkpoint_id = self.id_generator.new_id()
+                saved = self.storage.save_checkpoint({
+                    'checkpoint_id': new_checkpoint_id,
+                    'agent_pid': pid,
+                    'agent_name': process.name,
+                    'description': description or f"Suspended at {self._clock()}",
+                    'state': process.to_dict(),
+                    'context_pages': context_pages or [],
+                    'previous_checkpoint': process.checkpoint_id,
+                    'created_at': self._clock(),
+                })
+                if saved:
+                    checkpoint_id = new_checkpoint_id
+                    process.checkpoint_id = checkpoint_id
+                    self.stats['total_checkpoints'] += 1
+                    logger.info(f"Created checkpoint {checkpoint_id[:8]} for {process.name}")
+                else:
+                    logger.error(f"Storage rejected checkpoint for {process.name}")
             except Exception as e:
                 logger.error(f"Failed to create checkpoint: {e}")
         
         return checkpoint_id
     
+    @traced()
     def resume_process(self, pid: str, checkpoint_id: Optional[str] = None) -> bool:
         """
         恢复挂起的进程
@@ -520,8 +1162,9 @@ class AgentScheduler:
         if checkpoint_id and self.storage:
             checkpoint = self.storage.load_checkpoint(checkpoint_id)
             if checkpoint:
-                process = AgentProcess.from_dict(checkpoint['process_state'])
+                process = AgentProcess.from_dict(checkpoint['state'])
                 process.state = AgentState.READY
+                process.child_pids = [c for c in process.child_pids if c in self.processes]
                 self.processes[pid] = process
                 self.stats['total_restores'] += 1
                 logger.info(f"Restored process {process.name} from checkpoint {checkpoint_id[:8]}")
@@ -536,16 +1179,28 @@ class AgentScheduler:
         
         return False
     
-    def terminate_process(self, pid: str, reason: str = "completed"):
+    @traced()
+    def terminate_process(self, pid: str, reason: str = "completed", cascade: bool = False):
         """
         终止进程
         
         实现优雅终止：给进程机会清理资源。
+        
+        Args:
+            pid: 进程 ID
+            reason: 终止原因（"error" 计入错误统计）
+            cascade: 是否先终止所有仍在运行的子孙进程（原因为 "parent_terminated"）
         """
         process = self.processes.get(pid)
         if not process:
             return
         
+        if cascade:
+            for child_pid in list(process.child_pids):
+                child = self.processes.get(child_pid)
+                if child is not None and not child.is_finished():
+                    self.terminate_process(child_pid, "parent_terminated", cascade=True)
+        
         # 调用终止回调
         for callback in self._shutdown_callbacks:
             try:
@@ -554,29 +1209,218 @@ class AgentScheduler:
                 logger.error(f"Error in shutdown callback: {e}")
         
         process.state = AgentState.TERMINATED
-        process.terminated_at = time.time()
+        process.terminated_at = self._clock()
         
         if self.running and self.running.pid == pid:
             self.running = None
+            self._requeue_gang()
+        self._release_gang_member(pid)
         
         if pid in self.waiting_queue:
             del self.waiting_queue[pid]
         
         if reason == "error":
             self.stats['total_errors'] += 1
+            self._persist_task(process, TaskQueueStatus.FAILED)
         else:
             self.stats['total_completed'] += 1
+            self._persist_task(process, TaskQueueStatus.TERMINATED)
+        
+        # 依赖该进程的等待者可以被唤醒了
+        self._work_event.set()
         
         logger.info(f"Terminated {process.name} (reason: {reason})")
     
+    def terminate_group(self, pids: List[str], reason: str = "completed") -> int:
+        """
+        批量终止进程
+        
+        不存在或已结束的 PID 会被跳过。
+        
+        Args:
+            pids: 要终止的进程 PID 列表
+            reason: 终止原因（同 terminate_process）
+        
+        Returns:
+            实际终止的进程数
+        """
+        terminated = 0
+        for pid in pids:
+            process = self.processes.get(pid)
+            if process is None or process.is_finished():
+                continue
+            self.terminate_process(pid, reason)
+            terminated += 1
+        return terminated
+    
+    @traced()
+    def complete_process(self, pid: str, result: Any = None) -> bool:
+        """
+        标记进程正常完成
+        
+        状态置为 COMPLETED，记录结果，并将进程从运行、就绪和等待队列中
+        移除，之后不会再被调度。存储中的进程记录同步更新完成时间和结果。
+        
+        Args:
+            pid: 进程 ID
+            result: 完成结果
+        
+        Returns:
+            是否成功标记（进程不存在或已结束时返回 False）
+        """
+        process = self.processes.get(pid)
+        if not process or process.is_finished():
+            return False
+        
+        for callback in self._shutdown_callbacks:
+            try:
+                callback(process)
+            except Exception as e:
+                logger.error(f"Error in shutdown callback: {e}")
+        
+        process.state = AgentState.COMPLETED
+        process.result = result
+        process.terminated_at = self._clock()
+        
+        if self.running and self.running.pid == pid:
+            self.running = None
+            self._requeue_gang()
+        self._release_gang_member(pid)
+        self.waiting_queue.pop(pid, None)
+        self._remove_from_ready_queue(pid)
+        
+        self.stats['total_completed'] += 1
+        self._persist_task(process, TaskQueueStatus.COMPLETED)
+        
+        if self.storage:
+            try:
+                record = process.to_dict()
+                record['completed_at'] = process.terminated_at
+                self.storage.save(f"process:{pid}", record)
+            except Exception as e:
+                logger.warning(f"Failed to persist completion of {process.name}: {e}")
+        
+        # 依赖该进程的等待者可以被唤醒了
+        self._work_event.set()
+        
+        logger.info(f"Completed {process.name}")
+        return True
+    
+    _FINISHED_TASK_STATUSES = frozenset({
+        TaskQueueStatus.COMPLETED, TaskQueueStatus.FAILED, TaskQueueStatus.TERMINATED
+    })
+    
+    def _persist_task(self, process: AgentProcess, status: TaskQueueStatus):
+        """
+        把进程的任务状态写入存储（未启用 persist_tasks 时不做任何事）
+        
+        进程结束（完成、失败或终止）时保留其最终状态，并只保留最近的
+        finished_task_retention 条已结束记录，避免任务队列无限增长。
+        """
+        if not self.persist_tasks or not hasattr(self.storage, 'save_task'):
+            return
+        try:
+            self.storage.save_task({
+                'pid': process.pid,
+                'name': process.name,
+                'status': status.value,
+                'priority': process.priority,
+                'process': process.to_dict(),
+                'updated_at': self._clock(),
+            })
+            if status in self._FINISHED_TASK_STATUSES and hasattr(self.storage, 'prune_tasks'):
+                self.storage.prune_tasks([s.value for s in self._FINISHED_TASK_STATUSES],
+                                         keep=self.finished_task_retention)
+        except Exception as e:
+            logger.warning(f"Failed to persist task {process.name} ({status.value}): {e}")
+    
+    def recover_pending_tasks(self) -> List[str]:
+        """
+        从存储的任务队列重建就绪队列（崩溃恢复）
+        
+        状态为 PENDING 或 RUNNING 的任务（运行中崩溃的任务会重新执行）按
+        更新时间顺序重新加入调度器；PID 已在进程表中的任务会被跳过。
+        
+        Returns:
+            恢复的进程 PID 列表
+        """
+        if not hasattr(self.storage, 'list_tasks'):
+            return []
+        recovered = []
+        statuses = [TaskQueueStatus.PENDING.value, TaskQueueStatus.RUNNING.value]
+        for record in self.storage.list_tasks(statuses):
+            if record['pid'] in self.processes or not record.get('process'):
+                continue
+            self.add_process(AgentProcess.from_dict(record['process']))
+            recovered.append(record['pid'])
+        if recovered:
+            logger.info(f"Recovered {len(recovered)} pending tasks from storage")
+        return recovered
+    
+    def _remove_from_ready_queue(self, pid: str):
+        """从就绪队列中移除指定进程的所有条目"""
+        with self.ready_queue.mutex:
+            entries = self.ready_queue.queue
+            remaining = [entry for entry in entries if entry.process.pid != pid]
+            if len(remaining) != len(entries):
+                entries[:] = remaining
+                heapq.heapify(entries)
+                self.ready_queue.not_full.notify()
+    
+    @traced()
+    def record_error(self, pid: str, message: str) -> bool:
+        """
+        记录进程错误
+        
+        递增 error_count 并记录 last_error；达到 max_errors 时自动终止进程。
+        
+        Args:
+            pid: 进程 ID
+            message: 错误信息
+        
+        Returns:
+            进程是否因此被终止
+        """
+        process = self.processes.get(pid)
+        if not process or process.is_finished():
+            return False
+        
+        process.error_count += 1
+        process.last_error = message
+        logger.warning(f"Process {process.name} error "
+                       f"({process.error_count}/{process.max_errors}): {message}")
+        
+        if process.error_count >= process.max_errors:
+            self.terminate_process(pid, "error")
+            return True
+        
+        return False
+    
     def request_resources(self, agent_pid: str, tokens: int,
                          api_calls: int = 1) -> bool:
-        """请求资源配额"""
+        """
+        请求资源配额
+        
+        配额不足时进程进入等待，配额窗口重置后唤醒。
+        
+        Returns:
+            是否批准
+        
+        Raises:
+            QuotaExceededError: 请求超过单次或单个 Agent 的配额上限，等待也无法批准
+        """
+        satisfiable, reason = self.quota_manager.check_satisfiable(tokens, api_calls)
+        if not satisfiable:
+            raise QuotaExceededError(f"Resource request from {agent_pid[:8]} can never be approved: "
+                                     f"{reason}",
+                                     details={'agent_pid': agent_pid, 'tokens': tokens,
+                                              'api_calls': api_calls})
+        
         approved, reason = self.quota_manager.request_quota(agent_pid, tokens, api_calls)
         
         if not approved:
             logger.warning(f"Resource request denied for {agent_pid[:8]}: {reason}")
-            self.wait_process(agent_pid, reason)
+            self.wait_process(agent_pid, WaitReason.resource_tokens(tokens, reason))
         else:
             process = self.processes.get(agent_pid)
             if process:
@@ -585,28 +1429,43 @@ class AgentScheduler:
         
         return approved
     
-    def wait_process(self, pid: str, reason: str = "waiting"):
-        """将进程置为等待状态"""
+    @traced()
+    def wait_process(self, pid: str, reason: Union[str, WaitReason] = "waiting"):
+        """
+        将进程置为等待状态
+        
+        Args:
+            pid: 进程 ID
+            reason: 等待原因；字符串视为外部事件（WaitReason.external）
+        """
         process = self.processes.get(pid)
         if not process:
             return
         
+        if isinstance(reason, str):
+            reason = WaitReason.external(reason)
+        
         if self.running and self.running.pid == pid:
             self.running = None
+            self._requeue_gang()
+        self._release_gang_member(pid)
         
         process.state = AgentState.WAITING
-        process.waiting_since = time.time()
-        process.waiting_reason = reason
+        process.waiting_since = self._clock()
+        process.waiting_reason = reason.description
+        process.wait_reason = reason
         self.waiting_queue[pid] = process
         
-        logger.debug(f"Process {process.name} is now waiting ({reason})")
+        logger.debug(f"Process {process.name} is now waiting ({reason.description})")
     
+    @traced()
     def wakeup_process(self, pid: str):
         """唤醒等待中的进程"""
         if pid in self.waiting_queue:
             process = self.waiting_queue.pop(pid)
             process.waiting_since = None
             process.waiting_reason = None
+            process.wait_reason = None
             self._enqueue(process)
             logger.debug(f"Woke up process {process.name}")
     
@@ -642,6 +1501,7 @@ class AgentScheduler:
         """请求优雅终止"""
         logger.info("Shutdown requested, stopping scheduler...")
         self._shutdown_requested = True
+        self._work_event.set()
         
         # 为所有运行中的进程创建检查点
         if self.running:
@@ -655,21 +1515,341 @@ class AgentScheduler:
         """注册终止回调"""
         self._shutdown_callbacks.append(callback)
     
+    # ========== 后台任务 ==========
+    
+    def spawn_background_task(self, target: Callable[[threading.Event], Any],
+                              name: str) -> threading.Thread:
+        """
+        启动由调度器管理生命周期的后台任务
+        
+        target 接收停止事件，应周期性检查 event.is_set()（或使用 event.wait）
+        并在置位后尽快返回。
+        
+        Args:
+            target: 任务函数
+            name: 任务名（用于线程名和日志）
+        
+        Raises:
+            SchedulingError: 调度器已关闭
+        """
+        if self._stop_event.is_set():
+            raise SchedulingError(f"Cannot start task {name}: scheduler is shut down")
+        
+        thread = threading.Thread(target=target, args=(self._stop_event,),
+                                  name=f"scheduler-{name}", daemon=True)
+        self._background_tasks.append(thread)
+        thread.start()
+        logger.debug(f"Started background task {name}")
+        return thread
+    
+    def shutdown(self, timeout: float = 5.0) -> bool:
+        """
+        停止所有后台任务并等待其退出
+        
+        Args:
+            timeout: 等待所有任务退出的总时长（秒）
+        
+        Returns:
+            是否所有任务都已退出
+        """
+        self._stop_event.set()
+        
+        deadline = time.time() + timeout
+        for thread in self._background_tasks:
+            thread.join(max(0.0, deadline - time.time()))
+        
+        alive = [t for t in self._background_tasks if t.is_alive()]
+        for thread in alive:
+            logger.warning(f"Background task {thread.name} did not stop within {timeout}s")
+        self._background_tasks = alive
+        
+        return not alive
+    
+    # ========== 进程查询 ==========
+    
+    def process(self, pid: str) -> Optional[AgentProcess]:
+        """获取进程副本（不存在时返回 None）"""
+        process = self.processes.get(pid)
+        return copy.deepcopy(process) if process else None
+    
+    def running_processes(self) -> List[AgentProcess]:
+        """获取当前运行中的进程副本（含组调度成员和批量调度的进程）"""
+        running = [self.running] if self.running else []
+        return [copy.deepcopy(p) for p in running + self.gang_running + self.co_running]
+    
+    def list_processes(self) -> List[AgentProcess]:
+        """获取所有进程的副本（按创建时间排序）"""
+        return [copy.deepcopy(p)
+                for p in sorted(self.processes.values(), key=lambda p: p.created_at)]
+    
+    def children(self, pid: str) -> List[AgentProcess]:
+        """
+        获取进程的直接子进程副本（按创建顺序）
+        
+        Args:
+            pid: 父进程 ID
+        
+        Returns:
+            子进程列表；进程不存在时为空列表
+        """
+        process = self.processes.get(pid)
+        if process is None:
+            return []
+        return [copy.deepcopy(self.processes[child_pid])
+                for child_pid in process.child_pids if child_pid in self.processes]
+    
+    def tree(self, root_pid: Optional[str] = None) -> List[Dict[str, Any]]:
+        """
+        获取进程树
+        
+        Args:
+            root_pid: 只返回以该进程为根的子树（None 表示所有没有父进程的进程）
+        
+        Returns:
+            根节点列表，每个节点为 {'pid', 'name', 'state', 'children': [...]}
+        """
+        def node(process: AgentProcess, seen: Set[str]) -> Dict[str, Any]:
+            seen.add(process.pid)
+            return {
+                'pid': process.pid,
+                'name': process.name,
+                'state': process.state.value,
+                'children': [node(self.processes[c], seen) for c in process.child_pids
+                             if c in self.processes and c not in seen],
+            }
+        
+        if root_pid is not None:
+            root = self.processes.get(root_pid)
+            return [node(root, set())] if root else []
+        roots = [p for p in sorted(self.processes.values(), key=lambda p: p.created_at)
+                 if p.parent_pid not in self.processes]
+        return [node(root, set()) for root in roots]
+    
+    # ========== 状态持久化 ==========
+    
+    def snapshot(self) -> Dict[str, Any]:
+        """
+        导出完整调度器状态（可 JSON 序列化）
+        
+        包含进程表、就绪队列顺序、运行中进程、等待队列与资源使用，
+        配合 restore_from 实现热重启。
+        """
+        # 队列中可能残留已转入等待或终止的进程，只保留仍就绪的
+        ready: List[str] = []
+        for schedulable in sorted(self.ready_queue.queue):
+            process = schedulable.process
+            if process.state == AgentState.READY and process.pid not in ready:
+                ready.append(process.pid)
+        
+        return {
+            'processes': [p.to_dict() for p in self.processes.values()],
+            'ready': ready,
+            'running': self.running.pid if self.running else None,
+            'gang_running': [p.pid for p in self.gang_running],
+            'co_running': [p.pid for p in self.co_running],
+            'groups': {gid: list(pids) for gid, pids in self.groups.items()},
+            'waiting': {
+                pid: {
+                    'since': p.waiting_since,
+                    'reason': p.waiting_reason,
+                    'wait_reason': p.wait_reason.to_dict() if p.wait_reason else None,
+                }
+                for pid, p in self.waiting_queue.items()
+            },
+            'resource_usage': {
+                'window_start': self.quota_manager.window_start,
+                'current_usage': dict(self.quota_manager.current_usage),
+                'per_agent_usage': {
+                    pid: dict(usage)
+                    for pid, usage in self.quota_manager.per_agent_usage.items()
+                },
+            },
+            'stats': dict(self.stats),
+            'created_at': self._clock(),
+        }
+    
+    def restore_from(self, snapshot: Dict[str, Any]):
+        """
+        从 snapshot() 导出的状态重建调度器
+        
+        现有的进程表和队列会被替换。
+        """
+        self.processes = {
+            data['pid']: AgentProcess.from_dict(data)
+            for data in snapshot.get('processes', [])
+        }
+        # 快照中的子进程记录可能已不存在，只保留恢复后仍在进程表中的 PID
+        for process in self.processes.values():
+            process.child_pids = [c for c in process.child_pids if c in self.processes]
+        
+        self.ready_queue = PriorityQueue()
+        for pid in snapshot.get('ready', []):
+            if pid in self.processes:
+                self._enqueue(self.processes[pid])
+        
+        running_pid = snapshot.get('running')
+        self.running = self.processes.get(running_pid) if running_pid else None
+        if self.running:
+            self.running.state = AgentState.RUNNING
+        
+        self.groups = {gid: list(pids) for gid, pids in snapshot.get('groups', {}).items()}
+        self.gang_running = [
+            self.processes[pid] for pid in snapshot.get('gang_running', [])
+            if pid in self.processes
+        ]
+        self.co_running = [
+            self.processes[pid] for pid in snapshot.get('co_running', [])
+            if pid in self.processes
+        ]
+        for member in self.gang_running + self.co_running:
+            member.state = AgentState.RUNNING
+        
+        self.waiting_queue = {}
+        for pid, info in snapshot.get('waiting', {}).items():
+            process = self.processes.get(pid)
+            if process:
+                process.state = AgentState.WAITING
+                process.waiting_since = info.get('since')
+                process.waiting_reason = info.get('reason')
+                if info.get('wait_reason'):
+                    process.wait_reason = WaitReason.from_dict(info['wait_reason'])
+                else:
+                    process.wait_reason = WaitReason.external(info.get('reason') or "")
+                self.waiting_queue[pid] = process
+        
+        usage = snapshot.get('resource_usage', {})
+        self.quota_manager.window_start = usage.get('window_start', self._clock())
+        self.quota_manager.current_usage = dict(
+            usage.get('current_usage', {'tokens': 0, 'api_calls': 0})
+        )
+        self.quota_manager.per_agent_usage.clear()
+        for pid, agent_usage in usage.get('per_agent_usage', {}).items():
+            self.quota_manager.per_agent_usage[pid].update(agent_usage)
+        
+        self.stats.update(snapshot.get('stats', {}))
+        
+        logger.info(f"Restored scheduler state: {len(self.processes)} processes, "
+                    f"{self.ready_queue.qsize()} ready, {len(self.waiting_queue)} waiting")
+    
+    # ========== 模拟 ==========
+    
+    def simulate(self, events: List[SimEvent], cycle_seconds: float = 1.0,
+                 starvation_threshold: int = 10) -> SimReport:
+        """
+        模拟运行（dry-run）
+        
+        使用与当前调度器相同的配置（时间片、配额、公平调度、组大小）创建
+        独立的调度器，按逻辑时钟回放事件，不影响当前调度器的状态。
+        每个周期先应用到期的事件，再调用一次 schedule()。
+        
+        Args:
+            events: 模拟事件（无需排序）
+            cycle_seconds: 每个调度周期的虚拟时长
+            starvation_threshold: 连续多少个周期就绪却未运行记为一次饥饿
+        
+        Returns:
+            SimReport: 每周期的运行进程及各进程的等待/周转/饥饿统计
+        """
+        clock = MockClock()
+        sim = AgentScheduler(
+            time_slice=self.time_slice,
+            quota=copy.deepcopy(self.quota_manager.quota),
+            fair_share=self.fair_share,
+            max_gang_size=self.max_gang_size,
+            clock=clock,
+        )
+        
+        pending = sorted(events, key=lambda e: e.time)
+        last_time = pending[-1].time if pending else 0.0
+        processes: Dict[str, SimProcessStats] = {}
+        starving: Dict[str, int] = defaultdict(int)
+        timeline: List[Optional[str]] = []
+        
+        cycle = 0
+        index = 0
+        while clock.now() <= last_time:
+            # 应用到期事件
+            while index < len(pending) and pending[index].time <= clock.now():
+                event = pending[index]
+                index += 1
+                if event.kind == SimEventKind.ARRIVAL:
+                    sim.add_process(AgentProcess(
+                        pid=event.pid,
+                        name=event.name or event.pid,
+                        priority=event.priority,
+                        weight=event.weight,
+                        time_slice=event.time_slice,
+                        created_at=clock.now(),
+                    ))
+                    processes[event.pid] = SimProcessStats(event.pid, arrival_time=clock.now())
+                elif event.kind == SimEventKind.CONSUME:
+                    try:
+                        sim.request_resources(event.pid, event.tokens)
+                    except QuotaExceededError as e:
+                        logger.debug("Simulated request rejected: %s", e)
+                elif event.kind == SimEventKind.COMPLETE:
+                    if event.pid in sim.processes:
+                        sim.terminate_process(event.pid)
+                        if event.pid in processes:
+                            processes[event.pid].completion_time = clock.now()
+            
+            running = sim.schedule()
+            timeline.append(running.pid if running else None)
+            
+            # 统计等待与饥饿
+            for pid, process in sim.processes.items():
+                stats = processes.get(pid)
+                if stats is None:
+                    continue
+                if process.state == AgentState.RUNNING:
+                    stats.run_cycles += 1
+                    starving[pid] = 0
+                elif process.state == AgentState.READY:
+                    stats.wait_time += cycle_seconds
+                    starving[pid] += 1
+                    if starving[pid] >= starvation_threshold:
+                        stats.starvation_count += 1
+                        starving[pid] = 0
+            
+            cycle += 1
+            clock.set(cycle * cycle_seconds)
+        
+        return SimReport(
+            cycles=cycle,
+            timeline=timeline,
+            processes=processes,
+            stats=dict(sim.stats),
+        )
+    
     # ========== 统计 ==========
     
-    def get_process_stats(self) -> Dict[str, Any]:
-        """获取进程统计"""
+    def collect_stats(self) -> SchedulerStats:
+        """获取类型化的进程统计"""
         states = defaultdict(int)
         for p in self.processes.values():
             states[p.state.value] += 1
         
-        return {
-            **self.stats,
-            'total_processes': len(self.processes),
-            'active_processes': len([p for p in self.processes.values() if p.is_active()]),
-            'running': self.running.name if self.running else None,
-            'ready_queue_size': self.ready_queue.qsize(),
-            'waiting_queue_size': len(self.waiting_queue),
-            'state_distribution': dict(states),
-            'quota_usage': self.quota_manager.get_usage_stats(),
-        }
+        return SchedulerStats(
+            total_scheduled=self.stats['total_scheduled'],
+            total_preempted=self.stats['total_preempted'],
+            total_completed=self.stats['total_completed'],
+            total_errors=self.stats['total_errors'],
+            total_checkpoints=self.stats['total_checkpoints'],
+            total_restores=self.stats['total_restores'],
+            total_yields=self.stats['total_yields'],
+            total_processes=len(self.processes),
+            active_processes=len([p for p in self.processes.values() if p.is_active()]),
+            running=self.running.name if self.running else None,
+            gang_running=[p.name for p in self.gang_running],
+            co_running=[p.name for p in self.co_running],
+            running_count=self._running_count(),
+            groups={gid: list(pids) for gid, pids in self.groups.items()},
+            ready_queue_size=self.ready_queue.qsize(),
+            waiting_queue_size=len(self.waiting_queue),
+            state_distribution=dict(states),
+            quota_usage=self.quota_manager.get_usage_stats(),
+        )
+    
+    def get_process_stats(self) -> Dict[str, Any]:
+        """获取进程统计（SchedulerStats 的字典形式）"""
+        return self.collect_stats().to_dict()
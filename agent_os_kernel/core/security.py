@@ -10,17 +10,30 @@ Security Subsystem - 安全子系统
 """
 
 import os
+import re
+import json
 import uuid
 import time
 import logging
+import ipaddress
 import subprocess
-from typing import Optional, Dict, Any, List, Tuple
+from collections import deque
+from functools import lru_cache
+from typing import Optional, Dict, Any, List, Tuple, Union
 from dataclasses import dataclass, field
 from enum import Enum
 
+from .types import SecuritySeverity
+from .exceptions import ConfigurationError
+
 
 logger = logging.getLogger(__name__)
 
+# RFC 1123 主机名
+_HOSTNAME_PATTERN = re.compile(
+    r'^(?=.{1,253}$)([A-Za-z0-9]([A-Za-z0-9-]{0,61}[A-Za-z0-9])?)(\.[A-Za-z0-9]([A-Za-z0-9-]{0,61}[A-Za-z0-9])?)*$'
+)
+
 
 class PermissionLevel(Enum):
     """权限级别"""
@@ -30,6 +43,15 @@ class PermissionLevel(Enum):
     ADMIN = "admin"              # 管理权限
 
 
+class SecurityViolationType(Enum):
+    """安全违规类型（审计日志中记录为 security_violation:<value>）"""
+    FILE_ACCESS = "file_access"          # 越权文件访问
+    NETWORK_ACCESS = "network_access"    # 越权网络访问
+    TOOL_ACCESS = "tool_access"          # 使用未授权工具
+    RESOURCE_LIMIT = "resource_limit"    # 超出 token/内存/CPU 限制
+    SANDBOX_ESCAPE = "sandbox_escape"    # 路径穿越或符号链接逃逸
+
+
 @dataclass
 class SecurityPolicy:
     """
@@ -43,6 +65,7 @@ class SecurityPolicy:
     allowed_paths: List[str] = field(default_factory=lambda: ["/tmp", "/workspace"])
     blocked_paths: List[str] = field(default_factory=lambda: ["/etc", "/root", "/var/log"])
     read_only: bool = False
+    resolve_symlinks: bool = True   # 匹配前解析符号链接
     
     # 网络限制
     network_enabled: bool = True
@@ -50,6 +73,7 @@ class SecurityPolicy:
     blocked_hosts: List[str] = field(default_factory=list)
     
     # 资源限制
+    max_tokens: Optional[int] = None        # 单个 Agent 累计 token 上限（None 表示不限制）
     max_memory_mb: int = 512
     max_cpu_percent: int = 50
     max_execution_time: int = 300
@@ -63,6 +87,12 @@ class SecurityPolicy:
     use_sandbox: bool = True
     sandbox_image: str = "agent-sandbox:latest"
     
+    def allows_tool(self, tool_name: str) -> bool:
+        """工具是否被策略允许（blocked_tools 优先于 allowed_tools）"""
+        if tool_name in self.blocked_tools:
+            return False
+        return self.allowed_tools is None or tool_name in self.allowed_tools
+    
     def to_dict(self) -> Dict[str, Any]:
         """转换为字典"""
         return {
@@ -70,9 +100,11 @@ class SecurityPolicy:
             'allowed_paths': self.allowed_paths,
             'blocked_paths': self.blocked_paths,
             'read_only': self.read_only,
+            'resolve_symlinks': self.resolve_symlinks,
             'network_enabled': self.network_enabled,
             'allowed_hosts': self.allowed_hosts,
             'blocked_hosts': self.blocked_hosts,
+            'max_tokens': self.max_tokens,
             'max_memory_mb': self.max_memory_mb,
             'max_cpu_percent': self.max_cpu_percent,
             'max_execution_time': self.max_execution_time,
@@ -82,6 +114,160 @@ class SecurityPolicy:
             'use_sandbox': self.use_sandbox,
             'sandbox_image': self.sandbox_image,
         }
+    
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> 'SecurityPolicy':
+        """
+        从字典创建策略（缺失的字段使用默认值）
+        
+        Raises:
+            ConfigurationError: 包含未知字段、权限级别无效或校验失败
+        """
+        known = set(cls.__dataclass_fields__)
+        unknown = set(data) - known
+        if unknown:
+            raise ConfigurationError(f"Unknown security policy fields: {sorted(unknown)}")
+        
+        values = dict(data)
+        if 'permission_level' in values:
+            try:
+                values['permission_level'] = PermissionLevel(values['permission_level'])
+            except ValueError:
+                raise ConfigurationError(
+                    f"Invalid permission level: {values['permission_level']!r}")
+        
+        policy = cls(**values)
+        policy.validate()
+        return policy
+    
+    def validate(self):
+        """
+        校验策略
+        
+        Raises:
+            ConfigurationError: 文件系统路径不是绝对路径，或网络地址无法解析
+        """
+        for path in self.allowed_paths + self.blocked_paths:
+            if not os.path.isabs(path):
+                raise ConfigurationError(f"Filesystem path must be absolute: {path!r}")
+        
+        for host in self.allowed_hosts + self.blocked_hosts:
+            if not _is_valid_host(host):
+                raise ConfigurationError(f"Invalid network address: {host!r}")
+    
+    @classmethod
+    def from_json_file(cls, path: str) -> 'SecurityPolicy':
+        """
+        从 JSON 文件加载策略
+        
+        Args:
+            path: 文件路径
+        
+        Raises:
+            ConfigurationError: 文件无法读取、不是合法 JSON 或校验失败
+        """
+        try:
+            with open(path, 'r', encoding='utf-8') as f:
+                data = json.load(f)
+        except (OSError, json.JSONDecodeError) as e:
+            raise ConfigurationError(f"Cannot load security policy from {path}: {e}")
+        
+        if not isinstance(data, dict):
+            raise ConfigurationError(f"Security policy in {path} must be a JSON object")
+        return cls.from_dict(data)
+    
+    def to_json_file(self, path: str):
+        """
+        将策略保存为 JSON 文件
+        
+        Raises:
+            ConfigurationError: 策略校验失败
+        """
+        self.validate()
+        with open(path, 'w', encoding='utf-8') as f:
+            json.dump(self.to_dict(), f, ensure_ascii=False, indent=2)
+
+
+_GLOB_CHARS = set('*?[')
+# 字符类：[abc]、[a-z]、[!abc]；紧跟 [ 或 [! 的 ] 是普通字符
+_GLOB_CLASS = re.compile(r'\[!?\]?[^\]]*\]')
+
+
+def _glob_class_regex(body: str) -> str:
+    """把字符类内容（不含方括号）转换为不匹配 / 的正则字符类"""
+    negated = body.startswith('!')
+    if negated:
+        body = body[1:]
+    body = re.sub(r'([\\\[\]^])', r'\\\1', body)
+    return f'[^/{body}]' if negated else f'(?!/)[{body}]'
+
+
+@lru_cache(maxsize=256)
+def _compile_path_glob(pattern: str) -> 're.Pattern':
+    """
+    将路径 glob 编译为正则
+    
+    `**` 匹配任意层目录（包括零层），`*` 匹配单层内任意字符，`?` 匹配单个字符，
+    `[...]` 匹配方括号中的任一字符（`[!...]` 取反，均不匹配 `/`）；
+    没有闭合的 `[` 按普通字符处理。
+    """
+    regex = []
+    i = 0
+    while i < len(pattern):
+        if pattern.startswith('**/', i):
+            regex.append('(?:[^/]*/)*')
+            i += 3
+        elif pattern.startswith('**', i):
+            regex.append('.*')
+            i += 2
+        elif pattern[i] == '*':
+            regex.append('[^/]*')
+            i += 1
+        elif pattern[i] == '?':
+            regex.append('[^/]')
+            i += 1
+        elif pattern[i] == '[' and _GLOB_CLASS.match(pattern, i):
+            match = _GLOB_CLASS.match(pattern, i)
+            regex.append(_glob_class_regex(match.group()[1:-1]))
+            i = match.end()
+        else:
+            regex.append(re.escape(pattern[i]))
+            i += 1
+    return re.compile(''.join(regex) + '$')
+
+
+def path_rule_matches(pattern: str, path: str) -> bool:
+    """
+    路径是否匹配规则
+    
+    含通配符的规则按 glob 匹配；普通路径按目录前缀匹配（`/tmp` 匹配
+    `/tmp` 和 `/tmp/a`，但不匹配 `/tmpfoo`）。
+    """
+    if _GLOB_CHARS & set(pattern):
+        return bool(_compile_path_glob(pattern).match(path))
+    prefix = pattern.rstrip('/') or '/'
+    return path == prefix or path.startswith(prefix if prefix == '/' else prefix + '/')
+
+
+def _normalize_literal_path(path: str) -> str:
+    """去掉重复的分隔符和 . 组件，但保留 ..（用于判断路径是否经解析后才逃逸）"""
+    parts = [part for part in path.split(os.sep) if part not in ('', '.')]
+    return os.sep + os.sep.join(parts)
+
+
+def path_rule_specificity(pattern: str) -> int:
+    """规则的具体程度（非通配符字符数，字符类不计），越大越具体"""
+    return sum(1 for c in _GLOB_CLASS.sub('', pattern) if c not in _GLOB_CHARS)
+
+
+def _is_valid_host(host: str) -> bool:
+    """网络地址是否为合法的 IP、CIDR 网段或主机名"""
+    try:
+        ipaddress.ip_network(host, strict=False)
+        return True
+    except ValueError:
+        pass
+    return bool(_HOSTNAME_PATTERN.match(host))
 
 
 class SandboxManager:
@@ -91,8 +277,25 @@ class SandboxManager:
     使用 Docker 容器隔离 Agent 执行环境
     """
     
-    def __init__(self):
+    # 内存审计日志的默认容量（配置了存储时已有持久副本，只保留最近的少量记录）
+    AUDIT_LOG_CAPACITY = 4096
+    PERSISTED_AUDIT_LOG_CAPACITY = 256
+    
+    def __init__(self, storage: Optional[Any] = None,
+                 audit_log_capacity: Optional[int] = None):
+        """
+        Args:
+            storage: 存储管理器（用于记录安全违规审计日志，可选）
+            audit_log_capacity: 内存审计日志环形缓冲区容量，超出后丢弃最旧的记录
+                                （None 表示按是否配置存储选择默认值）
+        """
         self.containers: Dict[str, Any] = {}
+        self.storage = storage
+        if audit_log_capacity is None:
+            audit_log_capacity = (self.AUDIT_LOG_CAPACITY if storage is None
+                                  else self.PERSISTED_AUDIT_LOG_CAPACITY)
+        self.audit_log: deque = deque(maxlen=max(audit_log_capacity, 0))
+        self.total_audit_entries = 0
         self.docker_available = self._check_docker()
         
         if self.docker_available:
@@ -346,6 +549,46 @@ class SandboxManager:
         finally:
             del self.containers[agent_pid]
     
+    def log_audit(self, agent_pid: str,
+                  violation_type: Union[SecurityViolationType, str],
+                  message: str,
+                  severity: SecuritySeverity = SecuritySeverity.MEDIUM,
+                  details: Optional[Dict[str, Any]] = None):
+        """
+        记录安全违规
+        
+        以 security_violation:<type> 行为类型写入审计日志，并保留严重级别。
+        
+        Args:
+            agent_pid: Agent PID
+            violation_type: 违规类型
+            message: 违规说明
+            severity: 严重级别
+            details: 额外信息
+        """
+        if isinstance(violation_type, SecurityViolationType):
+            violation_type = violation_type.value
+        logger.warning(f"Security violation ({severity.value}) by {agent_pid[:8]}: {message}")
+        self.audit_log.append({
+            'agent_pid': agent_pid,
+            'action': f"security_violation:{violation_type}",
+            'details': {'input': details or {}, 'output': {}, 'reasoning': message},
+            'result': "denied",
+            'severity': severity.value,
+            'timestamp': time.time(),
+        })
+        self.total_audit_entries += 1
+        if self.storage is None:
+            return
+        self.storage.log_action(
+            agent_pid=agent_pid,
+            action_type=f"security_violation:{violation_type}",
+            input_data=details or {},
+            reasoning=message,
+            result="denied",
+            severity=severity.value
+        )
+    
     def validate_file_access(self, agent_pid: str, filepath: str, 
                             mode: str = 'read') -> bool:
         """
@@ -359,39 +602,220 @@ class SandboxManager:
         Returns:
             是否允许访问
         """
-        if agent_pid not in self.containers:
-            return False
-        
-        container = self.containers[agent_pid]
-        
-        # 获取策略
-        if hasattr(container, 'policy'):
-            policy = container.policy
-        elif isinstance(container, dict):
-            policy = container.get('policy', SecurityPolicy())
-        else:
+        policy = self._get_policy(agent_pid)
+        if policy is None:
             return False
         
         # 检查写权限
         if mode == 'write' and policy.read_only:
+            self.log_audit(agent_pid, SecurityViolationType.FILE_ACCESS, f"Write to read-only sandbox: {filepath}",
+                           SecuritySeverity.MEDIUM, {'path': filepath, 'mode': mode})
             return False
         
-        # 规范化路径
+        # 规范化路径：消除 . 和 ..，并按需解析符号链接
+        raw_path = filepath if os.path.isabs(filepath) else os.path.join(os.getcwd(), filepath)
+        literal_path = _normalize_literal_path(raw_path)
         abs_path = os.path.abspath(filepath)
+        if policy.resolve_symlinks:
+            abs_path = os.path.realpath(abs_path)
+        
+        decision = self._match_path_rules(policy, abs_path)
+        if decision is True:
+            return True
+        
+        # 字面上位于允许目录内、经 .. 或符号链接解析后落到允许目录之外：沙箱逃逸
+        if (abs_path != literal_path
+                and any(path_rule_matches(rule, literal_path) for rule in policy.allowed_paths)
+                and not any(path_rule_matches(rule, abs_path) for rule in policy.allowed_paths)):
+            self.log_audit(agent_pid, SecurityViolationType.SANDBOX_ESCAPE,
+                           f"Path escapes sandbox: {filepath} -> {abs_path}",
+                           SecuritySeverity.CRITICAL,
+                           {'path': filepath, 'resolved': abs_path, 'mode': mode})
+            return False
         
-        # 检查禁止路径
-        for blocked in policy.blocked_paths:
-            if abs_path.startswith(blocked):
-                return False
-        
-        # 检查允许路径
-        for allowed in policy.allowed_paths:
-            if abs_path.startswith(allowed):
-                return True
+        if decision is False:
+            self.log_audit(agent_pid, SecurityViolationType.FILE_ACCESS,
+                           f"Access to blocked path: {abs_path}",
+                           SecuritySeverity.HIGH, {'path': abs_path, 'mode': mode})
+            return False
         
         # 默认拒绝
+        self.log_audit(agent_pid, SecurityViolationType.FILE_ACCESS,
+                       f"Access outside allowed paths: {abs_path}",
+                       SecuritySeverity.LOW, {'path': abs_path, 'mode': mode})
+        return False
+    
+    def validate_tool_use(self, agent_pid: str, tool_name: str) -> bool:
+        """
+        验证 Agent 是否可以调用工具
+        
+        没有沙箱的 Agent 不受限制；沙箱内的 Agent 调用被策略禁止的工具时
+        记录 tool_access 违规。
+        
+        Args:
+            agent_pid: Agent PID
+            tool_name: 工具名称
+        
+        Returns:
+            是否允许调用
+        """
+        policy = self._get_policy(agent_pid)
+        if policy is None or policy.allows_tool(tool_name):
+            return True
+        reason = "blocked" if tool_name in policy.blocked_tools else "not in allowed tools"
+        self.log_audit(agent_pid, SecurityViolationType.TOOL_ACCESS,
+                       f"Tool {tool_name} is {reason}",
+                       SecuritySeverity.MEDIUM, {'tool': tool_name})
         return False
     
+    def check_resource_limits(self, agent_pid: str,
+                              tokens: int = 0,
+                              memory_mb: float = 0,
+                              cpu_percent: float = 0,
+                              execution_time: float = 0) -> bool:
+        """
+        检查 Agent 的资源使用是否在沙箱策略限制内
+        
+        超出限制时记录 resource_limit 违规。
+        
+        Args:
+            agent_pid: Agent PID
+            tokens: 累计 token 使用量
+            memory_mb: 内存使用（MB）
+            cpu_percent: CPU 使用率
+            execution_time: 累计执行时间（秒）
+        
+        Returns:
+            是否在限制内（没有沙箱的 Agent 不受限制）
+        """
+        policy = self._get_policy(agent_pid)
+        if policy is None:
+            return True
+        
+        exceeded = []
+        if policy.max_tokens is not None and tokens > policy.max_tokens:
+            exceeded.append(f"tokens {tokens} > {policy.max_tokens}")
+        if memory_mb > policy.max_memory_mb:
+            exceeded.append(f"memory {memory_mb:.0f}MB > {policy.max_memory_mb}MB")
+        if cpu_percent > policy.max_cpu_percent:
+            exceeded.append(f"cpu {cpu_percent:.0f}% > {policy.max_cpu_percent}%")
+        if execution_time > policy.max_execution_time:
+            exceeded.append(f"execution time {execution_time:.0f}s > {policy.max_execution_time}s")
+        
+        if not exceeded:
+            return True
+        
+        self.log_audit(agent_pid, SecurityViolationType.RESOURCE_LIMIT,
+                       f"Resource limit exceeded: {', '.join(exceeded)}",
+                       SecuritySeverity.HIGH,
+                       {'tokens': tokens, 'memory_mb': memory_mb, 'cpu_percent': cpu_percent,
+                        'execution_time': execution_time})
+        return False
+    
+    def get_resource_usage(self, agent_pid: str) -> Dict[str, float]:
+        """
+        读取 Agent 沙箱当前的内存与 CPU 使用
+        
+        只有 Docker 沙箱能单独度量；进程级隔离与内核共享进程，返回空字典。
+        
+        Returns:
+            包含 memory_mb、cpu_percent 的字典（无法度量时为空）
+        """
+        container = self.containers.get(agent_pid)
+        if container is None or not hasattr(container, 'stats'):
+            return {}
+        try:
+            stats = container.stats(stream=False)
+            memory_mb = stats['memory_stats'].get('usage', 0) / (1024 * 1024)
+            cpu, precpu = stats['cpu_stats'], stats['precpu_stats']
+            cpu_delta = cpu['cpu_usage']['total_usage'] - precpu['cpu_usage']['total_usage']
+            system_delta = cpu.get('system_cpu_usage', 0) - precpu.get('system_cpu_usage', 0)
+            cpu_percent = 0.0
+            if cpu_delta > 0 and system_delta > 0:
+                cpu_percent = cpu_delta / system_delta * cpu.get('online_cpus', 1) * 100
+            return {'memory_mb': memory_mb, 'cpu_percent': cpu_percent}
+        except Exception as e:
+            logger.warning(f"Failed to read sandbox stats for {agent_pid[:8]}: {e}")
+            return {}
+    
+    def get_audit_log(self, agent_pid: Optional[str] = None,
+                      violation_type: Optional[Union[SecurityViolationType, str]] = None,
+                      limit: int = 100) -> List[Dict[str, Any]]:
+        """
+        查询安全违规审计日志
+        
+        Args:
+            agent_pid: 只返回该 Agent 的违规（可选）
+            violation_type: 只返回该类型的违规（可选）
+            limit: 最大返回条数
+        
+        Returns:
+            审计日志列表（按时间顺序）；未配置存储时查询内存中的最近记录
+        """
+        if isinstance(violation_type, SecurityViolationType):
+            violation_type = violation_type.value
+        prefix = "security_violation:" + (violation_type or "")
+        if self.storage is not None:
+            return self.storage.get_audit_trail_filtered(
+                agent_pid=agent_pid, action_prefix=prefix, limit=limit)
+        if limit <= 0:
+            return []
+        logs = [entry for entry in self.audit_log
+                if entry['action'].startswith(prefix)
+                and (agent_pid is None or entry['agent_pid'] == agent_pid)]
+        return logs[-limit:]
+    
+    def get_audit_stats(self) -> Dict[str, int]:
+        """
+        内存审计日志统计
+        
+        Returns:
+            total_logged（累计记录数）、buffered（缓冲区中的记录数）、
+            capacity（缓冲区容量）、dropped（因容量限制丢弃的记录数）
+        """
+        return {
+            'total_logged': self.total_audit_entries,
+            'buffered': len(self.audit_log),
+            'capacity': self.audit_log.maxlen,
+            'dropped': self.total_audit_entries - len(self.audit_log),
+        }
+    
+    def _get_policy(self, agent_pid: str) -> Optional[SecurityPolicy]:
+        """获取 Agent 沙箱的策略（没有沙箱时返回 None）"""
+        container = self.containers.get(agent_pid)
+        if container is None:
+            return None
+        if hasattr(container, 'policy'):
+            return container.policy
+        if isinstance(container, dict):
+            return container.get('policy', SecurityPolicy())
+        return None
+    
+    @staticmethod
+    def _match_path_rules(policy: SecurityPolicy, path: str) -> Optional[bool]:
+        """
+        按策略匹配规范化后的路径
+        
+        最具体的匹配规则生效，同样具体时禁止优先。解析符号链接时，
+        普通路径规则也按其解析后的真实路径匹配。
+        
+        Returns:
+            True 允许，False 禁止，None 没有匹配的规则
+        """
+        matches = []
+        for rules, allowed in ((policy.blocked_paths, False), (policy.allowed_paths, True)):
+            for rule in rules:
+                candidates = {rule}
+                if policy.resolve_symlinks and not (_GLOB_CHARS & set(rule)):
+                    candidates.add(os.path.realpath(rule))
+                if any(path_rule_matches(candidate, path) for candidate in candidates):
+                    matches.append((path_rule_specificity(rule), not allowed))
+        
+        if not matches:
+            return None
+        _, denied = max(matches)
+        return not denied
+    
     def get_sandbox_info(self, agent_pid: str) -> Optional[Dict[str, Any]]:
         """获取沙箱信息"""
         if agent_pid not in self.containers:
@@ -439,15 +863,7 @@ class PermissionManager:
     
     def can_use_tool(self, agent_pid: str, tool_name: str) -> bool:
         """检查是否可以使用工具"""
-        policy = self.get_policy(agent_pid)
-        
-        if policy.allowed_tools is not None and tool_name not in policy.allowed_tools:
-            return False
-        
-        if tool_name in policy.blocked_tools:
-            return False
-        
-        return True
+        return self.get_policy(agent_pid).allows_tool(tool_name)
     
     def can_access_network(self, agent_pid: str, host: str) -> bool:
         """检查是否可以访问网络"""
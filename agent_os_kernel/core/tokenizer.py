@@ -0,0 +1,89 @@
+# -*- coding: utf-8 -*-
+"""
+Tokenizer - Token 计数抽象
+
+上下文管理器、内核和压缩器共用同一个 Tokenizer，保证换出、抢占和
+压缩决策所依据的 token 数一致。
+
+- HeuristicTokenizer: 启发式估计（无依赖，兼顾中英文）
+- TiktokenTokenizer: 基于 tiktoken 的精确计数（需要安装 tiktoken）
+"""
+
+import re
+import logging
+from abc import ABC, abstractmethod
+from typing import Any, Optional
+
+
+logger = logging.getLogger(__name__)
+
+# CJK 统一表意文字、日文假名、韩文音节
+_CJK_PATTERN = re.compile(r'[\u3040-\u30ff\u3400-\u4dbf\u4e00-\u9fff\uac00-\ud7af\uf900-\ufaff]')
+
+
+class Tokenizer(ABC):
+    """Token 计数器接口"""
+
+    @abstractmethod
+    def count_tokens(self, text: str) -> int:
+        """计算文本的 token 数"""
+        pass
+
+
+class HeuristicTokenizer(Tokenizer):
+    """
+    启发式 Token 计数器
+
+    非 CJK 文本按空格分词后乘以经验系数 1.3；CJK 字符每个计 1 个 token。
+    """
+
+    def __init__(self, words_factor: float = 1.3):
+        self.words_factor = words_factor
+
+    def count_tokens(self, text: str) -> int:
+        cjk_chars = len(_CJK_PATTERN.findall(text))
+        words = len(_CJK_PATTERN.sub(' ', text).split())
+        return int(words * self.words_factor) + cjk_chars
+
+
+class TiktokenTokenizer(Tokenizer):
+    """
+    基于 tiktoken 的 Token 计数器
+
+    与 OpenAI 模型的实际分词一致；编码失败时回退到启发式估计。
+    """
+
+    def __init__(self, model: str = "gpt-4",
+                 fallback: Optional[Tokenizer] = None):
+        """
+        Args:
+            model: 用于选择编码的模型名
+            fallback: 编码失败时使用的计数器（默认 HeuristicTokenizer）
+
+        Raises:
+            ImportError: 未安装 tiktoken
+        """
+        import tiktoken
+        self.model = model
+        self.fallback = fallback or HeuristicTokenizer()
+        self._encoding: Any = None
+        try:
+            self._encoding = tiktoken.encoding_for_model(model)
+        except Exception as e:
+            logger.warning(f"tiktoken encoding unavailable for {model}: {e}")
+
+    def count_tokens(self, text: str) -> int:
+        if self._encoding is not None:
+            try:
+                return len(self._encoding.encode(text))
+            except Exception:
+                pass
+        return self.fallback.count_tokens(text)
+
+
+def default_tokenizer() -> Tokenizer:
+    """获取默认 Tokenizer：已安装 tiktoken 时使用精确计数，否则使用启发式估计"""
+    try:
+        return TiktokenTokenizer()
+    except ImportError:
+        return HeuristicTokenizer()
@@ -14,6 +14,8 @@ class AgentState(Enum):
     READY = "ready"          # 就绪
     RUNNING = "running"      # 运行中
     WAITING = "waiting"      # 等待
+    SUSPENDED = "suspended"  # 挂起
+    COMPLETED = "completed"  # 已完成
     TERMINATED = "terminated" # 已终止
     ERROR = "error"          # 错误
 
@@ -37,6 +39,23 @@ class StorageBackend(Enum):
     VECTOR = "vector"
 
 
+class SecuritySeverity(Enum):
+    """安全事件严重级别（按 LOW < MEDIUM < HIGH < CRITICAL 排序）"""
+    LOW = "low"
+    MEDIUM = "medium"
+    HIGH = "high"
+    CRITICAL = "critical"
+    
+    @property
+    def rank(self) -> int:
+        """严重程度序号（越大越严重）"""
+        return list(SecuritySeverity).index(self)
+    
+    def at_least(self) -> List['SecuritySeverity']:
+        """不低于当前级别的所有级别"""
+        return [s for s in SecuritySeverity if s.rank >= self.rank]
+
+
 class ToolCategory(Enum):
     """工具类别"""
     CALCULATOR = "calculator"
@@ -138,6 +157,7 @@ class AuditLog:
     result: str = ""
     duration_ms: float = 0.0
     ip_address: Optional[str] = None
+    severity: Optional[str] = None
     
     def to_dict(self) -> Dict[str, Any]:
         return {
@@ -149,7 +169,8 @@ class AuditLog:
             "details": self.details,
             "result": self.result,
             "duration_ms": self.duration_ms,
-            "ip_address": self.ip_address
+            "ip_address": self.ip_address,
+            "severity": self.severity
         }
 
 
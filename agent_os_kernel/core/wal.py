@@ -0,0 +1,241 @@
+"""
+预写日志（WAL）- 上下文操作的追加式持久化
+
+ContextManager 在配置了 WAL 时，把每次页面分配、重要性调整、换出等
+操作作为一条记录追加到日志中。启动时先导入最近的上下文快照，再重放
+快照之后的记录，即可恢复到崩溃前的状态，而不必每次操作都做完整检查点。
+
+记录按 seq 单调递增；快照保存导出时的 seq（ContextSnapshot.wal_seq），
+快照持久化之后可以用 truncate() 丢弃已被快照覆盖的记录。
+"""
+
+import json
+import logging
+import os
+import threading
+import time
+from abc import ABC, abstractmethod
+from dataclasses import dataclass, field
+from typing import Any, Dict, Iterator, List
+
+logger = logging.getLogger(__name__)
+
+
+@dataclass
+class WALRecord:
+    """
+    一条 WAL 记录
+
+    Attributes:
+        seq: 序号（单调递增，从 1 开始）
+        op: 操作类型（allocate / update_importance / evict 等）
+        data: 操作参数（可 JSON 序列化）
+        timestamp: 写入时间
+    """
+    seq: int
+    op: str
+    data: Dict[str, Any] = field(default_factory=dict)
+    timestamp: float = field(default_factory=time.time)
+
+    def to_dict(self) -> Dict[str, Any]:
+        """序列化为字典"""
+        return {'seq': self.seq, 'op': self.op, 'data': self.data, 'timestamp': self.timestamp}
+
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> 'WALRecord':
+        """从字典反序列化"""
+        return cls(
+            seq=data['seq'],
+            op=data['op'],
+            data=data.get('data', {}),
+            timestamp=data.get('timestamp', 0.0),
+        )
+
+
+class WriteAheadLog(ABC):
+    """追加式日志接口"""
+
+    @property
+    @abstractmethod
+    def last_seq(self) -> int:
+        """最后一条记录的序号（空日志为 0）"""
+        pass
+
+    @abstractmethod
+    def append(self, op: str, data: Dict[str, Any]) -> WALRecord:
+        """
+        追加一条记录
+
+        Args:
+            op: 操作类型
+            data: 操作参数（可 JSON 序列化）
+
+        Returns:
+            写入的记录
+        """
+        pass
+
+    @abstractmethod
+    def records(self, after_seq: int = 0) -> Iterator[WALRecord]:
+        """按序号顺序遍历 seq > after_seq 的记录"""
+        pass
+
+    @abstractmethod
+    def truncate(self, up_to_seq: int) -> int:
+        """
+        丢弃 seq <= up_to_seq 的记录（已被快照覆盖）
+
+        Returns:
+            丢弃的记录数
+        """
+        pass
+
+    @abstractmethod
+    def advance_to(self, seq: int):
+        """
+        保证之后追加的记录序号大于 seq
+
+        日志被快照截断为空后重新打开时序号会从 0 开始，从快照恢复后需要
+        推进到快照的序号，否则新记录会被当作已包含在快照中而跳过。
+        """
+        pass
+
+    def close(self):
+        """关闭日志"""
+        pass
+
+
+class InMemoryWriteAheadLog(WriteAheadLog):
+    """内存 WAL（用于测试，不提供持久性）"""
+
+    def __init__(self):
+        self._records: List[WALRecord] = []
+        self._seq = 0
+        self._lock = threading.Lock()
+
+    @property
+    def last_seq(self) -> int:
+        return self._seq
+
+    def append(self, op: str, data: Dict[str, Any]) -> WALRecord:
+        with self._lock:
+            self._seq += 1
+            record = WALRecord(seq=self._seq, op=op, data=json.loads(json.dumps(data)))
+            self._records.append(record)
+            return record
+
+    def records(self, after_seq: int = 0) -> Iterator[WALRecord]:
+        with self._lock:
+            records = [r for r in self._records if r.seq > after_seq]
+        return iter(records)
+
+    def advance_to(self, seq: int):
+        with self._lock:
+            self._seq = max(self._seq, seq)
+
+    def truncate(self, up_to_seq: int) -> int:
+        with self._lock:
+            before = len(self._records)
+            self._records = [r for r in self._records if r.seq > up_to_seq]
+            return before - len(self._records)
+
+
+class FileWriteAheadLog(WriteAheadLog):
+    """
+    文件 WAL（每行一条 JSON 记录）
+
+    每次追加后 flush，进程崩溃不会丢失已返回的记录；fsync=True 时
+    额外调用 os.fsync，断电也不会丢失，但每次写入的开销显著增加。
+    崩溃时写了一半的最后一行会在打开时被截掉。
+    """
+
+    def __init__(self, path: str, fsync: bool = False):
+        """
+        打开（或创建）日志文件
+
+        Args:
+            path: 日志文件路径
+            fsync: 每次追加后是否 fsync
+        """
+        self.path = path
+        self.fsync = fsync
+        self._lock = threading.Lock()
+        self._discard_torn_tail()
+        self._seq = 0
+        for record in self._read():
+            self._seq = record.seq
+        self._file = open(path, 'a', encoding='utf-8')
+
+    @property
+    def last_seq(self) -> int:
+        return self._seq
+
+    def append(self, op: str, data: Dict[str, Any]) -> WALRecord:
+        with self._lock:
+            record = WALRecord(seq=self._seq + 1, op=op, data=data)
+            self._file.write(json.dumps(record.to_dict(), ensure_ascii=False,
+                                        separators=(',', ':')) + '\n')
+            self._file.flush()
+            if self.fsync:
+                os.fsync(self._file.fileno())
+            self._seq = record.seq
+            return record
+
+    def records(self, after_seq: int = 0) -> Iterator[WALRecord]:
+        with self._lock:
+            self._file.flush()
+            records = [r for r in self._read() if r.seq > after_seq]
+        return iter(records)
+
+    def advance_to(self, seq: int):
+        with self._lock:
+            self._seq = max(self._seq, seq)
+
+    def truncate(self, up_to_seq: int) -> int:
+        with self._lock:
+            records = list(self._read())
+            kept = [r for r in records if r.seq > up_to_seq]
+            tmp_path = f"{self.path}.tmp"
+            with open(tmp_path, 'w', encoding='utf-8') as f:
+                for record in kept:
+                    f.write(json.dumps(record.to_dict(), ensure_ascii=False,
+                                       separators=(',', ':')) + '\n')
+                f.flush()
+                os.fsync(f.fileno())
+            self._file.close()
+            os.replace(tmp_path, self.path)
+            self._file = open(self.path, 'a', encoding='utf-8')
+            return len(records) - len(kept)
+
+    def close(self):
+        with self._lock:
+            if not self._file.closed:
+                self._file.close()
+
+    def _read(self) -> Iterator[WALRecord]:
+        """读取文件中的全部记录（遇到损坏的行时停止）"""
+        if not os.path.exists(self.path):
+            return
+        with open(self.path, 'r', encoding='utf-8') as f:
+            for line_no, line in enumerate(f, 1):
+                if not line.strip():
+                    continue
+                try:
+                    yield WALRecord.from_dict(json.loads(line))
+                except (ValueError, KeyError) as e:
+                    logger.error(f"Corrupt WAL record at {self.path}:{line_no} ({e}), "
+                                 "ignoring it and all later records")
+                    return
+
+    def _discard_torn_tail(self):
+        """截掉崩溃时未写完的最后一行（没有换行符结尾）"""
+        if not os.path.exists(self.path):
+            return
+        with open(self.path, 'rb+') as f:
+            data = f.read()
+            if not data or data.endswith(b'\n'):
+                return
+            keep = data.rfind(b'\n') + 1
+            f.truncate(keep)
+        logger.warning(f"Discarded torn trailing WAL record in {self.path} "
+                       f"({len(data) - keep} bytes)")
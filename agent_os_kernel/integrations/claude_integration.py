@@ -13,6 +13,7 @@ import logging
 from typing import Dict, Any, Optional, List
 
 from ..kernel import AgentOSKernel
+from ..core.exceptions import QuotaExceededError
 from ..core.types import AgentProcess, LLMResponse
 from ..tools.base import Tool
 
@@ -138,9 +139,13 @@ If the task is complete, set "done" to true and omit the "action" field.
         
         # 4. 请求资源配额
         tokens_needed = len(response_text.split()) + len(context.split())
-        if not self.scheduler.request_resources(process.pid, tokens_needed):
-            logger.warning(f"[Agent {process.name}] Quota exceeded, waiting...")
-            return {"done": False, "waiting": True}
+        try:
+            if not self.scheduler.request_resources(process.pid, tokens_needed):
+                logger.warning(f"[Agent {process.name}] Quota exceeded, waiting...")
+                return {"done": False, "waiting": True}
+        except QuotaExceededError as e:
+            logger.error(f"[Agent {process.name}] {e}")
+            return {"done": False, "error": str(e)}
         
         # 5. 执行工具调用
         result = None
@@ -154,12 +159,9 @@ If the task is complete, set "done" to true and omit the "action" field.
                     "error": f"Tool '{tool_name}' is not allowed for this agent"
                 }
             else:
-                tool = self.tool_registry.get(tool_name)
-                if tool:
-                    logger.info(f"[Agent {process.name}] Calling tool: {tool_name}")
-                    result = tool.execute(**action.get('parameters', {}))
-                else:
-                    result = {"success": False, "error": f"Tool '{tool_name}' not found"}
+                logger.info(f"[Agent {process.name}] Calling tool: {tool_name}")
+                result = self.tool_registry.execute_as(process.pid, tool_name,
+                                                       **action.get('parameters', {}))
         
         # 6. 记录审计日志
         duration_ms = (time.time() - start_time) * 1000
@@ -173,13 +175,7 @@ If the task is complete, set "done" to true and omit the "action" field.
         
         # 7. 更新上下文
         if result:
-            result_text = f"Tool: {action['tool']}\nResult: {json.dumps(result['data']) if result.get('success') else result.get('error')}"
-            self.context_manager.allocate_page(
-                agent_pid=process.pid,
-                content=result_text,
-                importance=0.7,
-                page_type="tool_result"
-            )
+            self.record_observation(process.pid, action['tool'], result)
             
             # 更新对话缓存
             self._update_conversation_cache(process.pid, context, response_text)
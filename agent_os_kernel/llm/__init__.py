@@ -1,12 +1,22 @@
 # LLM Provider Module - Multi-Model LLM Support
 
-from .provider import LLMProvider, LLMConfig, ProviderType
+from .provider import (
+    LLMProvider, LLMConfig, LLMResponse, ModelPrice, ProviderType, CompletionOptions,
+    LLMError, RateLimitedError, ServerError, BadRequestError, CircuitOpenError,
+)
 from .factory import LLMProviderFactory
+from .delegating import DelegatingProvider
+from .fallback import FallbackProvider
+from .circuit_breaker import CircuitBreakerProvider
+from .logging_provider import LoggingProvider, redact_pii
+from .single_flight import SingleFlightProvider
+from .rate_limited import RateLimitedProvider, rate_limit_kwargs
 
 # Mock Provider (always available)
 from .mock_provider import (
     MockProvider,
     MockErrorProvider,
+    MockResponse,
     create_mock_provider,
     create_error_mock_provider
 )
@@ -99,10 +109,26 @@ __all__ = [
     'ProviderType',
     'LLMProviderFactory',
     'LLMResponse',
+    'ModelPrice',
+    'CompletionOptions',
+    'LLMError',
+    'RateLimitedError',
+    'ServerError',
+    'BadRequestError',
+    'CircuitOpenError',
+    'DelegatingProvider',
+    'FallbackProvider',
+    'CircuitBreakerProvider',
+    'LoggingProvider',
+    'redact_pii',
+    'SingleFlightProvider',
+    'RateLimitedProvider',
+    'rate_limit_kwargs',
     
     # Mock (always available)
     'MockProvider',
     'MockErrorProvider',
+    'MockResponse',
     'create_mock_provider',
     'create_error_mock_provider',
     
@@ -9,7 +9,9 @@ import json
 import logging
 from typing import List, Dict, Optional, AsyncIterator
 import httpx
-from .provider import LLMProvider, LLMConfig, LLMResponse, Message, ProviderType
+from .provider import (
+    LLMProvider, LLMConfig, LLMResponse, Message, ProviderType, ModelPrice, CompletionOptions,
+)
 
 logger = logging.getLogger(__name__)
 
@@ -17,6 +19,12 @@ logger = logging.getLogger(__name__)
 class AnthropicProvider(LLMProvider):
     """Anthropic Provider"""
     
+    PRICING = {
+        "claude-sonnet-4": ModelPrice(0.003, 0.015),
+        "claude-opus-4": ModelPrice(0.015, 0.075),
+        "claude-haiku": ModelPrice(0.00025, 0.00125),
+    }
+    
     ANTHROPIC_VERSION = "2023-06-01"
     
     def __init__(self, config: LLMConfig):
@@ -54,9 +62,10 @@ class AnthropicProvider(LLMProvider):
         self,
         messages: List[Message],
         tools: List[Dict] = None,
-        stream: bool = False
+        stream: bool = False,
+        options: Optional[CompletionOptions] = None
     ) -> LLMResponse:
-        """发送完成请求"""
+        """发送完成请求（options 控制温度、最大 token 数、停止序列等）"""
         if not self._client:
             raise RuntimeError("Anthropic provider not initialized")
         
@@ -78,14 +87,10 @@ class AnthropicProvider(LLMProvider):
         payload = {
             "model": self.config.model,
             "messages": formatted_messages,
-            "max_tokens": self.config.max_tokens,
-            "temperature": self.config.temperature,
-            "stream": stream
+            "stream": stream,
+            **self._completion_fields(options, tools)
         }
         
-        if tools:
-            payload["tools"] = tools
-        
         # Anthropic 使用不同的 endpoint
         endpoint = f"{self.base_url}/v1/messages"
         
@@ -101,7 +106,8 @@ class AnthropicProvider(LLMProvider):
     async def stream_complete(
         self,
         messages: List[Message],
-        tools: List[Dict] = None
+        tools: List[Dict] = None,
+        options: Optional[CompletionOptions] = None
     ) -> AsyncIterator[str]:
         """流式完成"""
         if not self._client:
@@ -123,14 +129,10 @@ class AnthropicProvider(LLMProvider):
         payload = {
             "model": self.config.model,
             "messages": formatted_messages,
-            "max_tokens": self.config.max_tokens,
-            "temperature": self.config.temperature,
-            "stream": True
+            "stream": True,
+            **self._completion_fields(options, tools)
         }
         
-        if tools:
-            payload["tools"] = tools
-        
         endpoint = f"{self.base_url}/v1/messages"
         
         async with self._client.stream("POST", endpoint, json=payload) as response:
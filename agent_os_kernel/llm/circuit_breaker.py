@@ -0,0 +1,194 @@
+# -*- coding: utf-8 -*-
+"""Circuit Breaker Provider - Provider 熔断
+
+Provider 连续失败达到阈值后打开熔断器，冷却期内直接抛出 CircuitOpenError
+而不再请求；冷却结束后放行一个试探请求（半开），成功则恢复，失败则重新打开。
+CircuitOpenError 可重试，放在 FallbackProvider 中时会直接跳到下一个 Provider。
+"""
+
+import logging
+from typing import Any, Callable, Dict, Optional
+
+from ..core.circuit_breaker import CircuitState
+from ..core.clock import Clock, SYSTEM_CLOCK
+from .delegating import DelegatingProvider
+from .provider import LLMProvider, CircuitOpenError, classify_llm_error
+
+logger = logging.getLogger(__name__)
+
+
+class CircuitBreakerProvider(DelegatingProvider):
+    """
+    熔断 Provider（装饰器）
+    
+    Example:
+        provider = FallbackProvider([
+            CircuitBreakerProvider(primary, failure_threshold=3, cooldown=60.0),
+            secondary,
+        ])
+    """
+    
+    def __init__(self, inner: LLMProvider,
+                 failure_threshold: int = 5,
+                 cooldown: float = 30.0,
+                 clock: Optional[Clock] = None,
+                 on_state_change: Optional[Callable[[CircuitState, CircuitState], None]] = None):
+        """
+        Args:
+            inner: 被包装的 Provider
+            failure_threshold: 打开熔断器所需的连续失败次数
+            cooldown: 打开后拒绝请求的时长（秒）
+            clock: 时间源（默认系统时钟）
+            on_state_change: 状态变化回调 (old_state, new_state)，用于上报指标
+        
+        Raises:
+            ValueError: failure_threshold < 1 或 cooldown < 0
+        """
+        if failure_threshold < 1:
+            raise ValueError(f"failure_threshold must be at least 1, got {failure_threshold}")
+        if cooldown < 0:
+            raise ValueError(f"cooldown must not be negative, got {cooldown}")
+        super().__init__(inner)
+        self.failure_threshold = failure_threshold
+        self.cooldown = cooldown
+        self.clock = clock or SYSTEM_CLOCK
+        self.on_state_change = on_state_change
+        
+        self._state = CircuitState.CLOSED
+        self._opened_at: Optional[float] = None
+        self._trial_in_flight = False
+        self.consecutive_failures = 0
+        self.rejected_requests = 0
+        self.times_opened = 0
+    
+    @property
+    def state(self) -> CircuitState:
+        """当前状态（冷却期结束的 OPEN 视为 HALF_OPEN）"""
+        if self._state == CircuitState.OPEN and self._cooldown_elapsed():
+            self._transition(CircuitState.HALF_OPEN)
+        return self._state
+    
+    async def complete(self, *args, **kwargs) -> Any:
+        """发送完成请求（熔断保护）"""
+        return await self._guarded('complete', *args, **kwargs)
+    
+    async def chat(self, *args, **kwargs) -> Any:
+        """发送聊天请求（熔断保护）"""
+        return await self._guarded('chat', *args, **kwargs)
+    
+    async def stream_complete(self, *args, **kwargs):
+        """
+        流式完成（流建立或中途失败都计为一次失败）
+        
+        调用方提前关闭流或任务被取消既不算成功也不算失败，只释放试探名额。
+        """
+        self._before_call()
+        try:
+            async for chunk in self.inner.stream_complete(*args, **kwargs):
+                yield chunk
+            self._record_success()
+        except Exception as e:
+            self._record_failure(e)
+            raise
+        finally:
+            self._trial_in_flight = False
+    
+    def reset(self):
+        """手动关闭熔断器并清零失败计数"""
+        self.consecutive_failures = 0
+        self._opened_at = None
+        self._trial_in_flight = False
+        self._transition(CircuitState.CLOSED)
+    
+    def get_stats(self) -> Dict[str, Any]:
+        """熔断器状态与计数"""
+        return {
+            'provider': self.provider_name,
+            'state': self.state.value,
+            'consecutive_failures': self.consecutive_failures,
+            'rejected_requests': self.rejected_requests,
+            'times_opened': self.times_opened,
+        }
+    
+    async def _guarded(self, method: str, *args, **kwargs) -> Any:
+        self._before_call()
+        try:
+            result = await getattr(self.inner, method)(*args, **kwargs)
+            self._record_success()
+            return result
+        except Exception as e:
+            self._record_failure(e)
+            raise
+        finally:
+            # 取消（CancelledError）不计入成功或失败，但必须释放半开试探名额
+            self._trial_in_flight = False
+    
+    def _before_call(self):
+        """
+        检查是否放行请求
+        
+        Raises:
+            CircuitOpenError: 熔断器打开，或半开状态下已有试探请求在进行
+        """
+        state = self.state
+        if state == CircuitState.CLOSED:
+            return
+        if state == CircuitState.HALF_OPEN and not self._trial_in_flight:
+            self._trial_in_flight = True
+            return
+        
+        self.rejected_requests += 1
+        self._metrics["failed_requests"] += 1
+        remaining = max(0.0, self._opened_at + self.cooldown - self.clock.now())
+        raise CircuitOpenError(
+            f"Circuit open for provider {self.provider_name} "
+            f"(retry in {remaining:.1f}s)"
+        )
+    
+    def _record_success(self):
+        self._metrics["total_requests"] += 1
+        self.consecutive_failures = 0
+        self._trial_in_flight = False
+        if self._state != CircuitState.CLOSED:
+            logger.info(f"Circuit closed for provider {self.provider_name}")
+            self._transition(CircuitState.CLOSED)
+    
+    def _record_failure(self, error: Exception):
+        """
+        记录失败
+        
+        请求本身有误（不可重试的 LLMError）不说明 Provider 故障，不计入；
+        半开状态下这样的试探不改变状态，下一个请求继续试探。
+        """
+        self._metrics["failed_requests"] += 1
+        self._trial_in_flight = False
+        classified = classify_llm_error(error)
+        if classified is not None and not classified.retryable:
+            return
+        
+        self.consecutive_failures += 1
+        if self._state == CircuitState.HALF_OPEN or \
+                self.consecutive_failures >= self.failure_threshold:
+            self._open()
+    
+    def _open(self):
+        self._opened_at = self.clock.now()
+        self.times_opened += 1
+        logger.warning(f"Circuit opened for provider {self.provider_name} after "
+                       f"{self.consecutive_failures} consecutive failures")
+        self._transition(CircuitState.OPEN)
+    
+    def _cooldown_elapsed(self) -> bool:
+        return self._opened_at is not None and \
+            self.clock.now() - self._opened_at >= self.cooldown
+    
+    def _transition(self, new_state: CircuitState):
+        old_state = self._state
+        if old_state == new_state:
+            return
+        self._state = new_state
+        if self.on_state_change:
+            try:
+                self.on_state_change(old_state, new_state)
+            except Exception as e:
+                logger.error(f"Error in circuit state callback: {e}")
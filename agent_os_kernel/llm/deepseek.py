@@ -9,7 +9,7 @@ import json
 import logging
 from typing import List, Dict, Optional
 import httpx
-from .provider import LLMProvider, LLMConfig, LLMResponse, Message, ProviderType
+from .provider import LLMProvider, LLMConfig, LLMResponse, Message, ProviderType, ModelPrice
 
 logger = logging.getLogger(__name__)
 
@@ -17,6 +17,11 @@ logger = logging.getLogger(__name__)
 class DeepSeekProvider(LLMProvider):
     """DeepSeek Provider"""
     
+    PRICING = {
+        "deepseek-chat": ModelPrice(0.00014, 0.00028),
+        "deepseek-reasoner": ModelPrice(0.00055, 0.00219),
+    }
+    
     def __init__(self, config: LLMConfig):
         super().__init__(config)
         self._client: Optional[httpx.AsyncClient] = None
@@ -0,0 +1,77 @@
+# -*- coding: utf-8 -*-
+"""Delegating Provider - Provider 装饰器基类
+
+LoggingProvider、CircuitBreakerProvider、SingleFlightProvider、RateLimitedProvider
+等装饰器都包装另一个 Provider：名称、模型列表、配置、定价和生命周期都转发给
+被包装的 Provider，子类只覆盖需要增强的请求方法。
+"""
+
+from typing import Any, List, Optional
+
+from .provider import LLMProvider, LLMConfig, LLMResponse, ModelPrice
+
+
+class DelegatingProvider(LLMProvider):
+    """
+    转发给被包装 Provider 的装饰器基类
+
+    未覆盖的属性（如 FallbackProvider.last_served_by）透传给被包装的 Provider。
+    """
+
+    def __init__(self, inner: LLMProvider):
+        """
+        Args:
+            inner: 被包装的 Provider
+        """
+        self.inner = inner
+        self.config = inner.config
+        self._initialized = False
+        self._metrics = {
+            "total_requests": 0,
+            "total_tokens": 0,
+            "failed_requests": 0
+        }
+
+    @property
+    def provider_name(self) -> str:
+        return self.inner.provider_name
+
+    @property
+    def supported_models(self) -> List[str]:
+        return self.inner.supported_models
+
+    def get_config(self) -> LLMConfig:
+        return self.inner.get_config()
+
+    def price_for(self, model: Optional[str] = None) -> Optional[ModelPrice]:
+        """使用被包装 Provider 的定价（包括 FallbackProvider 按成员查找的定价）"""
+        return self.inner.price_for(model)
+
+    def estimate_cost(self, response: LLMResponse) -> float:
+        return self.inner.estimate_cost(response)
+
+    async def initialize(self):
+        await self.inner.initialize()
+        self._initialized = True
+
+    async def shutdown(self):
+        await self.inner.shutdown()
+        self._initialized = False
+
+    def __getattr__(self, name: str) -> Any:
+        if name == 'inner':
+            raise AttributeError(name)
+        return getattr(self.inner, name)
+
+    async def complete(self, *args, **kwargs) -> Any:
+        """发送完成请求"""
+        return await self.inner.complete(*args, **kwargs)
+
+    async def chat(self, *args, **kwargs) -> Any:
+        """发送聊天请求"""
+        return await self.inner.chat(*args, **kwargs)
+
+    async def stream_complete(self, *args, **kwargs):
+        """流式完成"""
+        async for chunk in self.inner.stream_complete(*args, **kwargs):
+            yield chunk
@@ -0,0 +1,140 @@
+# -*- coding: utf-8 -*-
+"""Fallback Provider - Provider 故障转移链
+
+按顺序尝试多个 Provider：仅在限流或服务端错误时切换到下一个，
+请求本身有误时直接抛出。
+"""
+
+import logging
+from collections import defaultdict
+from typing import Any, Dict, List, Optional
+
+from .provider import LLMProvider, LLMConfig, ModelPrice, classify_llm_error
+
+logger = logging.getLogger(__name__)
+
+
+class FallbackProvider(LLMProvider):
+    """
+    故障转移 Provider
+    
+    Example:
+        provider = FallbackProvider([primary, secondary])
+        response = await provider.complete(messages)
+        provider.last_served_by  # "secondary" if primary was rate limited
+    """
+    
+    PROVIDER_NAME = "fallback"
+    
+    def __init__(self, providers: List[LLMProvider]):
+        """
+        Args:
+            providers: 按优先级排列的 Provider 列表
+        
+        Raises:
+            ValueError: providers 为空
+        """
+        if not providers:
+            raise ValueError("FallbackProvider requires at least one provider")
+        self.providers = providers
+        self.config = providers[0].config
+        self._initialized = False
+        self._metrics = {
+            "total_requests": 0,
+            "total_tokens": 0,
+            "failed_requests": 0
+        }
+        
+        # 可观测性：每个请求由哪个 Provider 完成
+        self.last_served_by: Optional[str] = None
+        self.served_counts: Dict[str, int] = defaultdict(int)
+        self.failover_count = 0
+    
+    @property
+    def provider_name(self) -> str:
+        return self.PROVIDER_NAME
+    
+    @property
+    def supported_models(self) -> List[str]:
+        models: List[str] = []
+        for provider in self.providers:
+            for model in getattr(provider, 'supported_models', None) or []:
+                if model not in models:
+                    models.append(model)
+        return models
+    
+    def get_config(self) -> LLMConfig:
+        return self.config
+    
+    def price_for(self, model: Optional[str] = None) -> Optional[ModelPrice]:
+        """按顺序查找第一个认识该模型的 Provider 的定价"""
+        for provider in self.providers:
+            price = provider.price_for(model)
+            if price is not None:
+                return price
+        return None
+    
+    async def initialize(self):
+        """初始化所有 Provider"""
+        for provider in self.providers:
+            await provider.initialize()
+        self._initialized = True
+    
+    async def shutdown(self):
+        """关闭所有 Provider"""
+        for provider in self.providers:
+            await provider.shutdown()
+        self._initialized = False
+    
+    async def complete(self, *args, **kwargs) -> Any:
+        """发送完成请求（按顺序故障转移）"""
+        return await self._call_with_fallback('complete', *args, **kwargs)
+    
+    async def chat(self, *args, **kwargs) -> Any:
+        """发送聊天请求（按顺序故障转移）"""
+        return await self._call_with_fallback('chat', *args, **kwargs)
+    
+    async def _call_with_fallback(self, method: str, *args, **kwargs) -> Any:
+        """
+        依次调用各 Provider 的同名方法
+        
+        Raises:
+            BadRequestError 等不可重试的错误：立即抛出
+            最后一个 Provider 的错误：所有 Provider 都失败
+        """
+        last_error: Optional[Exception] = None
+        candidates = [p for p in self.providers if hasattr(p, method)]
+        if not candidates:
+            raise NotImplementedError(f"No provider implements {method}")
+        
+        for index, provider in enumerate(candidates):
+            name = self._name_of(provider)
+            try:
+                result = await getattr(provider, method)(*args, **kwargs)
+            except Exception as e:
+                classified = classify_llm_error(e)
+                if classified is None or not classified.retryable:
+                    self._metrics["failed_requests"] += 1
+                    raise
+                last_error = e
+                if index + 1 < len(candidates):
+                    self.failover_count += 1
+                    logger.warning(f"Provider {name} failed ({type(classified).__name__}), "
+                                   f"falling back to {self._name_of(candidates[index + 1])}")
+                continue
+            
+            self.last_served_by = name
+            self.served_counts[name] += 1
+            self._metrics["total_requests"] += 1
+            logger.debug(f"Request served by {name}")
+            return result
+        
+        self._metrics["failed_requests"] += 1
+        raise last_error
+    
+    @staticmethod
+    def _name_of(provider: LLMProvider) -> str:
+        try:
+            return provider.provider_name
+        except (NotImplementedError, AttributeError):
+            return type(provider).__name__
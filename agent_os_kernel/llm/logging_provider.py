@@ -0,0 +1,165 @@
+# -*- coding: utf-8 -*-
+"""Logging Provider - LLM 请求/响应日志中间件
+
+包装任意 Provider（包括 FallbackProvider），在 debug 级别记录发送给模型的
+消息与模型的响应，附带 token 数和延迟；可选追加写入 JSON Lines 文件。
+日志中的 API key 总是被替换，可另外提供敏感内容的脱敏函数。
+"""
+
+import re
+import json
+import time
+import logging
+import threading
+from typing import Any, Callable, Dict, List, Optional
+
+from .delegating import DelegatingProvider
+from .provider import LLMProvider, LLMResponse, usage_tokens
+
+logger = logging.getLogger(__name__)
+
+REDACTED = "[REDACTED]"
+
+# 常见 PII：邮箱、手机号/电话、银行卡号
+_PII_PATTERNS = [
+    re.compile(r'[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}'),
+    re.compile(r'\b(?:\d[ -]?){13,19}\b'),
+    re.compile(r'(?<!\d)(?:\+?\d{1,3}[ -]?)?1[3-9]\d{9}(?!\d)'),
+    re.compile(r'\b\d{3}[ -.]\d{3}[ -.]\d{4}\b'),
+]
+
+
+def redact_pii(text: str) -> str:
+    """将邮箱、电话号码和银行卡号替换为 [REDACTED]"""
+    for pattern in _PII_PATTERNS:
+        text = pattern.sub(REDACTED, text)
+    return text
+
+
+class LoggingProvider(DelegatingProvider):
+    """
+    日志 Provider（装饰器）
+    
+    Example:
+        provider = LoggingProvider(FallbackProvider([primary, secondary]),
+                                   log_file="llm.jsonl", redactor=redact_pii)
+    """
+    
+    def __init__(self, inner: LLMProvider,
+                 log_file: Optional[str] = None,
+                 redactor: Optional[Callable[[str], str]] = None):
+        """
+        Args:
+            inner: 被包装的 Provider
+            log_file: 追加写入的 JSON Lines 日志文件（可选）
+            redactor: 内容脱敏函数（如 redact_pii），在写日志前应用
+        """
+        super().__init__(inner)
+        self.log_file = log_file
+        self.redactor = redactor
+        self._file_lock = threading.Lock()
+    
+    async def complete(self, messages: List[Any], *args, **kwargs) -> Any:
+        """发送完成请求并记录日志"""
+        return await self._logged('complete', messages, *args, **kwargs)
+    
+    async def chat(self, messages: List[Any], *args, **kwargs) -> Any:
+        """发送聊天请求并记录日志"""
+        return await self._logged('chat', messages, *args, **kwargs)
+    
+    async def stream_complete(self, messages: List[Any], *args, **kwargs):
+        """流式完成，流结束后记录完整响应"""
+        start = time.time()
+        chunks: List[str] = []
+        async for chunk in self.inner.stream_complete(messages, *args, **kwargs):
+            chunks.append(str(chunk))
+            yield chunk
+        self._metrics["total_requests"] += 1
+        self._write({
+            'method': 'stream_complete',
+            'provider': self.provider_name,
+            'request': self._serialize_messages(messages),
+            'response': self._clean("".join(chunks)),
+            'latency_ms': round((time.time() - start) * 1000, 2),
+        })
+    
+    async def _logged(self, method: str, messages: List[Any], *args, **kwargs) -> Any:
+        start = time.time()
+        try:
+            result = await getattr(self.inner, method)(messages, *args, **kwargs)
+        except Exception as e:
+            self._metrics["failed_requests"] += 1
+            self._write({
+                'method': method,
+                'provider': self.provider_name,
+                'request': self._serialize_messages(messages),
+                'error': self._clean(f"{type(e).__name__}: {e}"),
+                'latency_ms': round((time.time() - start) * 1000, 2),
+            })
+            raise
+        
+        content, usage = self._response_fields(result)
+        input_tokens, output_tokens = usage_tokens(usage)
+        self._metrics["total_requests"] += 1
+        self._metrics["total_tokens"] += input_tokens + output_tokens
+        self._write({
+            'method': method,
+            'provider': self.provider_name,
+            'request': self._serialize_messages(messages),
+            'response': self._clean(content),
+            'input_tokens': input_tokens,
+            'output_tokens': output_tokens,
+            'latency_ms': round((time.time() - start) * 1000, 2),
+        })
+        return result
+    
+    @staticmethod
+    def _response_fields(result: Any) -> tuple:
+        """取出响应内容和 usage（兼容 LLMResponse 和 dict）"""
+        if isinstance(result, LLMResponse):
+            return result.content or "", result.usage or {}
+        if isinstance(result, dict):
+            return result.get('content') or "", result.get('usage') or {}
+        return str(result), {}
+    
+    def _serialize_messages(self, messages: List[Any]) -> List[Dict[str, Any]]:
+        serialized = []
+        for message in messages:
+            if isinstance(message, dict):
+                role, content = message.get('role'), message.get('content')
+            else:
+                role, content = getattr(message, 'role', None), getattr(message, 'content', None)
+            serialized.append({'role': role, 'content': self._clean(str(content))})
+        return serialized
+    
+    def _api_keys(self) -> List[str]:
+        """被包装的 Provider 链（含 FallbackProvider 的所有成员）使用的 API key"""
+        keys: List[str] = []
+        pending: List[Any] = [self.inner]
+        while pending:
+            provider = pending.pop()
+            api_key = getattr(getattr(provider, 'config', None), 'api_key', None)
+            if api_key and api_key not in keys:
+                keys.append(api_key)
+            if isinstance(provider, DelegatingProvider):
+                pending.append(provider.inner)
+            else:
+                pending.extend(getattr(provider, 'providers', None) or [])
+        return keys
+    
+    def _clean(self, text: str) -> str:
+        """去除 API key 并应用脱敏函数"""
+        for api_key in self._api_keys():
+            text = text.replace(api_key, REDACTED)
+        if self.redactor:
+            text = self.redactor(text)
+        return text
+    
+    def _write(self, record: Dict[str, Any]):
+        logger.debug("LLM %s", json.dumps(record, ensure_ascii=False))
+        if not self.log_file:
+            return
+        record = {'timestamp': time.time(), **record}
+        with self._file_lock:
+            with open(self.log_file, 'a', encoding='utf-8') as f:
+                f.write(json.dumps(record, ensure_ascii=False) + "\n")
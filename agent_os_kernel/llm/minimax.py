@@ -9,7 +9,7 @@ import json
 import logging
 from typing import List, Dict, Optional
 import httpx
-from .provider import LLMProvider, LLMConfig, LLMResponse, Message, ProviderType
+from .provider import LLMProvider, LLMConfig, LLMResponse, Message, ProviderType, CompletionOptions
 
 logger = logging.getLogger(__name__)
 
@@ -51,24 +51,20 @@ class MiniMaxProvider(LLMProvider):
         self,
         messages: List[Message],
         tools: List[Dict] = None,
-        stream: bool = False
+        stream: bool = False,
+        options: Optional[CompletionOptions] = None
     ) -> LLMResponse:
-        """发送完成请求"""
+        """发送完成请求（options 控制温度、最大 token 数、停止序列等）"""
         if not self._client:
             raise RuntimeError("MiniMax provider not initialized")
         
         payload = {
             "model": self.config.model,
             "messages": self._format_messages(messages),
-            "max_tokens": self.config.max_tokens,
-            "temperature": self.config.temperature,
-            "top_p": self.config.top_p,
-            "stream": stream
+            "stream": stream,
+            **self._completion_fields(options, tools)
         }
         
-        if tools:
-            payload["tools"] = tools
-        
         endpoint = f"{self.base_url}/chat/completions"
         
         async def make_request():
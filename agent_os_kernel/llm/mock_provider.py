@@ -6,21 +6,36 @@ Mock Provider - 用于测试的模拟 Provider
 """
 
 import asyncio
+import hashlib
 import logging
-from typing import Dict, Any, List, Optional, AsyncIterator
+import random
+from collections import deque
+from typing import Dict, Any, List, Optional, AsyncIterator, Union
 from dataclasses import dataclass
 
 from .provider import (
     LLMProvider,
     LLMConfig,
+    LLMResponse,
+    Message,
     ChatMessage,
     StreamEvent,
-    StreamEventType
+    StreamEventType,
+    LLMError,
+    ServerError
 )
 
 logger = logging.getLogger(__name__)
 
 
+@dataclass
+class MockResponse:
+    """预设的 Mock 响应（可包含工具调用）"""
+    content: str = ""
+    tool_calls: Optional[List[Dict]] = None
+    finish_reason: str = "stop"
+
+
 class MockProvider(LLMProvider):
     """
     Mock Provider - 用于测试
@@ -29,14 +44,25 @@ class MockProvider(LLMProvider):
     - 无需 API key
     - 快速响应
     - 可配置的响应
-    - 模拟各种场景
+    - 按队列返回预设响应（含工具调用）
+    - 记录每次请求的消息
+    - 模拟各种场景（如 N 次调用后失败）
     """
     
     PROVIDER_NAME = "mock"
     
-    def __init__(self, config: LLMConfig = None):
+    def __init__(self, config: LLMConfig = None,
+                 responses: Optional[List[Union[str, MockResponse]]] = None,
+                 fail_after: Optional[int] = None,
+                 fail_with: Optional[LLMError] = None):
         """
         初始化 Mock Provider
+        
+        Args:
+            config: Provider 配置
+            responses: 预设响应队列，按调用顺序依次返回；耗尽后按关键字匹配
+            fail_after: 成功调用 N 次后，之后的调用都失败
+            fail_with: 失败时抛出的错误（默认 ServerError）
         """
         self.config = config or LLMConfig(
             provider="mock",
@@ -47,6 +73,15 @@ class MockProvider(LLMProvider):
         self._call_count = 0
         self._delay = 0.1
         
+        self._scripted: deque = deque()
+        for response in responses or []:
+            self.queue_response(response)
+        self.fail_after = fail_after
+        self.fail_with = fail_with
+        
+        # 每次请求收到的消息
+        self.requests: List[List[Message]] = []
+        
         # 预设响应
         self._responses = {
             "hello": "Hello! I'm a mock AI assistant.",
@@ -78,6 +113,47 @@ class MockProvider(LLMProvider):
         """设置预设响应"""
         self._responses[trigger.lower()] = response
     
+    def queue_response(self, response: Union[str, MockResponse],
+                       tool_calls: Optional[List[Dict]] = None):
+        """
+        追加一条预设响应
+        
+        Args:
+            response: 响应文本或 MockResponse
+            tool_calls: 工具调用（response 为文本时使用）
+        """
+        if isinstance(response, str):
+            response = MockResponse(content=response, tool_calls=tool_calls)
+        self._scripted.append(response)
+    
+    def _next_response(self, messages: List[Message]) -> MockResponse:
+        """记录请求并选出响应（优先使用预设队列）"""
+        if self.fail_after is not None and len(self.requests) >= self.fail_after:
+            self.requests.append(list(messages))
+            raise self.fail_with or ServerError(
+                f"Mock provider configured to fail after {self.fail_after} calls")
+        self.requests.append(list(messages))
+        
+        if self._scripted:
+            return self._scripted.popleft()
+        
+        # 获取最后一条用户消息
+        last_message = ""
+        for msg in reversed(messages):
+            if msg.role == "user":
+                last_message = msg.content.lower()
+                break
+        
+        # 选择响应
+        content = self._responses.get("default")
+        
+        for trigger, response in self._responses.items():
+            if trigger in last_message:
+                content = response
+                break
+        
+        return MockResponse(content=content)
+    
     def set_delay(self, delay: float):
         """设置响应延迟"""
         self._delay = delay
@@ -99,20 +175,8 @@ class MockProvider(LLMProvider):
         # 模拟延迟
         await asyncio.sleep(self._delay)
         
-        # 获取最后一条用户消息
-        last_message = ""
-        for msg in reversed(messages):
-            if msg.role == "user":
-                last_message = msg.content.lower()
-                break
-        
-        # 选择响应
-        content = self._responses.get("default")
-        
-        for trigger, response in self._responses.items():
-            if trigger in last_message:
-                content = response
-                break
+        response = self._next_response(messages)
+        content = response.content
         
         # 模拟 token 计算
         prompt_tokens = sum(len(m.content.split()) for m in messages) // 4
@@ -130,11 +194,39 @@ class MockProvider(LLMProvider):
                 "completion_tokens": completion_tokens,
                 "total_tokens": prompt_tokens + completion_tokens
             },
-            "stop_reason": "stop"
+            "stop_reason": response.finish_reason
         }
+        if response.tool_calls:
+            result["tool_calls"] = response.tool_calls
         
         return result
     
+    async def complete(
+        self,
+        messages: List[Message],
+        tools: List[Dict] = None,
+        stream: bool = False
+    ) -> LLMResponse:
+        """模拟完成请求（与真实 Provider 的 complete 接口一致）"""
+        result = await self.chat(messages)
+        return LLMResponse(
+            content=result["content"],
+            model=result["model"],
+            usage=result["usage"],
+            finish_reason=result["stop_reason"],
+            tool_calls=result.get("tool_calls")
+        )
+    
+    async def stream_complete(
+        self,
+        messages: List[Message],
+        tools: List[Dict] = None
+    ) -> AsyncIterator[str]:
+        """模拟流式完成（逐词产出）"""
+        response = await self.complete(messages, tools)
+        for word in response.content.split():
+            yield word + " "
+    
     async def chat_stream(
         self,
         messages: List[ChatMessage],
@@ -158,12 +250,13 @@ class MockProvider(LLMProvider):
         )
     
     async def embeddings(self, texts: List[str], model: str = "mock-embedding") -> List[List[float]]:
-        """模拟嵌入"""
-        import random
-        return [
-            [random.uniform(-1, 1) for _ in range(384)]
-            for _ in texts
-        ]
+        """模拟嵌入（同一文本总是得到相同的向量）"""
+        embeddings = []
+        for text in texts:
+            seed = int(hashlib.md5(text.encode('utf-8')).hexdigest()[:8], 16)
+            rng = random.Random(seed)
+            embeddings.append([rng.uniform(-1, 1) for _ in range(384)])
+        return embeddings
     
     async def count_tokens(self, text: str, model: str = "mock-model") -> int:
         """模拟 token 计算"""
@@ -180,6 +273,7 @@ class MockProvider(LLMProvider):
     def reset_metrics(self):
         """重置指标"""
         self._call_count = 0
+        self.requests.clear()
 
 
 class MockErrorProvider(MockProvider):
@@ -9,7 +9,8 @@ import json
 import logging
 from typing import List, Dict, Optional, AsyncIterator
 import httpx
-from .provider import LLMProvider, LLMConfig, LLMResponse, Message, ProviderType
+from .provider import LLMProvider, LLMConfig, LLMResponse, Message, ProviderType, CompletionOptions
+from .openai_impl import OPENAI_PRICING
 
 logger = logging.getLogger(__name__)
 
@@ -17,6 +18,8 @@ logger = logging.getLogger(__name__)
 class OpenAIProvider(LLMProvider):
     """OpenAI Provider"""
     
+    PRICING = OPENAI_PRICING
+    
     def __init__(self, config: LLMConfig):
         super().__init__(config)
         self._client: Optional[httpx.AsyncClient] = None
@@ -51,25 +54,20 @@ class OpenAIProvider(LLMProvider):
         self,
         messages: List[Message],
         tools: List[Dict] = None,
-        stream: bool = False
+        stream: bool = False,
+        options: Optional[CompletionOptions] = None
     ) -> LLMResponse:
-        """发送完成请求"""
+        """发送完成请求（options 控制温度、最大 token 数、停止序列等）"""
         if not self._client:
             raise RuntimeError("OpenAI provider not initialized")
         
         payload = {
             "model": self.config.model,
             "messages": self._format_messages(messages),
-            "max_tokens": self.config.max_tokens,
-            "temperature": self.config.temperature,
-            "top_p": self.config.top_p,
-            "stream": stream
+            "stream": stream,
+            **self._completion_fields(options, tools)
         }
         
-        if tools:
-            payload["tools"] = tools
-            payload["tool_choice"] = "auto"
-        
         # 添加额外参数
         for key, value in self.config.extra_params.items():
             if key not in payload:
@@ -88,7 +86,8 @@ class OpenAIProvider(LLMProvider):
     async def stream_complete(
         self,
         messages: List[Message],
-        tools: List[Dict] = None
+        tools: List[Dict] = None,
+        options: Optional[CompletionOptions] = None
     ) -> AsyncIterator[str]:
         """流式完成"""
         if not self._client:
@@ -97,15 +96,10 @@ class OpenAIProvider(LLMProvider):
         payload = {
             "model": self.config.model,
             "messages": self._format_messages(messages),
-            "max_tokens": self.config.max_tokens,
-            "temperature": self.config.temperature,
-            "top_p": self.config.top_p,
-            "stream": True
+            "stream": True,
+            **self._completion_fields(options, tools)
         }
         
-        if tools:
-            payload["tools"] = tools
-        
         endpoint = f"{self.base_url}/chat/completions"
         
         async with self._client.stream("POST", endpoint, json=payload) as response:
@@ -17,11 +17,23 @@ from .provider import (
     Message,
     ChatMessage,
     StreamEvent,
-    StreamEventType
+    StreamEventType,
+    ModelPrice
 )
 
 logger = logging.getLogger(__name__)
 
+# OpenAI 模型定价（USD / 1K tokens）
+OPENAI_PRICING = {
+    "gpt-4o-mini": ModelPrice(0.00015, 0.0006),
+    "gpt-4o": ModelPrice(0.005, 0.015),
+    "gpt-4-turbo": ModelPrice(0.01, 0.03),
+    "gpt-4": ModelPrice(0.03, 0.06),
+    "gpt-3.5-turbo": ModelPrice(0.0005, 0.0015),
+    "o1": ModelPrice(0.015, 0.06),
+    "o3-mini": ModelPrice(0.001, 0.01),
+}
+
 
 @dataclass
 class OpenAIConfig:
@@ -45,6 +57,7 @@ class OpenAIProvider(LLMProvider):
     """
     
     PROVIDER_NAME = "openai"
+    PRICING = OPENAI_PRICING
     SUPPORTED_MODELS = [
         "gpt-4o",
         "gpt-4o-mini",
@@ -141,10 +154,7 @@ class OpenAIProvider(LLMProvider):
         model = model or "gpt-4o"
         
         # 转换消息格式
-        openai_messages = [
-            {"role": msg.role, "content": msg.content}
-            for msg in messages
-        ]
+        openai_messages = [msg.to_dict() for msg in messages]
         
         # 构建请求参数
         params = {
@@ -7,8 +7,8 @@
 import os
 import logging
 from abc import ABC, abstractmethod
-from dataclasses import dataclass, field
-from typing import Any, Dict, List, Optional, AsyncIterator
+from dataclasses import dataclass, field, replace
+from typing import Any, Dict, List, Optional, AsyncIterator, Tuple, Union
 from enum import Enum
 
 logger = logging.getLogger(__name__)
@@ -88,15 +88,44 @@ class LLMConfig:
 
 @dataclass
 class Message:
-    """消息基类"""
+    """
+    消息基类
+    
+    工具调用往返需要的字段均为可选：assistant 消息的 tool_calls、
+    tool 消息的 tool_call_id 和 name；为 None 时不出现在请求中。
+    """
     role: str
     content: str
+    name: Optional[str] = None
+    tool_call_id: Optional[str] = None
+    tool_calls: Optional[List[Dict]] = None
+    
+    def to_dict(self) -> Dict[str, Any]:
+        """转换为 OpenAI 风格的消息字典（省略为 None 的可选字段）"""
+        data: Dict[str, Any] = {"role": self.role, "content": self.content}
+        if self.name is not None:
+            data["name"] = self.name
+        if self.tool_call_id is not None:
+            data["tool_call_id"] = self.tool_call_id
+        if self.tool_calls is not None:
+            data["tool_calls"] = self.tool_calls
+        return data
+    
+    @classmethod
+    def from_dict(cls, data: Dict[str, Any]) -> 'Message':
+        """从消息字典创建（忽略未知字段）"""
+        return cls(
+            role=data["role"],
+            content=data.get("content") or "",
+            name=data.get("name"),
+            tool_call_id=data.get("tool_call_id"),
+            tool_calls=data.get("tool_calls"),
+        )
 
 
 @dataclass
 class ChatMessage(Message):
     """聊天消息"""
-    name: Optional[str] = None
     function_call: Optional[Dict] = None
 
 
@@ -110,6 +139,29 @@ class LLMResponse:
     tool_calls: Optional[List[Dict]] = None
 
 
+@dataclass
+class ModelPrice:
+    """模型定价（USD / 1K tokens）"""
+    input_per_1k: float
+    output_per_1k: float
+    
+    def cost(self, input_tokens: int, output_tokens: int) -> float:
+        """计算一次调用的成本（USD）"""
+        return (input_tokens * self.input_per_1k + output_tokens * self.output_per_1k) / 1000
+
+
+def usage_tokens(usage: Dict[str, int]) -> Tuple[int, int]:
+    """
+    从响应的 usage 中取出 (输入 token, 输出 token)
+    
+    兼容 OpenAI 风格（prompt_tokens/completion_tokens）和
+    Anthropic 风格（input_tokens/output_tokens）。
+    """
+    input_tokens = usage.get('prompt_tokens', usage.get('input_tokens', 0)) or 0
+    output_tokens = usage.get('completion_tokens', usage.get('output_tokens', 0)) or 0
+    return int(input_tokens), int(output_tokens)
+
+
 @dataclass
 class Function:
     """函数定义"""
@@ -118,6 +170,97 @@ class Function:
     parameters: Dict
 
 
+@dataclass(frozen=True)
+class CompletionOptions:
+    """
+    生成参数
+    
+    with_* 方法返回修改后的副本；to_request 按 Provider 的字段名和取值范围
+    转换为请求体字段。
+    
+    Attributes:
+        temperature: 采样温度（超出 Provider 支持范围时截断）
+        max_tokens: 最大生成 token 数（None 时由 Provider 使用配置中的值）
+        stop: 停止序列
+        top_p: 核采样阈值（None 表示不设置）
+        tools: 可调用的工具（Function，或已是 Provider 格式的字典）
+    """
+    temperature: float = 0.7
+    max_tokens: Optional[int] = None
+    stop: Tuple[str, ...] = ()
+    top_p: Optional[float] = None
+    tools: Tuple[Union[Function, Dict], ...] = ()
+    
+    # 各 Provider 支持的温度范围
+    TEMPERATURE_RANGES = {
+        ProviderType.OPENAI: (0.0, 2.0),
+        ProviderType.ANTHROPIC: (0.0, 1.0),
+        ProviderType.MINIMAX: (0.01, 1.0),
+    }
+    
+    @classmethod
+    def from_config(cls, config: LLMConfig) -> 'CompletionOptions':
+        """以 LLMConfig 中的温度和最大 token 数为默认值"""
+        return cls(temperature=config.temperature, max_tokens=config.max_tokens)
+    
+    def with_temperature(self, temperature: float) -> 'CompletionOptions':
+        return replace(self, temperature=temperature)
+    
+    def with_max_tokens(self, max_tokens: Optional[int]) -> 'CompletionOptions':
+        return replace(self, max_tokens=max_tokens)
+    
+    def with_stop(self, *stop: str) -> 'CompletionOptions':
+        return replace(self, stop=tuple(stop))
+    
+    def with_top_p(self, top_p: Optional[float]) -> 'CompletionOptions':
+        return replace(self, top_p=top_p)
+    
+    def with_tools(self, *tools: Union[Function, Dict]) -> 'CompletionOptions':
+        return replace(self, tools=tuple(tools))
+    
+    def clamped_temperature(self, provider: ProviderType) -> float:
+        """截断到 Provider 支持的温度范围（未知 Provider 按 OpenAI 处理）"""
+        low, high = self.TEMPERATURE_RANGES.get(provider, self.TEMPERATURE_RANGES[ProviderType.OPENAI])
+        return min(max(self.temperature, low), high)
+    
+    def to_request(self, provider: ProviderType) -> Dict[str, Any]:
+        """
+        转换为 Provider 请求体中的生成参数字段
+        
+        Args:
+            provider: 目标 Provider（Anthropic 使用 stop_sequences 和 input_schema
+                      格式的工具，其余按 OpenAI 兼容格式）
+        
+        Returns:
+            可合并进请求体的字典（省略未设置的字段）
+        """
+        anthropic = provider == ProviderType.ANTHROPIC
+        request: Dict[str, Any] = {"temperature": self.clamped_temperature(provider)}
+        if self.max_tokens is not None:
+            request["max_tokens"] = self.max_tokens
+        if self.top_p is not None:
+            request["top_p"] = min(max(self.top_p, 0.0), 1.0)
+        if self.stop:
+            request["stop_sequences" if anthropic else "stop"] = list(self.stop)
+        if self.tools:
+            request["tools"] = [self._format_tool(tool, anthropic) for tool in self.tools]
+            if provider == ProviderType.OPENAI:
+                request["tool_choice"] = "auto"
+        return request
+    
+    @staticmethod
+    def _format_tool(tool: Union[Function, Dict], anthropic: bool) -> Dict[str, Any]:
+        if isinstance(tool, dict):
+            return tool
+        if anthropic:
+            return {"name": tool.name, "description": tool.description,
+                    "input_schema": tool.parameters}
+        return {"type": "function", "function": {
+            "name": tool.name, "description": tool.description,
+            "parameters": tool.parameters,
+        }}
+
+
 class StreamEventType(Enum):
     """流式事件类型"""
     CONTENT = "content"
@@ -132,9 +275,63 @@ class StreamEvent:
     data: Dict
 
 
+class LLMError(Exception):
+    """LLM 调用错误基类"""
+    
+    # 是否可以换用其他 Provider 重试
+    retryable = False
+
+
+class RateLimitedError(LLMError):
+    """请求被限流（HTTP 429）"""
+    retryable = True
+
+
+class ServerError(LLMError):
+    """服务端错误或网络故障（HTTP 5xx、连接失败、超时）"""
+    retryable = True
+
+
+class BadRequestError(LLMError):
+    """请求本身有误（HTTP 4xx），换 Provider 也无济于事"""
+
+
+class CircuitOpenError(LLMError):
+    """Provider 的熔断器处于打开状态，请求未发出"""
+    retryable = True
+
+
+def classify_llm_error(error: Exception) -> Optional[LLMError]:
+    """
+    将 Provider 抛出的异常归类为 LLMError
+    
+    Returns:
+        对应的 LLMError；无法识别的异常返回 None
+    """
+    if isinstance(error, LLMError):
+        return error
+    try:
+        import httpx
+    except ImportError:
+        return None
+    if isinstance(error, httpx.HTTPStatusError):
+        status = error.response.status_code
+        if status == 429:
+            return RateLimitedError(str(error))
+        if status >= 500:
+            return ServerError(str(error))
+        return BadRequestError(str(error))
+    if isinstance(error, httpx.RequestError):
+        return ServerError(str(error))
+    return None
+
+
 class LLMProvider(ABC):
     """LLM Provider 抽象基类"""
     
+    # 模型定价表（子类覆盖；按模型名精确匹配，否则取最长前缀匹配）
+    PRICING: Dict[str, ModelPrice] = {}
+    
     def __init__(self, config: LLMConfig):
         """初始化"""
         self.config = config
@@ -185,6 +382,33 @@ class LLMProvider(ABC):
         # 简化的 token 计算：约 4 个字符 = 1 个 token
         return len(text) // 4
     
+    def price_for(self, model: Optional[str] = None) -> Optional[ModelPrice]:
+        """
+        获取模型定价
+        
+        Args:
+            model: 模型名（默认使用配置中的模型）
+        
+        Returns:
+            定价，未知模型返回 None
+        """
+        model = model or getattr(self.config, 'model', '')
+        if model in self.PRICING:
+            return self.PRICING[model]
+        prefixes = [name for name in self.PRICING if model.startswith(name)]
+        if prefixes:
+            return self.PRICING[max(prefixes, key=len)]
+        return None
+    
+    def estimate_cost(self, response: LLMResponse) -> float:
+        """
+        根据响应的 usage 计算成本（USD），未知模型的成本为 0
+        """
+        price = self.price_for(response.model)
+        if price is None:
+            return 0.0
+        return price.cost(*usage_tokens(response.usage))
+    
     def get_metrics(self) -> Dict[str, Any]:
         """获取使用指标"""
         return self._metrics.copy()
@@ -229,12 +453,25 @@ class LLMProvider(ABC):
                     await asyncio.sleep(2 ** attempt)  # 指数退避
         raise last_error or Exception("Request failed")
 
-    def _format_messages(self, messages: List[Message]) -> List[Dict[str, str]]:
-        """格式化消息列表"""
-        return [
-            {"role": msg.role, "content": msg.content}
-            for msg in messages
-        ]
+    def _completion_fields(self, options: Optional[CompletionOptions] = None,
+                           tools: Optional[List[Dict]] = None) -> Dict[str, Any]:
+        """
+        按本 Provider 的格式生成请求体中的生成参数字段
+        
+        Args:
+            options: 生成参数（None 时使用配置中的温度和最大 token 数）
+            tools: 旧接口传入的工具列表（options 未指定工具时使用）
+        """
+        options = options or CompletionOptions.from_config(self.config)
+        if options.max_tokens is None:
+            options = options.with_max_tokens(self.config.max_tokens)
+        if tools and not options.tools:
+            options = options.with_tools(*tools)
+        return options.to_request(self.provider_type)
+    
+    def _format_messages(self, messages: List[Message]) -> List[Dict[str, Any]]:
+        """格式化消息列表（保留 name / tool_call_id / tool_calls）"""
+        return [msg.to_dict() for msg in messages]
 
     @abstractmethod
     async def chat(
@@ -0,0 +1,188 @@
+# -*- coding: utf-8 -*-
+"""Rate Limited Provider - 按 Agent 限制请求速率
+
+调度器的资源配额只限制 token 数；RateLimitedProvider 为每个 Agent 维护一个
+令牌桶，限制其每分钟请求数，超出时在本地直接抛出 RateLimitedError，请求不会
+发出，避免单个 Agent 耗尽共享的 Provider 配额。
+
+调用方通过 agent_pid 关键字参数标明请求所属的 Agent（该参数不会传给被包装的
+Provider）；未标明的请求共用一个匿名令牌桶。放在 FallbackProvider 外层，
+否则可重试的 RateLimitedError 会让请求转到下一个 Provider。调用方可以用
+rate_limit_kwargs 只在 Provider 链中有 RateLimitedProvider 时传入 agent_pid。
+已补满的令牌桶与新建的令牌桶等价，会被定期清理，结束的 Agent 不会一直占用内存。
+"""
+
+import logging
+from typing import Any, Dict, Optional, Tuple
+
+from ..core.clock import Clock, SYSTEM_CLOCK
+from .delegating import DelegatingProvider
+from .provider import LLMProvider, RateLimitedError
+
+logger = logging.getLogger(__name__)
+
+
+class RateLimitedProvider(DelegatingProvider):
+    """
+    按 Agent 限速的 Provider（装饰器）
+
+    Example:
+        provider = RateLimitedProvider(primary, requests_per_minute=30,
+                                       per_agent_limits={"planner": 120})
+        await provider.complete(messages, agent_pid=process.pid)
+    """
+
+    # 跟踪的令牌桶超过该数量时清理已补满的令牌桶
+    PRUNE_THRESHOLD = 256
+
+    def __init__(self, inner: LLMProvider,
+                 requests_per_minute: float = 60.0,
+                 burst: Optional[int] = None,
+                 per_agent_limits: Optional[Dict[str, float]] = None,
+                 clock: Optional[Clock] = None):
+        """
+        Args:
+            inner: 被包装的 Provider
+            requests_per_minute: 每个 Agent 默认的每分钟请求数
+            burst: 令牌桶容量，即允许的突发请求数（None 表示等于每分钟请求数）
+            per_agent_limits: 按 Agent PID 覆盖每分钟请求数
+            clock: 时间源（默认系统时钟）
+
+        Raises:
+            ValueError: 每分钟请求数不是正数，或 burst < 1
+        """
+        limits = [requests_per_minute, *(per_agent_limits or {}).values()]
+        if any(limit <= 0 for limit in limits):
+            raise ValueError(f"requests_per_minute must be positive, got {limits}")
+        if burst is not None and burst < 1:
+            raise ValueError(f"burst must be at least 1, got {burst}")
+        super().__init__(inner)
+        self.requests_per_minute = requests_per_minute
+        self.burst = burst
+        self.per_agent_limits: Dict[str, float] = dict(per_agent_limits or {})
+        self.clock = clock or SYSTEM_CLOCK
+        # agent_pid -> (剩余令牌, 上次补充时间)
+        self._buckets: Dict[Optional[str], Tuple[float, float]] = {}
+        self._prune_at = self.PRUNE_THRESHOLD
+        self.allowed_requests = 0
+        self.rejected_requests = 0
+
+    async def complete(self, *args, agent_pid: Optional[str] = None, **kwargs) -> Any:
+        """发送完成请求（按 Agent 限速）"""
+        self._acquire(agent_pid)
+        return await self.inner.complete(*args, **kwargs)
+
+    async def chat(self, *args, agent_pid: Optional[str] = None, **kwargs) -> Any:
+        """发送聊天请求（按 Agent 限速）"""
+        self._acquire(agent_pid)
+        return await self.inner.chat(*args, **kwargs)
+
+    async def stream_complete(self, *args, agent_pid: Optional[str] = None, **kwargs):
+        """流式完成（建立流时消耗一次请求额度）"""
+        self._acquire(agent_pid)
+        async for chunk in self.inner.stream_complete(*args, **kwargs):
+            yield chunk
+
+    def set_limit(self, agent_pid: str, requests_per_minute: Optional[float]):
+        """
+        设置单个 Agent 的每分钟请求数
+
+        Args:
+            agent_pid: Agent PID
+            requests_per_minute: 每分钟请求数（None 表示恢复默认值）
+
+        Raises:
+            ValueError: requests_per_minute 不是正数
+        """
+        if requests_per_minute is None:
+            self.per_agent_limits.pop(agent_pid, None)
+        elif requests_per_minute <= 0:
+            raise ValueError(f"requests_per_minute must be positive, got {requests_per_minute}")
+        else:
+            self.per_agent_limits[agent_pid] = requests_per_minute
+        self._buckets.pop(agent_pid, None)
+
+    def forget(self, agent_pid: str):
+        """丢弃 Agent 的令牌桶（Agent 结束后调用，释放内存）"""
+        self._buckets.pop(agent_pid, None)
+
+    def remaining(self, agent_pid: Optional[str] = None) -> float:
+        """Agent 当前可立即发出的请求数"""
+        tokens, _ = self._refill(agent_pid)
+        return tokens
+
+    def get_stats(self) -> Dict[str, Any]:
+        """限速统计"""
+        return {
+            'provider': self.provider_name,
+            'requests_per_minute': self.requests_per_minute,
+            'allowed_requests': self.allowed_requests,
+            'rejected_requests': self.rejected_requests,
+            'tracked_agents': len(self._buckets),
+        }
+
+    def _limit(self, agent_pid: Optional[str]) -> Tuple[float, float]:
+        """Agent 的 (每秒补充令牌数, 令牌桶容量)"""
+        rpm = self.per_agent_limits.get(agent_pid, self.requests_per_minute)
+        capacity = self.burst if self.burst is not None else max(rpm, 1.0)
+        return rpm / 60.0, float(capacity)
+
+    def _refill(self, agent_pid: Optional[str]) -> Tuple[float, float]:
+        rate, capacity = self._limit(agent_pid)
+        now = self.clock.now()
+        tokens, last = self._buckets.get(agent_pid, (capacity, now))
+        tokens = min(capacity, tokens + (now - last) * rate)
+        self._buckets[agent_pid] = (tokens, now)
+        return tokens, now
+
+    def _prune(self):
+        """丢弃已补满的令牌桶（与新建的令牌桶等价）"""
+        now = self.clock.now()
+        for agent_pid, (tokens, last) in list(self._buckets.items()):
+            rate, capacity = self._limit(agent_pid)
+            if tokens + (now - last) * rate >= capacity:
+                del self._buckets[agent_pid]
+        self._prune_at = max(self.PRUNE_THRESHOLD, 2 * len(self._buckets))
+
+    def _acquire(self, agent_pid: Optional[str]):
+        """
+        消耗一次请求额度
+
+        Raises:
+            RateLimitedError: Agent 的令牌桶已空（请求未发出）
+        """
+        if len(self._buckets) >= self._prune_at:
+            self._prune()
+        tokens, now = self._refill(agent_pid)
+        if tokens >= 1.0:
+            self._buckets[agent_pid] = (tokens - 1.0, now)
+            self.allowed_requests += 1
+            return
+
+        self.rejected_requests += 1
+        self._metrics["failed_requests"] += 1
+        rate, _ = self._limit(agent_pid)
+        retry_after = (1.0 - tokens) / rate
+        logger.debug(f"Rate limited agent {agent_pid} on {self.provider_name} "
+                     f"(retry in {retry_after:.1f}s)")
+        raise RateLimitedError(
+            f"Agent {agent_pid or '<anonymous>'} exceeded its request rate for provider "
+            f"{self.provider_name} (retry in {retry_after:.1f}s)"
+        )
+
+
+def rate_limit_kwargs(provider: Any, agent_pid: Optional[str]) -> Dict[str, Any]:
+    """
+    为 Provider 调用构造 agent_pid 关键字参数
+
+    只有沿 inner 包装链能找到 RateLimitedProvider 时才传入 agent_pid，
+    其他 Provider 不接受该参数。
+
+    Example:
+        await provider.complete(messages, **rate_limit_kwargs(provider, process.pid))
+    """
+    while agent_pid is not None and provider is not None:
+        if isinstance(provider, RateLimitedProvider):
+            return {'agent_pid': agent_pid}
+        provider = getattr(provider, '__dict__', {}).get('inner')
+    return {}
@@ -0,0 +1,123 @@
+# -*- coding: utf-8 -*-
+"""Single-Flight Provider - 合并并发的相同请求
+
+多个 Agent 共享系统上下文时，经常同时向同一个 Provider 发送完全相同的请求。
+SingleFlightProvider 按请求参数（消息 + 生成参数）的哈希合并正在进行中的请求：
+只有第一个调用方真正请求上游，其余调用方等待并得到结果的深拷贝。
+请求完成或失败后立即从进行中表移除，之后的相同请求会重新发出。
+流式请求不合并。
+"""
+
+import asyncio
+import copy
+import dataclasses
+import hashlib
+import json
+import logging
+from typing import Any, Dict
+
+from .delegating import DelegatingProvider
+from .provider import LLMProvider
+
+logger = logging.getLogger(__name__)
+
+
+def _normalize(value: Any) -> Any:
+    """把消息、生成参数等对象转换为可稳定序列化的结构"""
+    if hasattr(value, 'to_dict'):
+        return value.to_dict()
+    if dataclasses.is_dataclass(value) and not isinstance(value, type):
+        return dataclasses.asdict(value)
+    if hasattr(value, 'value') and hasattr(value, 'name'):
+        return value.value
+    return repr(value)
+
+
+class SingleFlightProvider(DelegatingProvider):
+    """
+    请求合并 Provider（装饰器）
+
+    Example:
+        provider = SingleFlightProvider(CircuitBreakerProvider(primary))
+    """
+
+    def __init__(self, inner: LLMProvider):
+        """
+        Args:
+            inner: 被包装的 Provider
+        """
+        super().__init__(inner)
+        self._in_flight: Dict[str, asyncio.Future] = {}
+        self.upstream_requests = 0
+        self.coalesced_requests = 0
+
+    @property
+    def in_flight(self) -> int:
+        """正在进行中的上游请求数"""
+        return len(self._in_flight)
+
+    async def complete(self, *args, **kwargs) -> Any:
+        """发送完成请求（合并并发的相同请求）"""
+        return await self._single_flight('complete', *args, **kwargs)
+
+    async def chat(self, *args, **kwargs) -> Any:
+        """发送聊天请求（合并并发的相同请求）"""
+        return await self._single_flight('chat', *args, **kwargs)
+
+    def get_stats(self) -> Dict[str, Any]:
+        """合并统计"""
+        return {
+            'provider': self.provider_name,
+            'upstream_requests': self.upstream_requests,
+            'coalesced_requests': self.coalesced_requests,
+            'in_flight': self.in_flight,
+        }
+
+    @staticmethod
+    def request_key(method: str, args: tuple, kwargs: Dict[str, Any]) -> str:
+        """计算请求的合并键（方法名 + 全部参数的哈希）"""
+        payload = json.dumps([method, list(args), kwargs], sort_keys=True,
+                             ensure_ascii=False, default=_normalize)
+        return hashlib.sha256(payload.encode('utf-8')).hexdigest()
+
+    async def _single_flight(self, method: str, *args, **kwargs) -> Any:
+        if kwargs.get('stream'):
+            return await getattr(self.inner, method)(*args, **kwargs)
+
+        key = self.request_key(method, args, kwargs)
+        while True:
+            future = self._in_flight.get(key)
+            if future is None:
+                return await self._lead(key, method, *args, **kwargs)
+            self.coalesced_requests += 1
+            try:
+                result = await asyncio.shield(future)
+            except asyncio.CancelledError:
+                # 发起请求的调用方被取消时重新发起；自身被取消则照常传播
+                if not future.cancelled():
+                    raise
+                continue
+            return copy.deepcopy(result)
+
+    async def _lead(self, key: str, method: str, *args, **kwargs) -> Any:
+        """作为第一个调用方请求上游，并把结果或异常交给等待者"""
+        future = asyncio.get_running_loop().create_future()
+        self._in_flight[key] = future
+        self.upstream_requests += 1
+        try:
+            result = await getattr(self.inner, method)(*args, **kwargs)
+        except asyncio.CancelledError:
+            future.cancel()
+            raise
+        except Exception as e:
+            self._metrics["failed_requests"] += 1
+            future.set_exception(e)
+            # 没有等待者时避免 "exception was never retrieved" 警告
+            future.exception()
+            raise
+        else:
+            self._metrics["total_requests"] += 1
+            future.set_result(result)
+            return result
+        finally:
+            self._in_flight.pop(key, None)
@@ -1,10 +1,12 @@
 # -*- coding: utf-8 -*-
 """Agent OS Kernel - 工具系统"""
 
-from .base import Tool, SimpleTool
-from .registry import ToolRegistry
+from .base import Tool, SimpleTool, ToolError, ToolErrorCode
+from .registry import ToolRegistry, ToolStats
 from .builtin import (
     CalculatorTool,
+    DateTimeTool,
+    JsonQueryTool,
     SearchTool,
     FileReadTool,
     FileWriteTool,
@@ -14,8 +16,13 @@ from .builtin import (
 __all__ = [
     "Tool",
     "SimpleTool",
+    "ToolError",
+    "ToolErrorCode",
     "ToolRegistry",
+    "ToolStats",
     "CalculatorTool",
+    "DateTimeTool",
+    "JsonQueryTool",
     "SearchTool",
     "FileReadTool",
     "FileWriteTool",
@@ -16,7 +16,7 @@ Tool System - 工具调用系统
 import json
 import subprocess
 from abc import ABC, abstractmethod
-from typing import Any, Dict, Optional, Callable, List, Tuple
+from typing import Any, Dict, Optional, Callable, List, Tuple, Union
 from dataclasses import dataclass, field
 from enum import Enum
 
@@ -46,13 +46,82 @@ class ToolErrorCode(Enum):
     INTERNAL_ERROR = 500        # 内部错误
     NOT_IMPLEMENTED = 501       # 未实现
     SERVICE_UNAVAILABLE = 503   # 服务不可用
+    
+    # 常用语义别名（与上面的错误码取值相同）
+    INVALID_INPUT = 400         # 输入不合法
+    PERMISSION_DENIED = 403     # 权限不足
+    EXECUTION_FAILED = 500      # 执行失败
+    
+    @property
+    def retryable(self) -> bool:
+        """是否为可重试的临时性失败（超时、服务不可用）"""
+        return self in (ToolErrorCode.TIMEOUT, ToolErrorCode.SERVICE_UNAVAILABLE)
+
+
+class ToolError(Exception):
+    """
+    结构化工具错误
+    
+    工具可以在 execute 中直接抛出 ToolError，由 ToolRegistry 转换为带错误码的
+    结果；调用方据此区分可重试的失败（超时等）和永久性失败（输入不合法等）。
+    
+    Attributes:
+        code: 错误码
+        message: 错误信息
+        details: 附加信息
+    """
+    
+    def __init__(self, message: str,
+                 code: ToolErrorCode = ToolErrorCode.EXECUTION_FAILED,
+                 details: Optional[Dict[str, Any]] = None):
+        super().__init__(message)
+        self.message = message
+        self.code = code
+        self.details = details or {}
+    
+    @property
+    def retryable(self) -> bool:
+        """是否可以重试"""
+        return self.code.retryable
+    
+    @classmethod
+    def from_exception(cls, exc: Union[BaseException, str]) -> 'ToolError':
+        """
+        将任意异常或错误字符串转换为 ToolError
+        
+        字符串和未知异常视为 EXECUTION_FAILED；超时和权限异常映射到
+        对应的错误码。
+        """
+        if isinstance(exc, ToolError):
+            return exc
+        if isinstance(exc, str):
+            return cls(exc)
+        if isinstance(exc, (TimeoutError, subprocess.TimeoutExpired)):
+            code = ToolErrorCode.TIMEOUT
+        elif isinstance(exc, PermissionError):
+            code = ToolErrorCode.PERMISSION_DENIED
+        else:
+            code = ToolErrorCode.EXECUTION_FAILED
+        return cls(str(exc) or type(exc).__name__, code,
+                   details={"exception": type(exc).__name__})
+    
+    def to_result(self) -> 'ToolResult':
+        """转换为失败的 ToolResult"""
+        return ToolResult.error(self.message, code=self.code, metadata=dict(self.details))
+    
+    def to_dict(self) -> Dict[str, Any]:
+        """转换为工具结果字典（与 ToolRegistry.execute 的返回格式一致）"""
+        return self.to_result().to_dict()
+    
+    def __repr__(self) -> str:
+        return f"ToolError(code={self.code.value}, message={self.message!r})"
 
 
 @dataclass
 class ToolParameter:
     """工具参数定义"""
     name: str
-    type: str  # string, integer, boolean, array, object
+    type: Union[str, List[str]]  # string, integer, boolean, array, object；可接受多种类型时为列表
     description: str
     required: bool = True
     default: Any = None
@@ -98,9 +167,15 @@ class ToolResult:
             "data": self.data,
             "error": self.error,
             "error_code": self.error_code.value,
+            "retryable": self.retryable,
             "metadata": self.metadata,
         }
     
+    @property
+    def retryable(self) -> bool:
+        """失败是否可以重试"""
+        return not self.success and self.error_code.retryable
+    
     def to_json(self) -> str:
         """转换为 JSON 字符串"""
         return json.dumps(self.to_dict(), ensure_ascii=False, indent=2)
@@ -140,6 +215,7 @@ class Tool(ABC):
     可选实现：
     - get_capabilities() - 返回机器可读的能力描述（--desc）
     - validate_params() - 参数验证
+    - cacheable() / cache_ttl() - 允许 ToolRegistry 缓存结果
     """
     
     @abstractmethod
@@ -161,7 +237,8 @@ class Tool(ABC):
         """
         执行工具
         
-        必须返回 ToolResult，包含结构化输出和标准化错误码。
+        必须返回 ToolResult，包含结构化输出和标准化错误码。失败时也可以
+        直接抛出 ToolError，由 ToolRegistry 转换为失败结果。
         """
         pass
     
@@ -182,6 +259,18 @@ class Tool(ABC):
         """获取使用示例"""
         return []
     
+    def cacheable(self) -> bool:
+        """
+        结果是否可以缓存
+        
+        只有确定性、无副作用的工具（相同参数总是返回相同结果）才应返回 True。
+        """
+        return False
+    
+    def cache_ttl(self) -> Optional[float]:
+        """缓存有效期（秒），None 表示使用 ToolRegistry 的默认值"""
+        return None
+    
     def validate_params(self, **kwargs) -> Tuple[bool, str]:
         """
         验证参数
@@ -244,11 +333,13 @@ class SimpleTool(Tool):
     """
     
     def __init__(self, name: str, description: str,
-                 func: Callable, parameters: Optional[List[ToolParameter]] = None):
+                 func: Callable, parameters: Optional[List[ToolParameter]] = None,
+                 cacheable: bool = False):
         self._name = name
         self._description = description
         self._func = func
         self._parameters = parameters or []
+        self._cacheable = cacheable
     
     def name(self) -> str:
         return self._name
@@ -259,16 +350,15 @@ class SimpleTool(Tool):
     def parameters(self) -> List[ToolParameter]:
         return self._parameters
     
+    def cacheable(self) -> bool:
+        return self._cacheable
+    
     def execute(self, **kwargs) -> ToolResult:
         try:
             result = self._func(**kwargs)
             return ToolResult.success(data=result)
         except Exception as e:
-            return ToolResult.error(
-                message=str(e),
-                code=ToolErrorCode.INTERNAL_ERROR,
-                metadata={"exception": type(e).__name__}
-            )
+            return ToolError.from_exception(e).to_result()
 
 
 class CLITool(Tool):
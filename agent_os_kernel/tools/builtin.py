@@ -6,12 +6,14 @@ Built-in Tools - 内置工具
 """
 
 import os
+import re
 import json
 import math
 import logging
-from typing import Any, Dict, List, Optional
+from datetime import datetime, timezone
+from typing import Any, Callable, Dict, List, Optional
 
-from .base import Tool, ToolParameter
+from .base import Tool, ToolParameter, ToolError, ToolErrorCode
 
 
 logger = logging.getLogger(__name__)
@@ -26,6 +28,9 @@ class CalculatorTool(Tool):
     def description(self) -> str:
         return "Evaluate mathematical expressions safely"
     
+    def cacheable(self) -> bool:
+        return True
+    
     def parameters(self) -> List[ToolParameter]:
         return [
             ToolParameter(
@@ -37,57 +42,58 @@ class CalculatorTool(Tool):
         ]
     
     def execute(self, expression: str, **kwargs) -> Dict[str, Any]:
-        """安全地计算数学表达式"""
+        """
+        安全地计算数学表达式
+        
+        Raises:
+            ToolError: 表达式不合法（INVALID_INPUT）或计算失败（EXECUTION_FAILED）
+        """
+        # 安全评估：只允许数学运算
+        allowed_names = {
+            "abs": abs,
+            "max": max,
+            "min": min,
+            "sum": sum,
+            "pow": pow,
+            "round": round,
+            "math": math,
+            "sin": math.sin,
+            "cos": math.cos,
+            "tan": math.tan,
+            "sqrt": math.sqrt,
+            "log": math.log,
+            "log10": math.log10,
+            "exp": math.exp,
+            "pi": math.pi,
+            "e": math.e,
+        }
+        
+        # 编译表达式
         try:
-            # 安全评估：只允许数学运算
-            allowed_names = {
-                "abs": abs,
-                "max": max,
-                "min": min,
-                "sum": sum,
-                "pow": pow,
-                "round": round,
-                "math": math,
-                "sin": math.sin,
-                "cos": math.cos,
-                "tan": math.tan,
-                "sqrt": math.sqrt,
-                "log": math.log,
-                "log10": math.log10,
-                "exp": math.exp,
-                "pi": math.pi,
-                "e": math.e,
-            }
-            
-            # 编译表达式
             code = compile(expression, "<string>", "eval")
-            
-            # 检查只允许安全的操作
-            for name in code.co_names:
-                if name not in allowed_names:
-                    return {
-                        "success": False,
-                        "data": None,
-                        "error": f"Disallowed name: {name}",
-                        "metadata": {}
-                    }
-            
+        except (SyntaxError, ValueError) as e:
+            raise ToolError(f"Invalid expression: {e}", ToolErrorCode.INVALID_INPUT,
+                            details={"expression": expression})
+        
+        # 检查只允许安全的操作
+        for name in code.co_names:
+            if name not in allowed_names:
+                raise ToolError(f"Disallowed name: {name}", ToolErrorCode.INVALID_INPUT,
+                                details={"expression": expression})
+        
+        try:
             result = eval(code, {"__builtins__": {}}, allowed_names)
-            
-            return {
-                "success": True,
-                "data": result,
-                "error": None,
-                "metadata": {"expression": expression}
-            }
-            
         except Exception as e:
-            return {
-                "success": False,
-                "data": None,
-                "error": str(e),
-                "metadata": {"expression": expression}
-            }
+            raise ToolError(str(e), ToolErrorCode.EXECUTION_FAILED,
+                            details={"expression": expression,
+                                     "exception": type(e).__name__})
+        
+        return {
+            "success": True,
+            "data": result,
+            "error": None,
+            "metadata": {"expression": expression}
+        }
 
 
 class SearchTool(Tool):
@@ -453,6 +459,305 @@ class WebFetchTool(Tool):
             }
 
 
+class DateTimeTool(Tool):
+    """
+    当前时间工具
+    
+    返回当前时间（默认 UTC），避免 LLM 臆造日期。
+    """
+    
+    def __init__(self, now: Optional[Callable[[], datetime]] = None):
+        """
+        Args:
+            now: 返回当前 UTC 时间的函数（测试时可注入固定时间）
+        """
+        self._now = now or (lambda: datetime.now(timezone.utc))
+    
+    def name(self) -> str:
+        return "datetime"
+    
+    def description(self) -> str:
+        return "Get the current date and time (UTC by default)"
+    
+    def parameters(self) -> List[ToolParameter]:
+        return [
+            ToolParameter(
+                name="format",
+                type="string",
+                description="Output format: 'rfc3339', 'unix', or a strftime pattern (e.g., '%Y-%m-%d')",
+                required=False,
+                default="rfc3339"
+            ),
+            ToolParameter(
+                name="timezone",
+                type="string",
+                description="IANA timezone name (e.g., 'Asia/Shanghai'), defaults to UTC",
+                required=False,
+                default="UTC"
+            )
+        ]
+    
+    def get_examples(self) -> List[Dict[str, Any]]:
+        return [
+            {"format": "rfc3339"},
+            {"format": "unix"},
+            {"format": "%Y-%m-%d %H:%M", "timezone": "Asia/Shanghai"},
+        ]
+    
+    def execute(self, format: str = "rfc3339", timezone: str = "UTC",
+                **kwargs) -> Dict[str, Any]:
+        """
+        获取当前时间
+        
+        Args:
+            format: rfc3339 / unix / strftime 格式串
+            timezone: IANA 时区名
+        
+        Raises:
+            ToolError: 时区未知（INVALID_INPUT）
+        """
+        now = self._now()
+        tz_name = timezone or "UTC"
+        if tz_name.upper() != "UTC":
+            try:
+                from zoneinfo import ZoneInfo
+                now = now.astimezone(ZoneInfo(tz_name))
+            except Exception:
+                raise ToolError(f"Unknown timezone: {tz_name}", ToolErrorCode.INVALID_INPUT,
+                                details={"timezone": tz_name})
+        
+        if format == "unix":
+            value: Any = int(now.timestamp())
+        elif format == "rfc3339":
+            value = now.isoformat(timespec="seconds")
+        else:
+            value = now.strftime(format)
+        
+        return {
+            "success": True,
+            "data": value,
+            "error": None,
+            "metadata": {"format": format, "timezone": tz_name}
+        }
+
+
+_BRACKET_PART = re.compile(r"""\s*('(?:[^'\\]|\\.)*'|"(?:[^"\\]|\\.)*"|[^,]+?)\s*(?:,|$)""")
+
+
+def _parse_jsonpath(query: str) -> List[tuple]:
+    """
+    解析 JSONPath 为步骤列表
+    
+    支持：$、.key、['key']、[n]、[a:b]、[a,b]、*、..key（递归下降）。
+    
+    Raises:
+        ValueError: 语法错误或不支持的表达式（如过滤器 ?()）
+    """
+    query = query.strip()
+    if not query.startswith('$'):
+        raise ValueError("JSONPath must start with '$'")
+    
+    steps: List[tuple] = []
+    i = 1
+    while i < len(query):
+        if query.startswith('..', i):
+            i += 2
+            if i < len(query) and query[i] == '[':
+                step, i = _parse_bracket(query, i)
+            else:
+                name, i = _read_name(query, i)
+                step = ('wildcard',) if name == '*' else ('key', name)
+            steps.append(('descend', step))
+        elif query[i] == '.':
+            name, i = _read_name(query, i + 1)
+            steps.append(('wildcard',) if name == '*' else ('key', name))
+        elif query[i] == '[':
+            step, i = _parse_bracket(query, i)
+            steps.append(step)
+        else:
+            raise ValueError(f"Unexpected character {query[i]!r} at position {i}")
+    return steps
+
+
+def _read_name(query: str, i: int):
+    """读取点号后的字段名"""
+    start = i
+    while i < len(query) and query[i] not in '.[':
+        i += 1
+    if i == start:
+        raise ValueError(f"Empty field name at position {start}")
+    return query[start:i], i
+
+
+def _parse_bracket(query: str, i: int):
+    """解析 [...] 选择器"""
+    end, quote = i + 1, None
+    while end < len(query):
+        ch = query[end]
+        if quote:
+            if ch == '\\':
+                end += 1
+            elif ch == quote:
+                quote = None
+        elif ch in '\'"':
+            quote = ch
+        elif ch == ']':
+            break
+        end += 1
+    else:
+        raise ValueError(f"Unclosed '[' at position {i}")
+    
+    inner = query[i + 1:end].strip()
+    if inner == '*':
+        return ('wildcard',), end + 1
+    if inner.startswith('?') or inner.startswith('('):
+        raise ValueError(f"Unsupported JSONPath expression: [{inner}]")
+    
+    selectors = []
+    pos = 0
+    while pos < len(inner):
+        match = _BRACKET_PART.match(inner, pos)
+        if match is None:
+            raise ValueError(f"Empty selector in [{inner}]")
+        part, pos = match.group(1), match.end()
+        if part[:1] in ('"', "'"):
+            if len(part) < 2 or part[-1] != part[0]:
+                raise ValueError(f"Invalid selector: [{part}]")
+            selectors.append(('key', part[1:-1].replace('\\' + part[0], part[0])))
+        elif ':' in part:
+            bounds = part.split(':')
+            if len(bounds) > 3:
+                raise ValueError(f"Invalid slice: [{part}]")
+            try:
+                values = [int(b) if b.strip() else None for b in bounds]
+            except ValueError:
+                raise ValueError(f"Invalid slice: [{part}]")
+            selectors.append(('slice', slice(*values)))
+        else:
+            try:
+                selectors.append(('index', int(part)))
+            except ValueError:
+                raise ValueError(f"Invalid selector: [{part}]")
+    if inner.endswith(','):
+        raise ValueError(f"Empty selector in [{inner}]")
+    
+    if not selectors:
+        raise ValueError(f"Empty selector at position {i}")
+    if len(selectors) == 1:
+        return selectors[0], end + 1
+    return ('union', selectors), end + 1
+
+
+def _walk(node: Any):
+    """先序遍历节点及其所有后代"""
+    yield node
+    children = node.values() if isinstance(node, dict) else node if isinstance(node, list) else ()
+    for child in children:
+        yield from _walk(child)
+
+
+def _apply_step(step: tuple, node: Any) -> List[Any]:
+    """对单个节点应用一个步骤，返回匹配的子节点"""
+    kind = step[0]
+    if kind == 'key':
+        return [node[step[1]]] if isinstance(node, dict) and step[1] in node else []
+    if kind == 'index':
+        if isinstance(node, list) and -len(node) <= step[1] < len(node):
+            return [node[step[1]]]
+        return []
+    if kind == 'slice':
+        return node[step[1]] if isinstance(node, list) else []
+    if kind == 'wildcard':
+        if isinstance(node, dict):
+            return list(node.values())
+        return list(node) if isinstance(node, list) else []
+    if kind == 'union':
+        return [m for s in step[1] for m in _apply_step(s, node)]
+    if kind == 'descend':
+        return [m for n in _walk(node) for m in _apply_step(step[1], n)]
+    return []
+
+
+def evaluate_jsonpath(data: Any, query: str) -> List[Any]:
+    """
+    对数据求值 JSONPath
+    
+    Returns:
+        所有匹配的值（按文档顺序）
+    
+    Raises:
+        ValueError: 表达式不合法
+    """
+    nodes = [data]
+    for step in _parse_jsonpath(query):
+        nodes = [m for node in nodes for m in _apply_step(step, node)]
+    return nodes
+
+
+class JsonQueryTool(Tool):
+    """
+    JSONPath 查询工具
+    
+    从较大的 JSON 数据中取出需要的部分，避免把整个工具输出放入上下文。
+    """
+    
+    def name(self) -> str:
+        return "json_query"
+    
+    def description(self) -> str:
+        return "Extract values from JSON data with a JSONPath query (e.g., '$.items[*].id')"
+    
+    def parameters(self) -> List[ToolParameter]:
+        return [
+            ToolParameter(
+                name="data",
+                type=["object", "array"],
+                description="JSON object or array to query",
+                required=True
+            ),
+            ToolParameter(
+                name="query",
+                type="string",
+                description="JSONPath expression, e.g. '$.store.book[0].title' or '$..price'",
+                required=True
+            )
+        ]
+    
+    def get_examples(self) -> List[Dict[str, Any]]:
+        return [
+            {"data": {"items": [{"id": 1}, {"id": 2}]}, "query": "$.items[*].id"},
+        ]
+    
+    def cacheable(self) -> bool:
+        return True
+    
+    def execute(self, data: Any, query: str, **kwargs) -> Dict[str, Any]:
+        """
+        执行 JSONPath 查询
+        
+        Raises:
+            ToolError: data 不是合法 JSON 或 query 不合法（INVALID_INPUT）
+        """
+        if isinstance(data, str):
+            try:
+                data = json.loads(data)
+            except json.JSONDecodeError as e:
+                raise ToolError(f"Invalid JSON: {e}", ToolErrorCode.INVALID_INPUT)
+        
+        try:
+            matches = evaluate_jsonpath(data, query)
+        except ValueError as e:
+            raise ToolError(f"Invalid JSONPath: {e}", ToolErrorCode.INVALID_INPUT,
+                            details={"query": query})
+        
+        return {
+            "success": True,
+            "data": matches,
+            "error": None,
+            "metadata": {"query": query, "count": len(matches)}
+        }
+
+
 class JsonTool(Tool):
     """JSON 处理工具"""
     
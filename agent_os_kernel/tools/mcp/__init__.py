@@ -1,6 +1,10 @@
 # MCP (Model Context Protocol) Tools Integration
 
-from .client import MCPClient
+from .client import MCPClient, MCPResource, MCPNotConnectedError, MCPServerConfig, MCPTransport
+from .transport import BaseTransport, HTTPTransport, StdioTransport
 from .registry import MCPToolRegistry
 
-__all__ = ['MCPClient', 'MCPToolRegistry']
+__all__ = [
+    'MCPClient', 'MCPResource', 'MCPNotConnectedError', 'MCPServerConfig', 'MCPTransport',
+    'BaseTransport', 'HTTPTransport', 'StdioTransport', 'MCPToolRegistry',
+]
@@ -1,10 +1,11 @@
 # -*- coding: utf-8 -*-
 """MCP Client - Model Context Protocol 客户端
 
-支持连接 MCP 服务器并调用工具。
+支持通过 STDIO 或 HTTP/SSE 连接 MCP 服务器、调用工具和读取资源。
 """
 
 import asyncio
+import itertools
 import json
 import logging
 from typing import Any, Dict, List, Optional, Callable
@@ -13,8 +14,15 @@ from enum import Enum
 import httpx
 import structlog
 
+from ..base import ToolError, ToolErrorCode
+from .transport import BaseTransport, HTTPTransport, StdioTransport
+
 logger = structlog.get_logger(__name__)
 
+# initialize 握手中发送的协议版本和客户端信息
+PROTOCOL_VERSION = "2024-11-05"
+CLIENT_INFO = {"name": "agent-os-kernel", "version": "0.2.0"}
+
 
 class MCPTransport(Enum):
     """MCP 传输方式"""
@@ -31,7 +39,11 @@ class MCPServerConfig:
     args: List[str] = field(default_factory=list)
     env: Dict[str, str] = field(default_factory=dict)
     url: Optional[str] = None  # HTTP/WebSocket 方式
+    headers: Dict[str, str] = field(default_factory=dict)  # HTTP 方式的附加请求头
+    transport: Optional[MCPTransport] = None  # None 表示按是否配置 url 自动选择
     timeout: float = 30.0
+    max_reconnect_attempts: int = 0  # 断线后自动重连的最大次数，0 表示不自动重连
+    reconnect_backoff: float = 0.5   # 首次重连前的等待（秒），之后每次翻倍
 
 
 @dataclass
@@ -43,6 +55,15 @@ class MCPToolDefinition:
     annotations: Optional[Dict[str, Any]] = None
 
 
+@dataclass
+class MCPResource:
+    """MCP 资源定义（文件、文档等）"""
+    uri: str
+    name: str
+    mime_type: Optional[str] = None
+    description: Optional[str] = None
+
+
 @dataclass
 class MCPToolCall:
     """MCP 工具调用请求"""
@@ -56,6 +77,63 @@ class MCPToolResult:
     content: List[Dict[str, Any]]
     is_error: bool = False
     error: Optional[str] = None
+    error_code: Optional[ToolErrorCode] = None
+    
+    @property
+    def retryable(self) -> bool:
+        """失败是否可以重试"""
+        return self.is_error and self.error_code is not None and self.error_code.retryable
+    
+    def to_tool_error(self) -> Optional[ToolError]:
+        """失败时转换为 ToolError，成功时返回 None"""
+        if not self.is_error:
+            return None
+        return ToolError(self.error or "MCP tool call failed",
+                         self.error_code or ToolErrorCode.EXECUTION_FAILED)
+
+
+# JSON-RPC 错误码到工具错误码的映射
+_JSONRPC_ERROR_CODES = {
+    -32700: ToolErrorCode.INVALID_INPUT,     # Parse error
+    -32600: ToolErrorCode.INVALID_INPUT,     # Invalid request
+    -32601: ToolErrorCode.NOT_FOUND,         # Method not found
+    -32602: ToolErrorCode.INVALID_INPUT,     # Invalid params
+    -32603: ToolErrorCode.EXECUTION_FAILED,  # Internal error
+}
+
+
+def _classify_exception(exc: Exception) -> ToolErrorCode:
+    """将请求异常归类为工具错误码"""
+    if isinstance(exc, (httpx.TimeoutException, asyncio.TimeoutError)):
+        return ToolErrorCode.TIMEOUT
+    if isinstance(exc, httpx.HTTPStatusError):
+        status = exc.response.status_code
+        if status in (401, 403):
+            return ToolErrorCode.PERMISSION_DENIED
+        if status == 404:
+            return ToolErrorCode.NOT_FOUND
+        if status == 429 or status >= 500:
+            return ToolErrorCode.SERVICE_UNAVAILABLE
+        return ToolErrorCode.INVALID_INPUT
+    if isinstance(exc, (httpx.ConnectError, ConnectionError, EOFError)):
+        return ToolErrorCode.SERVICE_UNAVAILABLE
+    return ToolError.from_exception(exc).code
+
+
+def _is_transport_failure(exc: Exception) -> bool:
+    """连接已断开（EOF、管道破裂、连接被拒绝等），而不是单次请求失败"""
+    if isinstance(exc, httpx.TimeoutException):
+        return False
+    return isinstance(exc, (httpx.TransportError, ConnectionError, EOFError))
+
+
+class MCPNotConnectedError(ToolError, RuntimeError):
+    """MCP 服务器未连接（或连接已断开且重连失败）"""
+    
+    def __init__(self, server_name: str):
+        super().__init__(f"Not connected to MCP server: {server_name}",
+                         ToolErrorCode.SERVICE_UNAVAILABLE,
+                         details={"server": server_name})
 
 
 class MCPClient:
@@ -63,67 +141,193 @@ class MCPClient:
     
     def __init__(self, config: MCPServerConfig):
         self.config = config
-        self._transport = None
-        self._http_client: Optional[httpx.AsyncClient] = None
+        self._transport: Optional[BaseTransport] = None
         self._connected = False
         self._tools: Dict[str, MCPToolDefinition] = {}
+        self._resources: Dict[str, MCPResource] = {}
+        self._request_ids = itertools.count(1)
+        self._supervised = False  # 连接成功后为 True，主动 close 后为 False
+        self.reconnect_count = 0
+    
+    @property
+    def connected(self) -> bool:
+        """是否已连接"""
+        return self._connected
     
     async def connect(self) -> bool:
         """连接到 MCP 服务器"""
         try:
-            if self.config.url:
-                # HTTP/WebSocket 方式
-                self._http_client = httpx.AsyncClient(timeout=self.config.timeout)
-                logger.info(f"Connected to MCP server: {self.config.name}")
-            else:
-                # STDIO 方式 (需要实现进程管理)
-                logger.info(f"STDIO server not implemented yet: {self.config.name}")
+            transport = self._create_transport()
+            if transport is None:
                 return False
+            await transport.start()
+            self._transport = transport
+            logger.info(f"Connected to MCP server: {self.config.name} "
+                        f"({self.transport_type.value})")
             
-            self._connected = True
+            # 握手完成之后会话才可用
             await self._initialize()
+            self._connected = True
+            self._supervised = True
             return True
         except Exception as e:
+            self._connected = False
+            await self._close_transport()
             logger.error(f"Failed to connect to MCP server: {e}")
             return False
     
+    @property
+    def transport_type(self) -> MCPTransport:
+        """实际使用的传输方式"""
+        if self.config.transport is not None:
+            return self.config.transport
+        return MCPTransport.HTTP if self.config.url else MCPTransport.STDIO
+    
+    def _create_transport(self) -> Optional[BaseTransport]:
+        """按配置创建传输层，配置不完整或不支持时返回 None"""
+        kind = self.transport_type
+        if kind == MCPTransport.HTTP:
+            if not self.config.url:
+                logger.error(f"MCP server {self.config.name}: HTTP transport requires url")
+                return None
+            return HTTPTransport(self.config.url, self.config.headers, self.config.timeout)
+        if kind == MCPTransport.STDIO:
+            if not self.config.command:
+                logger.error(f"MCP server {self.config.name}: STDIO transport requires command")
+                return None
+            return StdioTransport(self.config.command, self.config.args,
+                                  self.config.env, self.config.timeout)
+        logger.error(f"Unsupported MCP transport: {kind.value}")
+        return None
+    
+    async def _close_transport(self):
+        """关闭传输层，忽略关闭时的错误"""
+        if self._transport is not None:
+            try:
+                await self._transport.close()
+            except Exception as e:
+                logger.warning(f"Error closing MCP transport: {e}")
+            self._transport = None
+    
+    async def _ensure_connected(self):
+        """
+        确认连接可用；连接断开且配置了自动重连时先尝试重连
+        
+        Raises:
+            MCPNotConnectedError: 未连接且无法重连
+        """
+        if self._connected:
+            return
+        if self._supervised and self.config.max_reconnect_attempts > 0:
+            if await self.reconnect():
+                return
+        raise MCPNotConnectedError(self.config.name)
+    
+    def _mark_disconnected(self, reason: Exception):
+        """检测到连接断开，标记为未连接"""
+        if self._connected:
+            logger.warning(f"MCP server {self.config.name} disconnected: {reason}")
+        self._connected = False
+    
+    async def reconnect(self) -> bool:
+        """
+        重新连接服务器
+        
+        最多尝试 max_reconnect_attempts 次，每次失败后按指数退避等待。
+        重连成功后重新执行 initialize 握手并重新发现工具。
+        
+        Returns:
+            是否重连成功
+        """
+        attempts = max(1, self.config.max_reconnect_attempts)
+        for attempt in range(attempts):
+            await self._close_transport()
+            
+            if await self.connect():
+                self.reconnect_count += 1
+                logger.info(f"Reconnected to MCP server {self.config.name} "
+                            f"(attempt {attempt + 1})")
+                await self.list_tools()
+                return True
+            
+            if attempt + 1 < attempts:
+                await asyncio.sleep(self.config.reconnect_backoff * (2 ** attempt))
+        
+        logger.error(f"Failed to reconnect to MCP server {self.config.name} "
+                     f"after {attempts} attempts")
+        return False
+    
     async def _initialize(self):
-        """初始化连接"""
-        if self._http_client:
-            # 发送 initialize 请求
-            response = await self._send_request("initialize", {
-                "protocolVersion": "2024-11-05",
-                "capabilities": {}
-            })
-            logger.info(f"MCP Server {self.config.name} initialized")
+        """
+        初始化连接（initialize 握手）
+        
+        发送协议版本和客户端信息；服务器接受后发送 notifications/initialized 通知。
+        
+        Raises:
+            ToolError: 服务器拒绝 initialize 请求
+        """
+        response = await self._send_request("initialize", {
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": CLIENT_INFO
+        })
+        rpc_error = response.get("error")
+        if rpc_error:
+            raise ToolError(
+                f"MCP server {self.config.name} rejected initialize: "
+                f"{rpc_error.get('message', 'unknown error')}",
+                _JSONRPC_ERROR_CODES.get(rpc_error.get("code"), ToolErrorCode.EXECUTION_FAILED),
+                details={"server": self.config.name, "rpc_error": rpc_error}
+            )
+        await self._send_notification("notifications/initialized", {})
+        logger.info(f"MCP Server {self.config.name} initialized")
+    
+    async def _send_notification(self, method: str, params: Dict[str, Any]):
+        """发送通知到 MCP 服务器（不等待响应）"""
+        if self._transport is None:
+            raise MCPNotConnectedError(self.config.name)
+        
+        try:
+            await self._transport.notify({"jsonrpc": "2.0", "method": method, "params": params})
+        except Exception as e:
+            if _is_transport_failure(e):
+                self._mark_disconnected(e)
+            logger.error(f"MCP notification failed: {e}")
+            raise
     
     async def _send_request(self, method: str, params: Dict[str, Any]) -> Dict[str, Any]:
         """发送请求到 MCP 服务器"""
-        if not self._http_client:
-            raise RuntimeError("Not connected to MCP server")
+        if self._transport is None:
+            raise MCPNotConnectedError(self.config.name)
         
+        request_id = next(self._request_ids)
         payload = {
             "jsonrpc": "2.0",
-            "id": 1,
+            "id": request_id,
             "method": method,
             "params": params
         }
         
         try:
-            response = await self._http_client.post(
-                self.config.url,
-                json=payload
-            )
-            response.raise_for_status()
-            return response.json()
+            data = await self._transport.send(payload)
         except Exception as e:
+            if _is_transport_failure(e):
+                self._mark_disconnected(e)
             logger.error(f"MCP request failed: {e}")
             raise
+        
+        # 按 JSON-RPC id 关联请求和响应
+        if data.get("id") is not None and data.get("id") != request_id:
+            raise ToolError(
+                f"Mismatched JSON-RPC response id: expected {request_id}, got {data.get('id')}",
+                ToolErrorCode.EXECUTION_FAILED,
+                details={"method": method}
+            )
+        return data
     
     async def list_tools(self) -> List[MCPToolDefinition]:
         """列出可用工具"""
-        if not self._connected:
-            raise RuntimeError("Not connected to MCP server")
+        await self._ensure_connected()
         
         try:
             response = await self._send_request("tools/list", {})
@@ -145,8 +349,7 @@ class MCPClient:
     
     async def call_tool(self, call: MCPToolCall) -> MCPToolResult:
         """调用工具"""
-        if not self._connected:
-            raise RuntimeError("Not connected to MCP server")
+        await self._ensure_connected()
         
         try:
             response = await self._send_request("tools/call", {
@@ -154,28 +357,93 @@ class MCPClient:
                 "arguments": call.arguments
             })
             
+            rpc_error = response.get("error")
+            if rpc_error:
+                return MCPToolResult(
+                    content=[],
+                    is_error=True,
+                    error=rpc_error.get("message", "MCP request failed"),
+                    error_code=_JSONRPC_ERROR_CODES.get(
+                        rpc_error.get("code"), ToolErrorCode.EXECUTION_FAILED)
+                )
+            
             result = response.get("result", {})
             content = result.get("content", [])
+            is_error = result.get("isError", False)
             
             return MCPToolResult(
                 content=content,
-                is_error=result.get("isError", False),
-                error=result.get("error", {}).get("message") if result.get("isError") else None
+                is_error=is_error,
+                error=result.get("error", {}).get("message") if is_error else None,
+                error_code=ToolErrorCode.EXECUTION_FAILED if is_error else None
             )
         except Exception as e:
             logger.error(f"Tool call failed: {e}")
             return MCPToolResult(
                 content=[],
                 is_error=True,
-                error=str(e)
+                error=str(e),
+                error_code=_classify_exception(e)
             )
     
+    async def list_resources(self) -> List[MCPResource]:
+        """列出可用资源（resources/list）"""
+        await self._ensure_connected()
+        
+        try:
+            response = await self._send_request("resources/list", {})
+            
+            self._resources = {}
+            for data in response.get("result", {}).get("resources", []):
+                resource = MCPResource(
+                    uri=data["uri"],
+                    name=data.get("name", data["uri"]),
+                    mime_type=data.get("mimeType"),
+                    description=data.get("description")
+                )
+                self._resources[resource.uri] = resource
+            
+            return list(self._resources.values())
+        except Exception as e:
+            logger.error(f"Failed to list resources: {e}")
+            return []
+    
+    async def read_resource(self, uri: str) -> Dict[str, Any]:
+        """
+        读取资源（resources/read）
+        
+        Args:
+            uri: 资源 URI
+        
+        Returns:
+            服务器返回的 result，包含 contents 列表（每项带 text 或 blob）
+        
+        Raises:
+            ToolError: 请求失败或服务器返回错误
+        """
+        await self._ensure_connected()
+        
+        try:
+            response = await self._send_request("resources/read", {"uri": uri})
+        except ToolError:
+            raise
+        except Exception as e:
+            raise ToolError(str(e), _classify_exception(e), details={"uri": uri})
+        
+        rpc_error = response.get("error")
+        if rpc_error:
+            raise ToolError(
+                rpc_error.get("message", "MCP request failed"),
+                _JSONRPC_ERROR_CODES.get(rpc_error.get("code"), ToolErrorCode.EXECUTION_FAILED),
+                details={"uri": uri}
+            )
+        return response.get("result", {})
+    
     async def close(self):
         """关闭连接"""
         self._connected = False
-        if self._http_client:
-            await self._http_client.aclose()
-            self._http_client = None
+        self._supervised = False
+        await self._close_transport()
         logger.info(f"Disconnected from MCP server: {self.config.name}")
 
 
@@ -226,6 +494,24 @@ class MCPManager:
             raise ValueError(f"MCP server not found: {server_name}")
         return await self._clients[server_name].call_tool(call)
     
+    async def list_resources(self, server_name: Optional[str] = None) -> Dict[str, List[MCPResource]]:
+        """列出所有资源"""
+        resources = {}
+        if server_name:
+            if server_name in self._clients:
+                resources[server_name] = await self._clients[server_name].list_resources()
+        else:
+            for name, client in self._clients.items():
+                if client._connected:
+                    resources[name] = await client.list_resources()
+        return resources
+    
+    async def read_resource(self, server_name: str, uri: str) -> Dict[str, Any]:
+        """读取资源"""
+        if server_name not in self._clients:
+            raise ValueError(f"MCP server not found: {server_name}")
+        return await self._clients[server_name].read_resource(uri)
+    
     async def close_all(self):
         """关闭所有连接"""
         for client in self._clients.values():
@@ -8,7 +8,9 @@ import logging
 from typing import Any, Dict, List, Optional
 from dataclasses import dataclass
 
-from .client import MCPManager, MCPServerConfig, MCPToolCall, MCPToolResult
+from .client import (
+    MCPManager, MCPServerConfig, MCPToolCall, MCPToolResult, MCPResource, MCPTransport
+)
 from ..base import Tool, ToolParameter
 from ..registry import ToolRegistry
 
@@ -31,15 +33,19 @@ class MCPToolRegistry:
         self._tool_registry = tool_registry
         self._mcp_manager = MCPManager()
         self._wrapped_tools: Dict[str, MCPToolWrapper] = {}
+        self._resources: Dict[str, str] = {}  # uri -> server_name
     
-    def add_server(self, name: str, command: str, args: List[str] = None, env: Dict[str, str] = None, url: str = None) -> bool:
-        """添加 MCP 服务器"""
+    def add_server(self, name: str, command: str, args: List[str] = None, env: Dict[str, str] = None, url: str = None,
+                   headers: Dict[str, str] = None, transport: Optional[MCPTransport] = None) -> bool:
+        """添加 MCP 服务器（本地命令走 STDIO，url 走 HTTP/SSE）"""
         config = MCPServerConfig(
             name=name,
             command=command or "",
             args=args or [],
             env=env or {},
-            url=url
+            url=url,
+            headers=headers or {},
+            transport=transport
         )
         return self._mcp_manager.add_server(name, config)
     
@@ -132,6 +138,61 @@ class MCPToolRegistry:
                 'error': str(e)
             }
     
+    async def discover_resources(self, server_name: Optional[str] = None) -> List[MCPResource]:
+        """
+        发现 MCP 服务器暴露的资源
+        
+        Args:
+            server_name: 只查询该服务器，None 表示所有已连接的服务器
+        
+        Returns:
+            发现的资源列表
+        """
+        found = []
+        resources_by_server = await self._mcp_manager.list_resources(server_name)
+        for name, resources in resources_by_server.items():
+            for resource in resources:
+                self._resources[resource.uri] = name
+                found.append(resource)
+        logger.info(f"Discovered {len(found)} MCP resources")
+        return found
+    
+    async def read_resource(self, uri: str) -> Dict[str, Any]:
+        """
+        读取已发现的资源
+        
+        Raises:
+            ValueError: 资源未被发现
+            ToolError: 读取失败
+        """
+        if uri not in self._resources:
+            raise ValueError(f"Resource not found: {uri}")
+        return await self._mcp_manager.read_resource(self._resources[uri], uri)
+    
+    async def load_resource(self, context_manager, agent_pid: str, uri: str,
+                            importance: float = 0.6) -> str:
+        """
+        读取资源并作为长期记忆页（memory）放入 Agent 上下文
+        
+        Args:
+            context_manager: 上下文管理器
+            agent_pid: Agent 进程 ID
+            uri: 资源 URI
+            importance: 页面重要性
+        
+        Returns:
+            新页面的 ID
+        """
+        result = await self.read_resource(uri)
+        texts = [
+            item["text"] for item in result.get("contents", [])
+            if item.get("text") is not None
+        ]
+        content = f"[Resource {uri}]\n" + "\n".join(texts)
+        return context_manager.allocate_page(
+            agent_pid, content, importance=importance, page_type="memory"
+        )
+    
     async def health_check(self) -> Dict[str, bool]:
         """健康检查"""
         return await self._mcp_manager.health_check()
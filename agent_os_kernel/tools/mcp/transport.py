@@ -0,0 +1,193 @@
+# -*- coding: utf-8 -*-
+"""MCP Transport - MCP 传输层
+
+不同传输方式的 JSON-RPC 消息分帧不同：
+- StdioTransport: 子进程 stdin/stdout，每行一条 JSON 消息
+- HTTPTransport: HTTP POST，响应为 JSON 或 SSE 事件流（text/event-stream）
+
+MCPClient 只依赖 BaseTransport 接口，负责构造请求和按 id 关联响应。
+"""
+
+import asyncio
+import json
+import os
+from abc import ABC, abstractmethod
+from typing import Any, Dict, List, Optional
+
+import httpx
+import structlog
+
+logger = structlog.get_logger(__name__)
+
+
+class BaseTransport(ABC):
+    """MCP 传输接口"""
+
+    @abstractmethod
+    async def start(self):
+        """建立连接（启动子进程或创建 HTTP 客户端）"""
+        pass
+
+    @abstractmethod
+    async def send(self, payload: Dict[str, Any]) -> Dict[str, Any]:
+        """
+        发送一条 JSON-RPC 请求并返回对应的响应
+
+        Raises:
+            EOFError / ConnectionError: 连接已断开
+        """
+        pass
+
+    @abstractmethod
+    async def notify(self, payload: Dict[str, Any]):
+        """
+        发送一条 JSON-RPC 通知（没有 id，不等待响应）
+
+        Raises:
+            EOFError / ConnectionError: 连接已断开
+        """
+        pass
+
+    @abstractmethod
+    async def close(self):
+        """关闭连接"""
+        pass
+
+
+class StdioTransport(BaseTransport):
+    """
+    STDIO 传输
+
+    启动 MCP 服务器子进程，通过 stdin 写入、stdout 读取换行分隔的 JSON 消息。
+    读取时跳过服务器主动发出的通知（没有 id 或 id 不匹配的消息）。
+    """
+
+    def __init__(self, command: str, args: Optional[List[str]] = None,
+                 env: Optional[Dict[str, str]] = None, timeout: float = 30.0):
+        self.command = command
+        self.args = args or []
+        self.env = env or {}
+        self.timeout = timeout
+        self._process: Optional[asyncio.subprocess.Process] = None
+        self._lock = asyncio.Lock()
+
+    async def start(self):
+        self._process = await asyncio.create_subprocess_exec(
+            self.command, *self.args,
+            stdin=asyncio.subprocess.PIPE,
+            stdout=asyncio.subprocess.PIPE,
+            env={**os.environ, **self.env}
+        )
+        logger.info(f"Started MCP server process: {self.command} (pid {self._process.pid})")
+
+    async def send(self, payload: Dict[str, Any]) -> Dict[str, Any]:
+        if self._process is None or self._process.returncode is not None:
+            raise EOFError(f"MCP server process is not running: {self.command}")
+
+        async with self._lock:
+            self._process.stdin.write((json.dumps(payload) + "\n").encode())
+            await self._process.stdin.drain()
+
+            while True:
+                line = await asyncio.wait_for(self._process.stdout.readline(), self.timeout)
+                if not line:
+                    raise EOFError(f"MCP server closed stdout: {self.command}")
+                try:
+                    message = json.loads(line)
+                except json.JSONDecodeError:
+                    logger.warning(f"Ignoring non-JSON output from MCP server: {line[:200]!r}")
+                    continue
+                if message.get("id") == payload.get("id"):
+                    return message
+
+    async def notify(self, payload: Dict[str, Any]):
+        if self._process is None or self._process.returncode is not None:
+            raise EOFError(f"MCP server process is not running: {self.command}")
+
+        async with self._lock:
+            self._process.stdin.write((json.dumps(payload) + "\n").encode())
+            await self._process.stdin.drain()
+
+    async def close(self):
+        if self._process is None:
+            return
+        if self._process.returncode is None:
+            self._process.terminate()
+            try:
+                await asyncio.wait_for(self._process.wait(), 5.0)
+            except asyncio.TimeoutError:
+                self._process.kill()
+        self._process = None
+
+
+class HTTPTransport(BaseTransport):
+    """
+    HTTP/SSE 传输
+
+    每条请求以 POST 发送；服务器可以直接返回 JSON，也可以返回 SSE 事件流，
+    此时取 id 与请求匹配的那条消息。服务器返回的 Mcp-Session-Id 会在后续
+    请求中带上。
+    """
+
+    SESSION_HEADER = "Mcp-Session-Id"
+
+    def __init__(self, url: str, headers: Optional[Dict[str, str]] = None,
+                 timeout: float = 30.0):
+        self.url = url
+        self.headers = headers or {}
+        self.timeout = timeout
+        self._client: Optional[httpx.AsyncClient] = None
+        self._session_id: Optional[str] = None
+
+    async def start(self):
+        self._client = httpx.AsyncClient(timeout=self.timeout)
+
+    async def send(self, payload: Dict[str, Any]) -> Dict[str, Any]:
+        response = await self._post(payload)
+        if response.headers.get("content-type", "").startswith("text/event-stream"):
+            return self._parse_sse(response.text, payload.get("id"))
+        return response.json()
+
+    async def notify(self, payload: Dict[str, Any]):
+        # 服务器以 202 Accepted 确认通知，没有响应体
+        await self._post(payload)
+
+    async def _post(self, payload: Dict[str, Any]) -> httpx.Response:
+        """POST 一条消息，记录服务器返回的会话 ID"""
+        if self._client is None:
+            raise ConnectionError(f"HTTP transport not started: {self.url}")
+
+        headers = {"Accept": "application/json, text/event-stream", **self.headers}
+        if self._session_id:
+            headers[self.SESSION_HEADER] = self._session_id
+
+        response = await self._client.post(self.url, json=payload, headers=headers)
+        response.raise_for_status()
+
+        session_id = response.headers.get(self.SESSION_HEADER)
+        if session_id:
+            self._session_id = session_id
+        return response
+
+    @staticmethod
+    def _parse_sse(body: str, request_id: Any) -> Dict[str, Any]:
+        """从 SSE 事件流中取出与请求 id 匹配的 JSON-RPC 消息"""
+        for event in body.replace("\r\n", "\n").split("\n\n"):
+            data = "\n".join(
+                line[5:].lstrip() for line in event.split("\n")
+                if line.startswith("data:")
+            )
+            if not data:
+                continue
+            try:
+                message = json.loads(data)
+            except json.JSONDecodeError:
+                continue
+            if message.get("id") == request_id:
+                return message
+        raise EOFError(f"SSE stream ended without a response to request {request_id}")
+
+    async def close(self):
+        if self._client is not None:
+            await self._client.aclose()
+            self._client = None
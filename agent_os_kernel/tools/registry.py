@@ -5,25 +5,86 @@ Tool Registry - 工具注册表
 管理所有可用工具的注册和发现
 """
 
+import copy
+import hashlib
+import json
+import time
 import logging
-from typing import Dict, List, Optional, Any
+from dataclasses import dataclass
+from typing import Dict, List, Optional, Any, TYPE_CHECKING
 
-from .base import Tool
+from .base import Tool, ToolError, ToolErrorCode
+from ..core.cache_utils import LRUCache
+from ..core.metrics import MetricsCollector
+
+if TYPE_CHECKING:
+    from ..core.security import SandboxManager
 
 
 logger = logging.getLogger(__name__)
 
 
+@dataclass
+class ToolStats:
+    """单个工具的执行统计"""
+    name: str
+    calls: int = 0
+    errors: int = 0
+    total_latency: float = 0.0
+    
+    @property
+    def avg_latency(self) -> float:
+        """平均耗时（秒）"""
+        return self.total_latency / self.calls if self.calls else 0.0
+    
+    @property
+    def error_rate(self) -> float:
+        """失败比例"""
+        return self.errors / self.calls if self.calls else 0.0
+    
+    def to_dict(self) -> Dict[str, Any]:
+        return {
+            'name': self.name,
+            'calls': self.calls,
+            'errors': self.errors,
+            'total_latency': self.total_latency,
+            'avg_latency': self.avg_latency,
+            'error_rate': self.error_rate,
+        }
+
+
 class ToolRegistry:
     """
     工具注册表
     
-    管理工具的生命周期和访问
+    管理工具的生命周期和访问。启用结果缓存后，cacheable() 返回 True 的工具
+    以“工具名 + 规范化参数”为键缓存成功结果，其余工具总是直接执行。
     """
     
-    def __init__(self):
+    METRIC_CALLS = "tool_calls_total"
+    METRIC_ERRORS = "tool_errors_total"
+    METRIC_LATENCY = "tool_latency_seconds"
+    
+    def __init__(self, enable_cache: bool = False, cache_size: int = 256,
+                 default_cache_ttl: float = 300.0,
+                 metrics: Optional[MetricsCollector] = None,
+                 security: Optional['SandboxManager'] = None):
+        """
+        Args:
+            enable_cache: 是否缓存可缓存工具的结果
+            cache_size: 最多缓存的结果数
+            default_cache_ttl: 工具未指定 cache_ttl() 时的缓存有效期（秒）
+            metrics: 记录调用次数、失败次数和耗时的指标收集器，None 时使用独立实例
+            security: 沙箱管理器；execute_as 在分发前按 Agent 的沙箱策略检查工具权限
+        """
         self.tools: Dict[str, Tool] = {}
+        self.metrics = metrics or MetricsCollector()
+        self.security = security
         self.categories: Dict[str, List[str]] = {}
+        self.default_cache_ttl = default_cache_ttl
+        self._cache: Optional[LRUCache] = LRUCache(max_size=cache_size) if enable_cache else None
+        self._cache_hits = 0
+        self._cache_misses = 0
         logger.debug("ToolRegistry initialized")
     
     def register(self, tool: Tool, category: str = "general"):
@@ -52,6 +113,7 @@ class ToolRegistry:
         """注销工具"""
         if name in self.tools:
             del self.tools[name]
+            self.clear_cache(name)
             
             # 从分类中移除
             for category, tools in self.categories.items():
@@ -127,40 +189,133 @@ class ToolRegistry:
             **kwargs: 工具参数
         
         Returns:
-            执行结果
+            执行结果；失败时包含 error_code（ToolErrorCode 取值）和 retryable
         """
         tool = self.get(name)
         
         if not tool:
-            return {
-                "success": False,
-                "data": None,
-                "error": f"Tool '{name}' not found",
-                "metadata": {}
-            }
+            return ToolError(f"Tool '{name}' not found", ToolErrorCode.NOT_FOUND).to_dict()
+        
+        start = time.perf_counter()
+        result = self._run(tool, name, kwargs)
+        self._record_metrics(name, time.perf_counter() - start, self._is_success(result))
+        return result
+    
+    def execute_as(self, agent_pid: str, name: str, **kwargs) -> Dict[str, Any]:
+        """
+        以指定 Agent 的身份执行工具
+        
+        沙箱策略禁止该工具时不会分发，直接返回 PERMISSION_DENIED 错误。
         
+        Args:
+            agent_pid: 调用工具的 Agent PID
+            name: 工具名称
+            **kwargs: 工具参数
+        
+        Returns:
+            执行结果（同 execute）
+        """
+        if self.security is not None and not self.security.validate_tool_use(agent_pid, name):
+            return ToolError(f"Tool '{name}' is not allowed for agent {agent_pid}",
+                             ToolErrorCode.PERMISSION_DENIED).to_dict()
+        return self.execute(name, **kwargs)
+    
+    def _run(self, tool: Tool, name: str, kwargs: Dict[str, Any]) -> Any:
+        """验证参数、查询缓存并执行工具"""
         # 验证参数
         valid, error = tool.validate_params(**kwargs)
         if not valid:
-            return {
-                "success": False,
-                "data": None,
-                "error": error,
-                "metadata": {}
-            }
+            return ToolError(error, ToolErrorCode.INVALID_INPUT).to_dict()
+        
+        cache_key = self._cache_key(tool, name, kwargs)
+        if cache_key is not None:
+            entry = self._cache.get(cache_key)
+            if entry is not None and entry[1] > time.time():
+                self._cache_hits += 1
+                # 返回副本，调用方修改结果不会污染缓存
+                return copy.deepcopy(entry[0])
+            self._cache_misses += 1
         
         # 执行工具
         try:
             result = tool.execute(**kwargs)
+            if cache_key is not None and self._is_success(result):
+                ttl = tool.cache_ttl()
+                if ttl is None:
+                    ttl = self.default_cache_ttl
+                self._cache.set(cache_key, (copy.deepcopy(result), time.time() + ttl))
             return result
+        except ToolError as e:
+            logger.warning(f"Tool '{name}' failed ({e.code.value}): {e.message}")
+            return e.to_dict()
         except Exception as e:
             logger.exception(f"Error executing tool '{name}'")
-            return {
-                "success": False,
-                "data": None,
-                "error": str(e),
-                "metadata": {"exception": type(e).__name__}
-            }
+            return ToolError.from_exception(e).to_dict()
+    
+    def _record_metrics(self, name: str, elapsed: float, success: bool):
+        """记录一次工具调用的指标（经当前线程的累积器批量写入）"""
+        labels = {"tool": name}
+        batch = self.metrics.batch()
+        batch.counter(self.METRIC_CALLS, labels=labels)
+        if not success:
+            batch.counter(self.METRIC_ERRORS, labels=labels)
+        batch.histogram(self.METRIC_LATENCY, elapsed, labels=labels)
+    
+    def tool_stats(self) -> Dict[str, ToolStats]:
+        """
+        获取每个工具的执行统计
+        
+        Returns:
+            工具名 -> ToolStats，只包含至少被调用过一次的工具
+        """
+        stats = {}
+        for name in self.tools:
+            labels = {"tool": name}
+            calls = self.metrics.get(self.METRIC_CALLS, labels)
+            if calls is None:
+                continue
+            errors = self.metrics.get(self.METRIC_ERRORS, labels)
+            latency = self.metrics.get(self.METRIC_LATENCY, labels)
+            stats[name] = ToolStats(
+                name=name,
+                calls=int(calls.value),
+                errors=int(errors.value) if errors else 0,
+                total_latency=latency.sum_ if latency else 0.0,
+            )
+        return stats
+    
+    def _cache_key(self, tool: Tool, name: str, params: Dict[str, Any]) -> Optional[str]:
+        """
+        生成缓存键；工具不可缓存或参数无法规范化时返回 None
+        
+        规范化后的参数取哈希，参数中的大块数据（如 json_query 的 data）不会
+        原样留在缓存键里。
+        """
+        if self._cache is None or not tool.cacheable():
+            return None
+        try:
+            canonical = json.dumps(params, sort_keys=True, separators=(',', ':'))
+        except (TypeError, ValueError):
+            return None
+        return f"{name}:{hashlib.sha256(canonical.encode('utf-8')).hexdigest()}"
+    
+    @staticmethod
+    def _is_success(result: Any) -> bool:
+        """只缓存成功的结果，失败（尤其是可重试的失败）不应被缓存"""
+        if isinstance(result, dict):
+            return bool(result.get("success"))
+        return bool(getattr(result, "success", False))
+    
+    def clear_cache(self, name: Optional[str] = None):
+        """
+        清除结果缓存
+        
+        Args:
+            name: 只清除该工具的缓存，None 表示全部清除
+        """
+        if self._cache is None:
+            return
+        self._cache.clear(None if name is None else f"{name}:")
     
     def auto_discover_cli_tools(self):
         """
@@ -213,5 +368,9 @@ class ToolRegistry:
             'categories': {
                 cat: len(tools) 
                 for cat, tools in self.categories.items()
-            }
+            },
+            'cache_enabled': self._cache is not None,
+            'cache_size': self._cache.size if self._cache is not None else 0,
+            'cache_hits': self._cache_hits,
+            'cache_misses': self._cache_misses,
         }
@@ -0,0 +1,91 @@
+# -*- coding: utf-8 -*-
+"""
+ContextManager 基准测试 - 大量页面下的 get_agent_context
+
+构造一个拥有 5000 个页面的 Agent，对比两种收集方式：
+- full-scan: 先对全部页面排序并对全部内容分词（更新 KV-Cache token 集合），
+  再按 max_pages / token_budget 裁剪（旧实现）
+- select-first: 只按页面元数据排序和裁剪，仅对入选页面分词（当前实现）
+
+运行：
+    python benches/bench_context_manager.py [--pages 5000] [--iterations 20]
+"""
+
+import argparse
+import tracemalloc
+from typing import Callable, Dict, List
+
+from agent_os_kernel.core.benchmark import LatencyBenchmark, PerformanceReport
+from agent_os_kernel.core.context_manager import ContextManager, ContextPage
+
+AGENT = "bench-agent"
+
+
+def build_manager(num_pages: int) -> ContextManager:
+    """创建拥有 num_pages 个页面的上下文管理器（全部常驻内存）"""
+    cm = ContextManager(max_context_tokens=10 ** 9, dedup_pages=False)
+    for i in range(num_pages):
+        page_type = 'system' if i == 0 else ('task' if i == 1 else 'working')
+        content = f"page {i} " + " ".join(f"token{i}_{j}" for j in range(200))
+        cm.allocate_page(AGENT, content, importance=(i % 10) / 10, page_type=page_type)
+    return cm
+
+
+def full_scan(cm: ContextManager, max_pages: int = None,
+              token_budget: int = None) -> List[ContextPage]:
+    """旧实现：排序并对全部页面分词后再裁剪"""
+    pages = [cm.pages_in_memory[pid] for pid in cm.agent_pages[AGENT]]
+    pages = cm.kv_cache_optimizer.optimize_layout(pages)
+    cm.kv_cache_optimizer.update_previous_tokens(pages)
+    if max_pages:
+        pages = pages[:max_pages]
+    if token_budget is not None and sum(p.tokens for p in pages) > token_budget:
+        pages = cm._fit_by_priority(pages, token_budget)
+    return pages
+
+
+def select_first(cm: ContextManager, max_pages: int = None,
+                 token_budget: int = None) -> List[ContextPage]:
+    """当前实现"""
+    return cm._collect_context_pages(AGENT, max_pages, True, False, token_budget)[0]
+
+
+def peak_allocation(func: Callable[[], object]) -> int:
+    """单次调用的峰值内存分配（字节）"""
+    tracemalloc.start()
+    try:
+        func()
+        return tracemalloc.get_traced_memory()[1]
+    finally:
+        tracemalloc.stop()
+
+
+def run(num_pages: int, iterations: int) -> PerformanceReport:
+    cm = build_manager(num_pages)
+    budget = sum(p.tokens for p in list(cm.pages_in_memory.values())[:50])
+    scenarios: Dict[str, dict] = {
+        "max_pages=50": {"max_pages": 50},
+        f"token_budget={budget}": {"token_budget": budget},
+    }
+
+    report = PerformanceReport(f"get_agent_context with {num_pages} pages")
+    bench = LatencyBenchmark(warmup_iterations=2)
+    for scenario, kwargs in scenarios.items():
+        for name, impl in (("full-scan", full_scan), ("select-first", select_first)):
+            result = bench.measure(lambda: impl(cm, **kwargs), iterations)
+            report.add_latency_result(f"{scenario} {name}", result)
+            peak = peak_allocation(lambda: impl(cm, **kwargs))
+            report.add_section(f"{scenario} {name} memory", {"peak_alloc_kb": round(peak / 1024, 1)})
+    return report
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.split("\n")[1])
+    parser.add_argument("--pages", type=int, default=5000)
+    parser.add_argument("--iterations", type=int, default=20)
+    args = parser.parse_args()
+    print(run(args.pages, args.iterations).generate_text())
+
+
+if __name__ == "__main__":
+    main()
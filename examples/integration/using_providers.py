@@ -86,9 +86,9 @@ async def demo_kernel_with_mock():
     
     # 获取 Agent 信息
     agent = kernel.get_agent(agent_pid)
-    print(f"Agent 名称: {agent.get('name')}")
-    print(f"Agent 任务: {agent.get('task')}")
-    print(f"Agent 优先级: {agent.get('priority')}")
+    print(f"Agent 名称: {agent.name}")
+    print(f"Agent 任务: {agent.task}")
+    print(f"Agent 优先级: {agent.priority}")
     
     # 列出所有 Agent
     agents = kernel.list_agents()
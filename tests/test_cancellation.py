@@ -0,0 +1,70 @@
+"""测试取消令牌"""
+
+import asyncio
+import threading
+import time
+
+import pytest
+
+from agent_os_kernel.agents.base import BaseAgent, AgentConfig
+from agent_os_kernel.core.cancellation import CancellationToken
+from agent_os_kernel.core.exceptions import AgentCancelledError
+
+
+class SlowAgent(BaseAgent):
+    """每次思考都要很久的 Agent"""
+
+    @property
+    def agent_type(self) -> str:
+        return "slow"
+
+    async def _think(self, task):
+        await asyncio.sleep(30)
+        return {"stop": True}
+
+
+class TestCancellationToken:
+    """测试令牌状态与回调"""
+
+    def test_cancel_once(self):
+        token = CancellationToken()
+        calls = []
+        token.add_callback(lambda: calls.append(1))
+
+        assert token.cancel("stop") is True
+        assert token.cancel("again") is False
+        assert token.reason == "stop"
+        assert calls == [1]
+        with pytest.raises(AgentCancelledError):
+            token.raise_if_cancelled()
+
+    @pytest.mark.asyncio
+    async def test_race_interrupted_from_other_thread(self):
+        token = CancellationToken()
+        threading.Timer(0.05, token.cancel).start()
+        start = time.monotonic()
+
+        with pytest.raises(AgentCancelledError):
+            await token.race(asyncio.sleep(30))
+        assert time.monotonic() - start < 5
+
+    @pytest.mark.asyncio
+    async def test_agent_run_returns_cancelled(self):
+        token = CancellationToken()
+        agent = SlowAgent(AgentConfig(name="slow"))
+        asyncio.get_running_loop().call_later(0.05, token.cancel)
+
+        result = await agent.run("task", cancel_token=token)
+
+        assert result["success"] is False
+        assert result["error"] == "cancelled"
+        assert result["iterations"] == 1
+
+    @pytest.mark.asyncio
+    async def test_agent_run_without_iterations(self):
+        agent = SlowAgent(AgentConfig(name="slow", max_iterations=0))
+
+        result = await agent.run("task")
+
+        assert result["success"] is True
+        assert result["iterations"] == 0
@@ -0,0 +1,154 @@
+"""测试 Provider 熔断"""
+
+import pytest
+
+from agent_os_kernel.core.circuit_breaker import CircuitState
+from agent_os_kernel.core.clock import MockClock
+from agent_os_kernel.llm.circuit_breaker import CircuitBreakerProvider
+from agent_os_kernel.llm.fallback import FallbackProvider
+from agent_os_kernel.llm.mock_provider import MockProvider
+from agent_os_kernel.llm.provider import (
+    Message, ServerError, BadRequestError, CircuitOpenError
+)
+
+
+class FlakyProvider(MockProvider):
+    """按 errors 列表依次失败，之后成功的 Provider"""
+
+    def __init__(self, name, errors):
+        super().__init__()
+        self.PROVIDER_NAME = name
+        self.errors = list(errors)
+        self.calls = 0
+        self.set_delay(0)
+
+    async def chat(self, messages, **kwargs):
+        self.calls += 1
+        if self.errors:
+            raise self.errors.pop(0)
+        return await super().chat(messages, **kwargs)
+
+
+MESSAGES = [Message(role="user", content="hello")]
+
+
+class TestCircuitBreakerProvider:
+    """测试熔断状态转换"""
+
+    @pytest.mark.asyncio
+    async def test_opens_after_threshold_and_recovers(self):
+        clock = MockClock()
+        transitions = []
+        inner = FlakyProvider("primary", [ServerError("503")] * 3)
+        provider = CircuitBreakerProvider(
+            inner, failure_threshold=2, cooldown=10.0, clock=clock,
+            on_state_change=lambda old, new: transitions.append(new)
+        )
+
+        for _ in range(2):
+            with pytest.raises(ServerError):
+                await provider.chat(MESSAGES)
+        assert provider.state == CircuitState.OPEN
+
+        with pytest.raises(CircuitOpenError):
+            await provider.chat(MESSAGES)
+        assert inner.calls == 2
+
+        # 半开试探失败，重新打开
+        clock.advance(10)
+        assert provider.state == CircuitState.HALF_OPEN
+        with pytest.raises(ServerError):
+            await provider.chat(MESSAGES)
+        assert provider.state == CircuitState.OPEN
+
+        # 试探成功，关闭
+        clock.advance(10)
+        result = await provider.chat(MESSAGES)
+        assert result["content"]
+        assert provider.state == CircuitState.CLOSED
+        assert transitions == [CircuitState.OPEN, CircuitState.HALF_OPEN, CircuitState.OPEN,
+                               CircuitState.HALF_OPEN, CircuitState.CLOSED]
+        assert provider.get_stats()['rejected_requests'] == 1
+        assert provider.get_stats()['times_opened'] == 2
+
+    @pytest.mark.asyncio
+    async def test_bad_requests_do_not_trip(self):
+        inner = FlakyProvider("primary", [BadRequestError("400")] * 3)
+        provider = CircuitBreakerProvider(inner, failure_threshold=2, clock=MockClock())
+
+        for _ in range(3):
+            with pytest.raises(BadRequestError):
+                await provider.chat(MESSAGES)
+        assert provider.state == CircuitState.CLOSED
+        assert provider.consecutive_failures == 0
+
+    @pytest.mark.asyncio
+    async def test_fallback_skips_open_circuit(self):
+        inner = FlakyProvider("primary", [ServerError("503")] * 5)
+        secondary = FlakyProvider("secondary", [])
+        guarded = CircuitBreakerProvider(inner, failure_threshold=1, cooldown=60.0,
+                                         clock=MockClock())
+        provider = FallbackProvider([guarded, secondary])
+
+        await provider.chat(MESSAGES)
+        await provider.chat(MESSAGES)
+
+        assert inner.calls == 1
+        assert provider.served_counts["secondary"] == 2
+
+    @pytest.mark.asyncio
+    async def test_cancelled_trial_releases_half_open_slot(self):
+        import asyncio
+
+        class HangingProvider(FlakyProvider):
+            async def chat(self, messages, **kwargs):
+                if self.errors:
+                    return await super().chat(messages, **kwargs)
+                self.calls += 1
+                await asyncio.Event().wait()
+
+        clock = MockClock()
+        inner = HangingProvider("primary", [ServerError("503")])
+        provider = CircuitBreakerProvider(inner, failure_threshold=1, cooldown=10.0,
+                                          clock=clock)
+        with pytest.raises(ServerError):
+            await provider.chat(MESSAGES)
+
+        clock.advance(10)
+        trial = asyncio.ensure_future(provider.chat(MESSAGES))
+        await asyncio.sleep(0)
+        trial.cancel()
+        with pytest.raises(asyncio.CancelledError):
+            await trial
+
+        # 取消既不关闭也不重新打开熔断器，下一个请求可以继续试探
+        assert provider.state == CircuitState.HALF_OPEN
+        assert provider.consecutive_failures == 1
+        inner.errors = [ServerError("503")]
+        with pytest.raises(ServerError):
+            await provider.chat(MESSAGES)
+        assert provider.state == CircuitState.OPEN
+
+    @pytest.mark.asyncio
+    async def test_closed_stream_releases_half_open_slot(self):
+        clock = MockClock()
+        inner = FlakyProvider("primary", [ServerError("503")])
+        provider = CircuitBreakerProvider(inner, failure_threshold=1, cooldown=10.0,
+                                          clock=clock)
+        with pytest.raises(ServerError):
+            await provider.chat(MESSAGES)
+
+        clock.advance(10)
+        inner.set_response("hello", "one two three")
+        stream = provider.stream_complete(MESSAGES)
+        await stream.__anext__()
+        await stream.aclose()
+
+        assert provider.state == CircuitState.HALF_OPEN
+        chunks = [chunk async for chunk in provider.stream_complete(MESSAGES)]
+        assert chunks
+        assert provider.state == CircuitState.CLOSED
+
+    def test_rejects_invalid_threshold(self):
+        with pytest.raises(ValueError):
+            CircuitBreakerProvider(MockProvider(), failure_threshold=0)
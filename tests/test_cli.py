@@ -0,0 +1,69 @@
+"""测试命令行接口"""
+
+import json
+
+import pytest
+
+
+class TestCLIStorage:
+    """测试通过 --storage-url 在命令之间共享内核状态"""
+
+    def _run(self, capsys, *args):
+        from agent_os_kernel.cli.main import CLI
+        code = CLI().run(list(args))
+        out = capsys.readouterr().out
+        assert code == 0, out
+        return out
+
+    def test_create_list_and_ps(self, tmp_path, capsys):
+        url = f"file://{tmp_path}"
+        created = self._run(capsys, "--storage-url", url, "create", "-n", "Worker", "-t", "Do it")
+        pid = created.strip().split(": ")[1]
+
+        agents = json.loads(self._run(capsys, "--storage-url", url, "list", "--json"))
+        assert [(a["pid"], a["name"], a["task"]) for a in agents] == [(pid, "Worker", "Do it")]
+
+        table = self._run(capsys, "--storage-url", url, "ps").splitlines()
+        assert table[0].split() == ["PID", "Name", "State", "Prio", "Tokens", "Errors"]
+        assert table[1].split()[:2] == [pid[:8], "Worker"]
+
+    def test_checkpoint_and_restore(self, tmp_path, capsys):
+        url = f"file://{tmp_path}"
+        created = self._run(capsys, "-s", url, "create", "-n", "Worker", "-t", "Do it")
+        pid = created.strip().split(": ")[1]
+
+        checkpoint_id = self._run(capsys, "-s", url, "checkpoint", pid, "--desc", "manual").strip()
+        new_pid = self._run(capsys, "-s", url, "restore", checkpoint_id).strip()
+
+        agents = {a["pid"]: a for a in
+                  json.loads(self._run(capsys, "-s", url, "list", "--json"))}
+        assert set(agents) == {pid, new_pid}
+        assert agents[new_pid]["name"] == "Worker"
+        assert agents[new_pid]["task"] == "Do it"
+
+    def test_dump(self, tmp_path, capsys):
+        url = f"file://{tmp_path / 'data'}"
+        created = self._run(capsys, "-s", url, "create", "-n", "Worker", "-t", "Do it")
+        pid = created.strip().split(": ")[1]
+
+        snapshot = json.loads(self._run(capsys, "-s", url, "dump"))
+        assert [a["pid"] for a in snapshot["agents"]] == [pid]
+
+        output = tmp_path / "state.json"
+        assert self._run(capsys, "-s", url, "dump", "-o", str(output)).strip() == f"已写入: {output}"
+        assert json.loads(output.read_text(encoding="utf-8"))["agents"][0]["pid"] == pid
+
+    def test_errors_reported(self, tmp_path, capsys):
+        from agent_os_kernel.cli.main import CLI
+        url = f"file://{tmp_path}"
+
+        assert CLI().run(["-s", url, "checkpoint", "missing"]) == 1
+        assert "创建检查点失败" in capsys.readouterr().err
+        assert CLI().run(["-s", url, "restore", "00000000-0000-0000-0000-000000000000"]) == 1
+        assert "恢复失败" in capsys.readouterr().err
+        with pytest.raises(SystemExit):
+            CLI().run(["restore", "not-a-uuid"])
+
+    def test_default_kernel_is_in_memory(self, capsys):
+        self._run(capsys, "create", "-n", "Worker", "-t", "Do it")
+        assert json.loads(self._run(capsys, "list", "--json")) == []
@@ -2,9 +2,14 @@
 
 import pytest
 from agent_os_kernel.core.context_manager import (
-    ContextManager, ContextPage, PageStatus
+    ContextManager, ContextPage, PageStatus, ContentType, OverflowStrategy,
+    ContextSnapshot
 )
-from agent_os_kernel.core.exceptions import ContextOverflowError
+from agent_os_kernel.core.exceptions import (
+    ContextOverflowError, ContextBudgetExceededError, ConfigurationError
+)
+from agent_os_kernel.core.tokenizer import HeuristicTokenizer
+from agent_os_kernel.core.clock import MockClock, SequentialIdGenerator
 
 
 class TestContextPage:
@@ -61,6 +66,13 @@ class TestContextPage:
         
         score = page.get_lru_score(current_time=1000)
         assert score > 0
+    
+    def test_legacy_page_defaults_to_text(self):
+        data = ContextPage(agent_pid="test", content="old page").to_dict()
+        del data['content_type']
+        
+        page = ContextPage.from_dict(data)
+        assert page.content_type == ContentType.TEXT
 
 
 class TestContextManager:
@@ -143,6 +155,51 @@ class TestContextManager:
         page = manager.access_page(page_id)
         assert page.importance_score == 0.9
     
+    def test_update_and_boost_importance_clamped(self):
+        manager = ContextManager(max_context_tokens=12, tokenizer=HeuristicTokenizer())
+        old = manager.allocate_page("a1", "old page with some words here", importance=0.1)
+        manager.allocate_page("a1", "newer page with more words", importance=0.5)
+        assert old in manager.swapped_pages
+        
+        assert manager.update_importance(old, 1.7) == 1.0
+        assert manager.swapped_pages[old].importance_score == 1.0
+        assert manager.boost_importance(old, -0.25) == 0.75
+        assert manager.boost_importance(old, -5) == 0.0
+        assert manager.update_importance("missing", 0.5) is None
+        assert manager.boost_importance("missing", 0.1) is None
+    
+    def test_eviction_drains_to_low_watermark(self):
+        manager = ContextManager(max_context_tokens=100, tokenizer=HeuristicTokenizer(),
+                                 eviction_high_watermark=0.8, eviction_low_watermark=0.5)
+        for i in range(6):
+            manager.allocate_page("a1", "word " * 10, importance=0.3)   # 13 tokens each
+        assert manager.current_usage == 78
+        assert manager.stats['swaps_out'] == 0
+        
+        manager.allocate_page("a1", "word " * 10, importance=0.3)
+        
+        # 一次性降到低水位线（50）以下，而不是只腾出 13 个 token
+        assert manager.current_usage == 39
+        assert manager.stats['swaps_out'] == 4
+    
+    def test_create_validates_config(self):
+        with pytest.raises(ConfigurationError):
+            ContextManager.create(max_context_tokens=0)
+        with pytest.raises(ConfigurationError):
+            ContextManager.create(max_context_tokens=100, per_agent_token_limit=200)
+        assert ContextManager.create(max_context_tokens=100).max_context_tokens == 100
+        
+        # 直接构造时修正不合法的参数
+        manager = ContextManager(max_context_tokens=100, per_agent_token_limit=200)
+        assert manager.per_agent_token_limit == 100
+        assert ContextManager(max_context_tokens=0).max_context_tokens == 1
+    
+    def test_invalid_watermarks_rejected(self):
+        with pytest.raises(ConfigurationError):
+            ContextManager(eviction_high_watermark=0.8, eviction_low_watermark=0.8)
+        with pytest.raises(ConfigurationError):
+            ContextManager(eviction_high_watermark=1.2, eviction_low_watermark=0.5)
+    
     def test_context_overflow_error(self):
         """测试上下文溢出错误"""
         manager = ContextManager(max_context_tokens=10)
@@ -166,6 +223,20 @@ class TestContextManager:
         assert "max_tokens" in stats
         assert stats["max_tokens"] == 1000
     
+    def test_collect_stats_matches_get_stats(self):
+        import dataclasses
+        import json
+        from agent_os_kernel.core.context_manager import ContextStats
+        manager = ContextManager(max_context_tokens=1000)
+        manager.allocate_page(agent_pid="a1", content="test", importance=0.5)
+        
+        stats = manager.collect_stats()
+        assert isinstance(stats, ContextStats)
+        assert stats.pages_in_memory == 1
+        assert manager.get_stats() == stats.to_dict()
+        assert list(manager.get_stats()) == [f.name for f in dataclasses.fields(ContextStats)]
+        json.dumps(manager.get_stats())
+    
     def test_token_estimation(self):
         manager = ContextManager(max_context_tokens=10000)
         
@@ -175,6 +246,182 @@ class TestContextManager:
         long_text = "word " * 100
         tokens2 = manager._estimate_tokens(long_text)
         assert tokens2 > tokens1
+    
+    def test_export_agent_pages_includes_swapped(self):
+        manager = ContextManager(max_context_tokens=10)
+        first = manager.allocate_page("agent1", "one two three four five", importance=0.1)
+        second = manager.allocate_page("agent1", "six seven eight nine ten", importance=0.1)
+        manager.allocate_page("agent2", "other agent page")
+        
+        assert first in manager.swapped_pages
+        exported = manager.export_agent_pages("agent1")
+        assert [p.page_id for p in exported] == [first, second]
+        assert manager.export_agent_pages("missing") == []
+    
+    def test_recent_accesses_ring_buffer(self):
+        manager = ContextManager(max_context_tokens=10, access_history_size=3)
+        first = manager.allocate_page("agent1", "one two three four five", importance=0.1)
+        second = manager.allocate_page("agent1", "six seven eight nine ten", importance=0.1)
+        
+        manager.access_page(second)
+        manager.access_page(first)   # 缺页，换入
+        manager.access_page(first)
+        manager.access_page(second)  # 已被换出，再次缺页
+        
+        records = manager.recent_accesses()
+        assert len(records) == 3
+        assert [(r.page_id, r.hit) for r in records] == [
+            (first, False), (first, True), (second, False)
+        ]
+        assert [r.page_id for r in manager.recent_accesses(limit=1)] == [second]
+    
+    def test_allocate_pages_batch(self):
+        manager = ContextManager(max_context_tokens=12, tokenizer=HeuristicTokenizer())
+        old = manager.allocate_page("agent0", "old page with some words here", importance=0.1)
+        
+        ids = manager.allocate_pages("agent1", [
+            ("system prompt text", 1.0, "system"),
+            ("current task text here", 0.9, "task"),
+        ])
+        
+        assert len(ids) == 2
+        assert all(pid in manager.pages_in_memory for pid in ids)
+        assert manager.agent_pages["agent1"] == ids
+        assert old in manager.swapped_pages
+        assert manager.current_usage == sum(manager.pages_in_memory[p].tokens for p in ids)
+    
+    def test_agent_token_usage(self):
+        manager = ContextManager(max_context_tokens=10000)
+        a1 = manager.allocate_page("agent1", "Hello world from agent one")
+        manager.allocate_page("agent1", "Another page")
+        manager.allocate_page("agent2", "Agent two content")
+        
+        # 换出的页面仍计入 Agent 占用
+        manager.pages_in_memory[a1].status = PageStatus.SWAPPED
+        manager.swapped_pages[a1] = manager.pages_in_memory.pop(a1)
+        
+        usage = manager.all_agent_usage()
+        assert usage["agent1"] == manager.agent_token_usage("agent1")
+        assert usage["agent1"] + usage["agent2"] == sum(
+            p.tokens for p in manager.export_agent_pages("agent1") + manager.export_agent_pages("agent2")
+        )
+        assert manager.agent_token_usage("unknown") == 0
+        assert "unknown" not in manager.agent_pages
+    
+    def test_per_agent_budget_evicts_own_pages(self):
+        manager = ContextManager(max_context_tokens=10000, per_agent_token_limit=8, tokenizer=HeuristicTokenizer())
+        other = manager.allocate_page("agent2", "other agent content here", importance=0.1)
+        low = manager.allocate_page("agent1", "low importance page", importance=0.2)
+        high = manager.allocate_page("agent1", "high importance page", importance=0.8)
+        
+        manager.allocate_page("agent1", "new page content", importance=0.5)
+        
+        assert low in manager.swapped_pages
+        assert high in manager.pages_in_memory
+        assert other in manager.pages_in_memory
+        assert manager._agent_memory_usage("agent1") <= 8
+    
+    def test_per_agent_budget_rejects(self):
+        manager = ContextManager(max_context_tokens=10000, per_agent_token_limit=3, tokenizer=HeuristicTokenizer())
+        with pytest.raises(ContextBudgetExceededError):
+            manager.allocate_page("agent1", "this content is far too long for the budget")
+        assert manager.current_usage == 0
+    
+    def test_rejected_allocation_keeps_existing_pages(self):
+        manager = ContextManager(max_context_tokens=10000, per_agent_token_limit=8, tokenizer=HeuristicTokenizer())
+        page_id = manager.allocate_page("agent1", "small page", importance=0.2)
+        
+        with pytest.raises(ContextBudgetExceededError):
+            manager.allocate_page("agent1", "this content is far too long for the budget")
+        
+        assert page_id in manager.pages_in_memory
+        assert not manager.swapped_pages
+    
+    def test_get_agent_context_top_k(self):
+        manager = ContextManager(max_context_tokens=10000)
+        user_ids = [manager.allocate_page("agent1", f"user message {i}", importance=0.5) for i in range(5)]
+        system = manager.allocate_page("agent1", "system prompt", importance=1.0, page_type="system")
+        tools = manager.allocate_page("agent1", "tool list", importance=0.8, page_type="tools")
+        for _ in range(3):
+            manager.access_page(user_ids[3])
+        
+        full = [p.page_id for p in manager.kv_cache_optimizer.optimize_layout(
+            manager.export_agent_pages("agent1"))]
+        top = [p.page_id for p in manager.get_agent_context_top_k("agent1", 3)]
+        
+        assert top == full[:3] == [system, tools, user_ids[3]]
+        assert [p.page_id for p in manager.iter_agent_context("agent1")] == full
+        assert manager.get_agent_context_top_k("agent1", 0) == []
+    
+    def test_dedup_pages(self):
+        manager = ContextManager(max_context_tokens=10000, dedup_pages=True)
+        first = manager.allocate_page("agent1", "You are helpful", page_type="system")
+        usage = manager.current_usage
+        
+        assert manager.allocate_page("agent1", "You are helpful", page_type="system") == first
+        assert manager.allocate_page("agent1", "You are helpful", page_type="user") != first
+        assert manager.allocate_page("agent2", "You are helpful", page_type="system") != first
+        
+        batch = manager.allocate_pages("agent1", [
+            ("You are helpful", 1.0, "system"),
+            ("tools", 0.8, "tools"),
+            ("tools", 0.8, "tools"),
+        ])
+        assert batch[0] == first
+        assert batch[1] == batch[2]
+        assert manager.get_stats()['dedup_hits'] == 3
+        assert manager.current_usage > usage
+    
+    def test_dedup_disabled_by_default(self):
+        manager = ContextManager(max_context_tokens=10000)
+        first = manager.allocate_page("agent1", "same")
+        assert manager.allocate_page("agent1", "same") != first
+        assert manager.get_stats()['dedup_hits'] == 0
+    
+    def test_compact_agent(self):
+        manager = ContextManager(max_context_tokens=10000)
+        system = manager.allocate_page("agent1", "system", importance=1.0, page_type="system")
+        w1 = manager.allocate_page("agent1", "step one", page_type="working")
+        manager.allocate_page("agent1", "step two", page_type="working")
+        r1 = manager.allocate_page("agent1", "result", page_type="tool_result")
+        w3 = manager.allocate_page("agent1", "step three", page_type="working")
+        usage = manager.current_usage
+        
+        removed = manager.compact_agent("agent1")
+        
+        assert removed == 1
+        assert manager.agent_pages["agent1"] == [system, w1, r1, w3]
+        assert manager.pages_in_memory[w1].content == "step one\n\nstep two"
+        assert manager.pages_in_memory[w1].is_dirty()
+        assert manager.current_usage == usage
+        assert manager.compact_agent("agent1") == 0
+    
+    def test_compact_agent_with_storage(self):
+        from agent_os_kernel.core.storage import StorageManager
+        storage = StorageManager()
+        manager = ContextManager(max_context_tokens=10000, storage_backend=storage)
+        offloaded = manager.allocate_page("agent1", "offloaded", page_type="working")
+        w1 = manager.allocate_page("agent1", "step one", page_type="working")
+        w2 = manager.allocate_page("agent1", "step two", page_type="working")
+        storage.save_context_page(manager.pages_in_memory[w2])
+        manager._evict_page(manager.pages_in_memory[offloaded])
+        manager.vacuum(cold_after=0)
+        assert storage.load_context_page(w2) is not None
+        
+        assert manager.compact_agent("agent1") == 1
+        
+        assert manager.agent_pages["agent1"] == [offloaded, w1]
+        assert storage.load_context_page(w2) is None
+        assert manager.access_page(w2) is None
+        assert manager.access_page(offloaded).content == "offloaded"
+    
+    def test_allocate_pages_overflow(self):
+        manager = ContextManager(max_context_tokens=5, tokenizer=HeuristicTokenizer())
+        with pytest.raises(ContextOverflowError):
+            manager.allocate_pages("agent1", [
+                ("system prompt text", 0.5, "user"),
+                ("current task text here", 0.5, "user"),
+            ])
 
 
 class TestContextManagerKVCache:
@@ -185,3 +432,567 @@ class TestContextManagerKVCache:
         
         assert hasattr(manager, 'kv_cache_optimizer')
         assert hasattr(manager.kv_cache_optimizer, 'get_hit_rate_stats')
+
+
+class TestMultimodalContext:
+    """测试多模态页面的上下文输出"""
+    
+    def test_context_parts_keep_images_separate(self):
+        cm = ContextManager(max_context_tokens=10000)
+        cm.allocate_page("agent1", "Describe this image", page_type="user")
+        cm.allocate_page("agent1", "https://example.com/cat.png", page_type="user",
+                         content_type=ContentType.IMAGE_URL)
+        cm.allocate_page("agent1", '{"label": "cat"}', page_type="user",
+                         content_type=ContentType.TOOL_JSON)
+        cm.allocate_page("agent1", "Answer briefly", page_type="user")
+        
+        parts = cm.get_agent_context_parts("agent1", optimize_for_cache=False)
+        
+        assert parts == [
+            {'type': 'text', 'text': "Describe this image"},
+            {'type': 'image_url', 'image_url': {'url': "https://example.com/cat.png"}},
+            {'type': 'text', 'text': '{"label":"cat"}\n\nAnswer briefly'},
+        ]
+    
+    def test_storage_round_trip_keeps_content_type(self):
+        from agent_os_kernel.core.storage import StorageManager
+        storage = StorageManager()
+        page = ContextPage(agent_pid="agent1", content="https://example.com/a.png",
+                           content_type=ContentType.IMAGE_URL)
+        
+        assert storage.save_context_page(page) is True
+        loaded = storage.load_context_page(page.page_id)
+        assert loaded.content_type == ContentType.IMAGE_URL
+        assert loaded.content == page.content
+        assert storage.load_context_page("missing") is None
+
+
+class TestContextOverflow:
+    """测试上下文超出 token 预算时的处理"""
+    
+    def _fill(self, cm):
+        cm.allocate_page("agent1", "You are a helpful agent", importance=1.0, page_type="system")
+        cm.allocate_page("agent1", "Finish the quarterly report", importance=0.9, page_type="task")
+        for i in range(6):
+            cm.allocate_page("agent1", f"observation number {i} " + "detail " * 30,
+                             importance=0.3, page_type="memory")
+    
+    def test_priority_keeps_critical_pages(self):
+        cm = ContextManager(max_context_tokens=10000, tokenizer=HeuristicTokenizer())
+        self._fill(cm)
+        
+        context = cm.get_agent_context("agent1", optimize_for_cache=False, token_budget=40)
+        
+        assert "You are a helpful agent" in context
+        assert "Finish the quarterly report" in context
+        assert "observation number 0" not in context
+        assert cm.stats['context_overflows'] == 1
+    
+    def test_within_budget_is_untouched(self):
+        cm = ContextManager(max_context_tokens=10000, tokenizer=HeuristicTokenizer())
+        self._fill(cm)
+        
+        full = cm.get_agent_context("agent1", optimize_for_cache=False)
+        assert cm.get_agent_context("agent1", optimize_for_cache=False,
+                                    token_budget=10000) == full
+        assert cm.stats['context_overflows'] == 0
+    
+    def test_compress_summarizes_oldest_pages(self):
+        cm = ContextManager(max_context_tokens=10000, tokenizer=HeuristicTokenizer(),
+                            overflow_strategy=OverflowStrategy.COMPRESS)
+        self._fill(cm)
+        
+        context = cm.get_agent_context("agent1", optimize_for_cache=False, token_budget=180)
+        
+        assert "[历史对话摘要]" in context
+        assert "Finish the quarterly report" in context
+        assert "observation number 5" in context
+        assert cm.tokenizer.count_tokens(context) <= 180
+        # 摘要页面是临时的，不会注册到管理器
+        assert len(cm.agent_pages["agent1"]) == 8
+    
+    def test_model_limit_compresses_regardless_of_strategy(self):
+        cm = ContextManager(max_context_tokens=10000, tokenizer=HeuristicTokenizer())
+        self._fill(cm)
+        
+        context, report = cm.fit_agent_context("agent1", 180, optimize_for_cache=False)
+        
+        assert "[历史对话摘要]" in context
+        assert "You are a helpful agent" in context
+        assert cm.tokenizer.count_tokens(context) <= 180
+        assert report.model_limit == 180
+        assert report.compressed
+        assert report.final_tokens <= 180 < report.original_tokens
+        assert report.removed_pages
+        assert report.summary_pages == 1
+    
+    def test_model_limit_not_hit_leaves_no_report(self):
+        cm = ContextManager(max_context_tokens=10000, tokenizer=HeuristicTokenizer())
+        self._fill(cm)
+        
+        context, report = cm.fit_agent_context("agent1", 10000)
+        assert report is None
+        assert context == cm.get_agent_context("agent1", model_limit=10000)
+    
+    def test_get_agent_context_applies_model_limit(self):
+        cm = ContextManager(max_context_tokens=10000, tokenizer=HeuristicTokenizer())
+        self._fill(cm)
+        
+        context = cm.get_agent_context("agent1", optimize_for_cache=False, model_limit=180)
+        assert "[历史对话摘要]" in context
+        assert cm.tokenizer.count_tokens(context) <= 180
+
+
+class TestPageExpiry:
+    """测试页面 TTL 过期"""
+    
+    def test_expire_pages_drops_only_expired(self):
+        cm = ContextManager(max_context_tokens=10000, tokenizer=HeuristicTokenizer())
+        keep = cm.allocate_page("agent1", "persistent notes")
+        ephemeral = cm.allocate_page("agent1", "tool output listing", ttl=5)
+        created = cm.pages_in_memory[ephemeral].created_at
+        usage = cm.current_usage
+        
+        assert cm.expire_pages(current_time=created + 1) == 0
+        assert cm.expire_pages(current_time=created + 5) == 1
+        
+        assert ephemeral not in cm.pages_in_memory
+        assert cm.agent_pages["agent1"] == [keep]
+        assert cm.current_usage == usage - cm.tokenizer.count_tokens("tool output listing")
+        assert cm.get_stats()['pages_expired'] == 1
+        assert cm.expire_pages(current_time=created + 1000) == 0
+    
+    def test_expire_swapped_page(self):
+        cm = ContextManager(max_context_tokens=12, tokenizer=HeuristicTokenizer())
+        old = cm.allocate_page("agent1", "old page with some words here", importance=0.1, ttl=1)
+        cm.allocate_page("agent1", "newer page with more words", importance=0.5)
+        assert old in cm.swapped_pages
+        
+        assert cm.expire_pages(current_time=cm.swapped_pages[old].created_at + 2) == 1
+        assert old not in cm.swapped_pages
+        assert old not in cm.agent_pages["agent1"]
+    
+    def test_ttl_round_trip(self):
+        page = ContextPage(agent_pid="a", content="x", ttl=30.0)
+        assert ContextPage.from_dict(page.to_dict()).ttl == 30.0
+
+
+class TestInjectedClock:
+    """测试注入时钟与 ID 生成器"""
+    
+    def _manager(self, max_tokens=10000):
+        clock = MockClock(start=1000.0)
+        cm = ContextManager(max_context_tokens=max_tokens, tokenizer=HeuristicTokenizer(),
+                            clock=clock, id_generator=SequentialIdGenerator("page-"))
+        return cm, clock
+    
+    def test_page_ids_and_timestamps(self):
+        cm, clock = self._manager()
+        first = cm.allocate_page("agent1", "first")
+        clock.advance(5)
+        second = cm.allocate_page("agent1", "second")
+        
+        assert (first, second) == ("page-00000001", "page-00000002")
+        assert cm.pages_in_memory[first].created_at == 1000.0
+        assert cm.pages_in_memory[second].created_at == 1005.0
+        
+        clock.advance(5)
+        cm.access_page(first)
+        assert cm.pages_in_memory[first].last_accessed == 1010.0
+        assert cm.access_history[-1].timestamp == 1010.0
+    
+    def test_eviction_order_follows_clock(self):
+        cm, clock = self._manager(max_tokens=8)
+        older = cm.allocate_page("agent1", "alpha beta gamma")
+        clock.advance(60)
+        newer = cm.allocate_page("agent1", "delta epsilon zeta")
+        clock.advance(60)
+        cm.access_page(older)
+        clock.advance(60)
+        
+        cm.allocate_page("agent1", "eta theta iota")
+        assert len(cm.swapped_pages) == 1
+        assert newer in cm.swapped_pages
+        assert older in cm.pages_in_memory
+    
+    def test_expire_uses_clock(self):
+        cm, clock = self._manager()
+        page_id = cm.allocate_page("agent1", "tool output", ttl=30)
+        clock.advance(29)
+        assert cm.expire_pages() == 0
+        clock.advance(1)
+        assert cm.expire_pages() == 1
+        assert page_id not in cm.pages_in_memory
+
+
+class TestEvictionProtection:
+    """测试按页面类型的换出保护"""
+    
+    def test_never_and_always_types(self):
+        from agent_os_kernel.core.clock import MockClock
+        clock = MockClock()
+        cm = ContextManager(max_context_tokens=9, tokenizer=HeuristicTokenizer(), clock=clock)
+        system = cm.allocate_page("a", "alpha beta gamma", importance=0.1, page_type="system")
+        clock.advance(600)
+        note = cm.allocate_page("a", "delta epsilon zeta", importance=0.1)
+        result = cm.allocate_page("a", "eta theta iota", importance=0.99, page_type="tool_result")
+        
+        cm.allocate_page("a", "kappa lambda mu")
+        assert result in cm.swapped_pages
+        
+        cm.allocate_page("a", "nu xi omicron")
+        assert note in cm.swapped_pages
+        assert system in cm.pages_in_memory
+    
+    def test_override_levels(self):
+        from agent_os_kernel.core.context_manager import Evictability
+        cm = ContextManager(max_context_tokens=6, tokenizer=HeuristicTokenizer(),
+                            eviction_protection={'memory': Evictability.NEVER})
+        cm.allocate_page("a", "alpha beta gamma", page_type="memory")
+        cm.allocate_page("a", "delta epsilon zeta", page_type="memory")
+        
+        with pytest.raises(ContextOverflowError):
+            cm.allocate_page("a", "eta theta iota")
+        assert cm.evictability(cm.pages_in_memory[cm.agent_pages["a"][0]]) == Evictability.NEVER
+
+
+class TestImportanceScorer:
+    """测试未指定 importance 时的自动评分"""
+    
+    def test_default_scores_by_page_type(self):
+        cm = ContextManager(max_context_tokens=10000, tokenizer=HeuristicTokenizer())
+        system = cm.allocate_page("a", "You are helpful", page_type="system")
+        result = cm.allocate_page("a", "Tool: calc", page_type="tool_result")
+        other = cm.allocate_page("a", "misc notes")
+        explicit = cm.allocate_page("a", "pinned", importance=0.99, page_type="tool_result")
+        
+        assert cm.pages_in_memory[system].importance_score == 1.0
+        assert cm.pages_in_memory[result].importance_score == 0.7
+        assert cm.pages_in_memory[other].importance_score == 0.5
+        assert cm.pages_in_memory[explicit].importance_score == 0.99
+    
+    def test_defaults_match_previous_fixed_scores(self):
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        pid = kernel.spawn_agent(name="Worker", task="Score pages")
+        process = kernel.scheduler.processes[pid]
+        page_id = kernel.record_observation(pid, "calc", {"value": 1})
+        user = kernel.context_manager.allocate_page(pid, "hi", page_type="user")
+        
+        pages = kernel.context_manager.pages_in_memory
+        assert pages[process.context['system_page']].importance_score == 1.0
+        assert pages[page_id].importance_score == 0.7
+        assert pages[user].importance_score == 0.5
+    
+    def test_custom_scorer(self):
+        from agent_os_kernel.core.context_manager import ImportanceScorer
+        
+        class LengthScorer(ImportanceScorer):
+            def score(self, content, page_type):
+                return min(len(content) / 100, 1.0)
+        
+        cm = ContextManager(max_context_tokens=10000, tokenizer=HeuristicTokenizer(),
+                            importance_scorer=LengthScorer())
+        page_ids = cm.allocate_pages("a", [("x" * 30, None, "task"), ("y" * 10, 0.8, "task")])
+        
+        assert cm.pages_in_memory[page_ids[0]].importance_score == 0.3
+        assert cm.pages_in_memory[page_ids[1]].importance_score == 0.8
+    
+    def test_weight_overrides(self):
+        from agent_os_kernel.core.context_manager import PageTypeImportanceScorer
+        scorer = PageTypeImportanceScorer(weights={'tool_result': 0.7}, default=0.2)
+        assert scorer.score("", "tool_result") == 0.7
+        assert scorer.score("", "system") == 1.0
+        assert scorer.score("", "scratch") == 0.2
+
+
+class TestContextSnapshot:
+    """测试上下文快照导出与导入"""
+    
+    def test_snapshot_json_round_trip(self):
+        import json
+        cm = ContextManager(max_context_tokens=12, tokenizer=HeuristicTokenizer())
+        old = cm.allocate_page("agent1", "old page with some words here", importance=0.1)
+        new = cm.allocate_page("agent1", "newer page with more words", importance=0.5)
+        tool = cm.allocate_page("agent2", "ls", page_type="tools", ttl=60)
+        
+        data = json.loads(json.dumps(cm.export_snapshot().to_dict()))
+        
+        restored = ContextManager(max_context_tokens=12, tokenizer=HeuristicTokenizer())
+        restored.import_snapshot(ContextSnapshot.from_dict(data))
+        
+        assert set(restored.pages_in_memory) == {new, tool}
+        assert set(restored.swapped_pages) == {old}
+        assert restored.agent_pages == cm.agent_pages
+        assert restored.current_usage == cm.current_usage
+        assert restored.stats['swaps_out'] == cm.stats['swaps_out']
+        assert restored.get_agent_context("agent1", include_swapped=True,
+                                          optimize_for_cache=False) == \
+            cm.get_agent_context("agent1", include_swapped=True, optimize_for_cache=False)
+        assert restored.expire_pages(current_time=data['created_at'] + 120) == 1
+
+
+class TestMessagePages:
+    """测试对话消息的分配与还原"""
+    
+    def test_tool_call_conversation_round_trip(self):
+        cm = ContextManager(max_context_tokens=10000)
+        call = {"id": "call_1", "type": "function",
+                "function": {"name": "search", "arguments": "{}"}}
+        messages = [
+            {"role": "system", "content": "You are helpful"},
+            {"role": "user", "content": "find it"},
+            {"role": "assistant", "content": "", "tool_calls": [call]},
+            {"role": "tool", "content": "42", "name": "search", "tool_call_id": "call_1"},
+            {"role": "tool", "content": "42", "name": "search", "tool_call_id": "call_2"},
+        ]
+        page_ids = [cm.allocate_message("agent1", m) for m in messages]
+        
+        assert len(set(page_ids)) == 5
+        assert cm.pages_in_memory[page_ids[3]].page_type == "tool_result"
+        assert cm.get_agent_messages("agent1") == messages
+    
+    def test_plain_pages_get_default_roles(self):
+        cm = ContextManager(max_context_tokens=10000)
+        cm.allocate_page("agent1", "rules", page_type="system")
+        cm.allocate_page("agent1", "note", page_type="memory")
+        assert cm.get_agent_messages("agent1") == [
+            {"role": "system", "content": "rules"},
+            {"role": "user", "content": "note"},
+        ]
+    
+    def test_system_pages_lead_the_conversation(self):
+        cm = ContextManager(max_context_tokens=10000)
+        cm.allocate_page("agent1", "rules", page_type="system")
+        cm.allocate_page("agent1", "Current task: x", page_type="task")
+        cm.allocate_page("agent1", "Available tools: calc", page_type="tools")
+        cm.allocate_message("agent1", {"role": "assistant", "content": "ok"})
+        cm.allocate_message("agent1", {"role": "system", "content": "late rule"})
+        
+        messages = cm.get_agent_messages("agent1")
+        assert [m['role'] for m in messages] == ["system", "system", "system", "user", "assistant"]
+        assert [m['content'] for m in messages[:3]] == \
+            ["rules", "Available tools: calc", "late rule"]
+
+
+class TestLargeAgentContext:
+    """测试大量页面下的上下文收集"""
+    
+    def test_only_selected_pages_tokenized(self):
+        cm = ContextManager(max_context_tokens=10 ** 6)
+        for i in range(200):
+            cm.allocate_page("agent1", f"word{i}", importance=i / 200)
+        
+        pages, _ = cm._collect_context_pages("agent1", 5, True, False)
+        
+        assert len(pages) == 5
+        assert cm.kv_cache_optimizer.previous_tokens == {p.content for p in pages}
+        full = cm.kv_cache_optimizer.optimize_layout(
+            [cm.pages_in_memory[pid] for pid in cm.agent_pages["agent1"]])
+        assert pages == full[:5]
+
+    
+    def test_bulk_eviction_matches_one_by_one_order(self):
+        def build():
+            clock = MockClock(start=1000.0)
+            cm = ContextManager(max_context_tokens=10 ** 6, clock=clock,
+                                id_generator=SequentialIdGenerator())
+            for i in range(30):
+                cm.allocate_page("agent1", f"page {i}", importance=(i % 5) / 5,
+                                 page_type="tool_result" if i % 7 == 0 else "working")
+                clock.advance(1)
+            return cm
+        
+        one_by_one = build()
+        expected = []
+        while len(expected) < 12:
+            victim = one_by_one._plan_evictions(1, max_pages=1)[0]
+            one_by_one._evict_page(victim)
+            expected.append(victim.page_id)
+        
+        bulk = build()
+        tokens = sum(bulk.pages_in_memory[pid].tokens for pid in expected)
+        freed = bulk._free_tokens(tokens)
+        
+        assert freed == tokens
+        assert list(bulk.swapped_pages) == expected
+
+
+class TestContentChunking:
+    """测试大文档按 page_size 分块"""
+    
+    def _manager(self, **kwargs):
+        return ContextManager(max_context_tokens=1000, tokenizer=HeuristicTokenizer(),
+                              clock=MockClock(start=1000.0),
+                              id_generator=SequentialIdGenerator(), **kwargs)
+    
+    def test_large_content_is_split_and_reassembled(self):
+        cm = self._manager(page_size=20)
+        document = " ".join(f"word{i}" for i in range(60))
+        cm.allocate_page("agent1", "rules", page_type="system")
+        first = cm.allocate_page("agent1", document)
+        
+        chunk_ids = cm.chunk_page_ids(first)
+        chunks = [cm.pages_in_memory[pid] for pid in chunk_ids]
+        assert len(chunks) > 1
+        assert chunk_ids[0] == first
+        assert all(page.tokens <= 20 for page in chunks)
+        assert len({page.chunk_group for page in chunks}) == 1
+        assert cm.get_agent_context("agent1", optimize_for_cache=False) == f"rules\n\n{document}"
+    
+    def test_chunks_evict_independently(self):
+        cm = self._manager(page_size=20)
+        first = cm.allocate_page("agent1", " ".join(f"word{i}" for i in range(60)))
+        chunk_ids = cm.chunk_page_ids(first)
+        
+        cm._evict_page(cm.pages_in_memory[chunk_ids[0]])
+        
+        assert chunk_ids[0] in cm.swapped_pages
+        assert cm.get_agent_context("agent1", optimize_for_cache=False) == \
+            "".join(cm.pages_in_memory[pid].content for pid in chunk_ids[1:])
+        assert cm.chunk_page_ids(chunk_ids[-1]) == chunk_ids
+    
+    def test_small_content_and_messages_are_not_split(self):
+        cm = self._manager(page_size=5)
+        page_id = cm.allocate_page("agent1", "short")
+        message_id = cm.allocate_message(
+            "agent1", {"role": "user", "content": "a long message with many words in it"})
+        
+        assert cm.chunk_page_ids(page_id) == [page_id]
+        assert cm.pages_in_memory[message_id].chunk_group is None
+        assert cm.pages_in_memory[page_id].chunk_group is None
+
+
+class TestSampledContext:
+    """测试按重要性加权抽样组装上下文"""
+    
+    def _manager(self):
+        cm = ContextManager(max_context_tokens=10 ** 6, tokenizer=HeuristicTokenizer(),
+                            id_generator=SequentialIdGenerator())
+        cm.allocate_page("agent1", "rules", page_type="system")
+        cm.allocate_page("agent1", "goal", page_type="task")
+        for i in range(10):
+            cm.allocate_page("agent1", f"high{i}", importance=0.9)
+            cm.allocate_page("agent1", f"low{i}", importance=0.05)
+        return cm
+    
+    def test_sampling_is_weighted_and_deterministic(self):
+        import random
+        cm = self._manager()
+        
+        high = low = 0
+        for seed in range(50):
+            context = cm.get_agent_context_sampled("agent1", token_budget=6,
+                                                   rng=random.Random(seed))
+            parts = context.split("\n\n")
+            assert parts[:2] == ["rules", "goal"]
+            assert len(parts) == 6
+            high += sum(p.startswith("high") for p in parts)
+            low += sum(p.startswith("low") for p in parts)
+        
+        assert high > 3 * low
+        assert cm.get_agent_context_sampled("agent1", 6, rng=random.Random(7)) == \
+            cm.get_agent_context_sampled("agent1", 6, rng=random.Random(7))
+    
+    def test_high_temperature_flattens(self):
+        import random
+        cm = self._manager()
+        
+        low = sum(
+            cm.get_agent_context_sampled("agent1", 6, temperature=100.0,
+                                         rng=random.Random(seed)).count("low")
+            for seed in range(50)
+        )
+        
+        assert low > 50
+        with pytest.raises(ValueError):
+            cm.get_agent_context_sampled("agent1", 6, temperature=0)
+
+
+class TestVacuum:
+    """测试清理已换出的页面"""
+    
+    def test_vacuum_drops_orphans_and_offloads_cold_pages(self):
+        from unittest.mock import MagicMock
+        clock = MockClock(start=1000.0)
+        storage = MagicMock()
+        cm = ContextManager(max_context_tokens=1000, tokenizer=HeuristicTokenizer(),
+                            clock=clock, id_generator=SequentialIdGenerator(),
+                            storage_backend=storage)
+        cold = cm.allocate_page("agent1", "cold page")
+        clock.advance(100)
+        warm = cm.allocate_page("agent1", "warm page")
+        orphan = cm.allocate_page("agent2", "orphan page")
+        for page_id in (cold, warm, orphan):
+            cm._evict_page(cm.pages_in_memory[page_id])
+        del cm.agent_pages["agent2"]
+        
+        report = cm.vacuum(cold_after=50)
+        
+        assert (report.orphaned_pages, report.offloaded_pages) == (1, 1)
+        assert report.freed_bytes == len("orphan page") + len("cold page")
+        assert list(cm.swapped_pages) == [warm]
+        saved = storage.save_context_page.call_args[0][0]
+        assert saved.page_id == cold
+        assert cm.vacuum().freed_pages == 0
+
+
+class TestEvictionEvents:
+    """测试页面换出事件"""
+    
+    def _manager(self):
+        return ContextManager(max_context_tokens=1000, tokenizer=HeuristicTokenizer(),
+                              clock=MockClock(start=1000.0),
+                              id_generator=SequentialIdGenerator())
+    
+    def test_subscription_receives_events(self):
+        cm = self._manager()
+        subscription = cm.subscribe_evictions()
+        page_id = cm.allocate_page("agent1", "some content", page_type="user")
+        page = cm.pages_in_memory[page_id]
+        cm._evict_page(page)
+        
+        event = subscription.get(timeout=0)
+        assert (event.page_id, event.agent_pid, event.page_type) == (page_id, "agent1", "user")
+        assert event.tokens == page.tokens
+        assert event.timestamp == 1000.0
+        assert subscription.get(timeout=0) is None
+    
+    def test_slow_subscriber_drops_oldest(self):
+        cm = self._manager()
+        subscription = cm.subscribe_evictions(capacity=2)
+        page_ids = [cm.allocate_page("agent1", f"page {i}") for i in range(3)]
+        for page_id in page_ids:
+            cm._evict_page(cm.pages_in_memory[page_id])
+        
+        assert [e.page_id for e in subscription.drain()] == page_ids[1:]
+        assert subscription.lagged == 1
+    
+    def test_unsubscribe(self):
+        cm = self._manager()
+        subscription = cm.subscribe_evictions()
+        cm.unsubscribe_evictions(subscription)
+        page_id = cm.allocate_page("agent1", "content")
+        cm._evict_page(cm.pages_in_memory[page_id])
+        assert subscription.closed
+        assert subscription.get() is None
+    
+    def test_callback_does_not_block_eviction(self):
+        import threading
+        cm = self._manager()
+        release = threading.Event()
+        received = []
+        
+        def slow_callback(event):
+            release.wait(5)
+            received.append(event.page_id)
+        
+        cm.on_eviction(slow_callback)
+        page_id = cm.allocate_page("agent1", "content")
+        cm._evict_page(cm.pages_in_memory[page_id])
+        assert page_id in cm.swapped_pages
+        assert received == []
+        
+        release.set()
+        cm._eviction_executor.shutdown(wait=True)
+        assert received == [page_id]
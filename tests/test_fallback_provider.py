@@ -0,0 +1,85 @@
+"""测试 Provider 故障转移链"""
+
+import pytest
+
+from agent_os_kernel.llm.fallback import FallbackProvider
+from agent_os_kernel.llm.mock_provider import MockProvider
+from agent_os_kernel.llm.provider import (
+    Message, RateLimitedError, ServerError, BadRequestError
+)
+
+
+class FailingProvider(MockProvider):
+    """总是抛出指定错误的 Provider"""
+    
+    def __init__(self, name, error):
+        super().__init__()
+        self.PROVIDER_NAME = name
+        self.error = error
+        self.calls = 0
+    
+    async def chat(self, messages, **kwargs):
+        self.calls += 1
+        raise self.error
+
+
+class NamedProvider(MockProvider):
+    def __init__(self, name):
+        super().__init__()
+        self.PROVIDER_NAME = name
+        self.set_delay(0)
+
+
+MESSAGES = [Message(role="user", content="hello")]
+
+
+class TestFallbackProvider:
+    """测试故障转移"""
+    
+    @pytest.mark.asyncio
+    async def test_falls_back_on_rate_limit_and_server_error(self):
+        primary = FailingProvider("primary", RateLimitedError("429"))
+        secondary = FailingProvider("secondary", ServerError("503"))
+        tertiary = NamedProvider("tertiary")
+        provider = FallbackProvider([primary, secondary, tertiary])
+        
+        result = await provider.chat(MESSAGES)
+        
+        assert result["content"]
+        assert provider.last_served_by == "tertiary"
+        assert provider.served_counts["tertiary"] == 1
+        assert provider.failover_count == 2
+    
+    @pytest.mark.asyncio
+    async def test_bad_request_is_not_retried(self):
+        primary = FailingProvider("primary", BadRequestError("400"))
+        secondary = NamedProvider("secondary")
+        provider = FallbackProvider([primary, secondary])
+        
+        with pytest.raises(BadRequestError):
+            await provider.chat(MESSAGES)
+        assert provider.last_served_by is None
+    
+    @pytest.mark.asyncio
+    async def test_last_error_raised_when_all_fail(self):
+        provider = FallbackProvider([
+            FailingProvider("primary", RateLimitedError("429")),
+            FailingProvider("secondary", ServerError("502")),
+        ])
+        
+        with pytest.raises(ServerError):
+            await provider.chat(MESSAGES)
+    
+    def test_requires_providers(self):
+        with pytest.raises(ValueError):
+            FallbackProvider([])
+
+    
+    def test_price_from_member_providers(self):
+        from agent_os_kernel.llm.provider import ModelPrice
+        priced = NamedProvider("priced")
+        priced.PRICING = {"gpt-4o": ModelPrice(0.005, 0.015)}
+        provider = FallbackProvider([NamedProvider("free"), priced])
+        
+        assert provider.price_for("gpt-4o").input_per_1k == 0.005
+        assert provider.price_for("unknown") is None
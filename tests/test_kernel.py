@@ -1,6 +1,7 @@
 """测试内核"""
 
 import pytest
+from unittest.mock import patch
 
 
 class TestKernel:
@@ -27,3 +28,985 @@ class TestKernelStats:
         """测试统计存在"""
         from agent_os_kernel import KernelStats
         assert KernelStats is not None
+    
+    def test_cost_tracking(self):
+        import time
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.llm.mock_provider import MockProvider
+        from agent_os_kernel.llm.provider import LLMResponse, ModelPrice
+        
+        class PricedProvider(MockProvider):
+            PRICING = {"mock-model": ModelPrice(0.01, 0.02)}
+        
+        kernel = AgentOSKernel()
+        provider = PricedProvider()
+        pid = kernel.spawn_agent(name="Biller", task="count money")
+        response = LLMResponse(content="ok", model="mock-model",
+                               usage={"prompt_tokens": 1000, "completion_tokens": 500})
+        
+        assert kernel.record_completion(pid, response, provider) == pytest.approx(0.02)
+        time.sleep(0.01)
+        window_start = time.time()
+        kernel.record_completion(pid, response, provider)
+        unknown = LLMResponse(content="ok", model="other", usage={"input_tokens": 10})
+        assert kernel.record_completion("other-agent", unknown, provider) == 0.0
+        
+        assert kernel.stats.total_cost_usd == pytest.approx(0.04)
+        assert kernel.stats.total_api_calls == 3
+        assert kernel.agent_cost(pid) == pytest.approx(0.04)
+        assert kernel.cost_since(window_start) == pytest.approx(0.02)
+        
+        kernel.reset_cost()
+        assert kernel.stats.total_cost_usd == 0.0
+        assert kernel.agent_cost(pid) == 0.0
+        assert kernel.cost_since(0) == 0.0
+    
+    def test_llm_complete_records_cost(self):
+        import asyncio
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.llm.mock_provider import MockProvider
+        from agent_os_kernel.llm.provider import Message, ModelPrice
+        
+        class PricedProvider(MockProvider):
+            PRICING = {"mock-model": ModelPrice(1.0, 2.0)}
+        
+        kernel = AgentOSKernel()
+        provider = PricedProvider()
+        provider.set_delay(0)
+        pid = kernel.spawn_agent(name="Caller", task="t")
+        
+        response = asyncio.run(kernel.llm_complete(pid, provider, [Message(role="user", content="hi")]))
+        
+        assert response.content
+        assert kernel.stats.total_api_calls == 1
+        assert kernel.agent_cost(pid) > 0
+        assert kernel.agent_cost(pid) == pytest.approx(provider.estimate_cost(response))
+    
+    def test_cost_log_bounded(self):
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.llm.mock_provider import MockProvider
+        from agent_os_kernel.llm.provider import LLMResponse
+        kernel = AgentOSKernel()
+        response = LLMResponse(content="ok", model="mock-model", usage={"prompt_tokens": 1})
+        for _ in range(kernel.COST_LOG_CAPACITY + 5):
+            kernel.record_completion("agent", response, MockProvider())
+        assert len(kernel._cost_log) == kernel.COST_LOG_CAPACITY
+        assert kernel.stats.total_api_calls == kernel.COST_LOG_CAPACITY + 5
+    
+    def test_step_tokens_use_context_tokenizer(self):
+        """测试步骤 token 统计使用上下文管理器的分词器"""
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        from agent_os_kernel.core.tokenizer import Tokenizer
+        
+        class CharTokenizer(Tokenizer):
+            def count_tokens(self, text):
+                return len(text)
+        
+        kernel = AgentOSKernel(config=KernelConfig(tokenizer=CharTokenizer()))
+        
+        def step(process, context):
+            return {'success': True, 'done': True, 'reasoning': "two words"}
+        
+        kernel.spawn_agent(name="counter", task="t", agent=step)
+        kernel.run(max_iterations=1)
+        
+        assert kernel.stats.total_tokens == len("two words")
+
+
+class TestKernelHealth:
+    """测试健康检查汇总"""
+    
+    def test_healthy_kernel(self):
+        from agent_os_kernel import AgentOSKernel, KernelState
+        kernel = AgentOSKernel()
+        kernel.spawn_agent(name="a", task="t")
+        
+        report = kernel.health()
+        
+        assert report.healthy
+        assert report.state == KernelState.INITIALIZED
+        assert set(report.components) == {'kernel', 'storage', 'scheduler', 'context'}
+        assert report.components['scheduler'].detail['ready_queue_size'] == 1
+        assert 0 < report.components['context'].detail['pressure'] < 1
+    
+    def test_hung_check_times_out(self):
+        import time
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        with patch.object(kernel.storage, 'ping', side_effect=lambda: time.sleep(2) or True):
+            started = time.time()
+            report = kernel.health(timeout=0.1)
+        
+        assert time.time() - started < 1.5
+        assert not report.healthy
+        assert "timed out" in report.components['storage'].error
+    
+    def test_unhealthy_after_shutdown(self):
+        from agent_os_kernel import AgentOSKernel, KernelState
+        kernel = AgentOSKernel()
+        kernel.shutdown()
+        
+        report = kernel.health()
+        assert report.state == KernelState.STOPPED
+        assert not report.healthy
+        assert report.to_dict()['components']['kernel']['healthy'] is False
+
+
+class TestKernelPause:
+    """测试内核暂停与恢复"""
+    
+    def test_pause_mid_run(self):
+        import threading
+        import time
+        from agent_os_kernel import AgentOSKernel, KernelState
+        kernel = AgentOSKernel()
+        kernel.spawn_agent(name="worker", task="loop")
+        steps = []
+        
+        def step(process):
+            steps.append(process.pid)
+            time.sleep(0.005)
+            return {'success': True, 'yield': True}
+        
+        def wait_for(condition, timeout=2.0):
+            deadline = time.time() + timeout
+            while not condition() and time.time() < deadline:
+                time.sleep(0.01)
+            return condition()
+        
+        with patch.object(kernel, 'execute_agent_step', side_effect=step):
+            loop = threading.Thread(target=kernel.run, daemon=True)
+            loop.start()
+            assert wait_for(lambda: len(steps) > 0)
+            
+            assert kernel.pause()
+            time.sleep(0.05)
+            paused_at = len(steps)
+            time.sleep(0.2)
+            assert len(steps) == paused_at
+            assert kernel.state == KernelState.PAUSED
+            assert kernel.health().healthy
+            
+            assert kernel.resume()
+            assert not kernel.resume()
+            assert wait_for(lambda: len(steps) > paused_at)
+            assert kernel.state == KernelState.RUNNING
+            
+            kernel.shutdown()
+            loop.join(timeout=2)
+        
+        assert not loop.is_alive()
+
+
+class TestKernelConcurrency:
+    """测试每周期运行多个 Agent"""
+    
+    def test_run_executes_batch_per_iteration(self):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        kernel = AgentOSKernel(config=KernelConfig(max_concurrent=2))
+        for i in range(3):
+            kernel.spawn_agent(name=f"a{i}", task="t")
+        running_counts = []
+        
+        def step(process):
+            running_counts.append(len(kernel.scheduler.running_processes()))
+            return {'success': True, 'done': True}
+        
+        with patch.object(kernel, 'execute_agent_step', side_effect=step):
+            kernel.run(max_iterations=2)
+        
+        assert kernel.stats.total_iterations == 3
+        assert max(running_counts) == 2
+        assert kernel.scheduler.stats['total_completed'] == 3
+    
+    def test_gang_members_step_together(self):
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        pids = [kernel.spawn_agent(name=f"g{i}", task="t") for i in range(3)]
+        for pid in pids:
+            kernel.scheduler.add_process_to_group(kernel.scheduler.processes[pid], "team")
+        stepped = []
+        
+        def step(process):
+            stepped.append(process.pid)
+            return {'success': True, 'done': True}
+        
+        with patch.object(kernel, 'execute_agent_step', side_effect=step):
+            kernel.run(max_iterations=1)
+        
+        assert sorted(stepped) == sorted(pids)
+        assert kernel.scheduler.stats['total_completed'] == 3
+
+
+class TestSpawnIdempotency:
+    """测试 spawn_agent 幂等键"""
+    
+    def test_repeated_key_returns_same_pid(self):
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        first = kernel.spawn_agent(name="A", task="t", idempotency_key="req-1")
+        again = kernel.spawn_agent(name="A", task="t", idempotency_key="req-1")
+        other_scope = kernel.spawn_agent(name="A", task="t", idempotency_key="req-1",
+                                         idempotency_scope="client-2")
+        
+        assert again == first
+        assert other_scope != first
+        assert len(kernel.scheduler.processes) == 2
+    
+    def test_expired_key_spawns_again(self):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        kernel = AgentOSKernel(config=KernelConfig(idempotency_ttl=60))
+        with patch("agent_os_kernel.kernel.time.time", return_value=1000.0):
+            first = kernel.spawn_agent(name="A", task="t", idempotency_key="req-1")
+        with patch("agent_os_kernel.kernel.time.time", return_value=1059.0):
+            assert kernel.spawn_agent(name="A", task="t", idempotency_key="req-1") == first
+        with patch("agent_os_kernel.kernel.time.time", return_value=1060.0):
+            assert kernel.spawn_agent(name="A", task="t", idempotency_key="req-1") != first
+    
+    def test_concurrent_requests_spawn_once(self):
+        import threading
+        import time as real_time
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        spawn = kernel._spawn_process
+        
+        def slow_spawn(*args, **kwargs):
+            real_time.sleep(0.05)
+            return spawn(*args, **kwargs)
+        
+        kernel._spawn_process = slow_spawn
+        pids = []
+        threads = [threading.Thread(target=lambda: pids.append(
+            kernel.spawn_agent(name="A", task="t", idempotency_key="req-1")))
+            for _ in range(4)]
+        for thread in threads:
+            thread.start()
+        for thread in threads:
+            thread.join()
+        
+        assert len(set(pids)) == 1
+        assert len(kernel.scheduler.processes) == 1
+    
+    def test_expired_keys_swept_on_write(self):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        kernel = AgentOSKernel(config=KernelConfig(idempotency_ttl=60))
+        with patch("agent_os_kernel.kernel.time.time", return_value=1000.0):
+            kernel.spawn_agent(name="A", task="t", idempotency_key="stale")
+        with patch("agent_os_kernel.kernel.time.time", return_value=1030.0):
+            kernel.spawn_agent(name="B", task="t", idempotency_key="fresh")
+        assert len(kernel.storage.list_keys("idempotency:")) == 2
+        
+        with patch("agent_os_kernel.kernel.time.time", return_value=1070.0):
+            kernel.spawn_agent(name="C", task="t", idempotency_key="new")
+        assert sorted(kernel.storage.list_keys("idempotency:")) == [
+            "idempotency:default:fresh", "idempotency:default:new"]
+
+
+class TestKernelAgents:
+    """测试通过注册的 Agent 实现执行步骤"""
+    
+    def test_agent_run_receives_context(self):
+        from agent_os_kernel import AgentOSKernel
+        
+        class EchoAgent:
+            def __init__(self):
+                self.inputs = []
+            
+            async def run(self, task):
+                self.inputs.append(task)
+                return {'success': True, 'output': 'answer'}
+        
+        kernel = AgentOSKernel()
+        agent = EchoAgent()
+        pid = kernel.spawn_agent(name="echo", task="say hi", agent=agent)
+        kernel.run(max_iterations=1)
+        
+        assert "say hi" in agent.inputs[0]
+        process = kernel.scheduler.processes[pid]
+        assert process.result == 'answer'
+        assert kernel.scheduler.stats['total_completed'] == 1
+    
+    def test_step_function_errors_recorded(self):
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        
+        def failing_step(process, context):
+            raise RuntimeError("model unavailable")
+        
+        pid = kernel.spawn_agent(name="flaky", task="t", agent=failing_step)
+        kernel.run(max_iterations=1)
+        
+        process = kernel.scheduler.processes[pid]
+        assert process.error_count == 1
+        assert process.last_error == "model unavailable"
+    
+    def test_register_unknown_pid(self):
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.exceptions import AgentNotFoundError
+        kernel = AgentOSKernel()
+        with pytest.raises(AgentNotFoundError):
+            kernel.register_agent("missing", lambda process, context: {})
+    
+    def test_terminate_cancels_in_flight_step(self):
+        import asyncio
+        import threading
+        import time
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.scheduler import AgentState
+        kernel = AgentOSKernel()
+        
+        async def slow_step(process, context):
+            await asyncio.sleep(30)
+            return {'success': True, 'done': True}
+        
+        pid = kernel.spawn_agent(name="slow", task="t", agent=slow_step)
+        threading.Timer(0.2, kernel.scheduler.terminate_process, args=(pid, "cancelled")).start()
+        start = time.monotonic()
+        kernel.run(max_iterations=1)
+        
+        process = kernel.scheduler.processes[pid]
+        assert time.monotonic() - start < 10
+        assert process.state == AgentState.TERMINATED
+        assert process.error_count == 0
+        assert kernel.cancellation_token(pid).cancelled
+    
+    def test_cancellation_token_for_unknown_pid(self):
+        """测试未知 PID 不会创建并保存取消令牌"""
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.exceptions import AgentNotFoundError
+        kernel = AgentOSKernel()
+        with pytest.raises(AgentNotFoundError):
+            kernel.cancellation_token("missing")
+        assert "missing" not in kernel._cancel_tokens
+    
+    def test_async_steps_share_kernel_loop(self):
+        """测试 async 步骤复用内核事件循环，关闭时停止循环线程"""
+        import asyncio
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        loops = []
+        
+        async def step(process, context):
+            loops.append(asyncio.get_running_loop())
+            return {'success': True, 'done': len(loops) >= 3}
+        
+        kernel.spawn_agent(name="looper", task="t", agent=step)
+        kernel.run(max_iterations=3)
+        thread = kernel._loop_thread
+        kernel.shutdown()
+        
+        assert len(loops) == 3
+        assert loops[0] is loops[1] is loops[2]
+        assert not thread.is_alive()
+        assert loops[0].is_closed()
+    
+    def test_async_eviction_callback_uses_kernel_loop(self):
+        """测试 async 换出回调在内核事件循环中执行"""
+        import asyncio
+        import threading
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        loops = []
+        done = threading.Event()
+        
+        async def on_evict(event):
+            loops.append(asyncio.get_running_loop())
+            done.set()
+        
+        kernel.context_manager.on_eviction(on_evict)
+        pid = kernel.spawn_agent(name="evicted", task="t")
+        page_id = kernel.context_manager.allocate_page(pid, "content")
+        kernel.context_manager._evict_page(kernel.context_manager.pages_in_memory[page_id])
+        
+        assert done.wait(timeout=5)
+        assert loops == [kernel._loop]
+        kernel.shutdown()
+    
+    def test_list_agents(self):
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        
+        def failing_step(process, context):
+            raise RuntimeError("boom")
+        
+        first = kernel.spawn_agent(name="flaky", task="t1", priority=10, agent=failing_step)
+        second = kernel.spawn_agent(name="idle", task="t2", priority=60)
+        kernel.run(max_iterations=1)
+        
+        agents = kernel.list_agents()
+        assert [a.pid for a in agents] == [first, second]
+        assert agents[0].name == "flaky"
+        assert agents[0].task == "t1"
+        assert agents[0].priority == 10
+        assert agents[0].error_count == 1
+        assert agents[1].state == "ready"
+        assert agents[1].context_tokens == kernel.context_manager.agent_token_usage(second)
+        assert agents[1].context_tokens > 0
+        assert agents[1].to_dict()['pid'] == second
+    
+    def test_get_agent(self):
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        pid = kernel.spawn_agent(name="solo", task="t")
+        assert kernel.get_agent(pid).name == "solo"
+        assert kernel.get_agent("missing") is None
+    
+    def test_export_snapshot(self):
+        import json
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        pid = kernel.spawn_agent(name="dumped", task="t")
+        
+        snapshot = json.loads(json.dumps(kernel.export_snapshot(), default=str))
+        assert snapshot['state'] == "initialized"
+        assert [a['pid'] for a in snapshot['agents']] == [pid]
+        assert snapshot['scheduler']['ready'] == [pid]
+        assert snapshot['context']['agent_pages'][pid]
+        assert 'health' in snapshot and 'stats' in snapshot
+    
+    def test_export_snapshot_redacts_page_content(self):
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        pid = kernel.spawn_agent(name="dumped", task="rotate the secret token")
+        page_id = kernel.context_manager.allocate_page(pid, "secret api key")
+        
+        snapshot = kernel.export_snapshot(redact_content=True)
+        pages = {p['page_id']: p for p in snapshot['context']['pages']}
+        assert pages[page_id]['content'] == "<redacted 14 chars>"
+        assert snapshot['agents'][0]['task'] == "<redacted 23 chars>"
+        assert "secret" not in str(snapshot)
+        assert kernel.context_manager.pages_in_memory[page_id].content == "secret api key"
+        assert kernel.scheduler.processes[pid].context['task'] == "rotate the secret token"
+    
+    def test_export_snapshot_has_no_side_effects(self, tmp_path):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        kernel = AgentOSKernel(config=KernelConfig(context_wal_path=str(tmp_path / "context.wal")))
+        kernel.spawn_agent(name="dumped", task="t")
+        wal_records = len(list(kernel.context_manager.wal.records()))
+        
+        kernel.export_snapshot()
+        assert len(list(kernel.context_manager.wal.records())) == wal_records > 0
+        assert kernel.storage.retrieve(kernel.CONTEXT_SNAPSHOT_KEY) is None
+        assert not (tmp_path / "context.wal.snapshot").exists()
+
+
+class TestSchedulingTick:
+    """测试主循环在进程就绪时立即唤醒"""
+    
+    def test_idle_loop_wakes_on_spawn(self):
+        import threading
+        import time
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        kernel = AgentOSKernel(config=KernelConfig(scheduling_interval=30))
+        ran = threading.Event()
+        
+        def step(process, context):
+            ran.set()
+            return {'success': True, 'done': True}
+        
+        loop = threading.Thread(target=kernel.run, kwargs={'max_iterations': 2})
+        loop.start()
+        time.sleep(0.2)
+        start = time.monotonic()
+        kernel.spawn_agent(name="late", task="t", agent=step)
+        
+        assert ran.wait(timeout=5)
+        loop.join(timeout=5)
+        assert not loop.is_alive()
+        assert time.monotonic() - start < 5
+
+
+class TestAgentCompletion:
+    """测试 Agent 完成后的状态清理"""
+    
+    def test_completed_process_not_rescheduled(self):
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.scheduler import AgentState
+        kernel = AgentOSKernel()
+        events = []
+        kernel.on_event(lambda event: events.append(event.event_type.value))
+        calls = []
+        
+        def step(process, context):
+            calls.append(process.pid)
+            return {'success': True, 'done': True, 'output': {'answer': 42}}
+        
+        pid = kernel.spawn_agent(name="once", task="t", agent=step)
+        kernel.run(max_iterations=3)
+        kernel.shutdown()
+        
+        process = kernel.scheduler.processes[pid]
+        assert calls == [pid]
+        assert process.state == AgentState.COMPLETED
+        assert process.result == {'answer': 42}
+        assert kernel.storage.retrieve(f"process:{pid}")['completed_at'] is not None
+        assert "agent_completed" in events
+    
+    def test_complete_removes_from_queues(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess, AgentState
+        scheduler = AgentScheduler()
+        scheduler.add_process(AgentProcess(pid="a", name="a"))
+        scheduler.add_process(AgentProcess(pid="b", name="b", priority=60))
+        
+        assert scheduler.complete_process("a", "done")
+        assert not scheduler.complete_process("a")
+        assert scheduler.ready_queue.qsize() == 1
+        assert scheduler.schedule().pid == "b"
+        assert scheduler.processes["a"].state == AgentState.COMPLETED
+
+
+class TestKernelObservations:
+    """测试工具结果写回上下文"""
+    
+    def test_record_observation(self):
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        pid = kernel.spawn_agent(name="worker", task="t")
+        
+        result = kernel.tool_registry.execute("calculator", expression="6 * 7")
+        page_id = kernel.record_observation(pid, "calculator", result)
+        
+        page = kernel.context_manager.pages_in_memory[page_id]
+        assert page.page_type == "tool_result"
+        assert "Tool: calculator" in page.content
+        assert "42" in kernel.context_manager.get_agent_context(pid)
+        
+        failed = kernel.tool_registry.execute("calculator", expression="1 / 0")
+        page_id = kernel.record_observation(pid, "calculator", failed)
+        assert "Error: division by zero" in kernel.context_manager.pages_in_memory[page_id].content
+    
+    def test_replay_agent_from_audit_trail(self):
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.exceptions import AgentNotFoundError
+        kernel = AgentOSKernel()
+        pid = kernel.spawn_agent(
+            name="worker", task="t",
+            agent=lambda process, context: {'success': True, 'reasoning': "use calculator"}
+        )
+        kernel.execute_agent_step(kernel.scheduler.processes[pid])
+        kernel.record_observation(pid, "calculator",
+                                  kernel.tool_registry.execute("calculator", expression="6 * 7"))
+        
+        replay = kernel.replay_agent(pid)
+        
+        assert replay.context == kernel.context_manager.get_agent_context(pid)
+        assert (replay.steps, replay.tool_calls, replay.skipped) == (1, 1, 0)
+        assert replay.reasoning == ["use calculator"]
+        assert replay.context_manager is not kernel.context_manager
+        with pytest.raises(AgentNotFoundError):
+            kernel.replay_agent("missing")
+    
+    def test_replay_restored_agent_starts_from_checkpoint(self):
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        pid = kernel.spawn_agent(name="worker", task="t")
+        kernel.record_observation(pid, "calculator", {'success': True, 'data': 42})
+        restored = kernel.restore_checkpoint(kernel.create_checkpoint(pid))
+        kernel.record_observation(restored, "calculator", {'success': True, 'data': 43})
+        
+        replay = kernel.replay_agent(restored)
+        
+        assert replay.tool_calls == 1
+        assert "Result: 42" in replay.context and "Result: 43" in replay.context
+        assert replay.context == kernel.context_manager.get_agent_context(restored)
+    
+    def test_replay_without_trail_uses_current_context(self):
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.scheduler import AgentProcess
+        kernel = AgentOSKernel()
+        kernel.scheduler.add_process(AgentProcess(pid="snap", name="from-snapshot"))
+        kernel.context_manager.allocate_page("snap", "restored context")
+        kernel.record_observation("snap", "calculator", {'success': True, 'data': 42})
+        
+        replay = kernel.replay_agent("snap")
+        
+        assert replay.tool_calls == 0
+        assert replay.context == kernel.context_manager.get_agent_context("snap")
+    
+    def test_react_agent_feeds_observations(self):
+        import asyncio
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.agents import ReActAgent
+        kernel = AgentOSKernel()
+        pid = kernel.spawn_agent(name="react", task="t")
+        agent = ReActAgent(
+            name="react",
+            on_observation=lambda tool, result: kernel.record_observation(pid, tool, result)
+        )
+        
+        asyncio.run(agent._execute_action({"type": "tool", "tool": "search", "params": {"query": "kernel"}}))
+        
+        assert "Tool: search" in kernel.context_manager.get_agent_context(pid)
+
+
+class TestKernelConfig:
+    """测试内核配置"""
+    
+    def test_default_storage_is_memory(self):
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.storage import MemoryStorage
+        kernel = AgentOSKernel()
+        assert isinstance(kernel.storage._data, MemoryStorage)
+    
+    def test_storage_url(self, tmp_path):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        from agent_os_kernel.core.storage import FileStorage
+        kernel = AgentOSKernel(config=KernelConfig(storage_url=f"file://{tmp_path}"))
+        assert isinstance(kernel.storage._data, FileStorage)
+    
+    def test_vacuum_offloads_cold_pages_to_storage(self):
+        """测试启用 context_storage_offload 后清理时可迁移冷页面"""
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        kernel = AgentOSKernel(config=KernelConfig(context_vacuum_interval=0,
+                                                   context_vacuum_cold_after=0,
+                                                   context_storage_offload=True))
+        pid = kernel.spawn_agent(name="worker", task="vacuum")
+        cm = kernel.context_manager
+        page_id = cm.allocate_page(pid, "cold page")
+        cm._evict_page(cm.pages_in_memory[page_id])
+        
+        report = cm.vacuum(kernel.config.context_vacuum_cold_after)
+        
+        assert report.offloaded_pages > 0
+        assert page_id not in cm.swapped_pages
+        assert cm.access_page(page_id).content == "cold page"
+    
+    def test_storage_offload_disabled_by_default(self):
+        """测试默认不把换出的脏页写回存储"""
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        pid = kernel.spawn_agent(name="worker", task="no offload")
+        cm = kernel.context_manager
+        page_id = cm.allocate_page(pid, "draft")
+        cm.update_page_content(page_id, "edited")
+        cm._evict_page(cm.pages_in_memory[page_id])
+        
+        assert cm.storage is None
+        assert kernel.storage.load_context_page(page_id) is None
+    
+    def test_storage_offload_writes_back_and_loads(self):
+        """测试启用 context_storage_offload 后脏页换出时写回，并能从存储重新加载"""
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        kernel = AgentOSKernel(config=KernelConfig(context_storage_offload=True))
+        pid = kernel.spawn_agent(name="worker", task="offload")
+        cm = kernel.context_manager
+        page_id = cm.allocate_page(pid, "draft")
+        cm.update_page_content(page_id, "edited")
+        cm._evict_page(cm.pages_in_memory[page_id])
+        
+        assert kernel.storage.load_context_page(page_id).content == "edited"
+        del cm.swapped_pages[page_id]
+        assert cm.access_page(page_id).content == "edited"
+
+
+class TestKernelErrors:
+    """测试内核类型化错误"""
+    
+    def test_checkpoint_unknown_agent(self):
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.exceptions import AgentNotFoundError
+        kernel = AgentOSKernel()
+        with pytest.raises(AgentNotFoundError):
+            kernel.create_checkpoint("no-such-agent")
+    
+    def test_restore_unknown_checkpoint(self):
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.exceptions import CheckpointNotFoundError
+        kernel = AgentOSKernel()
+        with pytest.raises(CheckpointNotFoundError):
+            kernel.restore_checkpoint("00000000-0000-0000-0000-000000000000")
+    
+    def test_invalid_storage_url(self):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        from agent_os_kernel.core.exceptions import ConfigurationError
+        with pytest.raises(ConfigurationError):
+            AgentOSKernel(config=KernelConfig(storage_url="redis://localhost"))
+    
+    def test_invalid_context_config(self):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        from agent_os_kernel.core.exceptions import ConfigurationError
+        with pytest.raises(ConfigurationError):
+            AgentOSKernel(max_context_tokens=0)
+        with pytest.raises(ConfigurationError):
+            AgentOSKernel(max_context_tokens=1000,
+                          config=KernelConfig(per_agent_token_limit=5000))
+    
+    def test_spawn_after_shutdown(self):
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.exceptions import InvalidStateError
+        kernel = AgentOSKernel()
+        kernel.shutdown()
+        with pytest.raises(InvalidStateError):
+            kernel.spawn_agent(name="late", task="too late")
+
+
+class TestCheckpointRoundTrip:
+    """测试检查点创建与恢复"""
+    
+    def test_restore_rehydrates_agent(self):
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        pid = kernel.spawn_agent(name="Researcher", task="Survey papers", priority=20)
+        original = kernel.scheduler.processes[pid]
+        original_context = kernel.context_manager.get_agent_context(pid)
+        
+        checkpoint_id = kernel.create_checkpoint(pid, description="before restart")
+        new_pid = kernel.restore_checkpoint(checkpoint_id)
+        
+        restored = kernel.scheduler.processes[new_pid]
+        assert new_pid != pid
+        assert restored.name == original.name
+        assert restored.priority == original.priority
+        assert restored.context['task'] == "Survey papers"
+        assert restored.context['restored_from'] == pid
+        assert kernel.context_manager.get_agent_context(new_pid) == original_context
+        assert restored.context['system_page'] in kernel.context_manager.agent_pages[new_pid]
+    
+    def test_restore_drops_missing_children(self):
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        pid = kernel.spawn_agent(name="Parent", task="Delegate")
+        child = kernel.spawn_agent(name="Child", task="Help")
+        kernel.scheduler.processes[pid].child_pids = [child, "reaped"]
+        
+        checkpoint_id = kernel.create_checkpoint(pid)
+        new_pid = kernel.restore_checkpoint(checkpoint_id)
+        assert kernel.scheduler.processes[new_pid].child_pids == [child]
+    
+    def test_restore_preserves_page_metadata(self):
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.context_manager import ContentType
+        kernel = AgentOSKernel()
+        cm = kernel.context_manager
+        pid = kernel.spawn_agent(name="Reader", task="Read docs")
+        cm.allocate_page(pid, "https://example.com/a.png", page_type="memory",
+                         content_type=ContentType.IMAGE_URL, ttl=120.0)
+        cm.allocate_page(pid, " ".join(f"word{i}" for i in range(60)),
+                         page_type="memory", page_size=20)
+        original = cm.export_agent_pages(pid)
+        
+        new_pid = kernel.restore_checkpoint(kernel.create_checkpoint(pid))
+        restored = cm.export_agent_pages(new_pid)
+        
+        def shape(pages):
+            return [(p.content, p.page_type, p.content_type, p.ttl, p.chunk_index,
+                     p.chunk_group is not None) for p in pages]
+        assert shape(restored) == shape(original)
+        old_groups = {p.chunk_group for p in original if p.chunk_group}
+        new_groups = {p.chunk_group for p in restored if p.chunk_group}
+        assert len(new_groups) == 1 and not new_groups & old_groups
+        chunk = next(p for p in restored if p.chunk_group)
+        assert len(cm.chunk_page_ids(chunk.page_id)) == len(
+            [p for p in original if p.chunk_group])
+    
+    def test_restore_over_budget_leaves_no_pages(self):
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.exceptions import ContextBudgetExceededError
+        kernel = AgentOSKernel()
+        cm = kernel.context_manager
+        pid = kernel.spawn_agent(name="Big", task="Lots of context")
+        checkpoint_id = kernel.create_checkpoint(pid)
+        processes = set(kernel.scheduler.processes)
+        usage = cm.current_usage
+        
+        # 单个页面放得下，整批放不下
+        cm.per_agent_token_limit = sum(p.tokens for p in cm.export_agent_pages(pid)) - 1
+        with pytest.raises(ContextBudgetExceededError):
+            kernel.restore_checkpoint(checkpoint_id)
+        assert set(cm.agent_pages) <= processes
+        assert cm.current_usage == usage
+        assert set(kernel.scheduler.processes) == processes
+    
+    def test_list_checkpoints_newest_first(self):
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        pid = kernel.spawn_agent(name="Worker", task="Checkpoint me")
+        first = kernel.create_checkpoint(pid, description="first")
+        second = kernel.create_checkpoint(pid, description="second")
+        
+        infos = kernel.list_checkpoints(pid)
+        assert [i.checkpoint_id for i in infos] == [second, first]
+        assert infos[0].description == "second"
+        assert infos[0].previous_checkpoint == first
+        assert infos[0].page_count == 3
+        assert infos[1].previous_checkpoint is None
+        assert kernel.list_checkpoints("other") == []
+
+
+class TestWarmRestart:
+    """测试调度器状态热恢复"""
+    
+    def test_scheduler_state_survives_restart(self, tmp_path):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        from agent_os_kernel.core.scheduler import AgentState
+        url = f"file://{tmp_path}"
+        
+        kernel = AgentOSKernel(config=KernelConfig(storage_url=url))
+        pid = kernel.spawn_agent(name="Worker", task="Persist me", priority=20)
+        kernel.shutdown()
+        
+        restarted = AgentOSKernel(config=KernelConfig(storage_url=url,
+                                                      restore_scheduler_state=True))
+        process = restarted.scheduler.processes[pid]
+        assert process.name == "Worker"
+        assert process.state == AgentState.READY
+        assert restarted.scheduler.schedule() is process
+    
+    def test_context_restored_from_snapshot_and_wal(self, tmp_path):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        config = KernelConfig(context_wal_path=str(tmp_path / "context.wal"))
+        
+        kernel = AgentOSKernel(config=config)
+        pid = kernel.spawn_agent(name="Worker", task="Persist my context")
+        kernel.shutdown()
+        assert (tmp_path / "context.wal").read_text() == ""
+        assert (tmp_path / "context.wal.snapshot").exists()
+        
+        restarted = AgentOSKernel(config=config)
+        page_id = restarted.context_manager.allocate_page(pid, "written after the snapshot")
+        expected = set(restarted.context_manager.agent_pages[pid])
+        assert len(expected) == 4
+        restarted.context_manager.wal.close()  # 模拟崩溃：不调用 shutdown
+        
+        recovered = AgentOSKernel(config=config)
+        assert set(recovered.context_manager.agent_pages[pid]) == expected
+        assert page_id in recovered.context_manager.pages_in_memory
+    
+    def test_checkpoint_keeps_wal_with_memory_storage(self, tmp_path):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        config = KernelConfig(context_wal_path=str(tmp_path / "context.wal"))
+        
+        kernel = AgentOSKernel(config=config)
+        pid = kernel.spawn_agent(name="Worker", task="Persist my context")
+        kernel.create_checkpoint(pid)
+        kernel.context_manager.allocate_page(pid, "written after the checkpoint")
+        kernel.context_manager.wal.close()  # 模拟崩溃：不调用 shutdown
+        
+        restarted = AgentOSKernel(config=config)
+        assert len(restarted.context_manager.agent_pages[pid]) == 4
+
+
+class TestStorageFallback:
+    """测试存储不可达时的回退"""
+    
+    def test_required_storage_raises(self):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        from agent_os_kernel.core.storage import StorageManager
+        from agent_os_kernel.core.exceptions import StorageConnectionError
+        with patch.object(StorageManager, "from_url", side_effect=OSError("connection refused")):
+            with pytest.raises(StorageConnectionError):
+                AgentOSKernel(config=KernelConfig(storage_url="postgresql://localhost/db"))
+    
+    def test_optional_storage_falls_back_to_memory(self):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        from agent_os_kernel.core.storage import StorageManager, MemoryStorage
+        with patch.object(StorageManager, "from_url", side_effect=OSError("connection refused")):
+            kernel = AgentOSKernel(config=KernelConfig(
+                storage_url="postgresql://localhost/db",
+                storage_required=False
+            ))
+        assert kernel.storage_degraded is True
+        assert isinstance(kernel.storage._data, MemoryStorage)
+        assert kernel.spawn_agent(name="Local", task="demo")
+
+
+class TestKernelEvents:
+    """测试生命周期事件"""
+    
+    def test_spawn_event_reaches_callbacks(self):
+        import threading
+        from agent_os_kernel import AgentOSKernel, KernelEventType
+        kernel = AgentOSKernel()
+        received = []
+        done = threading.Event()
+        
+        async def async_callback(event):
+            received.append(event)
+            done.set()
+        
+        def failing_callback(event):
+            raise RuntimeError("callback failure must not crash the kernel")
+        
+        kernel.on_event(failing_callback)
+        kernel.on_event(async_callback)
+        pid = kernel.spawn_agent(name="Notifier", task="notify")
+        
+        assert done.wait(timeout=5)
+        assert received[0].event_type == KernelEventType.AGENT_SPAWNED
+        assert received[0].agent_pid == pid
+        assert received[0].data['name'] == "Notifier"
+    
+    def test_terminated_event_after_max_errors(self):
+        import threading
+        from agent_os_kernel import AgentOSKernel, KernelEventType
+        from agent_os_kernel.core.scheduler import AgentState
+        kernel = AgentOSKernel()
+        terminated = threading.Event()
+        kernel.on_event(lambda e: e.event_type == KernelEventType.AGENT_TERMINATED and terminated.set())
+        pid = kernel.spawn_agent(name="Flaky", task="fail")
+        process = kernel.scheduler.processes[pid]
+        
+        for _ in range(3):
+            kernel.record_step_error(process, "boom")
+        
+        assert process.state == AgentState.TERMINATED
+        assert terminated.wait(timeout=5)
+
+
+class TestSubAgents:
+    """测试父子 Agent"""
+    
+    def test_spawn_child_agent(self):
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.exceptions import AgentNotFoundError
+        kernel = AgentOSKernel()
+        parent = kernel.spawn_agent(name="Orchestrator", task="plan")
+        child = kernel.spawn_agent(name="Worker", task="do", parent_pid=parent)
+        
+        assert [p.pid for p in kernel.scheduler.children(parent)] == [child]
+        assert kernel.scheduler.process(child).parent_pid == parent
+        with pytest.raises(AgentNotFoundError):
+            kernel.spawn_agent(name="Orphan", task="x", parent_pid="missing")
+
+
+class TestFailedAgents:
+    """测试错误过多被终止的 Agent 进入死信队列"""
+    
+    def test_failed_agent_recorded_and_respawned(self):
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        pid = kernel.spawn_agent(name="Flaky", task="fail", priority=20)
+        process = kernel.scheduler.processes[pid]
+        for i in range(3):
+            kernel.record_step_error(process, f"boom {i}")
+        
+        [record] = kernel.list_failed_agents(limit=10)
+        assert (record['pid'], record['name'], record['task']) == (pid, "Flaky", "fail")
+        assert (record['last_error'], record['error_count']) == ("boom 2", 3)
+        
+        new_pid = kernel.respawn_failed_agent(pid)
+        
+        assert kernel.scheduler.processes[new_pid].priority == 20
+        assert kernel.scheduler.processes[new_pid].context['task'] == "fail"
+        assert kernel.list_failed_agents() == []
+    
+    def test_respawn_unknown_pid(self):
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.exceptions import AgentNotFoundError
+        with pytest.raises(AgentNotFoundError):
+            AgentOSKernel().respawn_failed_agent("missing")
+
+
+class TestKernelPythonSmoke:
+    """Python 端到端冒烟测试：从存储 URL 构造内核并编排 Agent"""
+    
+    def test_spawn_and_stats_from_storage_url(self):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        kernel = AgentOSKernel(config=KernelConfig(storage_url="memory://"))
+        pid = kernel.spawn_agent("Smoke", "check bindings", 10)
+        stats = kernel.get_stats()
+        
+        assert isinstance(pid, str)
+        assert isinstance(stats, dict)
+        assert stats['total_agents'] == 1
+        assert stats['active_agents'] == 1
@@ -1,7 +1,7 @@
 """测试 LLM Provider"""
 
 import pytest
-from agent_os_kernel.llm.provider import LLMConfig, LLMProvider, ProviderType
+from agent_os_kernel.llm.provider import LLMConfig, LLMProvider, ProviderType, Message
 
 
 class TestLLMConfig:
@@ -79,3 +79,59 @@ class TestProviderType:
         
         pt = ProviderType.from_string("deepseek")
         assert pt == ProviderType.DEEPSEEK
+
+
+class TestMessage:
+    """测试消息序列化"""
+    
+    def test_optional_fields_omitted(self):
+        assert Message(role="user", content="hi").to_dict() == {"role": "user", "content": "hi"}
+    
+    def test_tool_message_round_trip(self):
+        call = {"id": "call_1", "type": "function",
+                "function": {"name": "search", "arguments": "{}"}}
+        assistant = Message(role="assistant", content="", tool_calls=[call])
+        tool = Message(role="tool", content="42", name="search", tool_call_id="call_1")
+        
+        assert tool.to_dict() == {"role": "tool", "content": "42",
+                                  "name": "search", "tool_call_id": "call_1"}
+        assert Message.from_dict(assistant.to_dict()) == assistant
+        assert Message.from_dict(tool.to_dict()) == tool
+
+
+class TestCompletionOptions:
+    """测试生成参数到各 Provider 请求字段的映射"""
+    
+    def test_provider_field_mapping(self):
+        from agent_os_kernel.llm.provider import CompletionOptions, Function
+        search = Function(name="search", description="web search",
+                          parameters={"type": "object", "properties": {}})
+        options = (CompletionOptions()
+                   .with_temperature(1.5).with_max_tokens(256)
+                   .with_stop("END").with_top_p(0.9).with_tools(search))
+        
+        openai = options.to_request(ProviderType.OPENAI)
+        anthropic = options.to_request(ProviderType.ANTHROPIC)
+        minimax = options.to_request(ProviderType.MINIMAX)
+        
+        assert openai == {
+            "temperature": 1.5, "max_tokens": 256, "top_p": 0.9, "stop": ["END"],
+            "tools": [{"type": "function", "function": {
+                "name": "search", "description": "web search",
+                "parameters": {"type": "object", "properties": {}}}}],
+            "tool_choice": "auto",
+        }
+        assert anthropic["temperature"] == 1.0
+        assert anthropic["stop_sequences"] == ["END"]
+        assert anthropic["tools"][0]["input_schema"] == search.parameters
+        assert minimax["temperature"] == 1.0
+        assert "tool_choice" not in minimax
+    
+    def test_defaults_from_config(self):
+        from agent_os_kernel.llm.provider import CompletionOptions
+        config = LLMConfig(provider=ProviderType.MINIMAX, model="abab", max_tokens=512,
+                           temperature=0.0)
+        
+        request = CompletionOptions.from_config(config).to_request(ProviderType.MINIMAX)
+        
+        assert request == {"temperature": 0.01, "max_tokens": 512}
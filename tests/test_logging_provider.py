@@ -0,0 +1,90 @@
+"""测试 LLM 请求/响应日志中间件"""
+
+import json
+import pytest
+
+from agent_os_kernel.llm.fallback import FallbackProvider
+from agent_os_kernel.llm.logging_provider import LoggingProvider, redact_pii
+from agent_os_kernel.llm.mock_provider import MockProvider
+from agent_os_kernel.llm.provider import LLMConfig, Message, ModelPrice, ServerError
+
+
+def mock_provider(api_key="sk-secret-key", **kwargs):
+    provider = MockProvider(
+        config=LLMConfig(provider="mock", model="mock-model", api_key=api_key),
+        **kwargs
+    )
+    provider.set_delay(0)
+    return provider
+
+
+class TestLoggingProvider:
+    """测试日志记录与脱敏"""
+    
+    @pytest.mark.asyncio
+    async def test_writes_redacted_records(self, tmp_path):
+        log_file = str(tmp_path / "llm.jsonl")
+        provider = LoggingProvider(mock_provider(responses=["Mail me at bob@example.com"]),
+                                   log_file=log_file, redactor=redact_pii)
+        
+        response = await provider.complete([
+            Message(role="user", content="my key is sk-secret-key, call 555-123-4567"),
+        ])
+        
+        # 返回给调用方的响应不受影响
+        assert response.content == "Mail me at bob@example.com"
+        with open(log_file, encoding="utf-8") as f:
+            record = json.loads(f.readline())
+        assert record['method'] == "complete"
+        assert record['request'][0]['content'] == "my key is [REDACTED], call [REDACTED]"
+        assert record['response'] == "Mail me at [REDACTED]"
+        assert "latency_ms" in record and "input_tokens" in record
+        assert "sk-secret-key" not in open(log_file, encoding="utf-8").read()
+    
+    @pytest.mark.asyncio
+    async def test_composes_with_fallback(self, tmp_path):
+        log_file = str(tmp_path / "llm.jsonl")
+        failing = mock_provider(fail_after=0)
+        provider = LoggingProvider(FallbackProvider([failing, mock_provider(responses=["ok"])]),
+                                   log_file=log_file)
+        
+        response = await provider.complete([Message(role="user", content="hi")])
+        
+        assert response.content == "ok"
+        assert provider.failover_count == 1
+        with open(log_file, encoding="utf-8") as f:
+            records = [json.loads(line) for line in f]
+        assert [r['response'] for r in records] == ["ok"]
+    
+    @pytest.mark.asyncio
+    async def test_redacts_fallback_member_keys(self, tmp_path):
+        log_file = str(tmp_path / "llm.jsonl")
+        secondary = mock_provider(api_key="sk-secondary-key", responses=["ok"])
+        provider = LoggingProvider(FallbackProvider([mock_provider(), secondary]),
+                                   log_file=log_file)
+        
+        await provider.complete([Message(role="user", content="keys: sk-secondary-key")])
+        
+        with open(log_file, encoding="utf-8") as f:
+            record = json.loads(f.readline())
+        assert record['request'][0]['content'] == "keys: [REDACTED]"
+    
+    def test_forwards_pricing_to_fallback(self):
+        priced = mock_provider()
+        priced.PRICING = {"gpt-4o": ModelPrice(0.005, 0.015)}
+        provider = LoggingProvider(FallbackProvider([mock_provider(), priced]))
+        
+        assert provider.price_for("gpt-4o").input_per_1k == 0.005
+        assert provider.price_for("unknown") is None
+    
+    @pytest.mark.asyncio
+    async def test_logs_errors(self, tmp_path):
+        log_file = str(tmp_path / "llm.jsonl")
+        provider = LoggingProvider(mock_provider(fail_after=0), log_file=log_file)
+        
+        with pytest.raises(ServerError):
+            await provider.complete([Message(role="user", content="hi")])
+        
+        with open(log_file, encoding="utf-8") as f:
+            record = json.loads(f.readline())
+        assert record['error'].startswith("ServerError")
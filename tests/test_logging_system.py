@@ -0,0 +1,115 @@
+"""测试日志上下文"""
+
+import logging
+
+import pytest
+
+from agent_os_kernel.core.logging_system import (
+    StructuredLogger,
+    current_log_context,
+    install_log_context,
+    log_context,
+)
+
+
+class _Capture(logging.Handler):
+    def __init__(self):
+        super().__init__()
+        self.records = []
+
+    def emit(self, record):
+        self.records.append(record)
+
+
+@pytest.fixture
+def capture():
+    install_log_context()
+    handler = _Capture()
+    root = logging.getLogger()
+    root.addHandler(handler)
+    yield handler
+    root.removeHandler(handler)
+
+
+class TestLogContext:
+    """测试日志上下文作用域"""
+
+    def test_nested_scopes(self):
+        """测试嵌套作用域合并与恢复"""
+        with log_context(iteration=1):
+            with log_context(agent_pid="abc", iteration=2):
+                assert current_log_context() == {"iteration": 2, "agent_pid": "abc"}
+            assert current_log_context() == {"iteration": 1}
+        assert current_log_context() == {}
+
+    def test_records_carry_context(self, capture):
+        """测试标准 logging 记录带上下文字段"""
+        log = logging.getLogger("test.log_context")
+        with log_context(agent_pid="1234567890abcdef", iteration=3):
+            log.warning("inside")
+        log.warning("outside")
+
+        inside, outside = capture.records[-2:]
+        assert inside.agent_pid == "1234567890abcdef"
+        assert inside.iteration == 3
+        assert inside.log_context == "[pid=12345678 iter=3] "
+        assert outside.agent_pid == "-"
+        assert outside.log_context == ""
+
+    def test_scheduler_methods_traced(self, capture):
+        """测试调度器方法的日志带 agent_pid"""
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess
+        scheduler = AgentScheduler()
+        scheduler.add_process(AgentProcess(pid="proc-1", name="worker"))
+        scheduler.terminate_process("proc-1", "error")
+
+        record = next(r for r in capture.records if "Terminated worker" in r.getMessage())
+        assert record.agent_pid == "proc-1"
+
+    def test_structured_logger_includes_context(self):
+        """测试 StructuredLogger 的 extra 合并上下文"""
+        records = []
+        structured = StructuredLogger("test")
+        structured.add_handler(records.append)
+        with log_context(agent_pid="p1"):
+            structured.info("hello", step=1)
+        assert records[0].extra == {"agent_pid": "p1", "step": 1}
+
+
+class TestKernelLogContext:
+    """测试内核安装日志上下文的时机"""
+
+    def test_installed_by_kernel_not_import(self):
+        """测试导入内核模块不修改 logging，创建内核时才安装"""
+        import os
+        import subprocess
+        import sys
+        script = (
+            "import logging\n"
+            "factory = logging.getLogRecordFactory()\n"
+            "from agent_os_kernel import AgentOSKernel\n"
+            "assert logging.getLogRecordFactory() is factory\n"
+            "AgentOSKernel()\n"
+            "assert logging.getLogRecordFactory() is not factory\n"
+        )
+        env = dict(os.environ, PYTHONPATH=os.pathsep.join(sys.path))
+        result = subprocess.run([sys.executable, "-c", script], env=env,
+                                capture_output=True, text=True, timeout=60)
+        assert result.returncode == 0, result.stderr
+
+    def test_defaults_filter_fills_missing_context(self):
+        """测试处理器过滤器为记录工厂之外创建的记录补上空上下文"""
+        import io
+        from agent_os_kernel.core.logging_system import LogContextDefaults
+        stream = io.StringIO()
+        handler = logging.StreamHandler(stream)
+        handler.addFilter(LogContextDefaults())
+        handler.setFormatter(logging.Formatter("%(log_context)s%(message)s"))
+
+        handler.handle(logging.LogRecord("raw", logging.INFO, __file__, 1, "plain", None, None))
+        install_log_context()
+        with log_context(agent_pid="1234567890abcdef"):
+            handler.handle(logging.getLogger("ctx").makeRecord(
+                "ctx", logging.INFO, __file__, 1, "traced", None, None))
+
+        assert stream.getvalue().splitlines() == ["plain", "[pid=12345678] traced"]
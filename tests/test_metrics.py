@@ -3,6 +3,22 @@
 import pytest
 
 
+class TestMetricsCounter:
+    """测试计数器累加"""
+    
+    def test_first_sample_counted_once(self):
+        """测试新计数器的第一次累加不会被计入两次"""
+        from agent_os_kernel.core.metrics import MetricsCollector
+        metrics = MetricsCollector()
+        metrics.counter("calls", 2)
+        assert metrics.get("calls").value == 2
+        
+        metrics.counter("calls", 3)
+        assert metrics.get("calls").value == 5
+        assert metrics.get("calls").count == 2
+        assert metrics.get("calls").sum_ == 5
+
+
 class TestMetricsCollectorExists:
     """测试指标收集器存在"""
     
@@ -13,3 +29,43 @@ class TestMetricsCollectorExists:
     def test_type_import(self):
         from agent_os_kernel.core.metrics import MetricType
         assert MetricType is not None
+
+
+class TestMetricsBatch:
+    """测试批量累积指标"""
+    
+    def test_batch_flushes_at_threshold(self):
+        from agent_os_kernel.core.metrics import MetricsCollector
+        metrics = MetricsCollector()
+        batch = metrics.batch(flush_every=3, flush_interval=60)
+        
+        batch.counter("hits")
+        batch.histogram("latency", 0.2)
+        assert metrics._metrics.get("latency") is None
+        assert batch.pending == 2
+        
+        batch.counter("hits")
+        assert batch.pending == 0
+        assert metrics._metrics["hits"].value == 2
+        assert metrics._metrics["latency"].buckets[0.5] == 1
+    
+    def test_reads_flush_all_threads(self):
+        import threading
+        from agent_os_kernel.core.metrics import MetricsCollector
+        metrics = MetricsCollector()
+        
+        def work():
+            batch = metrics.batch(flush_every=1000, flush_interval=60)
+            for _ in range(500):
+                batch.counter("hits", labels={"tool": "calc"})
+        
+        threads = [threading.Thread(target=work) for _ in range(4)]
+        for t in threads:
+            t.start()
+        for t in threads:
+            t.join()
+        
+        hits = metrics.get("hits", {"tool": "calc"})
+        assert hits.value == 2000
+        assert hits.count == 2000
+        assert metrics.flush() == 0
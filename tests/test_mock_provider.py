@@ -25,3 +25,58 @@ class TestMockErrorProvider:
         """测试存在"""
         from agent_os_kernel.llm.mock_provider import MockErrorProvider
         assert MockErrorProvider is not None
+
+
+class TestScriptedMockProvider:
+    """测试预设响应、请求记录与失败注入"""
+    
+    @pytest.mark.asyncio
+    async def test_scripted_responses_and_tool_calls(self):
+        from agent_os_kernel.llm.mock_provider import MockProvider, MockResponse
+        from agent_os_kernel.llm.provider import Message
+        tool_call = {"id": "call_1", "type": "function",
+                     "function": {"name": "calculator", "arguments": "{\"expression\": \"1+1\"}"}}
+        provider = MockProvider(responses=[
+            MockResponse(tool_calls=[tool_call], finish_reason="tool_calls"),
+            "The answer is 2",
+        ])
+        provider.set_delay(0)
+        
+        first = await provider.complete([Message(role="user", content="what is 1+1?")])
+        second = await provider.complete([Message(role="user", content="continue")])
+        
+        assert first.tool_calls == [tool_call]
+        assert first.finish_reason == "tool_calls"
+        assert second.content == "The answer is 2"
+        assert [msgs[0].content for msgs in provider.requests] == ["what is 1+1?", "continue"]
+        
+        # 队列耗尽后回到关键字匹配
+        third = await provider.complete([Message(role="user", content="hello")])
+        assert third.content == "Hello! I'm a mock AI assistant."
+    
+    @pytest.mark.asyncio
+    async def test_fail_after_n_calls(self):
+        from agent_os_kernel.llm.mock_provider import MockProvider
+        from agent_os_kernel.llm.provider import Message, RateLimitedError
+        provider = MockProvider(fail_after=1, fail_with=RateLimitedError("quota"))
+        provider.set_delay(0)
+        messages = [Message(role="user", content="hi")]
+        
+        await provider.complete(messages)
+        with pytest.raises(RateLimitedError):
+            await provider.complete(messages)
+        assert len(provider.requests) == 2
+    
+    @pytest.mark.asyncio
+    async def test_streaming_and_embeddings(self):
+        from agent_os_kernel.llm.mock_provider import MockProvider
+        from agent_os_kernel.llm.provider import Message
+        provider = MockProvider(responses=["one two three"])
+        provider.set_delay(0)
+        
+        chunks = [chunk async for chunk in provider.stream_complete([Message(role="user", content="go")])]
+        assert "".join(chunks).split() == ["one", "two", "three"]
+        
+        first, again, other = await provider.embeddings(["a", "a", "b"])
+        assert first == again
+        assert first != other
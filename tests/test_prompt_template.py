@@ -0,0 +1,50 @@
+"""测试提示词模板"""
+
+import pytest
+
+from agent_os_kernel.core.prompt_template import PromptTemplate
+from agent_os_kernel.core.exceptions import TemplateVariableError, ConfigurationError
+
+
+class TestPromptTemplate:
+    """测试占位符解析与渲染"""
+    
+    def test_render(self):
+        template = PromptTemplate("You are {{name}}. {{ name }} works on {{task}}; keep {braces}.")
+        
+        assert template.variables == ["name", "task"]
+        assert template.render({"name": "A", "task": "x"}, task="y") == \
+            "You are A. A works on y; keep {braces}."
+    
+    def test_missing_variable_raises(self):
+        with pytest.raises(TemplateVariableError) as exc:
+            PromptTemplate("{{name}}: {{task}}").render(name="A")
+        assert exc.value.details['missing'] == ["task"]
+
+
+class TestPromptTemplateUsage:
+    """测试内核与压缩器使用可覆盖的模板"""
+    
+    def test_kernel_system_prompt_template(self):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        kernel = AgentOSKernel(config=KernelConfig(
+            system_prompt_template="你是{{name}}，任务：{{task}}"))
+        pid = kernel.spawn_agent(name="助手", task="翻译")
+        
+        page_id = kernel.scheduler.processes[pid].context['system_page']
+        assert kernel.context_manager.pages_in_memory[page_id].content == "你是助手，任务：翻译"
+        assert kernel.replay_agent(pid).context == kernel.context_manager.get_agent_context(pid)
+        with pytest.raises(ConfigurationError):
+            AgentOSKernel(config=KernelConfig(system_prompt_template="{{role}}"))
+    
+    def test_compressor_summary_prompt(self):
+        from agent_os_kernel.core.optimization.compressor import (
+            ContextCompressor, CompressionConfig, CompressionStrategy
+        )
+        compressor = ContextCompressor(CompressionConfig(
+            max_tokens=1, preserve_recent=1, summary_prompt="Summary:\n{{summary}}"))
+        messages = [{"role": "user", "content": f"message {i}"} for i in range(4)]
+        
+        compressed = compressor.compress_messages(messages, CompressionStrategy.SUMMARIZE)
+        
+        assert compressed[0]["content"].startswith("Summary:\n")
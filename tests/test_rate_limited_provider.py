@@ -0,0 +1,131 @@
+"""测试按 Agent 限速的 Provider"""
+
+import pytest
+
+from agent_os_kernel.core.clock import MockClock
+from agent_os_kernel.llm.mock_provider import MockProvider
+from agent_os_kernel.llm.provider import Message, RateLimitedError
+from agent_os_kernel.llm.rate_limited import RateLimitedProvider
+
+
+class CountingProvider(MockProvider):
+    """记录上游调用次数和收到的参数"""
+
+    def __init__(self):
+        super().__init__()
+        self.calls = 0
+        self.last_kwargs = None
+        self.set_delay(0)
+
+    async def chat(self, messages, **kwargs):
+        self.calls += 1
+        self.last_kwargs = kwargs
+        return await super().chat(messages, **kwargs)
+
+
+MESSAGES = [Message(role="user", content="hello")]
+
+
+class TestRateLimitedProvider:
+    """测试令牌桶限速"""
+
+    @pytest.mark.asyncio
+    async def test_rejects_locally_and_refills(self):
+        clock = MockClock()
+        inner = CountingProvider()
+        provider = RateLimitedProvider(inner, requests_per_minute=2, clock=clock)
+
+        await provider.chat(MESSAGES, agent_pid="a1", temperature=0.1)
+        await provider.chat(MESSAGES, agent_pid="a1")
+        with pytest.raises(RateLimitedError):
+            await provider.chat(MESSAGES, agent_pid="a1")
+        assert inner.calls == 2
+        assert "agent_pid" not in inner.last_kwargs
+
+        clock.advance(30)
+        await provider.chat(MESSAGES, agent_pid="a1")
+        assert inner.calls == 3
+        assert provider.get_stats()["rejected_requests"] == 1
+
+    @pytest.mark.asyncio
+    async def test_agents_have_independent_buckets(self):
+        clock = MockClock()
+        provider = RateLimitedProvider(CountingProvider(), requests_per_minute=1,
+                                       per_agent_limits={"vip": 3}, clock=clock)
+
+        await provider.chat(MESSAGES, agent_pid="noisy")
+        with pytest.raises(RateLimitedError):
+            await provider.chat(MESSAGES, agent_pid="noisy")
+        await provider.chat(MESSAGES, agent_pid="quiet")
+        for _ in range(3):
+            await provider.chat(MESSAGES, agent_pid="vip")
+        assert provider.remaining("vip") == 0
+
+        provider.set_limit("noisy", 10)
+        await provider.chat(MESSAGES, agent_pid="noisy")
+
+    def test_invalid_limits(self):
+        with pytest.raises(ValueError):
+            RateLimitedProvider(CountingProvider(), requests_per_minute=0)
+        with pytest.raises(ValueError):
+            RateLimitedProvider(CountingProvider(), per_agent_limits={"a": -1})
+
+    @pytest.mark.asyncio
+    async def test_prunes_refilled_buckets(self):
+        clock = MockClock()
+        provider = RateLimitedProvider(CountingProvider(), requests_per_minute=60, clock=clock)
+        provider.PRUNE_THRESHOLD = 4
+        provider._prune_at = 4
+
+        for pid in ("a1", "a2", "a3", "a4"):
+            await provider.chat(MESSAGES, agent_pid=pid)
+        clock.advance(1)
+        await provider.chat(MESSAGES, agent_pid="a5")
+
+        assert provider.get_stats()["tracked_agents"] == 1
+        assert provider.remaining("a1") == 60
+
+
+class TestRateLimitKwargs:
+    """测试按 Provider 链决定是否传入 agent_pid"""
+
+    def test_only_for_rate_limited_chain(self):
+        from agent_os_kernel.llm import LoggingProvider, rate_limit_kwargs
+        plain = CountingProvider()
+        limited = RateLimitedProvider(CountingProvider())
+
+        assert rate_limit_kwargs(plain, "a1") == {}
+        assert rate_limit_kwargs(limited, "a1") == {"agent_pid": "a1"}
+        assert rate_limit_kwargs(LoggingProvider(limited), "a1") == {"agent_pid": "a1"}
+        assert rate_limit_kwargs(limited, None) == {}
+
+    def test_kernel_llm_complete_limits_per_agent(self):
+        import asyncio
+        from agent_os_kernel import AgentOSKernel
+        kernel = AgentOSKernel()
+        first = kernel.spawn_agent(name="first", task="t")
+        second = kernel.spawn_agent(name="second", task="t")
+        provider = RateLimitedProvider(CountingProvider(), requests_per_minute=1, clock=MockClock())
+
+        asyncio.run(kernel.llm_complete(first, provider, MESSAGES))
+        with pytest.raises(RateLimitedError):
+            asyncio.run(kernel.llm_complete(first, provider, MESSAGES))
+        asyncio.run(kernel.llm_complete(second, provider, MESSAGES))
+        assert provider.remaining(first) == 0
+        assert provider.remaining(None) == 1
+
+    @pytest.mark.asyncio
+    async def test_react_agent_passes_pid(self):
+        from agent_os_kernel.agents.react import ReActAgent
+        from agent_os_kernel.llm.provider import LLMResponse
+
+        class PromptProvider(CountingProvider):
+            async def complete(self, prompt, **kwargs):
+                return LLMResponse(content="thought", model="mock-model", usage={})
+
+        provider = RateLimitedProvider(PromptProvider(), requests_per_minute=1, clock=MockClock())
+        agent = ReActAgent(name="r", llm=provider, agent_pid="react-1")
+
+        await agent._think("q", {})
+        assert provider.remaining("react-1") == 0
+        assert provider.remaining(None) == 1
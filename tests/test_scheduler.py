@@ -27,3 +27,614 @@ class TestSchedulerBasic:
         """测试调度器存在"""
         from agent_os_kernel.core.scheduler import AgentScheduler
         assert AgentScheduler is not None
+
+
+class TestSchedulerErrors:
+    """测试错误计数与自动终止"""
+    
+    def test_terminated_after_max_errors(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess, AgentState
+        scheduler = AgentScheduler()
+        process = AgentProcess(pid="p1", name="Flaky")
+        scheduler.add_process(process)
+        
+        assert scheduler.record_error("p1", "timeout") is False
+        assert scheduler.record_error("p1", "timeout") is False
+        assert scheduler.record_error("p1", "rate limited") is True
+        
+        assert process.state == AgentState.TERMINATED
+        assert process.error_count == 3
+        assert process.last_error == "rate limited"
+        assert scheduler.stats['total_errors'] == 1
+        assert scheduler.record_error("p1", "late") is False
+
+
+class TestSchedulerYield:
+    """测试协作式让出"""
+    
+    def test_yielded_process_rejoins_ready_queue(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess, AgentState
+        scheduler = AgentScheduler()
+        first = AgentProcess(pid="p1", name="First", priority=10)
+        second = AgentProcess(pid="p2", name="Second", priority=10)
+        scheduler.add_process(first)
+        scheduler.add_process(second)
+        
+        assert scheduler.schedule() is first
+        assert scheduler.yield_process("p2") is False
+        assert scheduler.yield_process("p1") is True
+        
+        assert first.state == AgentState.READY
+        assert scheduler.running is None
+        assert scheduler.ready_queue.qsize() == 2
+        assert scheduler.schedule() is second
+        assert scheduler.stats['total_yields'] == 1
+
+
+class TestSchedulerSnapshot:
+    """测试调度器状态快照"""
+    
+    def test_snapshot_round_trip(self):
+        import json
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess, AgentState
+        scheduler = AgentScheduler()
+        for pid, priority in (("p1", 10), ("p2", 20), ("p3", 30)):
+            scheduler.add_process(AgentProcess(pid=pid, name=pid, priority=priority))
+        scheduler.schedule()
+        scheduler.wait_process("p3", "quota exceeded")
+        scheduler.request_resources("p2", 100)
+        
+        snapshot = json.loads(json.dumps(scheduler.snapshot()))
+        restored = AgentScheduler()
+        restored.restore_from(snapshot)
+        
+        assert set(restored.processes) == {"p1", "p2", "p3"}
+        assert restored.running.pid == "p1"
+        assert restored.waiting_queue["p3"].waiting_reason == "quota exceeded"
+        assert restored.processes["p3"].state == AgentState.WAITING
+        assert restored.ready_queue.qsize() == 1
+        assert restored.quota_manager.per_agent_usage["p2"]["tokens"] == 100
+    
+    def test_restore_drops_missing_children(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess
+        scheduler = AgentScheduler()
+        scheduler.add_process(AgentProcess(pid="parent", name="parent"))
+        scheduler.add_process(AgentProcess(pid="child", name="child", parent_pid="parent"))
+        snapshot = scheduler.snapshot()
+        snapshot['processes'] = [p for p in snapshot['processes'] if p['pid'] != "child"]
+        snapshot['processes'][0]['child_pids'].append("gone")
+        
+        restored = AgentScheduler()
+        restored.restore_from(snapshot)
+        assert restored.processes["parent"].child_pids == []
+
+
+class TestWeightedFairScheduling:
+    """测试加权公平调度"""
+    
+    def test_proportional_share(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess
+        scheduler = AgentScheduler(fair_share=True)
+        light = AgentProcess(pid="light", name="Light", weight=1.0)
+        heavy = AgentProcess(pid="heavy", name="Heavy", weight=2.0)
+        scheduler.add_process(light)
+        scheduler.add_process(heavy)
+        
+        runs = {"light": 0, "heavy": 0}
+        for _ in range(300):
+            process = scheduler.schedule()
+            runs[process.pid] += 1
+            process.token_usage += 100
+            scheduler.yield_process(process.pid)
+        
+        assert runs["heavy"] / runs["light"] == pytest.approx(2.0, rel=0.05)
+    
+    def test_default_weight_is_even(self):
+        from agent_os_kernel.core.scheduler import AgentProcess
+        process = AgentProcess(pid="p", name="p")
+        assert process.weight == 1.0
+        assert AgentProcess.from_dict(process.to_dict()).weight == 1.0
+
+
+class TestGangScheduling:
+    """测试组调度"""
+    
+    def test_gang_runs_together_or_not_at_all(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess, AgentState
+        scheduler = AgentScheduler()
+        a = AgentProcess(pid="a", name="A", priority=10)
+        b = AgentProcess(pid="b", name="B", priority=20)
+        solo = AgentProcess(pid="solo", name="Solo", priority=30)
+        scheduler.add_process_to_group(a, "team")
+        scheduler.add_process_to_group(b, "team")
+        scheduler.add_process(solo)
+        scheduler.wait_process("b", "io")
+        
+        # b 未就绪，整组都不运行
+        assert scheduler.schedule() is solo
+        assert a.state == AgentState.READY
+        
+        scheduler.yield_process("solo")
+        scheduler.wakeup_process("b")
+        assert scheduler.schedule() is a
+        assert b.state == AgentState.RUNNING
+        assert scheduler.get_process_stats()['groups'] == {"team": ["a", "b"]}
+        
+        scheduler.yield_process("a")
+        assert a.state == AgentState.READY
+        assert b.state == AgentState.READY
+    
+    def test_max_gang_size(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess
+        from agent_os_kernel.core.exceptions import SchedulingError
+        scheduler = AgentScheduler(max_gang_size=1)
+        scheduler.add_process_to_group(AgentProcess(pid="a", name="A"), "team")
+        with pytest.raises(SchedulingError):
+            scheduler.add_process_to_group(AgentProcess(pid="b", name="B"), "team")
+
+
+class TestMaxConcurrent:
+    """测试并发运行上限"""
+    
+    def test_running_never_exceeds_max_concurrent(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess, AgentState
+        scheduler = AgentScheduler(max_concurrent=3)
+        for i in range(6):
+            scheduler.add_process(AgentProcess(pid=f"p{i}", name=f"P{i}", priority=i * 10))
+        
+        batch = scheduler.schedule_batch(10)
+        assert [p.pid for p in batch] == ["p0", "p1", "p2"]
+        assert all(p.state == AgentState.RUNNING for p in batch)
+        
+        seen = set()
+        while batch:
+            assert len(scheduler.running_processes()) <= 3
+            assert len(batch) <= 3
+            seen.update(p.pid for p in batch)
+            scheduler.terminate_process(batch[0].pid)
+            batch = scheduler.schedule_batch(10)
+        assert seen == {f"p{i}" for i in range(6)}
+    
+    def test_gang_larger_than_limit_is_deferred(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess, AgentState
+        scheduler = AgentScheduler(max_concurrent=2)
+        for pid in ("a", "b", "c"):
+            scheduler.add_process_to_group(AgentProcess(pid=pid, name=pid, priority=10), "team")
+        solo = AgentProcess(pid="solo", name="Solo", priority=30)
+        scheduler.add_process(solo)
+        
+        assert scheduler.schedule() is solo
+        assert [p.pid for p in scheduler.schedule_batch(2)] == ["solo"]
+        assert scheduler.processes["a"].state == AgentState.READY
+        assert scheduler.get_process_stats()['running_count'] == 1
+    
+    def test_invalid_max_concurrent(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler
+        from agent_os_kernel.core.exceptions import SchedulingError
+        with pytest.raises(SchedulingError):
+            AgentScheduler(max_concurrent=0)
+
+
+class TestProcessQueries:
+    """测试进程查询接口"""
+    
+    def test_running_processes_and_lookup(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess
+        scheduler = AgentScheduler()
+        scheduler.add_process(AgentProcess(pid="p1", name="First", priority=10))
+        scheduler.add_process(AgentProcess(pid="p2", name="Second", priority=20))
+        
+        assert scheduler.running_processes() == []
+        scheduler.schedule()
+        
+        running = scheduler.running_processes()
+        assert [p.pid for p in running] == ["p1"]
+        
+        # 返回的是副本，修改不影响调度器
+        running[0].name = "Changed"
+        assert scheduler.process("p1").name == "First"
+        assert scheduler.process("missing") is None
+        assert [p.pid for p in scheduler.list_processes()] == ["p1", "p2"]
+
+
+class TestProcessTree:
+    """测试父子进程关系"""
+    
+    def _scheduler(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess
+        from agent_os_kernel.core.clock import MockClock
+        clock = MockClock()
+        scheduler = AgentScheduler(clock=clock)
+        for pid, parent in [("root", None), ("worker1", "root"), ("worker2", "root"),
+                            ("helper", "worker1"), ("other", None)]:
+            scheduler.add_process(AgentProcess(pid=pid, name=pid, parent_pid=parent,
+                                               created_at=clock.now()))
+            clock.advance(1)
+        return scheduler
+    
+    def test_children_and_tree(self):
+        scheduler = self._scheduler()
+        
+        assert [p.pid for p in scheduler.children("root")] == ["worker1", "worker2"]
+        assert scheduler.children("missing") == []
+        tree = scheduler.tree()
+        assert [node['pid'] for node in tree] == ["root", "other"]
+        assert tree[0]['children'][0]['children'][0]['pid'] == "helper"
+        assert scheduler.tree("worker1")[0]['children'][0]['name'] == "helper"
+    
+    def test_cascade_terminate(self):
+        from agent_os_kernel.core.scheduler import AgentState
+        scheduler = self._scheduler()
+        
+        scheduler.terminate_process("worker2")
+        assert scheduler.process("root").state != AgentState.TERMINATED
+        
+        scheduler.terminate_process("root", cascade=True)
+        assert all(scheduler.process(pid).state == AgentState.TERMINATED
+                   for pid in ("root", "worker1", "helper"))
+        assert scheduler.process("other").state != AgentState.TERMINATED
+
+
+class TestSchedulerShutdown:
+    """测试调度器后台任务生命周期"""
+    
+    def test_shutdown_stops_background_tasks(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler
+        from agent_os_kernel.core.exceptions import SchedulingError
+        scheduler = AgentScheduler()
+        ticks = []
+        
+        def tick(stop):
+            while not stop.wait(0.01):
+                ticks.append(1)
+        
+        thread = scheduler.spawn_background_task(tick, "tick")
+        assert scheduler.shutdown(timeout=2.0) is True
+        assert not thread.is_alive()
+        
+        with pytest.raises(SchedulingError):
+            scheduler.spawn_background_task(tick, "late")
+
+
+class TestWaitReasons:
+    """测试结构化等待原因与自动唤醒"""
+    
+    def test_resource_waiter_resumes_after_quota_refresh(self):
+        from agent_os_kernel.core.scheduler import (
+            AgentScheduler, AgentProcess, AgentState, ResourceQuota, WaitReasonKind
+        )
+        scheduler = AgentScheduler(quota=ResourceQuota(
+            max_tokens_per_window=1000, max_tokens_per_request=1000))
+        scheduler.add_process(AgentProcess(pid="p1", name="Hungry"))
+        
+        assert scheduler.request_resources("p1", 200) is True
+        assert scheduler.request_resources("p1", 200) is False
+        process = scheduler.process("p1")
+        assert process.state == AgentState.WAITING
+        assert process.wait_reason.kind == WaitReasonKind.RESOURCE_TOKENS
+        assert process.wait_reason.tokens == 200
+        
+        # 配额窗口未刷新，保持等待
+        scheduler.schedule()
+        assert scheduler.process("p1").state == AgentState.WAITING
+        
+        scheduler.quota_manager.window_start -= scheduler.quota_manager.quota.window_seconds
+        scheduler.schedule()
+        assert scheduler.process("p1").state != AgentState.WAITING
+        assert scheduler.process("p1").wait_reason is None
+
+    def test_unsatisfiable_resource_request_rejected(self):
+        """测试超过单个 Agent 配额上限的请求直接拒绝，而不是永久等待"""
+        from agent_os_kernel.core.exceptions import QuotaExceededError
+        from agent_os_kernel.core.scheduler import (
+            AgentScheduler, AgentProcess, AgentState, ResourceQuota
+        )
+        scheduler = AgentScheduler(quota=ResourceQuota(
+            max_tokens_per_window=1000, max_tokens_per_request=1000))
+        scheduler.add_process(AgentProcess(pid="p1", name="Greedy"))
+
+        with pytest.raises(QuotaExceededError):
+            scheduler.request_resources("p1", 500)
+        with pytest.raises(QuotaExceededError):
+            scheduler.request_resources("p1", 2000)
+        process = scheduler.process("p1")
+        assert process.state != AgentState.WAITING
+        assert process.wait_reason is None
+        assert scheduler.request_resources("p1", 300) is True
+
+    def test_dependency_waiter_resumes_after_dependency_terminates(self):
+        from agent_os_kernel.core.scheduler import (
+            AgentScheduler, AgentProcess, AgentState, WaitReason
+        )
+        scheduler = AgentScheduler()
+        scheduler.add_process(AgentProcess(pid="dep", name="Dependency"))
+        scheduler.add_process(AgentProcess(pid="p1", name="Dependent"))
+        scheduler.wait_process("p1", WaitReason.dependency("dep"))
+        
+        scheduler.schedule()
+        assert scheduler.process("p1").state == AgentState.WAITING
+        
+        scheduler.terminate_process("dep")
+        scheduler.schedule()
+        assert scheduler.process("p1").state != AgentState.WAITING
+    
+    def test_completion_wakes_idle_loop(self):
+        """测试依赖完成时唤醒等待工作的主循环"""
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess, WaitReason
+        scheduler = AgentScheduler()
+        scheduler.add_process(AgentProcess(pid="dep", name="Dependency"))
+        scheduler.add_process(AgentProcess(pid="p1", name="Dependent"))
+        scheduler.wait_process("p1", WaitReason.dependency("dep"))
+        scheduler.wait_for_work(0)
+        
+        scheduler.complete_process("dep", result="done")
+        assert scheduler.wait_for_work(0)
+    
+    def test_wait_reason_survives_snapshot(self):
+        from agent_os_kernel.core.scheduler import (
+            AgentScheduler, AgentProcess, WaitReason, WaitReasonKind
+        )
+        scheduler = AgentScheduler()
+        scheduler.add_process(AgentProcess(pid="p1", name="Waiter"))
+        scheduler.wait_process("p1", WaitReason.resource_tokens(500))
+        
+        restored = AgentScheduler()
+        restored.restore_from(scheduler.snapshot())
+        reason = restored.process("p1").wait_reason
+        assert reason.kind == WaitReasonKind.RESOURCE_TOKENS
+        assert reason.tokens == 500
+
+
+class TestPriorityInheritance:
+    """测试依赖等待的优先级继承"""
+    
+    def _scheduler(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess, WaitReason
+        from agent_os_kernel.core.clock import MockClock
+        clock = MockClock()
+        scheduler = AgentScheduler(clock=clock)
+        for pid, priority in [("peer1", 50), ("low", 90), ("peer2", 50), ("urgent", 10)]:
+            scheduler.add_process(AgentProcess(pid=pid, name=pid, priority=priority))
+            clock.advance(1)
+        scheduler.wait_process("urgent", WaitReason.dependency("low"))
+        return scheduler
+    
+    def test_dependency_of_high_priority_waiter_runs_before_peers(self):
+        scheduler = self._scheduler()
+        assert scheduler.effective_priority("low") == 10
+        assert scheduler.schedule().pid == "low"
+    
+    def test_boost_reverts_when_dependency_completes(self):
+        scheduler = self._scheduler()
+        scheduler.schedule()
+        scheduler.complete_process("low")
+        
+        assert scheduler.effective_priority("low") == 90
+        assert scheduler.schedule().pid == "urgent"
+    
+    def test_boost_follows_dependency_chain(self):
+        from agent_os_kernel.core.scheduler import AgentProcess, WaitReason
+        scheduler = self._scheduler()
+        scheduler.add_process(AgentProcess(pid="lowest", name="lowest", priority=100))
+        scheduler.wait_process("low", WaitReason.dependency("lowest"))
+        
+        assert scheduler.effective_priority("lowest") == 10
+        assert scheduler.schedule().pid == "lowest"
+
+
+class TestSchedulerSimulation:
+    """测试调度模拟（dry-run）"""
+    
+    def test_simulate_reports_wait_and_turnaround(self):
+        from agent_os_kernel.core.scheduler import (
+            AgentScheduler, SimEvent, SimEventKind
+        )
+        scheduler = AgentScheduler()
+        events = [
+            SimEvent(0, SimEventKind.ARRIVAL, "high", priority=10, time_slice=2),
+            SimEvent(0, SimEventKind.ARRIVAL, "low", priority=60, time_slice=2),
+            SimEvent(5, SimEventKind.COMPLETE, "high"),
+            SimEvent(8, SimEventKind.COMPLETE, "low"),
+        ]
+        
+        report = scheduler.simulate(events, starvation_threshold=3)
+        
+        assert report.cycles == 9
+        assert report.timeline == ["high"] * 5 + ["low"] * 3 + [None]
+        assert report.processes["high"].turnaround_time == 5
+        assert report.processes["low"].turnaround_time == 8
+        assert report.processes["low"].wait_time == 5
+        assert report.processes["low"].starvation_count == 1
+        assert report.total_starvation == 1
+        
+        # 模拟不影响真实调度器
+        assert scheduler.list_processes() == []
+
+
+class TestInjectedClock:
+    """测试注入时钟与 ID 生成器"""
+    
+    def test_time_slice_expiry_is_deterministic(self):
+        from agent_os_kernel.core.clock import MockClock
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess
+        clock = MockClock(start=100.0)
+        scheduler = AgentScheduler(clock=clock)
+        scheduler.add_process(AgentProcess(pid="p1", name="First", time_slice=5))
+        scheduler.add_process(AgentProcess(pid="p2", name="Second", time_slice=5))
+        
+        assert scheduler.schedule().pid == "p1"
+        assert scheduler.process("p1").started_at == 100.0
+        clock.advance(5)
+        assert scheduler.schedule().pid == "p1"
+        clock.advance(0.5)
+        assert scheduler.schedule().pid == "p2"
+    
+    def test_checkpoint_ids_from_generator(self):
+        from agent_os_kernel.core.clock import MockClock, SequentialIdGenerator
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess
+        
+        class _Storage:
+            def __init__(self):
+                self.saved = []
+            
+            def save_checkpoint(self, data):
+                self.saved.append(data)
+                return True
+        
+        storage = _Storage()
+        scheduler = AgentScheduler(storage=storage, clock=MockClock(start=42.0),
+                                   id_generator=SequentialIdGenerator("ckpt-"))
+        scheduler.add_process(AgentProcess(pid="p1", name="Worker"))
+        
+        assert scheduler.suspend_process("p1") == "ckpt-00000001"
+        assert storage.saved[0]['created_at'] == 42.0
+
+
+class TestBulkProcessOperations:
+    """测试批量添加与终止"""
+    
+    def test_add_processes(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess, AgentState
+        scheduler = AgentScheduler()
+        team = [AgentProcess(pid=f"w{i}", name=f"Worker{i}") for i in range(3)]
+        
+        assert scheduler.add_processes(team) == ["w0", "w1", "w2"]
+        assert all(p.state == AgentState.READY for p in team)
+        assert scheduler.schedule().pid == "w0"
+    
+    def test_add_processes_rejects_duplicates_atomically(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess
+        from agent_os_kernel.core.exceptions import SchedulingError
+        scheduler = AgentScheduler()
+        scheduler.add_process(AgentProcess(pid="w1", name="Existing"))
+        
+        with pytest.raises(SchedulingError):
+            scheduler.add_processes([AgentProcess(pid="w0", name="New"),
+                                     AgentProcess(pid="w1", name="Clash")])
+        assert "w0" not in scheduler.processes
+        
+        with pytest.raises(SchedulingError):
+            scheduler.add_processes([AgentProcess(pid="w2", name="A"),
+                                     AgentProcess(pid="w2", name="B")])
+        assert "w2" not in scheduler.processes
+    
+    def test_add_processes_registers_children(self):
+        """测试批量添加的子进程登记到父进程并随父进程级联终止"""
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess, AgentState
+        scheduler = AgentScheduler()
+        scheduler.add_process(AgentProcess(pid="root", name="Root"))
+        scheduler.add_processes([
+            AgentProcess(pid="w0", name="Worker0", parent_pid="root"),
+            AgentProcess(pid="w1", name="Worker1", parent_pid="root"),
+            AgentProcess(pid="h0", name="Helper0", parent_pid="w1"),
+        ])
+        
+        assert scheduler.process("root").child_pids == ["w0", "w1"]
+        assert scheduler.process("w1").child_pids == ["h0"]
+        
+        scheduler.terminate_process("root", cascade=True)
+        assert all(scheduler.process(pid).state == AgentState.TERMINATED
+                   for pid in ("root", "w0", "w1", "h0"))
+    
+    def test_terminate_group(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess, AgentState
+        scheduler = AgentScheduler()
+        scheduler.add_processes([AgentProcess(pid=f"w{i}", name=f"Worker{i}") for i in range(3)])
+        scheduler.terminate_process("w2")
+        
+        assert scheduler.terminate_group(["w0", "w1", "w2", "missing"]) == 2
+        assert all(p.state == AgentState.TERMINATED for p in scheduler.processes.values())
+        assert scheduler.stats['total_completed'] == 3
+
+
+class TestSchedulerStats:
+    """测试类型化统计"""
+    
+    def test_collect_stats_matches_get_process_stats(self):
+        import dataclasses
+        import json
+        from agent_os_kernel.core.clock import MockClock
+        from agent_os_kernel.core.scheduler import (
+            AgentScheduler, AgentProcess, SchedulerStats
+        )
+        scheduler = AgentScheduler(clock=MockClock())
+        scheduler.add_processes([AgentProcess(pid="a", name="A"), AgentProcess(pid="b", name="B")])
+        scheduler.schedule()
+        
+        stats = scheduler.collect_stats()
+        assert isinstance(stats, SchedulerStats)
+        assert stats.running == "A"
+        assert stats.state_distribution == {"running": 1, "ready": 1}
+        assert scheduler.get_process_stats() == stats.to_dict()
+        assert list(scheduler.get_process_stats()) == [
+            f.name for f in dataclasses.fields(SchedulerStats)
+        ]
+        json.dumps(scheduler.get_process_stats())
+
+
+class TestTaskQueuePersistence:
+    """测试任务队列持久化与恢复"""
+    
+    def test_task_status_follows_process(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess
+        from agent_os_kernel.core.storage import StorageManager
+        storage = StorageManager()
+        scheduler = AgentScheduler(storage=storage, persist_tasks=True)
+        for pid in ("p1", "p2", "p3"):
+            scheduler.add_process(AgentProcess(pid=pid, name=pid))
+        
+        def status(pid):
+            return next(r['status'] for r in storage.list_tasks() if r['pid'] == pid)
+        
+        assert status("p1") == "pending"
+        scheduler.schedule()
+        assert status("p1") == "running"
+        assert [r['pid'] for r in storage.list_tasks(["pending"])] == ["p2", "p3"]
+    
+    def test_finished_tasks_keep_final_status(self):
+        """测试完成、失败和终止的进程保留最终状态，超出保留数时删除最早的记录"""
+        from agent_os_kernel.core.clock import MockClock
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess
+        from agent_os_kernel.core.storage import StorageManager
+        storage = StorageManager()
+        clock = MockClock()
+        scheduler = AgentScheduler(storage=storage, persist_tasks=True, clock=clock,
+                                   finished_task_retention=2)
+        for pid in ("p1", "p2", "p3", "p4"):
+            scheduler.add_process(AgentProcess(pid=pid, name=pid))
+        scheduler.schedule()
+        
+        scheduler.complete_process("p1", result="done")
+        clock.advance(1)
+        scheduler.terminate_process("p2", "error")
+        clock.advance(1)
+        scheduler.terminate_process("p3")
+        
+        statuses = {r['pid']: r['status'] for r in storage.list_tasks()}
+        assert statuses == {"p2": "failed", "p3": "terminated", "p4": "pending"}
+        assert sorted(storage.list_keys("task:")) == ["task:p2", "task:p3", "task:p4"]
+    
+    def test_recover_pending_tasks(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess, AgentState
+        from agent_os_kernel.core.storage import StorageManager
+        storage = StorageManager()
+        crashed = AgentScheduler(storage=storage, persist_tasks=True)
+        for pid in ("p1", "p2", "p3"):
+            crashed.add_process(AgentProcess(pid=pid, name=pid, priority=10))
+        crashed.schedule()
+        crashed.complete_process("p1")
+        crashed.schedule()
+        
+        recovered = AgentScheduler(storage=storage, persist_tasks=True)
+        assert sorted(recovered.recover_pending_tasks()) == ["p2", "p3"]
+        assert recovered.processes["p2"].state == AgentState.READY
+        assert recovered.ready_queue.qsize() == 2
+        assert recovered.recover_pending_tasks() == []
+    
+    def test_disabled_by_default(self):
+        from agent_os_kernel.core.scheduler import AgentScheduler, AgentProcess
+        from agent_os_kernel.core.storage import StorageManager
+        storage = StorageManager()
+        scheduler = AgentScheduler(storage=storage)
+        scheduler.add_process(AgentProcess(pid="p1", name="p1"))
+        assert storage.list_tasks() == []
@@ -13,3 +13,293 @@ class TestSecurityPolicyExists:
     def test_permission_import(self):
         from agent_os_kernel.core.security import PermissionLevel
         assert PermissionLevel is not None
+
+
+class TestViolationSeverity:
+    """测试安全违规严重级别"""
+    
+    def test_violations_filtered_by_severity(self):
+        from agent_os_kernel.core.security import SandboxManager, SecurityPolicy
+        from agent_os_kernel.core.storage import StorageManager
+        from agent_os_kernel.core.types import SecuritySeverity
+        storage = StorageManager()
+        sandbox = SandboxManager(storage=storage)
+        sandbox.containers["agent1"] = {'policy': SecurityPolicy()}
+        
+        assert sandbox.validate_file_access("agent1", "/etc/passwd") is False
+        assert sandbox.validate_file_access("agent1", "/opt/data") is False
+        sandbox.log_audit("agent1", "network_access", "exfiltration attempt",
+                          SecuritySeverity.CRITICAL)
+        
+        high = storage.get_violations_by_severity(SecuritySeverity.HIGH)
+        assert [v['severity'] for v in high] == ["high", "critical"]
+        assert high[0]['action'] == "security_violation:file_access"
+        assert len(storage.get_violations_by_severity("low")) == 3
+        assert len(storage.get_violations_by_severity(SecuritySeverity.CRITICAL, agent_pid="other")) == 0
+
+
+class TestSecurityPolicyFiles:
+    """测试安全策略 JSON 文件的读写"""
+    
+    def test_json_round_trip(self, tmp_path):
+        from agent_os_kernel.core.security import SecurityPolicy, PermissionLevel
+        policy = SecurityPolicy(
+            permission_level=PermissionLevel.RESTRICTED,
+            allowed_paths=["/data"],
+            allowed_hosts=["api.example.com", "10.0.0.0/8"],
+            allowed_tools=["calculator"],
+        )
+        path = str(tmp_path / "policy.json")
+        
+        policy.to_json_file(path)
+        loaded = SecurityPolicy.from_json_file(path)
+        
+        assert loaded == policy
+    
+    def test_rejects_invalid_policy(self, tmp_path):
+        import json
+        from agent_os_kernel.core.security import SecurityPolicy
+        from agent_os_kernel.core.exceptions import ConfigurationError
+        
+        for data in ({"allowed_paths": ["relative/dir"]},
+                     {"allowed_hosts": ["not a host"]},
+                     {"permission_level": "root"},
+                     {"unknown_field": 1}):
+            path = tmp_path / "policy.json"
+            path.write_text(json.dumps(data))
+            with pytest.raises(ConfigurationError):
+                SecurityPolicy.from_json_file(str(path))
+
+
+class TestPathRules:
+    """测试文件系统路径规则（glob 与前缀）"""
+    
+    def _sandbox(self, **policy_args):
+        from agent_os_kernel.core.security import SandboxManager, SecurityPolicy
+        sandbox = SandboxManager()
+        sandbox.containers["agent1"] = {'policy': SecurityPolicy(**policy_args)}
+        return sandbox
+    
+    def test_glob_allows_matching_files(self):
+        sandbox = self._sandbox(allowed_paths=["/workspace/**/*.txt"],
+                                blocked_paths=["/workspace/secrets"])
+        
+        assert sandbox.validate_file_access("agent1", "/workspace/notes.txt") is True
+        assert sandbox.validate_file_access("agent1", "/workspace/a/b/notes.txt") is True
+        assert sandbox.validate_file_access("agent1", "/workspace/notes.md") is False
+        assert sandbox.validate_file_access("agent1", "/workspace/secrets/key.txt") is False
+    
+    def test_most_specific_rule_wins(self):
+        sandbox = self._sandbox(allowed_paths=["/var/**/*.log", "/workspace/public"],
+                                blocked_paths=["/var", "/workspace"])
+        
+        assert sandbox.validate_file_access("agent1", "/var/log/app.log") is True
+        assert sandbox.validate_file_access("agent1", "/var/log/app.conf") is False
+        assert sandbox.validate_file_access("agent1", "/workspace/public/index.html") is True
+        assert sandbox.validate_file_access("agent1", "/workspace/private") is False
+    
+    def test_glob_character_classes(self):
+        sandbox = self._sandbox(allowed_paths=["/logs/app[0-9].log", "/data/[!.]*"],
+                                blocked_paths=[])
+
+        assert sandbox.validate_file_access("agent1", "/logs/app3.log") is True
+        assert sandbox.validate_file_access("agent1", "/logs/appx.log") is False
+        assert sandbox.validate_file_access("agent1", "/data/report.csv") is True
+        assert sandbox.validate_file_access("agent1", "/data/.env") is False
+
+    def test_prefix_matches_whole_components(self):
+        sandbox = self._sandbox(allowed_paths=["/tmp"], blocked_paths=[])
+        
+        assert sandbox.validate_file_access("agent1", "/tmp/file") is True
+        assert sandbox.validate_file_access("agent1", "/tmpfoo/file") is False
+    
+    def test_traversal_out_of_allowed_root_is_critical(self):
+        from agent_os_kernel.core.security import SandboxManager, SecurityPolicy
+        from agent_os_kernel.core.storage import StorageManager
+        from agent_os_kernel.core.types import SecuritySeverity
+        storage = StorageManager()
+        sandbox = SandboxManager(storage=storage)
+        sandbox.containers["agent1"] = {'policy': SecurityPolicy(
+            allowed_paths=["/workspace"], blocked_paths=[])}
+        
+        assert sandbox.validate_file_access("agent1", "/workspace/./a/../b") is True
+        assert sandbox.validate_file_access("agent1", "/workspace/../etc/passwd") is False
+        
+        critical = storage.get_violations_by_severity(SecuritySeverity.CRITICAL)
+        assert [v['action'] for v in critical] == ["security_violation:sandbox_escape"]
+    
+    def test_blocked_literal_path_is_not_an_escape(self):
+        from agent_os_kernel.core.security import SandboxManager, SecurityPolicy
+        from agent_os_kernel.core.storage import StorageManager
+        from agent_os_kernel.core.types import SecuritySeverity
+        storage = StorageManager()
+        sandbox = SandboxManager(storage=storage)
+        sandbox.containers["agent1"] = {'policy': SecurityPolicy(
+            allowed_paths=["/workspace"], blocked_paths=["/workspace/secrets"])}
+        
+        assert sandbox.validate_file_access("agent1", "/workspace/secrets/key") is False
+        assert sandbox.validate_file_access("agent1", "/workspace/a/../secrets/key") is False
+        
+        assert storage.get_violations_by_severity(SecuritySeverity.CRITICAL) == []
+        high = storage.get_violations_by_severity(SecuritySeverity.HIGH)
+        assert [v['action'] for v in high] == ["security_violation:file_access"] * 2
+    
+    def test_symlink_out_of_allowed_root_is_denied(self, tmp_path):
+        import os
+        root = tmp_path / "root"
+        outside = tmp_path / "outside"
+        root.mkdir()
+        outside.mkdir()
+        (outside / "secret.txt").write_text("secret")
+        os.symlink(str(outside), str(root / "link"))
+        sandbox = self._sandbox(allowed_paths=[str(root)], blocked_paths=[])
+        
+        assert sandbox.validate_file_access("agent1", str(root / "file.txt")) is True
+        assert sandbox.validate_file_access("agent1", str(root / "link" / "secret.txt")) is False
+
+
+class TestViolationTypes:
+    """测试资源限制与沙箱逃逸违规的记录与查询"""
+    
+    def test_resource_limit_and_escape_are_distinguishable(self):
+        from agent_os_kernel.core.security import (
+            SandboxManager, SecurityPolicy, SecurityViolationType
+        )
+        from agent_os_kernel.core.storage import StorageManager
+        storage = StorageManager()
+        sandbox = SandboxManager(storage=storage)
+        sandbox.containers["agent1"] = {'policy': SecurityPolicy(
+            allowed_paths=["/workspace"], max_tokens=1000, max_memory_mb=256)}
+        
+        assert sandbox.check_resource_limits("agent1", tokens=500, memory_mb=128) is True
+        assert sandbox.check_resource_limits("agent1", tokens=1500) is False
+        assert sandbox.check_resource_limits("agent1", memory_mb=512) is False
+        assert sandbox.validate_file_access("agent1", "/workspace/../etc/shadow") is False
+        # 没有沙箱的 Agent 不受限制
+        assert sandbox.check_resource_limits("other", tokens=10 ** 9) is True
+        
+        limits = sandbox.get_audit_log(violation_type=SecurityViolationType.RESOURCE_LIMIT)
+        escapes = sandbox.get_audit_log(violation_type=SecurityViolationType.SANDBOX_ESCAPE)
+        assert len(limits) == 2
+        assert all(log['severity'] == "high" for log in limits)
+        assert len(escapes) == 1
+        assert escapes[0]['severity'] == "critical"
+        assert len(sandbox.get_audit_log(agent_pid="agent1")) == 3
+    
+    def test_docker_usage_read_from_stats(self):
+        from agent_os_kernel.core.security import SandboxManager, SecurityPolicy
+        
+        class FakeContainer:
+            policy = SecurityPolicy(max_memory_mb=256, max_cpu_percent=50)
+            
+            def stats(self, stream=True):
+                return {
+                    'memory_stats': {'usage': 300 * 1024 * 1024},
+                    'cpu_stats': {'cpu_usage': {'total_usage': 300}, 'system_cpu_usage': 1000,
+                                  'online_cpus': 2},
+                    'precpu_stats': {'cpu_usage': {'total_usage': 100}, 'system_cpu_usage': 600},
+                }
+        
+        sandbox = SandboxManager()
+        sandbox.containers["agent1"] = FakeContainer()
+        sandbox.containers["agent2"] = {'policy': SecurityPolicy()}
+        
+        assert sandbox.get_resource_usage("agent1") == {'memory_mb': 300, 'cpu_percent': 100}
+        assert sandbox.get_resource_usage("agent2") == {}
+        assert not sandbox.check_resource_limits("agent1", **sandbox.get_resource_usage("agent1"))
+    
+    def test_kernel_checks_execution_time(self):
+        """测试内核把进程的执行时间交给沙箱检查"""
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.security import SecurityPolicy, SecurityViolationType
+        kernel = AgentOSKernel(enable_sandbox=True)
+        pid = kernel.spawn_agent(name="Slow", task="t",
+                                 policy=SecurityPolicy(max_execution_time=0, use_sandbox=False))
+        process = kernel.scheduler.processes[pid]
+        
+        assert kernel.execute_agent_step(process)['success']
+        assert process.execution_time > 0
+        result = kernel.execute_agent_step(process)
+        
+        assert result['error'] == 'Sandbox resource limit exceeded'
+        limits = kernel.security.get_audit_log(violation_type=SecurityViolationType.RESOURCE_LIMIT)
+        assert limits[0]['details']['input']['execution_time'] == process.execution_time
+
+
+class TestToolAccess:
+    """测试沙箱策略对工具调用的限制"""
+    
+    def test_blocked_tool_is_not_dispatched(self):
+        from agent_os_kernel.core.security import (
+            SandboxManager, SecurityPolicy, SecurityViolationType
+        )
+        from agent_os_kernel.core.storage import StorageManager
+        from agent_os_kernel.tools.registry import ToolRegistry
+        from agent_os_kernel.tools.builtin import CalculatorTool
+        sandbox = SandboxManager(storage=StorageManager())
+        sandbox.containers["agent1"] = {'policy': SecurityPolicy(
+            allowed_tools=["calculator", "shell"], blocked_tools=["shell"])}
+        registry = ToolRegistry(security=sandbox)
+        registry.register(CalculatorTool())
+        
+        assert registry.execute_as("agent1", "calculator", expression="6 * 7")['success']
+        denied = registry.execute_as("agent1", "shell", command="ls")
+        assert denied['error_code'] == 403
+        assert not sandbox.validate_tool_use("agent1", "read_file")
+        # 没有沙箱的 Agent 不受限制
+        assert sandbox.validate_tool_use("other", "shell")
+        
+        violations = sandbox.get_audit_log(violation_type=SecurityViolationType.TOOL_ACCESS)
+        assert [log['details']['input']['tool'] for log in violations] == ["shell", "read_file"]
+    
+    def test_kernel_tool_calls_enforce_policy(self):
+        """测试内核代表 Agent 执行工具时检查沙箱策略"""
+        from agent_os_kernel import AgentOSKernel
+        from agent_os_kernel.core.security import SecurityPolicy
+        kernel = AgentOSKernel(enable_sandbox=True)
+        pid = kernel.spawn_agent(name="Sandboxed", task="t",
+                                 policy=SecurityPolicy(blocked_tools=["calculator"],
+                                                       use_sandbox=False))
+        other = kernel.spawn_agent(name="Free", task="t")
+        
+        assert kernel.execute_tool(pid, "calculator", expression="6 * 7")['error_code'] == 403
+        assert kernel.execute_tool(other, "calculator", expression="6 * 7")['success']
+        assert "Error:" in kernel.context_manager.get_agent_context(pid)
+
+
+class TestAuditLogBuffer:
+    """测试内存审计日志的容量限制"""
+    
+    def test_buffer_never_exceeds_capacity(self):
+        from agent_os_kernel.core.security import SandboxManager, SecurityViolationType
+        sandbox = SandboxManager(audit_log_capacity=5)
+        for i in range(12):
+            sandbox.log_audit(f"agent{i % 2}", SecurityViolationType.TOOL_ACCESS, f"violation {i}")
+            assert len(sandbox.audit_log) <= 5
+        
+        logs = sandbox.get_audit_log()
+        assert [log['details']['reasoning'] for log in logs] == [f"violation {i}" for i in range(7, 12)]
+        assert len(sandbox.get_audit_log(agent_pid="agent1", limit=2)) == 2
+        assert sandbox.get_audit_stats() == {
+            'total_logged': 12, 'buffered': 5, 'capacity': 5, 'dropped': 7}
+    
+    def test_default_capacity_smaller_with_storage(self):
+        from agent_os_kernel.core.security import SandboxManager
+        from agent_os_kernel.core.storage import StorageManager
+        assert SandboxManager().audit_log.maxlen == SandboxManager.AUDIT_LOG_CAPACITY
+        persisted = SandboxManager(storage=StorageManager())
+        assert persisted.audit_log.maxlen == SandboxManager.PERSISTED_AUDIT_LOG_CAPACITY
+    
+    def test_kernel_audit_log_bounded(self):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        from agent_os_kernel.core.security import SecurityPolicy, SecurityViolationType
+        kernel = AgentOSKernel(enable_sandbox=True, config=KernelConfig(audit_log_capacity=5))
+        pid = kernel.spawn_agent(name="Sandboxed", task="t",
+                                 policy=SecurityPolicy(blocked_tools=["shell"], use_sandbox=False))
+        for i in range(12):
+            assert kernel.tool_registry.execute_as(pid, "shell", command=f"ls {i}")['error_code'] == 403
+        
+        violations = kernel.security.get_audit_log(violation_type=SecurityViolationType.TOOL_ACCESS)
+        assert len(violations) == 5
+        assert len(kernel.storage.get_audit_logs(limit=100)) == 5
+        assert len(kernel.security.audit_log) == 5
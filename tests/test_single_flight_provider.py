@@ -0,0 +1,67 @@
+"""测试合并并发的相同 LLM 请求"""
+
+import asyncio
+
+import pytest
+
+from agent_os_kernel.llm.mock_provider import MockProvider
+from agent_os_kernel.llm.provider import Message, ServerError
+from agent_os_kernel.llm.single_flight import SingleFlightProvider
+
+
+class CountingProvider(MockProvider):
+    """记录上游调用次数，可按需失败的 Provider"""
+
+    def __init__(self, error=None):
+        super().__init__()
+        self.calls = 0
+        self.error = error
+        self.set_delay(0.05)
+
+    async def chat(self, messages, **kwargs):
+        self.calls += 1
+        if self.error:
+            await asyncio.sleep(0.05)
+            raise self.error
+        return await super().chat(messages, **kwargs)
+
+
+class TestSingleFlightProvider:
+    """测试请求合并"""
+
+    @pytest.mark.asyncio
+    async def test_concurrent_identical_requests_share_one_call(self):
+        inner = CountingProvider()
+        provider = SingleFlightProvider(inner)
+        messages = [Message(role="user", content="hello")]
+
+        results = await asyncio.gather(*[
+            provider.chat([Message(role="user", content="hello")], temperature=0.2)
+            for _ in range(5)
+        ])
+        await asyncio.gather(
+            provider.chat(messages, temperature=0.2),
+            provider.chat(messages, temperature=0.9),
+        )
+
+        assert inner.calls == 3
+        assert all(r == results[0] for r in results)
+        assert len({id(r) for r in results}) == 5
+        assert provider.get_stats()["coalesced_requests"] == 4
+        assert provider.in_flight == 0
+
+    @pytest.mark.asyncio
+    async def test_error_reaches_all_waiters_and_is_cleaned_up(self):
+        inner = CountingProvider(error=ServerError("503"))
+        provider = SingleFlightProvider(inner)
+        messages = [Message(role="user", content="hello")]
+
+        results = await asyncio.gather(*[provider.chat(messages) for _ in range(3)],
+                                       return_exceptions=True)
+
+        assert inner.calls == 1
+        assert all(isinstance(r, ServerError) for r in results)
+        assert provider.in_flight == 0
+        inner.error = None
+        assert (await provider.chat(messages))["content"]
+        assert inner.calls == 2
@@ -0,0 +1,49 @@
+"""测试 Tokenizer 抽象"""
+
+import pytest
+from agent_os_kernel.core.tokenizer import Tokenizer, HeuristicTokenizer
+from agent_os_kernel.core.context_manager import ContextManager
+from agent_os_kernel.core.optimization.compressor import ContextCompressor
+
+
+class FixedTokenizer(Tokenizer):
+    """每个字符计 1 个 token"""
+    
+    def count_tokens(self, text: str) -> int:
+        return len(text)
+
+
+class TestHeuristicTokenizer:
+    """测试启发式计数"""
+    
+    def test_english_words(self):
+        tokenizer = HeuristicTokenizer()
+        assert tokenizer.count_tokens("") == 0
+        assert tokenizer.count_tokens("one two three four five six seven eight nine ten") == 13
+    
+    def test_cjk_characters(self):
+        tokenizer = HeuristicTokenizer()
+        # 中文没有空格分词，按字符计数
+        assert tokenizer.count_tokens("上下文管理") == 5
+        assert tokenizer.count_tokens("上下文 context") == 4
+
+
+class TestSharedTokenizer:
+    """测试各组件共用同一个 Tokenizer"""
+    
+    def test_context_manager_and_compressor_agree(self):
+        tokenizer = FixedTokenizer()
+        manager = ContextManager(max_context_tokens=1000, tokenizer=tokenizer)
+        compressor = ContextCompressor(tokenizer=tokenizer)
+        
+        page_id = manager.allocate_page("agent1", "hello world")
+        assert manager.pages_in_memory[page_id].tokens == 11
+        
+        overhead = compressor.config.token_per_message
+        assert compressor._count_tokens([{"role": "user", "content": "hello world"}]) == 11 + overhead
+    
+    def test_kernel_uses_configured_tokenizer(self):
+        from agent_os_kernel import AgentOSKernel, KernelConfig
+        tokenizer = FixedTokenizer()
+        kernel = AgentOSKernel(config=KernelConfig(tokenizer=tokenizer))
+        assert kernel.context_manager.tokenizer is tokenizer
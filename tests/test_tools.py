@@ -2,6 +2,8 @@
 
 import pytest
 
+from agent_os_kernel.tools.mcp.transport import BaseTransport
+
 
 class TestToolResult:
     """测试工具结果"""
@@ -34,3 +36,480 @@ class TestToolRegistry:
         registry = ToolRegistry()
         stats = registry.get_stats()
         assert stats is not None
+
+
+class TestToolError:
+    """测试结构化工具错误"""
+    
+    def test_from_exception(self):
+        """测试异常到错误码的映射"""
+        from agent_os_kernel.tools import ToolError, ToolErrorCode
+        assert ToolError.from_exception("boom").code == ToolErrorCode.EXECUTION_FAILED
+        assert ToolError.from_exception(TimeoutError("slow")).code == ToolErrorCode.TIMEOUT
+        assert ToolError.from_exception(PermissionError("no")).code == ToolErrorCode.PERMISSION_DENIED
+    
+    def test_retryable(self):
+        """测试只有临时性失败可重试"""
+        from agent_os_kernel.tools import ToolError, ToolErrorCode
+        assert ToolError("slow", ToolErrorCode.TIMEOUT).retryable is True
+        assert ToolError("bad", ToolErrorCode.INVALID_INPUT).retryable is False
+        assert ToolError("fail").retryable is False
+    
+    def test_registry_converts_errors(self):
+        """测试注册表把错误转换为带错误码的结果"""
+        from agent_os_kernel.tools import ToolRegistry, CalculatorTool, ToolErrorCode
+        registry = ToolRegistry()
+        registry.register(CalculatorTool())
+        
+        result = registry.execute("calculator", expression="2 + 3")
+        assert result["success"] is True
+        assert result["data"] == 5
+        
+        result = registry.execute("calculator", expression="__import__('os')")
+        assert result["success"] is False
+        assert result["error_code"] == ToolErrorCode.INVALID_INPUT.value
+        assert result["retryable"] is False
+        
+        result = registry.execute("missing")
+        assert result["error_code"] == ToolErrorCode.NOT_FOUND.value
+
+
+class TestToolCache:
+    """测试工具结果缓存"""
+    
+    def _counting_tool(self, cacheable: bool):
+        from agent_os_kernel.tools import SimpleTool
+        from agent_os_kernel.tools.base import ToolParameter
+        
+        calls = []
+        
+        def double(x):
+            calls.append(x)
+            return x * 2
+        
+        tool = SimpleTool("double", "Double a number", double,
+                          parameters=[ToolParameter("x", "integer", "Number to double")],
+                          cacheable=cacheable)
+        return tool, calls
+    
+    def test_cached_call_skips_tool(self):
+        """测试相同参数的第二次调用直接返回缓存结果"""
+        from agent_os_kernel.tools import ToolRegistry
+        registry = ToolRegistry(enable_cache=True)
+        tool, calls = self._counting_tool(cacheable=True)
+        registry.register(tool)
+        
+        first = registry.execute("double", x=21)
+        second = registry.execute("double", x=21)
+        
+        assert first == second
+        assert calls == [21]
+        assert registry.get_stats()['cache_hits'] == 1
+        
+        registry.execute("double", x=1)
+        assert calls == [21, 1]
+    
+    def test_non_cacheable_bypasses(self):
+        """测试不可缓存的工具每次都执行"""
+        from agent_os_kernel.tools import ToolRegistry
+        registry = ToolRegistry(enable_cache=True)
+        tool, calls = self._counting_tool(cacheable=False)
+        registry.register(tool)
+        
+        registry.execute("double", x=21)
+        registry.execute("double", x=21)
+        assert calls == [21, 21]
+    
+    def test_cached_result_is_copied(self):
+        """测试修改返回结果不会影响缓存"""
+        from agent_os_kernel.tools import ToolRegistry, SimpleTool
+        registry = ToolRegistry(enable_cache=True)
+        registry.register(SimpleTool("items", "List items", lambda: {"items": [1, 2]},
+                                     cacheable=True))
+        
+        first = registry.execute("items")
+        first.data["items"].append(3)
+        second = registry.execute("items")
+        second.data["items"].append(4)
+        
+        assert registry.execute("items").data == {"items": [1, 2]}
+        assert registry.get_stats()['cache_hits'] == 2
+    
+    def test_clear_cache_by_tool(self):
+        """测试按工具名清除缓存"""
+        from agent_os_kernel.tools import ToolRegistry
+        registry = ToolRegistry(enable_cache=True)
+        tool, calls = self._counting_tool(cacheable=True)
+        registry.register(tool)
+        
+        registry.execute("double", x=21)
+        registry.clear_cache("other")
+        registry.execute("double", x=21)
+        registry.clear_cache("double")
+        registry.execute("double", x=21)
+        assert calls == [21, 21]
+    
+    def test_lru_clear_prefix(self):
+        """测试 LRUCache 按前缀清除"""
+        from agent_os_kernel.core.cache_utils import LRUCache
+        cache = LRUCache()
+        cache.set("a:1", 1)
+        cache.set("a:2", 2)
+        cache.set("b:1", 3)
+        
+        cache.clear("a:")
+        assert cache.size == 1
+        assert cache.get("b:1") == 3
+        cache.clear()
+        assert cache.size == 0
+
+
+class TestToolMetrics:
+    """测试工具执行指标"""
+    
+    def test_tool_stats(self):
+        """测试按工具统计调用次数、失败次数和耗时"""
+        from agent_os_kernel.tools import ToolRegistry, CalculatorTool
+        from agent_os_kernel.core.metrics import MetricsCollector
+        metrics = MetricsCollector()
+        registry = ToolRegistry(metrics=metrics)
+        registry.register(CalculatorTool())
+        
+        registry.execute("calculator", expression="1 + 1")
+        registry.execute("calculator", expression="1 / 0")
+        registry.execute("calculator", expression="2 * 3")
+        
+        stats = registry.tool_stats()["calculator"]
+        assert stats.calls == 3
+        assert stats.errors == 1
+        assert stats.error_rate == pytest.approx(1 / 3)
+        assert stats.total_latency >= 0
+        
+        latency = metrics.get(ToolRegistry.METRIC_LATENCY, {"tool": "calculator"})
+        assert latency.count == 3
+
+
+class _FakeMCPServer(BaseTransport):
+    """按 method 返回固定 result 的 JSON-RPC 传输"""
+    
+    def __init__(self, results):
+        self.results = results
+        self.requests = []
+        self.notifications = []
+    
+    async def start(self):
+        pass
+    
+    async def send(self, payload):
+        self.requests.append(payload)
+        if isinstance(self.results.get(payload["method"]), Exception):
+            error = self.results[payload["method"]]
+            return {"jsonrpc": "2.0", "id": payload["id"],
+                    "error": {"code": -32602, "message": str(error)}}
+        return {"jsonrpc": "2.0", "id": payload["id"],
+                "result": self.results.get(payload["method"], {})}
+    
+    async def notify(self, payload):
+        self.notifications.append(payload)
+    
+    async def close(self):
+        pass
+
+
+class TestMCPResources:
+    """测试 MCP 资源"""
+    
+    def _client(self, results):
+        from agent_os_kernel.tools.mcp.client import MCPClient, MCPServerConfig
+        client = MCPClient(MCPServerConfig(name="docs", command="", url="http://mcp"))
+        client._transport = _FakeMCPServer(results)
+        client._connected = True
+        return client
+    
+    @pytest.mark.asyncio
+    async def test_list_and_read_resources(self):
+        """测试列出和读取资源"""
+        client = self._client({
+            "resources/list": {"resources": [
+                {"uri": "file:///readme.md", "name": "README", "mimeType": "text/markdown"}
+            ]},
+            "resources/read": {"contents": [
+                {"uri": "file:///readme.md", "text": "# Hello"}
+            ]},
+        })
+        
+        resources = await client.list_resources()
+        assert len(resources) == 1
+        assert resources[0].mime_type == "text/markdown"
+        
+        result = await client.read_resource("file:///readme.md")
+        assert result["contents"][0]["text"] == "# Hello"
+        
+        ids = [r["id"] for r in client._transport.requests]
+        assert ids == [1, 2]
+        assert client._transport.requests[1]["params"] == {"uri": "file:///readme.md"}
+    
+    @pytest.mark.asyncio
+    async def test_read_resource_error(self):
+        """测试资源读取失败时抛出 ToolError"""
+        from agent_os_kernel.tools import ToolError, ToolErrorCode
+        client = self._client({})
+        
+        async def send(payload):
+            return {"jsonrpc": "2.0", "id": payload["id"],
+                    "error": {"code": -32602, "message": "Unknown resource"}}
+        client._transport.send = send
+        
+        with pytest.raises(ToolError) as exc_info:
+            await client.read_resource("file:///missing")
+        assert exc_info.value.code == ToolErrorCode.INVALID_INPUT
+
+
+class _BrokenMCPServer(_FakeMCPServer):
+    """连接已断开的服务器"""
+    
+    def __init__(self):
+        super().__init__({})
+    
+    async def send(self, payload):
+        raise ConnectionError("broken pipe")
+
+
+class TestMCPReconnect:
+    """测试 MCP 断线检测和重连"""
+    
+    def _client(self, **config):
+        from agent_os_kernel.tools.mcp.client import MCPClient, MCPServerConfig
+        client = MCPClient(MCPServerConfig(name="docs", command="", url="http://mcp", **config))
+        client._transport = _BrokenMCPServer()
+        client._connected = True
+        client._supervised = True
+        return client
+    
+    @pytest.mark.asyncio
+    async def test_disconnect_detected(self):
+        """测试断线后标记未连接，后续调用返回 NotConnected"""
+        from agent_os_kernel.tools import ToolErrorCode
+        from agent_os_kernel.tools.mcp import MCPNotConnectedError
+        from agent_os_kernel.tools.mcp.client import MCPToolCall
+        client = self._client()
+        
+        result = await client.call_tool(MCPToolCall(tool="search", arguments={}))
+        assert result.is_error
+        assert result.error_code == ToolErrorCode.SERVICE_UNAVAILABLE
+        assert client.connected is False
+        
+        with pytest.raises(MCPNotConnectedError):
+            await client.call_tool(MCPToolCall(tool="search", arguments={}))
+    
+    @pytest.mark.asyncio
+    async def test_auto_reconnect(self):
+        """测试自动重连并重新握手、发现工具"""
+        from unittest.mock import patch
+        from agent_os_kernel.tools.mcp.client import MCPToolCall
+        client = self._client(max_reconnect_attempts=3, reconnect_backoff=0)
+        server = _FakeMCPServer({
+            "tools/list": {"tools": [{"name": "search", "inputSchema": {}}]},
+            "tools/call": {"content": [{"type": "text", "text": "ok"}]},
+        })
+        
+        await client.call_tool(MCPToolCall(tool="search", arguments={}))
+        assert client.connected is False
+        
+        with patch("agent_os_kernel.tools.mcp.client.HTTPTransport", return_value=server):
+            result = await client.call_tool(MCPToolCall(tool="search", arguments={}))
+        
+        assert not result.is_error
+        assert client.connected is True
+        assert client.reconnect_count == 1
+        assert [r["method"] for r in server.requests] == ["initialize", "tools/list", "tools/call"]
+        assert [n["method"] for n in server.notifications] == ["notifications/initialized"]
+
+
+class TestMCPHandshake:
+    """测试 initialize 握手"""
+    
+    def _client(self):
+        from agent_os_kernel.tools.mcp.client import MCPClient, MCPServerConfig
+        return MCPClient(MCPServerConfig(name="docs", command="", url="http://mcp"))
+    
+    @pytest.mark.asyncio
+    async def test_initialize_sends_client_info_then_initialized(self):
+        from unittest.mock import patch
+        client = self._client()
+        server = _FakeMCPServer({})
+        
+        with patch("agent_os_kernel.tools.mcp.client.HTTPTransport", return_value=server):
+            assert await client.connect() is True
+        
+        params = server.requests[0]["params"]
+        assert params["clientInfo"]["name"] == "agent-os-kernel"
+        assert params["protocolVersion"] == "2024-11-05"
+        assert server.notifications == [
+            {"jsonrpc": "2.0", "method": "notifications/initialized", "params": {}}]
+        assert client.connected is True
+    
+    @pytest.mark.asyncio
+    async def test_initialize_error_fails_connect(self):
+        from unittest.mock import patch
+        client = self._client()
+        server = _FakeMCPServer({"initialize": ValueError("Unsupported protocol version")})
+        
+        with patch("agent_os_kernel.tools.mcp.client.HTTPTransport", return_value=server):
+            assert await client.connect() is False
+        
+        assert client.connected is False
+        assert server.notifications == []
+
+
+_STDIO_SERVER = """
+import json, sys
+for line in sys.stdin:
+    msg = json.loads(line)
+    if "id" not in msg:
+        continue
+    print(json.dumps({"jsonrpc": "2.0", "method": "notifications/progress"}), flush=True)
+    print(json.dumps({"jsonrpc": "2.0", "id": msg["id"], "result": {"echo": msg["method"]}}), flush=True)
+"""
+
+
+class TestMCPTransport:
+    """测试 MCP 传输层"""
+    
+    def test_parse_sse(self):
+        """测试从 SSE 事件流中取出匹配 id 的响应"""
+        from agent_os_kernel.tools.mcp.transport import HTTPTransport
+        body = (
+            'event: message\ndata: {"jsonrpc": "2.0", "method": "notifications/progress"}\n\n'
+            'event: message\ndata: {"jsonrpc": "2.0", "id": 7, "result": {"ok": true}}\n\n'
+        )
+        assert HTTPTransport._parse_sse(body, 7)["result"] == {"ok": True}
+    
+    def test_transport_selection(self):
+        """测试按配置选择传输方式"""
+        from agent_os_kernel.tools.mcp.client import MCPClient, MCPServerConfig, MCPTransport
+        from agent_os_kernel.tools.mcp.transport import HTTPTransport, StdioTransport
+        http = MCPClient(MCPServerConfig(name="remote", command="", url="https://mcp.example.com",
+                                         headers={"Authorization": "Bearer x"}))
+        stdio = MCPClient(MCPServerConfig(name="local", command="uvx", args=["mcp-server"]))
+        assert http.transport_type == MCPTransport.HTTP
+        assert isinstance(http._create_transport(), HTTPTransport)
+        assert isinstance(stdio._create_transport(), StdioTransport)
+    
+    @pytest.mark.asyncio
+    async def test_stdio_roundtrip(self):
+        """测试 STDIO 传输跳过通知并按 id 返回响应"""
+        import sys
+        from agent_os_kernel.tools.mcp.client import MCPClient, MCPServerConfig
+        client = MCPClient(MCPServerConfig(name="echo", command=sys.executable,
+                                           args=["-c", _STDIO_SERVER], timeout=5.0))
+        assert await client.connect() is True
+        try:
+            response = await client._send_request("ping", {})
+            assert response["result"] == {"echo": "ping"}
+        finally:
+            await client.close()
+
+
+class TestDateTimeTool:
+    """测试当前时间工具"""
+    
+    def _tool(self):
+        from datetime import datetime, timezone
+        from agent_os_kernel.tools import DateTimeTool
+        fixed = datetime(2024, 3, 1, 12, 30, 45, tzinfo=timezone.utc)
+        return DateTimeTool(now=lambda: fixed)
+    
+    def test_rfc3339(self):
+        """测试默认 RFC 3339 格式"""
+        result = self._tool().execute()
+        assert result["data"] == "2024-03-01T12:30:45+00:00"
+    
+    def test_unix(self):
+        """测试 Unix 时间戳"""
+        result = self._tool().execute(format="unix")
+        assert result["data"] == 1709296245
+    
+    def test_strftime(self):
+        """测试 strftime 格式"""
+        result = self._tool().execute(format="%Y-%m-%d %H:%M")
+        assert result["data"] == "2024-03-01 12:30"
+    
+    def test_timezone(self):
+        """测试时区转换和未知时区"""
+        from agent_os_kernel.tools import ToolRegistry, ToolErrorCode
+        registry = ToolRegistry()
+        registry.register(self._tool())
+        
+        result = registry.execute("datetime", format="%H:%M", timezone="Asia/Shanghai")
+        assert result["data"] == "20:30"
+        
+        result = registry.execute("datetime", timezone="Mars/Olympus")
+        assert result["error_code"] == ToolErrorCode.INVALID_INPUT.value
+
+
+class TestJsonQueryTool:
+    """测试 JSONPath 查询工具"""
+    
+    DATA = {
+        "store": {
+            "book": [
+                {"title": "A", "price": 8},
+                {"title": "B", "price": 12},
+                {"title": "C", "price": 22},
+            ],
+            "bicycle": {"color": "red", "price": 19},
+        }
+    }
+    
+    def _query(self, query, data=None):
+        from agent_os_kernel.tools import JsonQueryTool
+        return JsonQueryTool().execute(data=self.DATA if data is None else data, query=query)["data"]
+    
+    def test_queries(self):
+        """测试常用选择器"""
+        assert self._query("$.store.book[0].title") == ["A"]
+        assert self._query("$.store.book[-1].title") == ["C"]
+        assert self._query("$.store.book[*].price") == [8, 12, 22]
+        assert self._query("$.store.book[0:2].title") == ["A", "B"]
+        assert self._query("$.store.book[0,2].title") == ["A", "C"]
+        assert self._query("$.store['bicycle'].color") == ["red"]
+        assert sorted(self._query("$..price")) == [8, 12, 19, 22]
+        assert self._query("$.store.missing") == []
+    
+    def test_json_string_input(self):
+        """测试 data 为 JSON 字符串"""
+        assert self._query("$.a[1]", data='{"a": [1, 2]}') == [2]
+    
+    def test_invalid_query(self):
+        """测试非法表达式返回 INVALID_INPUT"""
+        from agent_os_kernel.tools import ToolRegistry, JsonQueryTool, ToolErrorCode
+        registry = ToolRegistry()
+        registry.register(JsonQueryTool())
+        
+        for query in ("store.book", "$.store[", "$.store[?(@.price > 10)]",
+                      "$.store.book[1,,2]", "$.store.book[0,]", "$.store['bicycle'x]"):
+            result = registry.execute("json_query", data=self.DATA, query=query)
+            assert result["success"] is False
+            assert result["error_code"] == ToolErrorCode.INVALID_INPUT.value
+    
+    def test_schema_accepts_object_or_array(self):
+        """测试 data 参数声明为对象或数组"""
+        from agent_os_kernel.tools import JsonQueryTool
+        schema = JsonQueryTool().get_schema()
+        assert schema["parameters"]["properties"]["data"]["type"] == ["object", "array"]
+        assert self._query("$[1]", data=[1, 2]) == [2]
+    
+    def test_cache_key_hashes_data(self):
+        """测试缓存键不包含原始数据，不同数据不会命中同一缓存"""
+        from agent_os_kernel.tools import ToolRegistry, JsonQueryTool
+        registry = ToolRegistry(enable_cache=True)
+        registry.register(JsonQueryTool())
+        
+        first = registry.execute("json_query", data={"a": "x" * 1000}, query="$.a")
+        second = registry.execute("json_query", data={"a": "y"}, query="$.a")
+        
+        assert first["data"] == ["x" * 1000] and second["data"] == ["y"]
+        key = registry._cache_key(registry.tools["json_query"], "json_query",
+                                  {"data": {"a": "x" * 1000}, "query": "$.a"})
+        assert key.startswith("json_query:") and len(key) < 100
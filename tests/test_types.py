@@ -16,6 +16,15 @@ class TestAgentState:
         from agent_os_kernel.core.types import AgentState
         # 检查主要状态
         assert hasattr(AgentState, 'IDLE') or hasattr(AgentState, 'RUNNING')
+    
+    def test_exported_state_covers_scheduler_states(self):
+        """测试 core 导出的 AgentState 包含调度器使用的所有状态"""
+        from agent_os_kernel.core import AgentState
+        from agent_os_kernel.core.scheduler import AgentState as SchedulerState
+        assert AgentState.COMPLETED.value == "completed"
+        assert AgentState.SUSPENDED.value == "suspended"
+        for state in SchedulerState:
+            assert AgentState(state.value).name == state.name
 
 
 class TestResourceQuota:
@@ -0,0 +1,84 @@
+"""测试上下文预写日志"""
+
+import os
+import tempfile
+
+from agent_os_kernel.core.context_manager import ContextManager
+from agent_os_kernel.core.tokenizer import HeuristicTokenizer
+from agent_os_kernel.core.clock import MockClock, SequentialIdGenerator
+from agent_os_kernel.core.wal import InMemoryWriteAheadLog, FileWriteAheadLog
+
+
+def make_manager(wal, clock=None):
+    return ContextManager(max_context_tokens=12, tokenizer=HeuristicTokenizer(),
+                          clock=clock or MockClock(start=1000.0),
+                          id_generator=SequentialIdGenerator(), wal=wal)
+
+
+def visible_state(cm):
+    return (sorted(cm.pages_in_memory), sorted(cm.swapped_pages),
+            cm.agent_pages, cm.current_usage,
+            {pid: p.importance_score for pid, p in cm.pages_in_memory.items()})
+
+
+class TestContextReplay:
+    """测试从 WAL 重放恢复上下文"""
+
+    def test_replay_reconstructs_state(self):
+        wal = InMemoryWriteAheadLog()
+        cm = make_manager(wal)
+        old = cm.allocate_page("agent1", "old page with some words here", importance=0.1)
+        new = cm.allocate_page("agent1", "newer page with more words", importance=0.5)
+        cm.allocate_page("agent2", "ls", page_type="tools", ttl=60)
+        cm.update_importance(new, 0.9)
+        cm.expire_pages(current_time=2000.0)
+
+        restored = make_manager(wal)
+        assert restored.replay_wal() == wal.last_seq
+
+        assert old in restored.swapped_pages
+        assert visible_state(restored) == visible_state(cm)
+        assert restored.replay_wal() == 0
+
+    def test_replay_after_snapshot(self):
+        wal = InMemoryWriteAheadLog()
+        cm = make_manager(wal)
+        cm.allocate_page("agent1", "first page", importance=0.3)
+        snapshot = cm.export_snapshot()
+        wal.truncate(snapshot.wal_seq)
+        cm.allocate_page("agent1", "second page", importance=0.4)
+
+        restored = make_manager(wal)
+        restored.import_snapshot(snapshot)
+        assert restored.replay_wal() == 1
+        assert visible_state(restored) == visible_state(cm)
+
+
+class TestFileWriteAheadLog:
+    """测试文件 WAL"""
+
+    def test_records_survive_reopen_and_torn_tail(self):
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, "context.wal")
+            wal = FileWriteAheadLog(path)
+            wal.append("allocate", {"n": 1})
+            wal.append("evict", {"n": 2})
+            wal.close()
+            with open(path, "a", encoding="utf-8") as f:
+                f.write('{"seq":3,"op":"ev')
+
+            reopened = FileWriteAheadLog(path)
+            assert [r.op for r in reopened.records()] == ["allocate", "evict"]
+            assert reopened.append("remove", {}).seq == 3
+            reopened.close()
+
+    def test_truncate(self):
+        with tempfile.TemporaryDirectory() as tmp:
+            wal = FileWriteAheadLog(os.path.join(tmp, "context.wal"))
+            for i in range(5):
+                wal.append("op", {"i": i})
+
+            assert wal.truncate(3) == 3
+            assert [r.seq for r in wal.records()] == [4, 5]
+            assert wal.append("op", {}).seq == 6
+            wal.close()
@@ -0,0 +1,157 @@
+"""测试工作流 Agent 的步骤动作"""
+
+import asyncio
+
+import pytest
+
+from agent_os_kernel.agents.base import BaseAgent, AgentConfig
+from agent_os_kernel.agents.workflow_agent import (
+    WorkflowAgent, WorkflowConfig, StepStatus, CallTool, RunAgent, Transform
+)
+from agent_os_kernel.agents.react import ReActAgent
+from agent_os_kernel.tools import ToolRegistry, CalculatorTool
+
+
+class EchoAgent(BaseAgent):
+    """记录收到的任务后立即停止的 Agent"""
+
+    def __init__(self):
+        super().__init__(AgentConfig(name="echo"))
+        self.tasks = []
+
+    @property
+    def agent_type(self) -> str:
+        return "echo"
+
+    def input_schema(self):
+        return {"type": "object", "properties": {"task": {"type": "string"}}}
+
+    async def _think(self, task):
+        self.tasks.append(task)
+        return {"stop": True}
+
+
+class TestAgentValueInput:
+    """测试 Agent 的结构化输入"""
+
+    @pytest.mark.asyncio
+    async def test_run_value_extracts_task(self):
+        agent = EchoAgent()
+        await agent.run_value({"task": "summarize"})
+        await agent.run_value({"topic": "rust"})
+        await agent.run_value(3)
+        assert agent.tasks == ["summarize", '{"topic": "rust"}', "3"]
+
+    def test_schema_declaration(self):
+        agent = EchoAgent()
+        assert agent.input_schema()["type"] == "object"
+        assert agent.output_schema() is None
+
+    @pytest.mark.asyncio
+    async def test_workflow_runs_agent_with_upstream_output(self):
+        agent = EchoAgent()
+        workflow = WorkflowAgent(WorkflowConfig(name="agents"))
+        workflow.add_step("plan", "plan", task="",
+                          action=Transform(lambda topic: {"task": f"research {topic}"}))
+        workflow.add_step("run", "run", task="", depends_on=["plan"], action=RunAgent(agent))
+
+        result = await workflow.run(input_data="kernels")
+
+        assert result["success"] is True
+        assert agent.tasks == ["research kernels"]
+
+
+class TestStepActions:
+    """测试步骤动作的执行与输出传递"""
+
+    @pytest.mark.asyncio
+    async def test_outputs_thread_through_steps(self):
+        registry = ToolRegistry()
+        registry.register(CalculatorTool())
+        workflow = WorkflowAgent(WorkflowConfig(name="calc"))
+        workflow.add_step("expr", "build", task="",
+                          action=Transform(lambda n: f"{n} * 2"))
+        workflow.add_step("calc", "calculate", task="", depends_on=["expr"],
+                          action=CallTool(registry, "calculator", input_param="expression"))
+        workflow.add_step("fmt", "format", task="", depends_on=["calc"],
+                          action=Transform(lambda r: f"result={r['data']}"))
+
+        result = await workflow.run(input_data=21)
+
+        assert result["success"] is True
+        assert workflow.steps["fmt"].result["output"] == "result=42"
+
+    @pytest.mark.asyncio
+    async def test_multiple_dependencies_receive_dict(self):
+        workflow = WorkflowAgent(WorkflowConfig(name="join"))
+        workflow.add_step("a", "a", task="", action=Transform(lambda _: 1))
+        workflow.add_step("b", "b", task="", action=Transform(lambda _: 2))
+        workflow.add_step("sum", "sum", task="", depends_on=["a", "b"],
+                          action=Transform(lambda d: d["a"] + d["b"]))
+
+        await workflow.run()
+
+        assert workflow.steps["sum"].result["output"] == 3
+
+    @pytest.mark.asyncio
+    async def test_failure_skips_dependents(self):
+        def boom(_):
+            raise ValueError("boom")
+
+        workflow = WorkflowAgent(WorkflowConfig(name="fail", continue_on_failure=True))
+        workflow.add_step("bad", "bad", task="", action=Transform(boom))
+        workflow.add_step("next", "next", task="", depends_on=["bad"],
+                          action=Transform(lambda x: x))
+        workflow.add_step("other", "other", task="", action=Transform(lambda x: "ok"))
+
+        result = await workflow.run()
+
+        assert result["success"] is False
+        assert workflow.steps["bad"].error == "boom"
+        assert workflow.steps["next"].status == StepStatus.SKIPPED
+        assert workflow.steps["other"].result["output"] == "ok"
+
+
+def drain(queue):
+    events = []
+    while not queue.empty():
+        events.append(queue.get_nowait())
+    return events
+
+
+class TestProgress:
+    """测试运行进度上报"""
+
+    @pytest.mark.asyncio
+    async def test_workflow_reports_each_step(self):
+        workflow = WorkflowAgent(WorkflowConfig(name="progress"))
+        workflow.add_step("a", "a", task="", action=Transform(lambda x: x))
+        workflow.add_step("b", "b", task="", depends_on=["a"], action=Transform(lambda x: x))
+        queue = asyncio.Queue()
+
+        result = await workflow.run_with_progress("in", queue)
+
+        events = drain(queue)
+        assert result["success"] is True
+        assert [(e.step, e.fraction, e.done) for e in events] == \
+            [(1, 0.5, False), (2, 1.0, False), (2, 1.0, True)]
+        assert events[0].message == "step a completed"
+
+    @pytest.mark.asyncio
+    async def test_react_reports_steps_and_final_event(self):
+        agent = ReActAgent(name="react", max_steps=3)
+        queue = asyncio.Queue()
+
+        await agent.run_with_progress("question", queue)
+
+        events = drain(queue)
+        assert [e.step for e in events[:-1]] == list(range(1, len(events)))
+        assert events[-1].done is True
+        assert agent._progress is None
+
+    @pytest.mark.asyncio
+    async def test_plain_run_does_not_report(self):
+        agent = EchoAgent()
+        result = await agent.run("task")
+        assert result["success"] is True
+        assert agent._progress is None